@@ -0,0 +1,27 @@
+//! Mixing and WAV encoding throughput, e.g. to compare the `simd` feature
+//! with the scalar loops:
+//!
+//! ```sh
+//! cargo bench --target wasm32-unknown-unknown --bench mix
+//! RUSTFLAGS="-C target-feature=+simd128" \
+//!     cargo bench --target wasm32-unknown-unknown --bench mix --features simd
+//! ```
+//!
+//! with `wasm-bindgen-test-runner` set as the wasm32 runner.
+
+#![cfg(target_arch = "wasm32")]
+
+use wasm_audio_combiner::{AudioCombiner, SingleAudioFile};
+use wasm_bindgen_test::{wasm_bindgen_bench, Criterion};
+
+#[wasm_bindgen_bench]
+fn combine_four_ten_second_files(c: &mut Criterion) {
+    let files = [220.0, 330.0, 440.0, 550.0]
+        .iter()
+        .map(|&freq| SingleAudioFile::sine(freq, 10_000.0, 44100, 0.2).unwrap())
+        .collect();
+    let combiner = AudioCombiner::new(files).unwrap();
+    c.bench_function("combine 4 x 10 s", |b| {
+        b.iter(|| combiner.combine(vec![50, 60, 70, 80]).unwrap())
+    });
+}
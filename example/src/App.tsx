@@ -66,6 +66,21 @@ function App() {
       case wasm.SingleAudioFileType.Ogg:
         audioType = "audio/ogg";
         break;
+      case wasm.SingleAudioFileType.Flac:
+        audioType = "audio/flac";
+        break;
+      case wasm.SingleAudioFileType.Aac:
+        audioType = "audio/mp4";
+        break;
+      case wasm.SingleAudioFileType.Webm:
+        audioType = "audio/webm";
+        break;
+      case wasm.SingleAudioFileType.Aiff:
+        audioType = "audio/aiff";
+        break;
+      case wasm.SingleAudioFileType.Auto:
+      case wasm.SingleAudioFileType.Pcm:
+        throw new Error("Combined output always has a concrete type");
       default:
         assertNever(file.type);
         break;
@@ -127,7 +142,13 @@ function App() {
               {
                 "audio/mpeg": wasm.SingleAudioFileType.Mpeg,
                 "audio/wav": wasm.SingleAudioFileType.Wav,
-              }[file.type] ?? wasm.SingleAudioFileType.Mpeg,
+                "audio/flac": wasm.SingleAudioFileType.Flac,
+                "audio/mp4": wasm.SingleAudioFileType.Aac,
+                "audio/x-m4a": wasm.SingleAudioFileType.Aac,
+                "audio/webm": wasm.SingleAudioFileType.Webm,
+                "audio/aiff": wasm.SingleAudioFileType.Aiff,
+                "audio/x-aiff": wasm.SingleAudioFileType.Aiff,
+              }[file.type] ?? wasm.SingleAudioFileType.Auto,
           };
         })
       )
@@ -35,6 +35,18 @@ const api = {
                     type: resultFile.type,
                 };
             },
+            // deno-lint-ignore require-await
+            async combineRaw(volumes: Uint8Array) {
+                const pcm = combiner.combine_raw(volumes);
+                // `samples` is a JS-owned copy, so it outlives `pcm`.
+                const result = {
+                    samples: pcm.samples,
+                    sampleRate: pcm.sample_rate,
+                    channels: pcm.channels,
+                };
+                pcm.free();
+                return Comlink.transfer(result, [result.samples.buffer]);
+            },
             // Good practice: allow manual memory cleanup
             free() {
                 combiner.free();
@@ -0,0 +1,68 @@
+//! Base64 for `SingleAudioFile::from_base64` and `from_data_url`, decoded in
+//! one pass straight into the file's bytes.
+
+use crate::CombineError;
+
+/// Value of a digit in the standard alphabet or the URL-safe one.
+fn digit(c: u8) -> Option<u32> {
+    let value = match c {
+        b'A'..=b'Z' => c - b'A',
+        b'a'..=b'z' => c - b'a' + 26,
+        b'0'..=b'9' => c - b'0' + 52,
+        b'+' | b'-' => 62,
+        b'/' | b'_' => 63,
+        _ => return None,
+    };
+    Some(value as u32)
+}
+
+fn invalid(offset: usize, detail: &str) -> CombineError {
+    CombineError::InvalidBase64 {
+        offset,
+        detail: detail.into(),
+    }
+}
+
+/// Decodes `data`, padded or not, skipping ASCII whitespace anywhere in it.
+/// Errors give the byte offset in `data` of what could not be read.
+pub fn decode(data: &str) -> Result<Vec<u8>, CombineError> {
+    let mut bytes = Vec::with_capacity(data.len() / 4 * 3 + 2);
+    // Digits of the current group of four, most significant first.
+    let mut group = 0u32;
+    let mut digits = 0;
+    let mut padding = 0;
+    for (offset, c) in data.bytes().enumerate() {
+        if c.is_ascii_whitespace() {
+            continue;
+        }
+        if c == b'=' {
+            // Padding fills out a final group of two or three digits.
+            if digits < 2 || digits + padding == 4 {
+                return Err(invalid(offset, "unexpected padding"));
+            }
+            padding += 1;
+            continue;
+        }
+        if padding > 0 {
+            return Err(invalid(offset, "data after padding"));
+        }
+        let value = digit(c).ok_or_else(|| invalid(offset, "not a base64 digit"))?;
+        group = group << 6 | value;
+        digits += 1;
+        if digits == 4 {
+            bytes.extend_from_slice(&[(group >> 16) as u8, (group >> 8) as u8, group as u8]);
+            group = 0;
+            digits = 0;
+        }
+    }
+    match digits {
+        0 => {}
+        1 => return Err(invalid(data.len(), "ends one digit into a group")),
+        2 => bytes.push((group >> 4) as u8),
+        _ => bytes.extend_from_slice(&[(group >> 10) as u8, (group >> 2) as u8]),
+    }
+    if padding > 0 && digits + padding != 4 {
+        return Err(invalid(data.len(), "incomplete padding"));
+    }
+    Ok(bytes)
+}
@@ -0,0 +1,144 @@
+//! The Broadcast Wave `bext` chunk of `CombineOptions::bext`, laid out as
+//! in EBU Tech 3285.
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::CombineError;
+
+/// Bytes of the fixed part of a `bext` chunk, up to the coding history.
+pub(crate) const BEXT_SIZE: usize = 602;
+
+/// Widths of the text fields, in the order they are written.
+const DESCRIPTION_LEN: usize = 256;
+const ORIGINATOR_LEN: usize = 32;
+const ORIGINATOR_REFERENCE_LEN: usize = 32;
+const DATE_LEN: usize = 10;
+const TIME_LEN: usize = 8;
+
+/// Whole sample counts a JS number holds exactly.
+const MAX_TIME_REFERENCE: f64 = 9_007_199_254_740_991.0;
+
+/// Broadcast Wave Format details for WAV output. Text is ASCII, and is
+/// NUL-padded to its field; an empty date or time is left blank. Nothing is
+/// read from the clock, so the same settings give the same bytes.
+#[wasm_bindgen]
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct BroadcastExtension {
+    /// Free text of up to 256 characters.
+    #[wasm_bindgen(getter_with_clone)]
+    pub description: String,
+    /// Who made the file, up to 32 characters.
+    #[wasm_bindgen(getter_with_clone)]
+    pub originator: String,
+    /// The originator's own id for the file, up to 32 characters.
+    #[wasm_bindgen(getter_with_clone)]
+    pub originator_reference: String,
+    /// `"yyyy-mm-dd"`.
+    #[wasm_bindgen(getter_with_clone)]
+    pub origination_date: String,
+    /// `"hh:mm:ss"`.
+    #[wasm_bindgen(getter_with_clone)]
+    pub origination_time: String,
+    /// Where the file starts, in samples since midnight at the output rate.
+    /// A whole number.
+    pub time_reference: f64,
+}
+
+#[wasm_bindgen]
+impl BroadcastExtension {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Whether `value` is digits laid out as `pattern`, where each `9` is a
+/// digit and anything else must match exactly.
+fn matches_pattern(value: &str, pattern: &str) -> bool {
+    value.len() == pattern.len()
+        && value.bytes().zip(pattern.bytes()).all(|(c, p)| match p {
+            b'9' => c.is_ascii_digit(),
+            _ => c == p,
+        })
+}
+
+impl BroadcastExtension {
+    pub(crate) fn validate(&self) -> Result<(), CombineError> {
+        let invalid = |detail: String| Err(CombineError::InvalidOption { detail });
+        for (name, value, len) in [
+            ("description", &self.description, DESCRIPTION_LEN),
+            ("originator", &self.originator, ORIGINATOR_LEN),
+            (
+                "originator_reference",
+                &self.originator_reference,
+                ORIGINATOR_REFERENCE_LEN,
+            ),
+        ] {
+            if !value.is_ascii() {
+                return invalid(format!("bext {} must be ASCII", name));
+            }
+            if value.len() > len {
+                return invalid(format!(
+                    "bext {} must be at most {} characters, got {}",
+                    name,
+                    len,
+                    value.len()
+                ));
+            }
+        }
+        for (name, value, pattern) in [
+            ("origination_date", &self.origination_date, "9999-99-99"),
+            ("origination_time", &self.origination_time, "99:99:99"),
+        ] {
+            if !value.is_empty() && !matches_pattern(value, pattern) {
+                return invalid(format!(
+                    "bext {} must look like {:?}, got {:?}",
+                    name, pattern, value
+                ));
+            }
+        }
+        let reference = self.time_reference;
+        if !(0.0..=MAX_TIME_REFERENCE).contains(&reference) || reference.fract() != 0.0 {
+            return invalid(format!(
+                "bext time_reference must be a whole number of samples, got {}",
+                reference
+            ));
+        }
+        Ok(())
+    }
+
+    /// The same details for output that starts `frames` later, as a
+    /// segment of a longer file does.
+    pub(crate) fn advanced(&self, frames: usize) -> Self {
+        Self {
+            time_reference: self.time_reference + frames as f64,
+            ..self.clone()
+        }
+    }
+
+    /// The whole `bext` chunk, header included. Version 1, with no UMID,
+    /// loudness values or coding history.
+    pub(crate) fn chunk(&self) -> Vec<u8> {
+        let mut chunk = Vec::with_capacity(8 + BEXT_SIZE);
+        chunk.extend_from_slice(b"bext");
+        chunk.extend_from_slice(&(BEXT_SIZE as u32).to_le_bytes());
+        for (value, len) in [
+            (&self.description, DESCRIPTION_LEN),
+            (&self.originator, ORIGINATOR_LEN),
+            (&self.originator_reference, ORIGINATOR_REFERENCE_LEN),
+            (&self.origination_date, DATE_LEN),
+            (&self.origination_time, TIME_LEN),
+        ] {
+            let start = chunk.len();
+            chunk.extend_from_slice(value.as_bytes());
+            chunk.resize(start + len, 0);
+        }
+        chunk.extend_from_slice(&(self.time_reference as u64).to_le_bytes());
+        chunk.extend_from_slice(&1u16.to_le_bytes());
+        // UMID, loudness and the reserved bytes.
+        chunk.resize(8 + BEXT_SIZE, 0);
+        chunk
+    }
+}
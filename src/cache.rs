@@ -0,0 +1,113 @@
+//! The cache behind `AudioCombiner::enable_decode_cache`, which keeps each
+//! file as last prepared for the mix so re-mixes that only change gains skip
+//! the trimming, filtering and resampling.
+
+use std::cell::RefCell;
+
+use crate::{Equalizer, MixTrack, NoiseGate};
+
+/// The settings that shape a file before its gains are applied. A cached
+/// file is reused only while these stay the same.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct PrepareKey {
+    sample_rate: u32,
+    trim_start_ms: f64,
+    trim_end_ms: Option<f64>,
+    auto_trim_silence: Option<f32>,
+    swap_channels: bool,
+    invert_phase: bool,
+    force_mono: bool,
+    remove_dc: bool,
+    reversed: bool,
+    highpass_hz: Option<f32>,
+    lowpass_hz: Option<f32>,
+    eq: Option<Equalizer>,
+    gate: Option<NoiseGate>,
+    speed: f32,
+    loop_count: u32,
+    loop_to_ms: Option<f64>,
+}
+
+impl PrepareKey {
+    pub(crate) fn new(track: &MixTrack, sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            trim_start_ms: track.trim_start_ms,
+            trim_end_ms: track.trim_end_ms,
+            auto_trim_silence: track.auto_trim_silence,
+            swap_channels: track.swap_channels,
+            invert_phase: track.invert_phase,
+            force_mono: track.force_mono,
+            remove_dc: track.remove_dc,
+            reversed: track.reversed,
+            highpass_hz: track.highpass_hz,
+            lowpass_hz: track.lowpass_hz,
+            eq: track.eq,
+            gate: track.gate,
+            speed: track.speed,
+            loop_count: track.loop_count,
+            loop_to_ms: track.loop_to_ms,
+        }
+    }
+}
+
+struct Prepared {
+    key: PrepareKey,
+    samples: Vec<f32>,
+}
+
+/// One slot per file of the combiner, in the same order.
+pub(crate) struct PrepareCache {
+    entries: RefCell<Vec<Option<Prepared>>>,
+}
+
+impl PrepareCache {
+    pub(crate) fn new(files: usize) -> Self {
+        Self {
+            entries: RefCell::new((0..files).map(|_| None).collect()),
+        }
+    }
+
+    /// A copy of file `index` as prepared under `key`, if it was.
+    pub(crate) fn get(&self, index: usize, key: &PrepareKey) -> Option<Vec<f32>> {
+        match &self.entries.borrow()[index] {
+            Some(prepared) if prepared.key == *key => Some(prepared.samples.clone()),
+            _ => None,
+        }
+    }
+
+    /// Keeps file `index` as prepared under `key`, replacing what was there.
+    pub(crate) fn insert(&self, index: usize, key: PrepareKey, samples: &[f32]) {
+        self.entries.borrow_mut()[index] = Some(Prepared {
+            key,
+            samples: samples.to_vec(),
+        });
+    }
+
+    /// Makes room for a file added at the end.
+    pub(crate) fn push(&mut self) {
+        self.entries.get_mut().push(None);
+    }
+
+    /// Forgets file `index`, moving later files down by one as the
+    /// combiner does.
+    pub(crate) fn remove(&mut self, index: usize) {
+        self.entries.get_mut().remove(index);
+    }
+
+    /// Forgets every file but keeps a slot for each.
+    pub(crate) fn clear(&mut self) {
+        for entry in self.entries.get_mut() {
+            *entry = None;
+        }
+    }
+
+    pub(crate) fn memory_bytes(&self) -> usize {
+        self.entries
+            .borrow()
+            .iter()
+            .flatten()
+            .map(|prepared| prepared.samples.len() * std::mem::size_of::<f32>())
+            .sum()
+    }
+}
@@ -0,0 +1,91 @@
+use wasm_bindgen::prelude::*;
+
+use crate::{OutputFormat, SingleAudioFileType};
+
+/// What this build can read and write, from `capabilities`.
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Capabilities {
+    pub(crate) input_types: Vec<SingleAudioFileType>,
+    pub(crate) output_formats: Vec<OutputFormat>,
+    pub(crate) simd: bool,
+    pub(crate) threads: bool,
+    pub(crate) version: String,
+}
+
+#[wasm_bindgen]
+impl Capabilities {
+    /// File types whose codec is compiled in, plus `Auto` and `Pcm`.
+    #[wasm_bindgen(getter)]
+    pub fn input_types(&self) -> Vec<SingleAudioFileType> {
+        self.input_types.clone()
+    }
+
+    /// Formats `CombineOptions::format` can be set to without an
+    /// `OUTPUT_DISABLED` error.
+    #[wasm_bindgen(getter)]
+    pub fn output_formats(&self) -> Vec<OutputFormat> {
+        self.output_formats.clone()
+    }
+
+    /// Whether mixing and quantization use wasm SIMD, which needs both the
+    /// `simd` feature and the `simd128` target feature.
+    #[wasm_bindgen(getter)]
+    pub fn simd(&self) -> bool {
+        self.simd
+    }
+
+    /// Whether the `threads` feature is on, so `initThreadPool` exists.
+    #[wasm_bindgen(getter)]
+    pub fn threads(&self) -> bool {
+        self.threads
+    }
+
+    /// Version of this crate.
+    #[wasm_bindgen(getter)]
+    pub fn version(&self) -> String {
+        self.version.clone()
+    }
+}
+
+/// Reports the input types, output formats and features of this build, read
+/// from the same compiled features that decoding and encoding check so it
+/// cannot drift from what the build actually does.
+#[wasm_bindgen]
+pub fn capabilities() -> Capabilities {
+    let input_types = [
+        SingleAudioFileType::Wav,
+        SingleAudioFileType::Mpeg,
+        SingleAudioFileType::Ogg,
+        SingleAudioFileType::Flac,
+        SingleAudioFileType::Aac,
+        SingleAudioFileType::Webm,
+        SingleAudioFileType::Aiff,
+        SingleAudioFileType::Auto,
+        SingleAudioFileType::Pcm,
+    ]
+    .iter()
+    .copied()
+    .filter(|file_type| file_type.built_in())
+    .collect();
+    let output_formats = [
+        (OutputFormat::Wav, true),
+        (OutputFormat::Ogg, cfg!(feature = "ogg-output")),
+        (OutputFormat::Mpeg, cfg!(feature = "mp3-output")),
+    ]
+    .iter()
+    .copied()
+    .filter_map(|(format, enabled)| enabled.then_some(format))
+    .collect();
+    Capabilities {
+        input_types,
+        output_formats,
+        simd: cfg!(all(
+            feature = "simd",
+            target_arch = "wasm32",
+            target_feature = "simd128"
+        )),
+        threads: cfg!(feature = "threads"),
+        version: env!("CARGO_PKG_VERSION").into(),
+    }
+}
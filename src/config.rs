@@ -0,0 +1,118 @@
+//! The plain JS object `AudioCombiner::combine_with_config` takes in place
+//! of a track list and `CombineOptions`.
+
+use serde::Deserialize;
+use wasm_bindgen::prelude::*;
+
+use crate::{BitDepth, CombineError, CombineMode, CombineOptions, MixTrack, OutputFormat};
+
+#[wasm_bindgen(typescript_custom_section)]
+const MIX_CONFIG_TS: &'static str = r#"
+/** Fields of `MixTrack`, each defaulting as `new MixTrack()` does. Enums are
+ * given by variant name, such as `"EqualPower"`. */
+export interface MixTrackConfig {
+    gain?: number;
+    gain_left?: number;
+    gain_right?: number;
+    pan?: number;
+    offset_frames?: number;
+    offset_ms?: number;
+    trim_start_ms?: number;
+    trim_end_ms?: number;
+    auto_trim_silence?: number;
+    reversed?: boolean;
+    remove_dc?: boolean;
+    swap_channels?: boolean;
+    invert_phase?: boolean;
+    force_mono?: boolean;
+    highpass_hz?: number;
+    lowpass_hz?: number;
+    eq?: {
+        low_gain_db?: number;
+        low_freq?: number;
+        mid_gain_db?: number;
+        mid_freq?: number;
+        mid_q?: number;
+        high_gain_db?: number;
+        high_freq?: number;
+    };
+    gate?: {
+        threshold_db?: number;
+        attack_ms?: number;
+        hold_ms?: number;
+        release_ms?: number;
+        floor_db?: number;
+    };
+    speed?: number;
+    loop_count?: number;
+    loop_to_ms?: number;
+    fade_in_ms?: number;
+    fade_out_ms?: number;
+    fade_curve?: "Linear" | "Exponential" | "EqualPower";
+    automation?: { time_ms: number; gain: number }[];
+    ducking?: {
+        source: number;
+        threshold_db?: number;
+        amount_db?: number;
+        attack_ms?: number;
+        release_ms?: number;
+    };
+    mute?: boolean;
+    solo?: boolean;
+}
+
+/** Everything `combine_with_config` needs. Missing fields take the defaults
+ * of `MixTrack` and `CombineOptions`, and unknown ones are ignored. */
+export interface MixConfig {
+    tracks?: MixTrackConfig[];
+    output?: {
+        sample_rate?: number;
+        bit_depth?: "Int16" | "Int24" | "Float32";
+        format?: "Wav" | "Ogg" | "Mpeg";
+    };
+    mode?: "Mix" | "Concat";
+}
+"#;
+
+#[wasm_bindgen]
+extern "C" {
+    /// A `MixConfig` object from JS.
+    #[wasm_bindgen(typescript_type = "MixConfig")]
+    pub type MixConfig;
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Config {
+    tracks: Vec<MixTrack>,
+    output: OutputConfig,
+    mode: Option<CombineMode>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct OutputConfig {
+    sample_rate: Option<u32>,
+    bit_depth: Option<BitDepth>,
+    format: Option<OutputFormat>,
+}
+
+/// Reads `config` into tracks and options, naming the path of the first
+/// field that does not fit, as in `tracks[2].gain: invalid type`.
+pub fn parse(config: MixConfig) -> Result<(Vec<MixTrack>, CombineOptions), CombineError> {
+    let deserializer = serde_wasm_bindgen::Deserializer::from(JsValue::from(config));
+    let config: Config = serde_path_to_error::deserialize(deserializer).map_err(|error| {
+        CombineError::InvalidOption {
+            detail: format!("{}: {}", error.path(), error.inner()),
+        }
+    })?;
+    let defaults = CombineOptions::default();
+    let options = CombineOptions {
+        mode: config.mode.unwrap_or(defaults.mode),
+        sample_rate: config.output.sample_rate.unwrap_or(defaults.sample_rate),
+        bit_depth: config.output.bit_depth.unwrap_or(defaults.bit_depth),
+        format: config.output.format.unwrap_or(defaults.format),
+        ..defaults
+    };
+    Ok((config.tracks, options))
+}
@@ -0,0 +1,108 @@
+//! Cue points for WAV output: `CombineOptions::markers`, and with
+//! `mark_file_starts` one where each file starts, written as a `cue ` chunk
+//! with a LIST/adtl chunk of labels.
+
+use std::convert::TryFrom;
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::CombineOptions;
+
+/// A labelled position in the combined output for
+/// `CombineOptions::markers`.
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Marker {
+    /// Position in the output, in milliseconds.
+    pub time_ms: f64,
+    #[wasm_bindgen(getter_with_clone)]
+    pub label: String,
+}
+
+#[wasm_bindgen]
+impl Marker {
+    #[wasm_bindgen(constructor)]
+    pub fn new(time_ms: f64, label: String) -> Self {
+        Self { time_ms, label }
+    }
+}
+
+/// A marker placed on a frame of the output.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct CuePoint {
+    pub(crate) frame: usize,
+    pub(crate) label: String,
+}
+
+/// The cue points of an output `frames` long whose audible files start at
+/// `starts`, given as (file index, first frame), in order of position.
+/// Markers past the end are left out.
+pub(crate) fn cue_points(
+    options: &CombineOptions,
+    starts: &[(usize, usize)],
+    frames: usize,
+) -> Vec<CuePoint> {
+    let file_starts = starts
+        .iter()
+        .filter(|_| options.mark_file_starts)
+        .map(|&(index, frame)| CuePoint {
+            frame,
+            label: format!("Track {}", index + 1),
+        });
+    let markers = options.markers.iter().map(|marker| CuePoint {
+        frame: (marker.time_ms * options.sample_rate as f64 / 1000.0).round() as usize,
+        label: marker.label.clone(),
+    });
+    let mut cues: Vec<CuePoint> = file_starts
+        .chain(markers)
+        .filter(|cue| cue.frame <= frames)
+        .collect();
+    // Stable, so a file start keeps ahead of a marker on the same frame.
+    cues.sort_by_key(|cue| cue.frame);
+    cues
+}
+
+/// Appends a RIFF chunk of `id` holding `body`, padded to an even length.
+fn push_chunk(out: &mut Vec<u8>, id: &[u8; 4], body: &[u8]) {
+    out.extend_from_slice(id);
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend_from_slice(body);
+    if body.len() % 2 == 1 {
+        out.push(0);
+    }
+}
+
+/// A `cue ` chunk of `cues`, numbered from 1 in order, and a LIST/adtl
+/// chunk with a `labl` for each, or nothing when there are none. Cues past
+/// the 32-bit frame offsets the chunk holds, only reachable in RF64 output,
+/// are left out.
+pub(crate) fn chunks(cues: &[CuePoint]) -> Vec<u8> {
+    let cues: Vec<(u32, &str)> = cues
+        .iter()
+        .filter_map(|cue| Some((u32::try_from(cue.frame).ok()?, cue.label.as_str())))
+        .collect();
+    if cues.is_empty() {
+        return Vec::new();
+    }
+    let mut points = (cues.len() as u32).to_le_bytes().to_vec();
+    let mut labels = b"adtl".to_vec();
+    for (id, (frame, label)) in (1u32..).zip(cues) {
+        points.extend_from_slice(&id.to_le_bytes());
+        // Play order position, then where in `data` the point falls.
+        points.extend_from_slice(&frame.to_le_bytes());
+        points.extend_from_slice(b"data");
+        points.extend_from_slice(&0u32.to_le_bytes());
+        points.extend_from_slice(&0u32.to_le_bytes());
+        points.extend_from_slice(&frame.to_le_bytes());
+
+        let mut labl = id.to_le_bytes().to_vec();
+        labl.extend_from_slice(label.as_bytes());
+        labl.push(0);
+        push_chunk(&mut labels, b"labl", &labl);
+    }
+    let mut chunks = Vec::new();
+    push_chunk(&mut chunks, b"cue ", &points);
+    push_chunk(&mut chunks, b"LIST", &labels);
+    chunks
+}
@@ -0,0 +1,44 @@
+//! The time budget of `CombineOptions::max_ms`.
+
+use crate::now;
+
+/// Packets decoded between reads of the clock. Reading it costs a call out
+/// to JS, which would add up over the thousands of packets in a long file.
+const PACKETS_PER_CHECK: u32 = 32;
+
+/// When a combine has to give up, checked every `PACKETS_PER_CHECK` packets.
+pub(crate) struct Deadline {
+    max_ms: f64,
+    until: f64,
+    countdown: u32,
+}
+
+impl Deadline {
+    /// Starts the clock on a budget of `max_ms`, or gives no deadline at all.
+    pub(crate) fn start(max_ms: Option<f64>) -> Option<Self> {
+        max_ms.map(|max_ms| Self {
+            max_ms,
+            until: now() + max_ms,
+            countdown: PACKETS_PER_CHECK,
+        })
+    }
+
+    pub(crate) fn max_ms(&self) -> f64 {
+        self.max_ms
+    }
+
+    /// Counts a packet, and on every `PACKETS_PER_CHECK`th says whether the
+    /// budget has run out.
+    pub(crate) fn passed_after_packet(&mut self) -> bool {
+        self.countdown -= 1;
+        if self.countdown > 0 {
+            return false;
+        }
+        self.countdown = PACKETS_PER_CHECK;
+        self.passed()
+    }
+
+    pub(crate) fn passed(&self) -> bool {
+        now() >= self.until
+    }
+}
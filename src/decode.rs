@@ -0,0 +1,1140 @@
+use std::sync::{Arc, OnceLock};
+
+use symphonia::core::audio::{Channels, SampleBuffer};
+use symphonia::core::codecs::{
+    CodecParameters, CodecRegistry, Decoder, CODEC_TYPE_AAC, CODEC_TYPE_NULL, CODEC_TYPE_OPUS,
+    CODEC_TYPE_VORBIS,
+};
+use symphonia::core::errors::Error;
+use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo, Track};
+use symphonia::core::io::{MediaSource, ReadOnlySource};
+use symphonia::core::probe::ProbeResult;
+use symphonia::core::units::{Time, TimeBase};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::levels::{LevelMeter, Levels};
+use crate::{resample, utils};
+use crate::{
+    AudioCombinerSingleFile, CombineError, DecodeStage, DecodeWarning, FileInfo, FileMetadata,
+    MixTrack, SingleAudioFile, SingleAudioFileType, MASTER_SAMPLE_RATE,
+};
+
+/// Symphonia's decoders for the enabled codecs, plus the Opus one of the
+/// `opus` feature.
+fn codecs() -> &'static CodecRegistry {
+    static CODECS: OnceLock<CodecRegistry> = OnceLock::new();
+    CODECS.get_or_init(|| {
+        let mut registry = CodecRegistry::new();
+        symphonia::default::register_enabled_codecs(&mut registry);
+        #[cfg(feature = "opus")]
+        registry.register_all::<crate::opus::OpusDecoder>();
+        registry
+    })
+}
+
+/// Detects streams symphonia can demux but not decode.
+///
+/// The Ogg and WebM readers recognise Opus, which only the `opus` feature
+/// decodes, and then only with one or two channels. The AAC decoder only
+/// implements AAC-LC, so HE-AAC (SBR) and HE-AAC v2 (PS) are rejected up
+/// front based on the object type in the AudioSpecificConfig.
+fn unsupported_codec(params: &CodecParameters) -> Option<&'static str> {
+    if params.codec == CODEC_TYPE_OPUS {
+        if !cfg!(feature = "opus") {
+            return Some("Opus");
+        }
+        // The channel mapping family of the OpusHead.
+        return match params.extra_data.as_deref()?.get(18)? {
+            0 => None,
+            _ => Some("multichannel Opus"),
+        };
+    }
+    if params.codec != CODEC_TYPE_AAC {
+        return None;
+    }
+    match params.extra_data.as_deref()?.first()? >> 3 {
+        5 => Some("HE-AAC"),
+        29 => Some("HE-AAC v2"),
+        _ => None,
+    }
+}
+
+fn decode_error(index: usize, error: Error) -> CombineError {
+    match error {
+        Error::Unsupported(what) => CombineError::UnsupportedCodec {
+            index,
+            codec: what.to_string(),
+        },
+        e => CombineError::DecodeFailed {
+            index,
+            detail: e.to_string(),
+        },
+    }
+}
+
+/// Whether a decoder error means bad data rather than a stream the decoder
+/// cannot handle. Past the first packet, an unsupported feature is corrupt
+/// data that happens to parse as one, as AAC often does.
+fn is_corrupt(error: &Error, decoded_before: bool) -> bool {
+    match error {
+        Error::DecodeError(_) | Error::IoError(_) => true,
+        Error::Unsupported(_) => decoded_before,
+        _ => false,
+    }
+}
+
+/// Milliseconds from the start of a track to `ts`, in its time base if it has
+/// one. Matroska counts in its own timescale rather than in frames.
+fn timestamp_ms(ts: u64, time_base: Option<TimeBase>, sample_rate: u32) -> f64 {
+    match time_base {
+        Some(time_base) => {
+            let time = time_base.calc_time(ts);
+            (time.seconds as f64 + time.frac) * 1000.0
+        }
+        None => ts as f64 * 1000.0 / sample_rate as f64,
+    }
+}
+
+/// Upper bound on progress callbacks per second of decoded audio.
+const PROGRESS_CALLS_PER_SECOND: u32 = 30;
+
+/// Calls `progress(file_index, fraction)`, turning a throw into an error.
+pub fn report_progress(
+    progress: &js_sys::Function,
+    index: usize,
+    fraction: f64,
+) -> Result<(), CombineError> {
+    progress
+        .call2(
+            &JsValue::NULL,
+            &JsValue::from(index as u32),
+            &JsValue::from(fraction),
+        )
+        .map(|_| ())
+        .map_err(|e| CombineError::CallbackFailed {
+            index,
+            detail: thrown_message(&e),
+        })
+}
+
+/// The message of a value thrown by a JS callback.
+pub fn thrown_message(thrown: &JsValue) -> String {
+    match thrown.dyn_ref::<js_sys::Error>() {
+        Some(error) => String::from(error.message()),
+        None => thrown
+            .as_string()
+            .unwrap_or_else(|| format!("{:?}", thrown)),
+    }
+}
+
+/// Decodes a file into interleaved stereo samples at its native rate.
+///
+/// When the track length is known, `progress` is told how far decoding has
+/// got, at most `PROGRESS_CALLS_PER_SECOND` times per second of audio.
+pub fn decode_file(
+    index: usize,
+    file: SingleAudioFile,
+    progress: Option<&js_sys::Function>,
+) -> Result<AudioCombinerSingleFile, CombineError> {
+    if file.r#type == SingleAudioFileType::Pcm {
+        return decode_pcm(index, file);
+    }
+
+    let mut decoder = FileDecoder::open(index, file)?;
+    decoder.reserve_track();
+    while decoder.decode_packet(progress)? {}
+    decoder.finish()
+}
+
+/// Like `decode_file`, also telling a failure to open the file apart from
+/// one partway through its packets, and keeping only the frames in
+/// `track`'s trim window. `check(decoded_ms)` runs after every packet with
+/// how far into the track decoding has got, and can stop the decode.
+///
+/// Decoding stops at the end of the window, and starts shortly before it
+/// where the container can seek there, as for `decode_window`.
+pub fn decode_file_staged(
+    index: usize,
+    file: SingleAudioFile,
+    track: &MixTrack,
+    mut check: impl FnMut(f64) -> Result<(), CombineError>,
+) -> Result<AudioCombinerSingleFile, (DecodeStage, CombineError)> {
+    let (mut decoded, first) = decode_range(
+        index,
+        file,
+        track.trim_start_ms,
+        track.trim_end_ms,
+        &mut check,
+    )?;
+    let window = track.trim(first + decoded.samples.len() / 2, decoded.sample_rate);
+    decoded.samples.truncate((window.end - first) * 2);
+    decoded.samples.drain(..(window.start - first) * 2);
+    Ok(decoded)
+}
+
+/// How far ahead of a window's start `decode_window` seeks to. Decoders
+/// with overlapping blocks, and MP3 with its bit reservoir, give the same
+/// samples as a decode from the top only once a few packets have gone
+/// through them.
+const SEEK_PREROLL_MS: f64 = 250.0;
+
+/// Decodes a file only as far as `end_ms` and keeps the frames from
+/// `start_ms` on, each rounded to the nearest frame.
+///
+/// Where the container can seek, decoding starts `SEEK_PREROLL_MS` before
+/// `start_ms` instead of at the top of the file, so a window late in a long
+/// file costs about as much as one at its start.
+pub fn decode_window(
+    index: usize,
+    file: SingleAudioFile,
+    start_ms: f64,
+    end_ms: f64,
+) -> Result<AudioCombinerSingleFile, CombineError> {
+    let (decoded, first) = decode_range(index, file, start_ms, Some(end_ms), &mut |_| Ok(()))
+        .map_err(|(_, error)| error)?;
+    cut_window(decoded, first, start_ms, end_ms)
+}
+
+fn to_frame(ms: f64, sample_rate: u32) -> usize {
+    (ms * sample_rate as f64 / 1000.0).round() as usize
+}
+
+/// Keeps the frames of `decoded` from `start_ms` to `end_ms`, where its
+/// first frame is frame `first` of the file.
+fn cut_window(
+    mut decoded: AudioCombinerSingleFile,
+    first: usize,
+    start_ms: f64,
+    end_ms: f64,
+) -> Result<AudioCombinerSingleFile, CombineError> {
+    let frames = first + decoded.samples.len() / 2;
+    let end = to_frame(end_ms, decoded.sample_rate);
+    if end > frames {
+        return Err(CombineError::SliceOutOfRange {
+            start_ms,
+            end_ms,
+            duration_ms: frames as f64 * 1000.0 / decoded.sample_rate as f64,
+        });
+    }
+    decoded.samples.truncate((end - first) * 2);
+    decoded
+        .samples
+        .drain(..(to_frame(start_ms, decoded.sample_rate) - first) * 2);
+    Ok(decoded)
+}
+
+/// Decodes a file as far as `end_ms`, or to its end, from shortly before
+/// `start_ms` where the container can seek there and from the top
+/// otherwise. Gives the decoded frames and which frame of the file the
+/// first of them is.
+fn decode_range(
+    index: usize,
+    file: SingleAudioFile,
+    start_ms: f64,
+    end_ms: Option<f64>,
+    check: &mut impl FnMut(f64) -> Result<(), CombineError>,
+) -> Result<(AudioCombinerSingleFile, usize), (DecodeStage, CombineError)> {
+    if file.r#type == SingleAudioFileType::Pcm {
+        return decode_pcm(index, file)
+            .map(|decoded| (decoded, 0))
+            .map_err(|e| (DecodeStage::Probe, e));
+    }
+    if start_ms > SEEK_PREROLL_MS {
+        if let Some(decoded) = decode_range_seeking(index, file.clone(), start_ms, end_ms, check)? {
+            return Ok(decoded);
+        }
+    }
+
+    let mut decoder = FileDecoder::open(index, file).map_err(|e| (DecodeStage::Probe, e))?;
+    if end_ms.is_none() {
+        decoder.reserve_track();
+    }
+    let mut decode = || {
+        while decoder.sample_rate().is_none_or(|rate| {
+            end_ms.is_none_or(|end_ms| decoder.decoded_frames() < to_frame(end_ms, rate))
+        }) {
+            if !decoder.decode_packet(None)? {
+                break;
+            }
+            let rate = decoder.sample_rate().unwrap_or(MASTER_SAMPLE_RATE);
+            check(decoder.decoded_frames() as f64 * 1000.0 / rate as f64)?;
+        }
+        Ok(())
+    };
+    decode().map_err(|e| (DecodeStage::Decode, e))?;
+    decoder
+        .finish()
+        .map(|decoded| (decoded, 0))
+        .map_err(|e| (DecodeStage::Decode, e))
+}
+
+/// `decode_range` by seeking to shortly before `start_ms`, or `None` when
+/// the container cannot seek there.
+///
+/// Timestamps say where the decoded frames are in the file, so the first
+/// frame is counted back from the end of the last packet.
+fn decode_range_seeking(
+    index: usize,
+    file: SingleAudioFile,
+    start_ms: f64,
+    end_ms: Option<f64>,
+    check: &mut impl FnMut(f64) -> Result<(), CombineError>,
+) -> Result<Option<(AudioCombinerSingleFile, usize)>, (DecodeStage, CombineError)> {
+    let mut decoder = FileDecoder::open(index, file).map_err(|e| (DecodeStage::Probe, e))?;
+    let mut decode = || -> Result<Option<u32>, CombineError> {
+        let Some(rate) = decoder.seek_before(start_ms - SEEK_PREROLL_MS)? else {
+            return Ok(None);
+        };
+        let end = end_ms.map(|end_ms| to_frame(end_ms, rate) as u64);
+        while end.is_none_or(|end| decoder.position < end) {
+            if !decoder.decode_packet(None)? {
+                break;
+            }
+            check(decoder.position as f64 * 1000.0 / rate as f64)?;
+        }
+        Ok(Some(rate))
+    };
+    let Some(rate) = decode().map_err(|e| (DecodeStage::Decode, e))? else {
+        return Ok(None);
+    };
+    let Some(first) = (decoder.position as usize).checked_sub(decoder.decoded_frames()) else {
+        return Ok(None);
+    };
+    if first > to_frame(start_ms, rate) {
+        return Ok(None);
+    }
+    decoder
+        .finish()
+        .map(|decoded| Some((decoded, first)))
+        .map_err(|e| (DecodeStage::Decode, e))
+}
+
+/// How much of each file `validate` decodes, in seconds.
+const VALIDATE_SECONDS: u32 = 1;
+
+/// Checks that a file opens and that its first `VALIDATE_SECONDS` of audio
+/// decode, without decoding the rest.
+///
+/// Files that end before the length in their headers within that time are
+/// reported as truncated.
+pub fn validate(index: usize, file: SingleAudioFile) -> Result<(), CombineError> {
+    if file.r#type == SingleAudioFileType::Pcm {
+        return match file.pcm_spec {
+            Some(_) => Ok(()),
+            None => Err(pcm_spec_missing(index)),
+        };
+    }
+
+    let mut decoder = FileDecoder::open(index, file)?;
+    loop {
+        if !decoder.decode_packet(None)? {
+            return decoder.check_complete();
+        }
+        let limit = decoder
+            .sample_rate
+            .map_or(0, |rate| rate * VALIDATE_SECONDS);
+        if decoder.decoded_frames() >= limit as usize {
+            return Ok(());
+        }
+    }
+}
+
+/// Measures peak and RMS level a packet at a time, so only one packet of
+/// the file is ever decoded at once.
+pub fn levels(file: SingleAudioFile) -> Result<Levels, CombineError> {
+    let mut meter = LevelMeter::default();
+    if file.r#type == SingleAudioFileType::Pcm {
+        let (sample_rate, channels) = file.pcm_spec.ok_or_else(|| pcm_spec_missing(0))?;
+        for block in file.bytes.chunks(4 * 4096) {
+            let samples: Vec<f32> = block
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect();
+            meter.add(&samples);
+        }
+        return Ok(meter.levels(sample_rate, channels));
+    }
+
+    let mut decoder = FileDecoder::open(0, file)?;
+    while decoder.decode_packet(None)? {
+        let frames = decoder.decoded_frames();
+        meter.add(decoder.take_frames(frames).as_slice());
+    }
+    let sample_rate = decoder
+        .sample_rate
+        .ok_or_else(|| CombineError::DecodeFailed {
+            index: 0,
+            detail: "unknown sample rate".into(),
+        })?;
+    Ok(meter.levels(sample_rate, 2))
+}
+
+/// Reads the newest tags and cover art from ahead of the container and from
+/// the container itself, without decoding any audio.
+pub fn metadata(file: &SingleAudioFile) -> Result<FileMetadata, CombineError> {
+    let mut metadata = FileMetadata::default();
+    if file.r#type == SingleAudioFileType::Pcm {
+        return Ok(metadata);
+    }
+    let mut probed = probe_format(0, file.r#type, file.bytes.clone())?;
+    if let Some(revision) = probed
+        .metadata
+        .get()
+        .as_mut()
+        .and_then(|log| log.skip_to_latest())
+    {
+        metadata.add(revision);
+    }
+    if let Some(revision) = probed.format.metadata().skip_to_latest() {
+        metadata.add(revision);
+    }
+    Ok(metadata)
+}
+
+/// Cap on `FileDecoder::reserve_track`, about an hour of stereo at 48 kHz,
+/// so a corrupt length cannot exhaust memory before decoding starts.
+const MAX_RESERVED_SAMPLES: usize = 2 * 48000 * 3600;
+
+/// Packet-at-a-time decoder for one compressed file, so async callers can
+/// yield to the event loop in between.
+pub struct FileDecoder {
+    index: usize,
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    sample_buf: Option<SampleBuffer<f32>>,
+    /// Rate of the whole decoded file, set by its first packet.
+    sample_rate: Option<u32>,
+    /// Rate and first sample of the current link of a chained stream, which
+    /// is resampled to `sample_rate` when it ends if the two differ.
+    chain_rate: Option<u32>,
+    chain_start: usize,
+    time_base: Option<TimeBase>,
+    n_frames: Option<u64>,
+    /// End of the last decoded packet, in the track's time base.
+    position: u64,
+    reported_frames: usize,
+    mono: bool,
+    decoded_samples: Vec<f32>,
+    /// Skip corrupt packets instead of failing on them.
+    tolerate_errors: bool,
+    warnings: Vec<DecodeWarning>,
+}
+
+/// The bytes of a `SingleAudioFile`, which symphonia reads without a copy.
+struct SharedBytes(Arc<Vec<u8>>);
+
+impl AsRef<[u8]> for SharedBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Opens the container of a compressed file.
+fn open_format(
+    index: usize,
+    r#type: SingleAudioFileType,
+    bytes: Arc<Vec<u8>>,
+) -> Result<Box<dyn FormatReader>, CombineError> {
+    Ok(probe_format(index, r#type, bytes)?.format)
+}
+
+/// Opens the container of a compressed file, keeping any tags found ahead of
+/// it, such as ID3v2.
+fn probe_format(
+    index: usize,
+    r#type: SingleAudioFileType,
+    bytes: Arc<Vec<u8>>,
+) -> Result<ProbeResult, CombineError> {
+    if let (false, Some((codec, feature))) = (r#type.built_in(), r#type.codec_feature()) {
+        return Err(CombineError::CodecDisabled {
+            index,
+            codec,
+            feature,
+        });
+    }
+    let extension = r#type.extension();
+    // On a seekable source symphonia's Ogg reader looks for the end of the
+    // first link and comes back without its packets, so chained files are
+    // read front to back instead. They lose their length up front.
+    let src: Box<dyn MediaSource> = if is_chained_ogg(&bytes) {
+        Box::new(ReadOnlySource::new(std::io::Cursor::new(SharedBytes(
+            bytes,
+        ))))
+    } else {
+        Box::new(std::io::Cursor::new(SharedBytes(bytes)))
+    };
+    let mss = symphonia::core::io::MediaSourceStream::new(src, Default::default());
+
+    let mut hint = symphonia::core::probe::Hint::new();
+    if let Some(extension) = extension {
+        hint.with_extension(extension);
+    }
+
+    // Gapless reading drops the encoder delay and padding recorded in the
+    // stream, such as an MP3's LAME tag, so back to back files join cleanly.
+    let format_options = FormatOptions {
+        enable_gapless: true,
+        ..Default::default()
+    };
+    utils::catch_panic(
+        || format!("file {} panicked while probing", index),
+        || symphonia::default::get_probe().format(&hint, mss, &format_options, &Default::default()),
+    )?
+    .map_err(|e| CombineError::UnsupportedFormat {
+        index,
+        container: extension,
+        detail: e.to_string(),
+    })
+}
+
+/// Whether `bytes` are an Ogg file with more than one link, that is a page
+/// starting a stream after pages of an earlier one. Only page headers are
+/// read.
+fn is_chained_ogg(bytes: &[u8]) -> bool {
+    let mut offset = 0;
+    let mut past_first_pages = false;
+    while let Some(header) = bytes.get(offset..offset + 27) {
+        if &header[..4] != b"OggS" {
+            return false;
+        }
+        let first_page = header[5] & 0x02 != 0;
+        if first_page && past_first_pages {
+            return true;
+        }
+        past_first_pages |= !first_page;
+        let segments = header[26] as usize;
+        let table = match bytes.get(offset + 27..offset + 27 + segments) {
+            Some(table) => table,
+            None => return false,
+        };
+        offset += 27 + segments + table.iter().map(|&len| len as usize).sum::<usize>();
+    }
+    false
+}
+
+/// Containers such as MP4 may carry several tracks, so pick the first one
+/// with an audio codec rather than trusting the default track.
+fn audio_track(index: usize, format: &dyn FormatReader) -> Result<&Track, CombineError> {
+    format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or(CombineError::NoAudioTrack { index })
+}
+
+/// Channel count from an AAC AudioSpecificConfig, which HE-AAC shares even
+/// though it cannot be decoded.
+fn aac_channels(config: &[u8]) -> Option<u16> {
+    let bits = u64::from_be_bytes({
+        let mut bytes = [0; 8];
+        let len = config.len().min(8);
+        bytes[..len].copy_from_slice(&config[..len]);
+        bytes
+    });
+    let field = |offset: u32, len: u32| (bits >> (64 - offset - len)) & ((1 << len) - 1);
+    // An escaped object type shifts everything after it.
+    if field(0, 5) == 31 {
+        return None;
+    }
+    // An escaped frequency index is followed by an explicit 24-bit rate.
+    let offset = if field(5, 4) == 15 { 33 } else { 9 };
+    match field(offset, 4) {
+        0 => None,
+        7 => Some(8),
+        n => Some(n as u16),
+    }
+}
+
+/// Decodes packets until one from the track yields its channel layout.
+fn first_packet_channels(format: &mut dyn FormatReader, params: CodecParameters) -> Option<u16> {
+    let mut decoder = codecs().make(&params, &Default::default()).ok()?;
+    let track_id = format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec == params.codec)?
+        .id;
+    while let Ok(packet) = format.next_packet() {
+        if packet.track_id() == track_id {
+            if let Ok(decoded) = decoder.decode(&packet) {
+                return Some(decoded.spec().channels.count() as u16);
+            }
+        }
+    }
+    None
+}
+
+/// Reads a file's properties from its container headers, decoding at most
+/// until the first packet when the headers leave out the channel layout.
+pub fn probe(file: &SingleAudioFile) -> Result<FileInfo, CombineError> {
+    if let Some((sample_rate, channels)) = file.pcm_spec {
+        let frames = file.bytes.len() / 4 / channels as usize;
+        return Ok(FileInfo {
+            duration_ms: Some(frames as f64 * 1000.0 / sample_rate as f64),
+            sample_rate,
+            channels,
+            codec: "pcm_f32le".into(),
+        });
+    }
+
+    let mut format = open_format(0, file.r#type, file.bytes.clone())?;
+    let params = audio_track(0, format.as_ref())?.codec_params.clone();
+    let sample_rate = params.sample_rate.ok_or(CombineError::UnknownProperty {
+        field: "sample_rate",
+    })?;
+    // MP4 and Matroska leave the layout to the codec's own config.
+    let channels = match params.channels {
+        Some(channels) => Some(channels.count() as u16),
+        None if params.codec == CODEC_TYPE_AAC => {
+            params.extra_data.as_deref().and_then(aac_channels)
+        }
+        None => first_packet_channels(format.as_mut(), params.clone()),
+    }
+    .ok_or(CombineError::UnknownProperty { field: "channels" })?;
+    // HE-AAC shares AAC's codec type, so name it before the registry does.
+    let codec = unsupported_codec(&params)
+        .or_else(|| {
+            codecs()
+                .get_codec(params.codec)
+                .map(|codec| codec.short_name)
+        })
+        .ok_or(CombineError::UnknownProperty { field: "codec" })?;
+    // Ogg Opus counts the pre-skip its decoder drops.
+    let skipped = match params.codec {
+        CODEC_TYPE_OPUS => params.delay.unwrap_or(0) as u64,
+        _ => 0,
+    };
+    let duration_ms = params.n_frames.map(|frames| {
+        timestamp_ms(
+            frames.saturating_sub(skipped),
+            params.time_base,
+            sample_rate,
+        )
+    });
+    Ok(FileInfo {
+        duration_ms,
+        sample_rate,
+        channels,
+        codec: codec.into(),
+    })
+}
+
+impl FileDecoder {
+    /// Probes the container and sets up a decoder for its first audio track.
+    pub fn open(index: usize, file: SingleAudioFile) -> Result<Self, CombineError> {
+        // No container can be read from nothing.
+        if file.bytes.is_empty() {
+            return Err(CombineError::EmptyFile { index });
+        }
+        let tolerate_errors = file.tolerate_decode_errors;
+        let format = open_format(index, file.r#type, file.bytes)?;
+        let track = audio_track(index, format.as_ref())?;
+        if let Some(codec) = unsupported_codec(&track.codec_params) {
+            return Err(CombineError::UnsupportedCodec {
+                index,
+                codec: codec.into(),
+            });
+        }
+        let decoder = codecs()
+            .make(&track.codec_params, &Default::default())
+            .map_err(|e| decode_error(index, e))?;
+
+        Ok(Self {
+            index,
+            track_id: track.id,
+            sample_rate: track.codec_params.sample_rate,
+            chain_rate: None,
+            chain_start: 0,
+            time_base: track.codec_params.time_base,
+            n_frames: track.codec_params.n_frames,
+            format,
+            decoder,
+            sample_buf: None,
+            position: 0,
+            reported_frames: 0,
+            mono: false,
+            decoded_samples: Vec::new(),
+            tolerate_errors,
+            warnings: Vec::new(),
+        })
+    }
+
+    /// Replaces the decoder after the stream changed under it, as at the
+    /// start of a new link in a chained Ogg file.
+    fn reset_decoder(&mut self) -> Result<(), CombineError> {
+        self.end_chain();
+        let index = self.index;
+        let track = audio_track(index, self.format.as_ref())?;
+        self.track_id = track.id;
+        self.time_base = track.codec_params.time_base;
+        // Timestamps and lengths start over with each link.
+        self.n_frames = track.codec_params.n_frames;
+        self.position = 0;
+        self.decoder = codecs()
+            .make(&track.codec_params, &Default::default())
+            .map_err(|e| decode_error(index, e))?;
+        Ok(())
+    }
+
+    /// Brings the samples of the link that just ended to the file's rate.
+    fn end_chain(&mut self) {
+        if let (Some(rate), Some(chain_rate)) = (self.sample_rate, self.chain_rate) {
+            if chain_rate != rate {
+                let chain = resample::resample(
+                    &self.decoded_samples[self.chain_start..],
+                    rate as f64 / chain_rate as f64,
+                );
+                self.decoded_samples.truncate(self.chain_start);
+                self.decoded_samples.extend(chain);
+            }
+        }
+        self.chain_rate = None;
+        self.chain_start = self.decoded_samples.len();
+    }
+
+    /// Fails on a corrupt stretch of the stream at `ts`, or records it as a
+    /// warning when errors are tolerated.
+    fn corrupt(&mut self, ts: u64, error: Error) -> Result<(), CombineError> {
+        let timestamp_ms = timestamp_ms(ts, self.time_base, self.sample_rate.unwrap_or(1));
+        if !self.tolerate_errors {
+            return Err(CombineError::CorruptPacket {
+                index: self.index,
+                timestamp_ms,
+                detail: error.to_string(),
+            });
+        }
+        self.warnings.push(DecodeWarning {
+            index: self.index,
+            timestamp_ms,
+            message: error.to_string(),
+        });
+        Ok(())
+    }
+
+    /// Decodes the next packet, returning `false` once the stream has ended.
+    pub fn decode_packet(
+        &mut self,
+        progress: Option<&js_sys::Function>,
+    ) -> Result<bool, CombineError> {
+        let index = self.index;
+        let format = &mut self.format;
+        let packet = match utils::catch_panic(
+            || format!("file {} panicked while demuxing", index),
+            || format.next_packet(),
+        )? {
+            Ok(packet) => packet,
+            // How every demuxer reports the end of the stream.
+            Err(Error::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                return Ok(false)
+            }
+            Err(Error::ResetRequired) => {
+                self.reset_decoder()?;
+                return Ok(true);
+            }
+            // A demuxer cannot find its way past a broken container, so the
+            // track ends here even when errors are tolerated.
+            Err(e) => {
+                self.corrupt(self.position, e)?;
+                return Ok(false);
+            }
+        };
+        if packet.track_id() != self.track_id {
+            return Ok(true);
+        }
+        let decoder = &mut self.decoder;
+        let decoded = match utils::catch_panic(
+            || format!("file {} panicked while decoding", index),
+            || decoder.decode(&packet),
+        )? {
+            Ok(decoded) => decoded,
+            Err(Error::ResetRequired) => {
+                self.reset_decoder()?;
+                return Ok(true);
+            }
+            Err(e) if is_corrupt(&e, self.sample_buf.is_some()) => {
+                self.corrupt(packet.ts(), e)?;
+                // Silence in place of the packet keeps the rest of the file
+                // in time.
+                if let Some(rate) = self.sample_rate {
+                    let ms = timestamp_ms(packet.dur(), self.time_base, rate);
+                    let frames = (ms * rate as f64 / 1000.0).round() as usize;
+                    let len = self.decoded_samples.len();
+                    self.decoded_samples.resize(len + frames * 2, 0.0);
+                }
+                self.position = packet.ts() + packet.dur();
+                return Ok(true);
+            }
+            Err(e) => return Err(decode_error(index, e)),
+        };
+        let spec = *decoded.spec();
+        self.position = packet.ts() + packet.dur();
+        let num_channels = spec.channels.count();
+        if self.sample_buf.is_none() {
+            self.sample_rate = Some(spec.rate);
+        }
+        self.chain_rate = Some(spec.rate);
+        self.mono = num_channels == 1;
+
+        // Packets are usually the same size, so one buffer serves them all
+        // unless a larger one turns up.
+        let needed = decoded.capacity() * num_channels;
+        if self
+            .sample_buf
+            .as_ref()
+            .is_none_or(|b| b.capacity() < needed)
+        {
+            self.sample_buf = Some(SampleBuffer::new(decoded.capacity() as u64, spec));
+        }
+        let buf = self.sample_buf.as_mut().unwrap();
+        buf.copy_interleaved_ref(decoded);
+
+        // Convert everything to Stereo (2 channels) during ingestion
+        let gains = downmix_gains(spec.channels);
+        for frame in buf.samples().chunks(num_channels) {
+            let (mut left, mut right) = (0.0, 0.0);
+            for (&sample, gain) in frame.iter().zip(&gains) {
+                left += sample * gain[0];
+                right += sample * gain[1];
+            }
+            self.decoded_samples.push(left);
+            self.decoded_samples.push(right);
+        }
+
+        if let (Some(progress), Some(n_frames)) = (progress, self.n_frames) {
+            let frames = self.decoded_samples.len() / 2;
+            if frames - self.reported_frames >= (spec.rate / PROGRESS_CALLS_PER_SECOND) as usize {
+                self.reported_frames = frames;
+                report_progress(
+                    progress,
+                    index,
+                    (self.position as f64 / n_frames as f64).min(1.0),
+                )?;
+            }
+        }
+        Ok(true)
+    }
+
+    /// Jumps to a packet at or shortly before `ms`, where decoding carries
+    /// on from, and returns the sample rate. `None` means the container
+    /// could not seek there, which can leave it anywhere.
+    ///
+    /// Only tracks that count time in frames are sought, so the frames
+    /// decoded next can be placed exactly from their timestamps. Vorbis is
+    /// left alone: decoded from the top it keeps frames its granule
+    /// positions leave out, so the two would not line up. Opus is too, as
+    /// its timestamps count the pre-skip its decoder drops.
+    ///
+    /// The seek is accurate rather than coarse. A coarse MP3 seek guesses the
+    /// timestamp from the byte offset, while an accurate one reads only frame
+    /// headers on the way, which is still far cheaper than decoding.
+    pub fn seek_before(&mut self, ms: f64) -> Result<Option<u32>, CombineError> {
+        let Some(rate) = self.sample_rate else {
+            return Ok(None);
+        };
+        let track = audio_track(self.index, self.format.as_ref())?;
+        if [CODEC_TYPE_VORBIS, CODEC_TYPE_OPUS].contains(&track.codec_params.codec)
+            || self
+                .time_base
+                .is_some_and(|time_base| time_base != TimeBase::new(1, rate))
+        {
+            return Ok(None);
+        }
+        let to = SeekTo::Time {
+            time: Time::from(ms / 1000.0),
+            track_id: Some(self.track_id),
+        };
+        let (index, format) = (self.index, &mut self.format);
+        let seeked = match utils::catch_panic(
+            || format!("file {} panicked while seeking", index),
+            || format.seek(SeekMode::Accurate, to),
+        )? {
+            Ok(seeked) => seeked,
+            Err(_) => return Ok(None),
+        };
+        if seeked.track_id != self.track_id || seeked.actual_ts > seeked.required_ts {
+            return Ok(None);
+        }
+        // Symphonia's decoders must start over after a seek.
+        self.decoder.reset();
+        self.position = seeked.actual_ts;
+        Ok(Some(rate))
+    }
+
+    /// Allocates room for the whole track up front when its length is known,
+    /// instead of growing as packets are decoded.
+    pub fn reserve_track(&mut self) {
+        if let Some(n_frames) = self.n_frames {
+            let samples = (n_frames as usize).saturating_mul(2);
+            self.decoded_samples
+                .reserve(samples.min(MAX_RESERVED_SAMPLES));
+        }
+    }
+
+    /// Frames decoded and not yet taken.
+    pub fn decoded_frames(&self) -> usize {
+        self.decoded_samples.len() / 2
+    }
+
+    /// Removes up to `frames` of the oldest decoded frames.
+    pub fn take_frames(&mut self, frames: usize) -> std::vec::Drain<'_, f32> {
+        let end = (frames * 2).min(self.decoded_samples.len());
+        self.chain_start = self.chain_start.saturating_sub(end);
+        self.decoded_samples.drain(..end)
+    }
+
+    /// Rate of the decoded audio, once the headers or a packet have said.
+    pub fn sample_rate(&self) -> Option<u32> {
+        self.sample_rate
+    }
+
+    /// Whether the last decoded packet had one channel.
+    pub fn mono(&self) -> bool {
+        self.mono
+    }
+
+    /// Fails when the stream ended before the length in its headers.
+    pub fn check_complete(&self) -> Result<(), CombineError> {
+        match self.n_frames {
+            Some(n_frames) if self.position < n_frames => Err(CombineError::Truncated {
+                index: self.index,
+                position: self.position,
+                n_frames,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Hands over everything decoded. A stream without a single frame of
+    /// audio becomes silence with a warning rather than an error.
+    pub fn finish(mut self) -> Result<AudioCombinerSingleFile, CombineError> {
+        self.end_chain();
+        let index = self.index;
+        if self.decoded_samples.is_empty() {
+            self.warnings.push(DecodeWarning {
+                index,
+                timestamp_ms: 0.0,
+                message: "no audio frames, treated as silence".into(),
+            });
+        }
+        let sample_rate = match self.sample_rate {
+            Some(rate) => rate,
+            None if self.decoded_samples.is_empty() => MASTER_SAMPLE_RATE,
+            None => {
+                return Err(CombineError::DecodeFailed {
+                    index,
+                    detail: "unknown sample rate".into(),
+                })
+            }
+        };
+        Ok(AudioCombinerSingleFile {
+            samples: self.decoded_samples,
+            sample_rate,
+            mono: self.mono,
+            decode_ms: 0.0,
+            warnings: self.warnings,
+            replaygain: OnceLock::new(),
+        })
+    }
+}
+
+/// ITU-R BS.775 gain for centre and surround channels folded into stereo.
+const DOWNMIX_GAIN: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+/// Left/right gains for each channel of a layout, in interleaved order.
+///
+/// Mono is copied to both sides. Otherwise left and right channels keep
+/// unity gain, every other left- or right-side speaker is folded into its side
+/// and centre speakers into both at `DOWNMIX_GAIN`, and LFE is dropped as in
+/// the ITU downmix.
+fn downmix_gains(channels: Channels) -> Vec<[f32; 2]> {
+    if channels.count() == 1 {
+        return vec![[1.0, 1.0]];
+    }
+    let lefts = Channels::REAR_LEFT
+        | Channels::FRONT_LEFT_CENTRE
+        | Channels::SIDE_LEFT
+        | Channels::TOP_FRONT_LEFT
+        | Channels::TOP_REAR_LEFT
+        | Channels::REAR_LEFT_CENTRE
+        | Channels::FRONT_LEFT_WIDE
+        | Channels::FRONT_LEFT_HIGH;
+    let rights = Channels::REAR_RIGHT
+        | Channels::FRONT_RIGHT_CENTRE
+        | Channels::SIDE_RIGHT
+        | Channels::TOP_FRONT_RIGHT
+        | Channels::TOP_REAR_RIGHT
+        | Channels::REAR_RIGHT_CENTRE
+        | Channels::FRONT_RIGHT_WIDE
+        | Channels::FRONT_RIGHT_HIGH;
+    let lfe = Channels::LFE1 | Channels::LFE2;
+
+    // `Channels::iter` yields speakers in bit order, which is also the order
+    // symphonia interleaves them in.
+    channels
+        .iter()
+        .map(|channel| {
+            if channel == Channels::FRONT_LEFT {
+                [1.0, 0.0]
+            } else if channel == Channels::FRONT_RIGHT {
+                [0.0, 1.0]
+            } else if lefts.contains(channel) {
+                [DOWNMIX_GAIN, 0.0]
+            } else if rights.contains(channel) {
+                [0.0, DOWNMIX_GAIN]
+            } else if lfe.contains(channel) {
+                [0.0, 0.0]
+            } else {
+                [DOWNMIX_GAIN, DOWNMIX_GAIN]
+            }
+        })
+        .collect()
+}
+
+fn pcm_spec_missing(index: usize) -> CombineError {
+    CombineError::DecodeFailed {
+        index,
+        detail: "raw PCM input must be created with from_pcm_f32".into(),
+    }
+}
+
+/// Reads the little-endian f32 samples stored by `SingleAudioFile::from_pcm_f32`.
+pub fn decode_pcm(
+    index: usize,
+    file: SingleAudioFile,
+) -> Result<AudioCombinerSingleFile, CombineError> {
+    let (sample_rate, channels) = file.pcm_spec.ok_or_else(|| pcm_spec_missing(index))?;
+    let samples = file
+        .bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+    Ok(AudioCombinerSingleFile {
+        samples: match channels {
+            1 => samples.flat_map(|s| [s, s]).collect(),
+            _ => samples.collect(),
+        },
+        sample_rate,
+        mono: channels == 1,
+        decode_ms: 0.0,
+        warnings: Vec::new(),
+        replaygain: OnceLock::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The Vorbis WebM fixture with one byte of its headers changed, which
+    /// makes symphonia's Matroska reader unwrap a failed integer conversion.
+    #[cfg(feature = "ogg-vorbis")]
+    const PROBE_PANIC_WEBM: &[u8] =
+        include_bytes!("../tests/fixtures/malformed_mkv_probe_panic.webm");
+
+    #[test]
+    #[cfg(feature = "ogg-vorbis")]
+    fn panics_while_probing_are_internal_errors() {
+        let file = SingleAudioFile::new(PROBE_PANIC_WEBM.to_vec(), SingleAudioFileType::Webm);
+        match probe(&file) {
+            Err(CombineError::Internal { detail }) => {
+                assert!(
+                    detail.starts_with("file 0 panicked while probing: "),
+                    "{}",
+                    detail
+                );
+            }
+            other => panic!("expected an internal error, got {:?}", other),
+        }
+        let file = SingleAudioFile::new(PROBE_PANIC_WEBM.to_vec(), SingleAudioFileType::Webm);
+        assert_eq!(validate(3, file).unwrap_err().code(), "INTERNAL_ERROR");
+    }
+
+    #[test]
+    fn types_built_without_their_codec_are_rejected() {
+        for r#type in [
+            SingleAudioFileType::Wav,
+            SingleAudioFileType::Mpeg,
+            SingleAudioFileType::Ogg,
+            SingleAudioFileType::Flac,
+            SingleAudioFileType::Aac,
+            SingleAudioFileType::Webm,
+            SingleAudioFileType::Aiff,
+        ] {
+            // Not a file of any type, so the probe fails if it runs at all.
+            let file = SingleAudioFile::new(vec![0; 64], r#type);
+            let error = validate(2, file).unwrap_err();
+            let expected = match r#type.built_in() {
+                true => "UNSUPPORTED_FORMAT",
+                false => "CODEC_DISABLED",
+            };
+            assert_eq!(error.code(), expected, "{:?}", r#type);
+            assert_eq!(error.index(), Some(2));
+        }
+        if !cfg!(feature = "aac") {
+            let file = SingleAudioFile::new(vec![0; 64], SingleAudioFileType::Aac);
+            assert_eq!(
+                probe(&file).unwrap_err().to_string(),
+                "file 0: built without AAC support; enable the `aac` feature"
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(all(
+        feature = "mp3",
+        feature = "aac",
+        feature = "flac",
+        feature = "ogg-vorbis"
+    ))]
+    fn seeking_to_a_window_gives_the_frames_of_a_decode_from_the_top() {
+        for (bytes, r#type) in [
+            (
+                &include_bytes!("../tests/fixtures/sine_440_stereo_44100.mp3")[..],
+                SingleAudioFileType::Mpeg,
+            ),
+            (
+                include_bytes!("../tests/fixtures/sine_440_stereo_44100.m4a"),
+                SingleAudioFileType::Aac,
+            ),
+            (
+                include_bytes!("../tests/fixtures/sine_440_stereo_44100_24bit.flac"),
+                SingleAudioFileType::Flac,
+            ),
+        ] {
+            let file = || SingleAudioFile::new(bytes.to_vec(), r#type);
+            let (from_top, _) = decode_range(0, file(), 0.0, Some(900.0), &mut |_| Ok(())).unwrap();
+            let from_top = cut_window(from_top, 0, 600.0, 900.0).unwrap();
+            let (seeking, first) =
+                decode_range_seeking(0, file(), 600.0, Some(900.0), &mut |_| Ok(()))
+                    .unwrap()
+                    .unwrap_or_else(|| panic!("{:?} did not seek", r#type));
+            let seeking = cut_window(seeking, first, 600.0, 900.0).unwrap();
+            assert_eq!(seeking.samples.len(), to_frame(300.0, 44100) * 2);
+            assert_eq!(seeking.samples, from_top.samples, "{:?}", r#type);
+
+            // Trimmed as a track would be, with an end past the file's
+            // clamped to it rather than out of range.
+            let whole = decode_file_staged(0, file(), &MixTrack::new(), |_| Ok(())).unwrap();
+            let mut track = MixTrack::new();
+            track.trim_start_ms = 600.0;
+            track.trim_end_ms = Some(60_000.0);
+            let trimmed = decode_file_staged(0, file(), &track, |_| Ok(())).unwrap();
+            let window = track.trim(whole.samples.len() / 2, 44100);
+            assert_eq!(
+                trimmed.samples,
+                whole.samples[window.start * 2..window.end * 2],
+                "{:?}",
+                r#type
+            );
+        }
+        let ogg = SingleAudioFile::new(
+            include_bytes!("../tests/fixtures/sine_440_stereo_44100.ogg").to_vec(),
+            SingleAudioFileType::Ogg,
+        );
+        assert!(
+            decode_range_seeking(0, ogg, 600.0, Some(900.0), &mut |_| Ok(()))
+                .unwrap()
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,47 @@
+//! Triangular (TPDF) dither for 16-bit output.
+
+/// Noise source for `quantize_i16`, giving the same noise for the same seed.
+pub struct Dither {
+    state: u32,
+}
+
+impl Dither {
+    pub fn new(seed: u32) -> Self {
+        Self { state: seed }
+    }
+
+    /// Next value of a counter run through an integer hash, which is
+    /// well mixed from any seed, zero included.
+    fn next_u32(&mut self) -> u32 {
+        self.state = self.state.wrapping_add(0x9e37_79b9);
+        let mut z = self.state;
+        z = (z ^ (z >> 16)).wrapping_mul(0x21f0_aaad);
+        z = (z ^ (z >> 15)).wrapping_mul(0x735a_2d97);
+        z ^ (z >> 15)
+    }
+
+    /// Uniform in `0.0..1.0`.
+    fn next_unit(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
+
+    /// Triangular noise between -1 and 1 LSB, the difference of two uniform
+    /// draws.
+    fn next_tpdf(&mut self) -> f32 {
+        self.next_unit() - self.next_unit()
+    }
+
+    /// Appends samples to `out` as little-endian 16-bit PCM with dither
+    /// added before rounding, clamped to full scale.
+    ///
+    /// Rounding rather than truncating keeps the noise centred on zero, so
+    /// silence comes out as a floor of -1, 0 and 1.
+    pub fn quantize_i16(&mut self, samples: &[f32], out: &mut Vec<u8>) {
+        for &sample in samples {
+            let scaled = sample.clamp(-1.0, 1.0) * i16::MAX as f32 + self.next_tpdf();
+            // `as` saturates, so rounding past full scale stays in range.
+            let s = scaled.round() as i16;
+            out.extend_from_slice(&s.to_le_bytes());
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::envelope::coefficient;
+use crate::{db_to_linear, CombineError};
+
+/// Turns a track down while another one is playing, set on the track to duck
+/// through `MixTrack::ducking`.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Ducking {
+    /// Index of the file whose level triggers the ducking.
+    pub source: u32,
+    /// Source level, in dBFS, above which the track is ducked.
+    #[serde(default = "defaults::threshold_db")]
+    pub threshold_db: f32,
+    /// How far the track is turned down while ducked, in dB.
+    #[serde(default = "defaults::amount_db")]
+    pub amount_db: f32,
+    /// Time constant for turning down once the source crosses the threshold.
+    #[serde(default = "defaults::attack_ms")]
+    pub attack_ms: f32,
+    /// Time constant for recovering once it drops back under.
+    #[serde(default = "defaults::release_ms")]
+    pub release_ms: f32,
+}
+
+/// `Ducking::new`'s values for fields a config leaves out. The source has
+/// no sensible default, so it is required.
+mod defaults {
+    pub fn threshold_db() -> f32 {
+        super::Ducking::new(0).threshold_db
+    }
+
+    pub fn amount_db() -> f32 {
+        super::Ducking::new(0).amount_db
+    }
+
+    pub fn attack_ms() -> f32 {
+        super::Ducking::new(0).attack_ms
+    }
+
+    pub fn release_ms() -> f32 {
+        super::Ducking::new(0).release_ms
+    }
+}
+
+#[wasm_bindgen]
+impl Ducking {
+    /// Ducks by 12 dB whenever `source` is above -30 dBFS.
+    #[wasm_bindgen(constructor)]
+    pub fn new(source: u32) -> Self {
+        Self {
+            source,
+            threshold_db: -30.0,
+            amount_db: 12.0,
+            attack_ms: 10.0,
+            release_ms: 300.0,
+        }
+    }
+}
+
+impl Ducking {
+    pub(crate) fn validate(&self, index: usize, files: usize) -> Result<(), CombineError> {
+        let invalid = |detail: String| Err(CombineError::InvalidTrack { index, detail });
+        let source = self.source as usize;
+        if source >= files || source == index {
+            return invalid(format!(
+                "ducking source must be another file's index, got {}",
+                source
+            ));
+        }
+        if !self.threshold_db.is_finite() {
+            return invalid(format!(
+                "ducking threshold_db must be a number, got {}",
+                self.threshold_db
+            ));
+        }
+        for &(name, value) in &[
+            ("amount_db", self.amount_db),
+            ("attack_ms", self.attack_ms),
+            ("release_ms", self.release_ms),
+        ] {
+            if !value.is_finite() || value < 0.0 {
+                return invalid(format!(
+                    "ducking {} must not be negative, got {}",
+                    name, value
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Gain for each of `frames` master frames, keyed by the interleaved
+    /// `source` samples with each channel scaled by its entry of
+    /// `source_gains`, starting at frame `source_start`.
+    pub(crate) fn gains(
+        &self,
+        source: &[f32],
+        source_start: usize,
+        source_gains: [f32; 2],
+        frames: usize,
+        sample_rate: u32,
+    ) -> Vec<f32> {
+        let threshold = db_to_linear(self.threshold_db);
+        let ducked = db_to_linear(-self.amount_db);
+        let attack = coefficient(self.attack_ms, sample_rate) as f32;
+        let release = coefficient(self.release_ms, sample_rate) as f32;
+
+        let mut gain = 1.0;
+        (0..frames)
+            .map(|n| {
+                let level = n
+                    .checked_sub(source_start)
+                    .and_then(|i| source.get(i * 2..i * 2 + 2))
+                    .map_or(0.0, |frame| {
+                        (frame[0] * source_gains[0])
+                            .abs()
+                            .max((frame[1] * source_gains[1]).abs())
+                    });
+                let target = if level > threshold { ducked } else { 1.0 };
+                let coefficient = if target < gain { attack } else { release };
+                gain = target + (gain - target) * coefficient;
+                gain
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,12 @@
+//! The one-pole smoothing shared by the ducking, the noise gate, the
+//! compressor and the limiter.
+
+/// Per-sample coefficient of a one-pole follower with a `time_ms` time
+/// constant at `sample_rate`, or 0 to follow instantly.
+pub(crate) fn coefficient(time_ms: f32, sample_rate: u32) -> f64 {
+    if time_ms > 0.0 {
+        (-1000.0 / (time_ms as f64 * sample_rate as f64)).exp()
+    } else {
+        0.0
+    }
+}
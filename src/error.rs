@@ -0,0 +1,389 @@
+use std::fmt;
+
+use wasm_bindgen::prelude::*;
+
+/// Everything that can go wrong while building, decoding or combining.
+///
+/// In JS it arrives as an `Error` named `CombineError` whose `code` property
+/// is one of the stable strings returned by `CombineError::code`, plus an
+/// `index` property naming the offending input file where there is one.
+/// `TIMED_OUT` errors also carry a `processedMs` array of how far each file
+/// was decoded.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CombineError {
+    /// `SingleAudioFile::from_bytes` did not recognise the magic bytes.
+    UnknownFormat,
+    /// The compressed file at `index` has no bytes at all.
+    EmptyFile { index: usize },
+    /// There are no files to combine.
+    EmptyInput,
+    /// The container could not be probed. `container` is the extension it
+    /// was expected to have, or `None` when the type was `Auto`.
+    UnsupportedFormat {
+        index: usize,
+        container: Option<&'static str>,
+        detail: String,
+    },
+    /// The container has no track with a codec symphonia knows.
+    NoAudioTrack { index: usize },
+    /// The track's codec is recognised but cannot be decoded.
+    UnsupportedCodec { index: usize, codec: String },
+    /// The file's type needs `codec`, which this build left out; the cargo
+    /// `feature` builds it in.
+    CodecDisabled {
+        index: usize,
+        codec: &'static str,
+        feature: &'static str,
+    },
+    /// The stream is corrupt or otherwise failed mid-decode.
+    DecodeFailed { index: usize, detail: String },
+    /// The stream is corrupt `timestamp_ms` into the track and the file does
+    /// not tolerate decode errors.
+    CorruptPacket {
+        index: usize,
+        timestamp_ms: f64,
+        detail: String,
+    },
+    /// The stream ended at `position` though its headers promise `n_frames`,
+    /// both in the track's time base.
+    Truncated {
+        index: usize,
+        position: u64,
+        n_frames: u64,
+    },
+    /// `SingleAudioFile::from_pcm_f32` was given an unusable spec.
+    InvalidPcm { detail: String },
+    /// `combine_files` was given `buffers` buffers but `types` types.
+    TypeCountMismatch { buffers: usize, types: usize },
+    /// `SingleAudioFile::from_base64` or `from_data_url` could not read the
+    /// base64 at byte `offset` of the string.
+    InvalidBase64 { offset: usize, detail: String },
+    /// `SingleAudioFile::from_data_url` was given something other than a
+    /// base64 `data:` URL.
+    InvalidDataUrl { detail: String },
+    /// `SingleAudioFile::from_url` could not reach `url`, or lost the
+    /// connection partway. Browsers report a request blocked by CORS this
+    /// way too, without saying so.
+    FetchFailed { url: String, detail: String },
+    /// The stream given to `SingleAudioFile::from_stream` errored or gave
+    /// something other than bytes.
+    ReadFailed { detail: String },
+    /// `SingleAudioFile::from_url` got a response outside 200-299. In JS,
+    /// `status` is also on the error.
+    HttpStatus {
+        url: String,
+        status: u16,
+        status_text: String,
+    },
+    /// `CombineOptions::require_explicit_volumes` is set and the file at
+    /// `index` has no volume.
+    MissingVolume { index: usize },
+    /// The gain for the file at `index` is negative or not a finite number.
+    InvalidGain { index: usize, gain: f32 },
+    /// A `MixTrack` field other than the gain is out of range.
+    InvalidTrack { index: usize, detail: String },
+    /// The file at `index` is at `found` Hz where `expected` Hz is needed,
+    /// as in a streamed combine, which cannot resample.
+    SampleRateMismatch {
+        index: usize,
+        found: u32,
+        expected: u32,
+    },
+    /// `SingleAudioFile::slice` was asked for a window running past the
+    /// end of a file `duration_ms` long.
+    SliceOutOfRange {
+        start_ms: f64,
+        end_ms: f64,
+        duration_ms: f64,
+    },
+    /// A `CombineOptions` field is out of range.
+    InvalidOption { detail: String },
+    /// `AudioCombiner::import_project` could not read the project, or was
+    /// given a different number of files than it names.
+    InvalidProject { detail: String },
+    /// The file at `index` has the id `found` where the project saved
+    /// `expected`.
+    ProjectFileMismatch {
+        index: usize,
+        expected: String,
+        found: String,
+    },
+    /// The output encoder reported an error.
+    EncodeFailed { detail: String },
+    /// The WAV output of `bytes` is too big for RIFF's 32-bit sizes without
+    /// `CombineOptions::rf64`, or for memory.
+    TooLarge { bytes: u64 },
+    /// The requested output format was not compiled in.
+    OutputDisabled { format: &'static str },
+    /// `SingleAudioFile::probe` found no value for `field` in the headers.
+    UnknownProperty { field: &'static str },
+    /// No file has this index.
+    IndexOutOfRange { index: usize, len: usize },
+    /// A progress callback threw.
+    CallbackFailed { index: usize, detail: String },
+    /// The callback given chunks by `AudioCombiner::combine_streamed` threw.
+    ChunkCallbackFailed { detail: String },
+    /// An `AbortSignal` cancelled the operation.
+    Cancelled,
+    /// `CombineOptions::max_ms` ran out while the file at `index` was being
+    /// decoded, or with no `index` once they all were, `processed_ms` into
+    /// each file.
+    TimedOut {
+        index: Option<usize>,
+        max_ms: f64,
+        processed_ms: Vec<f64>,
+    },
+    /// A dependency panicked on input it should have rejected, such as a
+    /// malformed container. Only reported where the build can unwind; see
+    /// `init`.
+    Internal { detail: String },
+}
+
+impl CombineError {
+    /// Machine-checkable identifier, exposed to JS as `error.code`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            CombineError::UnknownFormat => "UNKNOWN_FORMAT",
+            CombineError::EmptyFile { .. } => "EMPTY_FILE",
+            CombineError::EmptyInput => "EMPTY_INPUT",
+            CombineError::UnsupportedFormat { .. } => "UNSUPPORTED_FORMAT",
+            CombineError::NoAudioTrack { .. } => "NO_AUDIO_TRACK",
+            CombineError::UnsupportedCodec { .. } => "UNSUPPORTED_CODEC",
+            CombineError::CodecDisabled { .. } => "CODEC_DISABLED",
+            CombineError::DecodeFailed { .. } => "DECODE_FAILED",
+            CombineError::CorruptPacket { .. } => "CORRUPT_PACKET",
+            CombineError::Truncated { .. } => "TRUNCATED",
+            CombineError::InvalidPcm { .. } => "INVALID_PCM",
+            CombineError::TypeCountMismatch { .. } => "TYPE_COUNT_MISMATCH",
+            CombineError::InvalidBase64 { .. } => "INVALID_BASE64",
+            CombineError::InvalidDataUrl { .. } => "INVALID_DATA_URL",
+            CombineError::FetchFailed { .. } => "FETCH_FAILED",
+            CombineError::HttpStatus { .. } => "HTTP_STATUS",
+            CombineError::ReadFailed { .. } => "READ_FAILED",
+            CombineError::MissingVolume { .. } => "MISSING_VOLUME",
+            CombineError::InvalidGain { .. } => "INVALID_GAIN",
+            CombineError::InvalidTrack { .. } => "INVALID_TRACK",
+            CombineError::SampleRateMismatch { .. } => "SAMPLE_RATE_MISMATCH",
+            CombineError::SliceOutOfRange { .. } => "SLICE_OUT_OF_RANGE",
+            CombineError::InvalidOption { .. } => "INVALID_OPTION",
+            CombineError::InvalidProject { .. } => "INVALID_PROJECT",
+            CombineError::ProjectFileMismatch { .. } => "PROJECT_FILE_MISMATCH",
+            CombineError::EncodeFailed { .. } => "ENCODE_FAILED",
+            CombineError::TooLarge { .. } => "TOO_LARGE",
+            CombineError::OutputDisabled { .. } => "OUTPUT_DISABLED",
+            CombineError::UnknownProperty { .. } => "UNKNOWN_PROPERTY",
+            CombineError::IndexOutOfRange { .. } => "INDEX_OUT_OF_RANGE",
+            CombineError::CallbackFailed { .. } | CombineError::ChunkCallbackFailed { .. } => {
+                "CALLBACK_FAILED"
+            }
+            CombineError::Cancelled => "CANCELLED",
+            CombineError::TimedOut { .. } => "TIMED_OUT",
+            CombineError::Internal { .. } => "INTERNAL_ERROR",
+        }
+    }
+
+    /// Index of the input file the error is about, if any.
+    pub fn index(&self) -> Option<usize> {
+        match *self {
+            CombineError::EmptyFile { index }
+            | CombineError::UnsupportedFormat { index, .. }
+            | CombineError::NoAudioTrack { index }
+            | CombineError::UnsupportedCodec { index, .. }
+            | CombineError::CodecDisabled { index, .. }
+            | CombineError::DecodeFailed { index, .. }
+            | CombineError::CorruptPacket { index, .. }
+            | CombineError::Truncated { index, .. }
+            | CombineError::MissingVolume { index }
+            | CombineError::InvalidGain { index, .. }
+            | CombineError::InvalidTrack { index, .. }
+            | CombineError::SampleRateMismatch { index, .. }
+            | CombineError::ProjectFileMismatch { index, .. }
+            | CombineError::IndexOutOfRange { index, .. }
+            | CombineError::CallbackFailed { index, .. } => Some(index),
+            CombineError::TimedOut { index, .. } => index,
+            _ => None,
+        }
+    }
+
+    #[cfg(any(feature = "ogg-output", feature = "mp3-output"))]
+    pub(crate) fn encode_failed(error: impl fmt::Display) -> Self {
+        CombineError::EncodeFailed {
+            detail: error.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for CombineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CombineError::UnknownFormat => write!(f, "could not detect format"),
+            CombineError::EmptyFile { index } => write!(f, "file {} is empty", index),
+            CombineError::EmptyInput => write!(f, "no files to combine"),
+            CombineError::UnsupportedFormat {
+                index,
+                container: Some(container),
+                detail,
+            } => write!(
+                f,
+                "file {}: could not read {} container: {}",
+                index, container, detail
+            ),
+            CombineError::UnsupportedFormat {
+                index,
+                container: None,
+                detail,
+            } => write!(f, "could not detect format for file {}: {}", index, detail),
+            CombineError::NoAudioTrack { index } => {
+                write!(f, "file {}: no supported audio track", index)
+            }
+            CombineError::UnsupportedCodec { index, codec } => {
+                write!(f, "file {}: codec not supported: {}", index, codec)
+            }
+            CombineError::CodecDisabled {
+                index,
+                codec,
+                feature,
+            } => write!(
+                f,
+                "file {}: built without {} support; enable the `{}` feature",
+                index, codec, feature
+            ),
+            CombineError::DecodeFailed { index, detail } => write!(f, "file {}: {}", index, detail),
+            CombineError::CorruptPacket {
+                index,
+                timestamp_ms,
+                detail,
+            } => write!(
+                f,
+                "file {}: corrupt data at {:.0} ms: {}",
+                index, timestamp_ms, detail
+            ),
+            CombineError::Truncated {
+                index,
+                position,
+                n_frames,
+            } => write!(
+                f,
+                "file {}: stream ends at {} of {}, the file may be truncated",
+                index, position, n_frames
+            ),
+            CombineError::MissingVolume { index } => {
+                write!(f, "missing volume for file {}", index)
+            }
+            CombineError::InvalidGain { index, gain } => {
+                write!(f, "invalid gain for file {}: {}", index, gain)
+            }
+            CombineError::InvalidTrack { index, detail } => {
+                write!(f, "file {}: {}", index, detail)
+            }
+            CombineError::SampleRateMismatch {
+                index,
+                found,
+                expected,
+            } => write!(
+                f,
+                "file {} is at {} Hz but {} Hz is needed",
+                index, found, expected
+            ),
+            CombineError::SliceOutOfRange {
+                start_ms,
+                end_ms,
+                duration_ms,
+            } => write!(
+                f,
+                "slice {:.1} to {:.1} ms runs past the end of a {:.1} ms file",
+                start_ms, end_ms, duration_ms
+            ),
+            CombineError::InvalidPcm { detail }
+            | CombineError::InvalidOption { detail }
+            | CombineError::EncodeFailed { detail } => write!(f, "{}", detail),
+            CombineError::TypeCountMismatch { buffers, types } => {
+                write!(f, "got {} buffers but {} types", buffers, types)
+            }
+            CombineError::InvalidBase64 { offset, detail } => {
+                write!(f, "invalid base64 at byte {}: {}", offset, detail)
+            }
+            CombineError::InvalidDataUrl { detail } => write!(f, "invalid data URL: {}", detail),
+            CombineError::FetchFailed { url, detail } => write!(
+                f,
+                "fetching {} failed: {} (if it is on another origin, check that it \
+                 allows CORS requests from this page)",
+                url, detail
+            ),
+            CombineError::HttpStatus {
+                url,
+                status,
+                status_text,
+            } => write!(f, "fetching {} gave HTTP {} {}", url, status, status_text),
+            CombineError::ReadFailed { detail } => {
+                write!(f, "reading the stream failed: {}", detail)
+            }
+            CombineError::InvalidProject { detail } => write!(f, "invalid project: {}", detail),
+            CombineError::ProjectFileMismatch {
+                index,
+                expected,
+                found,
+            } => write!(
+                f,
+                "file {} is {:?} but the project saved {:?}",
+                index, found, expected
+            ),
+            CombineError::TooLarge { bytes } => {
+                write!(f, "WAV output of {} bytes is too large", bytes)
+            }
+            CombineError::OutputDisabled { format } => write!(f, "{} output not enabled", format),
+            CombineError::UnknownProperty { field } => {
+                write!(f, "could not determine {} from the headers", field)
+            }
+            CombineError::IndexOutOfRange { index, len } => {
+                write!(f, "no file at index {} of {}", index, len)
+            }
+            CombineError::CallbackFailed { index, detail } => {
+                write!(f, "file {}: progress callback threw: {}", index, detail)
+            }
+            CombineError::ChunkCallbackFailed { detail } => {
+                write!(f, "chunk callback threw: {}", detail)
+            }
+            CombineError::Cancelled => write!(f, "cancelled"),
+            CombineError::TimedOut {
+                index: Some(index),
+                max_ms,
+                processed_ms,
+            } => write!(
+                f,
+                "ran past the {} ms budget {:.1} ms into file {}",
+                max_ms, processed_ms[*index], index
+            ),
+            CombineError::TimedOut {
+                index: None,
+                max_ms,
+                ..
+            } => write!(f, "ran past the {} ms budget before mixing", max_ms),
+            CombineError::Internal { detail } => write!(f, "internal error: {}", detail),
+        }
+    }
+}
+
+impl std::error::Error for CombineError {}
+
+impl From<CombineError> for JsValue {
+    fn from(error: CombineError) -> Self {
+        let js_error = js_sys::Error::new(&error.to_string());
+        js_error.set_name("CombineError");
+        let index = match error.index() {
+            Some(index) => JsValue::from(index as u32),
+            None => JsValue::UNDEFINED,
+        };
+        // Setting properties on a fresh Error object cannot fail.
+        let _ = js_sys::Reflect::set(&js_error, &"code".into(), &error.code().into());
+        let _ = js_sys::Reflect::set(&js_error, &"index".into(), &index);
+        if let CombineError::TimedOut { processed_ms, .. } = &error {
+            let processed = js_sys::Float64Array::from(processed_ms.as_slice());
+            let _ = js_sys::Reflect::set(&js_error, &"processedMs".into(), &processed);
+        }
+        if let CombineError::HttpStatus { status, .. } = &error {
+            let _ = js_sys::Reflect::set(&js_error, &"status".into(), &(*status).into());
+        }
+        js_error.into()
+    }
+}
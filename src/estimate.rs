@@ -0,0 +1,162 @@
+//! Length and size of a mix worked out from container headers alone, for
+//! `AudioCombiner::estimate_duration` and `estimate_output_size`.
+
+use wasm_bindgen::prelude::*;
+
+use crate::smpl::LoopPoints;
+use crate::track::{self, Layout};
+use crate::{cue, ogg, wav, CombineError, CombineOptions, FileInfo, MixTrack, OutputFormat};
+
+/// How long a combine of probed files would come out.
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq)]
+pub struct DurationEstimate {
+    pub(crate) file_ms: Vec<f64>,
+    pub(crate) total_ms: f64,
+    pub(crate) complete: bool,
+    /// `total_ms` in frames at the output rate.
+    frames: usize,
+    /// (file index, first frame) of each audible file, as in
+    /// `CombineStats::file_starts`.
+    file_starts: Vec<(usize, usize)>,
+    /// Whether WAV output would have one channel, as `CombineStats::mono`.
+    mono: bool,
+}
+
+#[wasm_bindgen]
+impl DurationEstimate {
+    /// Each file's length in the mix after its trim, speed and loops, in
+    /// ms, or `NaN` when its headers give no length. Muted files are listed
+    /// too, though they take no room in the mix.
+    #[wasm_bindgen(getter)]
+    pub fn file_ms(&self) -> Vec<f64> {
+        self.file_ms.clone()
+    }
+
+    /// Length of the whole mix, in ms, counting files of unknown length as
+    /// empty.
+    #[wasm_bindgen(getter)]
+    pub fn total_ms(&self) -> f64 {
+        self.total_ms
+    }
+
+    /// Whether every file in the mix has a known length and no
+    /// `auto_trim_silence`, so `total_ms` is only off by any encoder
+    /// padding the headers leave out.
+    #[wasm_bindgen(getter)]
+    pub fn complete(&self) -> bool {
+        self.complete
+    }
+}
+
+/// Lays out files of the lengths in `infos` as `AudioCombiner::mix` would.
+pub(crate) fn estimate(
+    infos: &[FileInfo],
+    tracks: Vec<MixTrack>,
+    options: &CombineOptions,
+) -> Result<DurationEstimate, CombineError> {
+    options.validate()?;
+    for (index, track) in tracks.iter().enumerate() {
+        track.validate(index, infos.len())?;
+    }
+    let mut tracks = tracks;
+    tracks.resize_with(infos.len(), MixTrack::default);
+    let rate = options.sample_rate;
+
+    let lengths: Vec<Option<usize>> = infos
+        .iter()
+        .zip(&tracks)
+        .map(|(info, track)| {
+            let ms = info.duration_ms?;
+            let frames = (ms * info.sample_rate as f64 / 1000.0).round() as usize;
+            Some(track.prepared_frames(frames, info.sample_rate, rate))
+        })
+        .collect();
+
+    let soloing = tracks.iter().any(|t| t.solo);
+    let (audible, audible_tracks): (Vec<Option<usize>>, Vec<MixTrack>) = lengths
+        .iter()
+        .zip(&tracks)
+        .filter(|(_, track)| track.is_audible(soloing))
+        .map(|(&frames, track)| (frames, track.clone()))
+        .unzip();
+    let indices = (0..tracks.len()).filter(|&i| tracks[i].is_audible(soloing));
+    let complete = audible.iter().all(Option::is_some)
+        && audible_tracks.iter().all(|t| t.auto_trim_silence.is_none());
+    let frames: Vec<usize> = audible.iter().map(|f| f.unwrap_or(0)).collect();
+    let layout = Layout::new(&frames, &audible_tracks, options);
+
+    let to_ms = |frames: usize| frames as f64 * 1000.0 / rate as f64;
+    Ok(DurationEstimate {
+        file_ms: lengths.iter().map(|f| f.map_or(f64::NAN, to_ms)).collect(),
+        total_ms: to_ms(layout.frames),
+        complete,
+        frames: layout.frames,
+        file_starts: indices.into_iter().zip(layout.starts).collect(),
+        mono: track::is_mono_mix(&tracks, |i| infos[i].channels() == 1),
+    })
+}
+
+/// How big the output of a combine of probed files would come out.
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SizeEstimate {
+    pub(crate) bytes: f64,
+    pub(crate) is_estimate: bool,
+}
+
+#[wasm_bindgen]
+impl SizeEstimate {
+    #[wasm_bindgen(getter)]
+    pub fn bytes(&self) -> f64 {
+        self.bytes
+    }
+
+    /// Whether `bytes` is approximate: for compressed output, whose size
+    /// depends on the encoder, or when a duration was not `complete`.
+    #[wasm_bindgen(getter)]
+    pub fn is_estimate(&self) -> bool {
+        self.is_estimate
+    }
+}
+
+/// Sizes the output of a mix as long as `estimate` makes it: exactly for
+/// WAV, header and tags included, and from the nominal bitrate for
+/// compressed formats.
+pub(crate) fn output_size(
+    infos: &[FileInfo],
+    tracks: Vec<MixTrack>,
+    options: &CombineOptions,
+) -> Result<SizeEstimate, CombineError> {
+    let duration = estimate(infos, tracks, options)?;
+    let kbps_bytes = |kbps: f64| (kbps * 1000.0 / 8.0 * duration.total_ms / 1000.0).round();
+    let bytes = match options.format {
+        OutputFormat::Wav => {
+            let channels = if duration.mono { 1 } else { 2 };
+            let data_size = duration.frames as u64
+                * channels as u64
+                * options.bit_depth.bytes_per_sample() as u64;
+            let header = wav::wav_header(
+                data_size,
+                options.sample_rate,
+                channels,
+                options.bit_depth,
+                wav::WavTags {
+                    bext: options.bext.as_ref(),
+                    // Only the size of the chunk matters here.
+                    sample_loop: options.sample_loop.map(|_| LoopPoints::default()),
+                    metadata: options.metadata.as_ref(),
+                    cues: &cue::cue_points(options, &duration.file_starts, duration.frames),
+                },
+                options.rf64,
+            )?;
+            (header.len() as u64 + data_size) as f64
+        }
+        OutputFormat::Mpeg => kbps_bytes(options.mp3_bitrate.kbps() as f64),
+        OutputFormat::Ogg => kbps_bytes(ogg::nominal_kbps(options.quality)),
+    };
+    Ok(SizeEstimate {
+        bytes,
+        is_estimate: options.format != OutputFormat::Wav || !duration.complete,
+    })
+}
@@ -0,0 +1,63 @@
+//! Downloads for `SingleAudioFile::from_url`, read off the response body a
+//! chunk at a time into wasm memory, so the whole file never sits in a JS
+//! `ArrayBuffer` as well.
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+
+use crate::decode::thrown_message;
+use crate::{ingest, CombineError};
+
+#[wasm_bindgen]
+extern "C" {
+    /// The global `fetch`, which windows, workers and Node all have.
+    #[wasm_bindgen(js_name = fetch)]
+    fn fetch_with_init(url: &str, init: &web_sys::RequestInit) -> js_sys::Promise;
+}
+
+/// A downloaded body and the `Content-Type` it came with.
+pub struct Download {
+    pub bytes: Vec<u8>,
+    pub content_type: Option<String>,
+}
+
+fn failed(url: &str, thrown: &JsValue) -> CombineError {
+    CombineError::FetchFailed {
+        url: url.into(),
+        detail: thrown_message(thrown),
+    }
+}
+
+/// GETs `url`, failing on any status outside 200-299.
+pub async fn download(
+    url: &str,
+    signal: Option<&web_sys::AbortSignal>,
+) -> Result<Download, CombineError> {
+    let init = web_sys::RequestInit::new();
+    init.set_signal(signal);
+    let response: web_sys::Response = match JsFuture::from(fetch_with_init(url, &init)).await {
+        Ok(response) => response.unchecked_into(),
+        Err(_) if signal.is_some_and(|s| s.aborted()) => return Err(CombineError::Cancelled),
+        Err(thrown) => return Err(failed(url, &thrown)),
+    };
+    if !response.ok() {
+        return Err(CombineError::HttpStatus {
+            url: url.into(),
+            status: response.status(),
+            status_text: response.status_text(),
+        });
+    }
+    let header = |name| response.headers().get(name).ok().flatten();
+    let bytes = match response.body() {
+        Some(body) => {
+            let size = header("content-length").and_then(|l| l.parse().ok());
+            ingest::read_to_end(&body, size, signal, |thrown| failed(url, thrown)).await?
+        }
+        None => Vec::new(),
+    };
+    Ok(Download {
+        bytes,
+        content_type: header("content-type"),
+    })
+}
@@ -0,0 +1,281 @@
+//! High- and low-pass filters for `MixTrack::highpass_hz` and `lowpass_hz`,
+//! the DC blocker for `remove_dc`, and the three-band `Equalizer`.
+
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::CombineError;
+
+/// Q of the two biquads that make up a fourth-order Butterworth filter,
+/// which falls 24 dB per octave past the cutoff with a flat passband.
+const BUTTERWORTH_Q: [f64; 2] = [0.541_196_100_146_197, 1.306_562_964_876_376_5];
+
+/// Highest cutoff as a fraction of the sample rate, just under Nyquist.
+const MAX_CUTOFF: f64 = 0.49;
+
+/// Cutoff of `remove_dc`, far enough under the lowest notes of a bass or kick
+/// drum to leave them alone.
+const DC_CUTOFF_HZ: f64 = 5.0;
+
+/// One second-order section, normalised so `a0` is 1. The filters here take
+/// RBJ cookbook coefficients; the loudness meter builds its K-weighting from
+/// BS.1770's analogue prototypes.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+}
+
+/// Sine and cosine of the angular frequency of `freq_hz`, held just under
+/// Nyquist.
+fn angle(freq_hz: f64, sample_rate: u32) -> (f64, f64) {
+    let freq = freq_hz.min(sample_rate as f64 * MAX_CUTOFF);
+    (2.0 * PI * freq / sample_rate as f64).sin_cos()
+}
+
+impl Biquad {
+    pub(crate) fn normalized(b: [f64; 3], a: [f64; 3]) -> Self {
+        Self {
+            b: b.map(|b| b / a[0]),
+            a: [a[1] / a[0], a[2] / a[0]],
+        }
+    }
+
+    fn pass(highpass: bool, cutoff_hz: f64, q: f64, sample_rate: u32) -> Self {
+        let (sin, cos) = angle(cutoff_hz, sample_rate);
+        let alpha = sin / (2.0 * q);
+        let b = if highpass {
+            [(1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0]
+        } else {
+            [(1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0]
+        };
+        Self::normalized(b, [1.0 + alpha, -2.0 * cos, 1.0 - alpha])
+    }
+
+    /// Boosts or cuts by `gain_db` around `freq_hz`.
+    fn peak(freq_hz: f64, gain_db: f64, q: f64, sample_rate: u32) -> Self {
+        let a = 10f64.powf(gain_db / 40.0);
+        let (sin, cos) = angle(freq_hz, sample_rate);
+        let alpha = sin / (2.0 * q);
+        Self::normalized(
+            [1.0 + alpha * a, -2.0 * cos, 1.0 - alpha * a],
+            [1.0 + alpha / a, -2.0 * cos, 1.0 - alpha / a],
+        )
+    }
+
+    /// Boosts or cuts by `gain_db` below `freq_hz` for a low shelf or above
+    /// it for a high one, with the steepest slope that does not overshoot.
+    /// At `freq_hz` itself the gain is half way.
+    fn shelf(high: bool, freq_hz: f64, gain_db: f64, sample_rate: u32) -> Self {
+        let a = 10f64.powf(gain_db / 40.0);
+        let (sin, cos) = angle(freq_hz, sample_rate);
+        let beta = a.sqrt() * sin * std::f64::consts::SQRT_2;
+        let (plus, minus) = (a + 1.0, a - 1.0);
+        if high {
+            Self::normalized(
+                [
+                    a * (plus + minus * cos + beta),
+                    -2.0 * a * (minus + plus * cos),
+                    a * (plus + minus * cos - beta),
+                ],
+                [
+                    plus - minus * cos + beta,
+                    2.0 * (minus - plus * cos),
+                    plus - minus * cos - beta,
+                ],
+            )
+        } else {
+            Self::normalized(
+                [
+                    a * (plus - minus * cos + beta),
+                    2.0 * a * (minus - plus * cos),
+                    a * (plus - minus * cos - beta),
+                ],
+                [
+                    plus + minus * cos + beta,
+                    -2.0 * (minus + plus * cos),
+                    plus + minus * cos - beta,
+                ],
+            )
+        }
+    }
+
+    /// Filters the next sample `x` of a signal in transposed direct form
+    /// II, keeping the signal's history in `state`, which starts at rest as
+    /// `[0.0; 2]`.
+    pub(crate) fn process(&self, x: f64, state: &mut [f64; 2]) -> f64 {
+        let y = self.b[0] * x + state[0];
+        state[0] = self.b[1] * x - self.a[0] * y + state[1];
+        state[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+
+    /// Filters one channel of interleaved stereo `samples` in place,
+    /// starting from rest.
+    fn run(&self, samples: &mut [f32], channel: usize) {
+        let mut state = [0.0; 2];
+        for frame in samples.chunks_exact_mut(2) {
+            frame[channel] = self.process(frame[channel] as f64, &mut state) as f32;
+        }
+    }
+}
+
+/// Cuts everything below `cutoff_hz` from interleaved stereo `samples` at
+/// `sample_rate`, each channel separately.
+pub fn highpass(samples: &mut [f32], cutoff_hz: f32, sample_rate: u32) {
+    butterworth(samples, true, cutoff_hz, sample_rate);
+}
+
+/// Cuts everything above `cutoff_hz`, like `highpass`.
+pub fn lowpass(samples: &mut [f32], cutoff_hz: f32, sample_rate: u32) {
+    butterworth(samples, false, cutoff_hz, sample_rate);
+}
+
+/// Takes any DC offset out of interleaved stereo `samples` at `sample_rate`
+/// with a one-pole high-pass at `DC_CUTOFF_HZ`, each channel separately.
+///
+/// Each channel starts as if its first sample had always been there, so an
+/// offset present from the start is removed without a thump while the
+/// filter settles.
+pub fn remove_dc(samples: &mut [f32], sample_rate: u32) {
+    let pole = (-2.0 * PI * DC_CUTOFF_HZ / sample_rate as f64).exp();
+    for channel in 0..2 {
+        let mut previous = samples.get(channel).map_or(0.0, |&s| s as f64);
+        let mut y = 0.0;
+        for frame in samples.chunks_exact_mut(2) {
+            let x = frame[channel] as f64;
+            y = x - previous + pole * y;
+            previous = x;
+            frame[channel] = y as f32;
+        }
+    }
+}
+
+/// Runs both sections of a fourth-order Butterworth filter over each
+/// channel. Cutoffs are held just under half the sample rate.
+fn butterworth(samples: &mut [f32], highpass: bool, cutoff_hz: f32, sample_rate: u32) {
+    for q in BUTTERWORTH_Q {
+        let section = Biquad::pass(highpass, cutoff_hz as f64, q, sample_rate);
+        for channel in 0..2 {
+            section.run(samples, channel);
+        }
+    }
+}
+
+/// Low shelf, mid peak and high shelf gains for `MixTrack::eq`. A band at
+/// 0 dB is skipped.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Equalizer {
+    /// Boost or cut below `low_freq`, in dB.
+    pub low_gain_db: f32,
+    /// Where the low shelf is half way, in Hz.
+    pub low_freq: f32,
+    /// Boost or cut around `mid_freq`, in dB.
+    pub mid_gain_db: f32,
+    pub mid_freq: f32,
+    /// Sharpness of the mid band; higher is narrower.
+    pub mid_q: f32,
+    /// Boost or cut above `high_freq`, in dB.
+    pub high_gain_db: f32,
+    /// Where the high shelf is half way, in Hz.
+    pub high_freq: f32,
+}
+
+#[wasm_bindgen]
+impl Equalizer {
+    /// A flat EQ with bands at 100 Hz, 1 kHz and 10 kHz.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self {
+            low_gain_db: 0.0,
+            low_freq: 100.0,
+            mid_gain_db: 0.0,
+            mid_freq: 1000.0,
+            mid_q: 1.0,
+            high_gain_db: 0.0,
+            high_freq: 10_000.0,
+        }
+    }
+}
+
+impl Default for Equalizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Equalizer {
+    pub(crate) fn validate(&self, index: usize) -> Result<(), CombineError> {
+        for (name, value) in [
+            ("low_gain_db", self.low_gain_db),
+            ("mid_gain_db", self.mid_gain_db),
+            ("high_gain_db", self.high_gain_db),
+        ] {
+            if !value.is_finite() {
+                return Err(CombineError::InvalidTrack {
+                    index,
+                    detail: format!("eq {} must be a number, got {}", name, value),
+                });
+            }
+        }
+        for (name, value) in [
+            ("low_freq", self.low_freq),
+            ("mid_freq", self.mid_freq),
+            ("mid_q", self.mid_q),
+            ("high_freq", self.high_freq),
+        ] {
+            if !value.is_finite() || value <= 0.0 {
+                return Err(CombineError::InvalidTrack {
+                    index,
+                    detail: format!("eq {} must be a positive number, got {}", name, value),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether every band is at 0 dB, so the EQ changes nothing.
+    pub(crate) fn is_flat(&self) -> bool {
+        self.low_gain_db == 0.0 && self.mid_gain_db == 0.0 && self.high_gain_db == 0.0
+    }
+
+    /// Runs each band that is not at 0 dB over interleaved stereo `samples`
+    /// at `sample_rate`, each channel separately.
+    pub(crate) fn apply(&self, samples: &mut [f32], sample_rate: u32) {
+        let bands = [
+            (self.low_gain_db != 0.0).then(|| {
+                Biquad::shelf(
+                    false,
+                    self.low_freq as f64,
+                    self.low_gain_db as f64,
+                    sample_rate,
+                )
+            }),
+            (self.mid_gain_db != 0.0).then(|| {
+                Biquad::peak(
+                    self.mid_freq as f64,
+                    self.mid_gain_db as f64,
+                    self.mid_q as f64,
+                    sample_rate,
+                )
+            }),
+            (self.high_gain_db != 0.0).then(|| {
+                Biquad::shelf(
+                    true,
+                    self.high_freq as f64,
+                    self.high_gain_db as f64,
+                    sample_rate,
+                )
+            }),
+        ];
+        for band in bands.iter().flatten() {
+            for channel in 0..2 {
+                band.run(samples, channel);
+            }
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//! The noise gate of `MixTrack::gate`, which silences a track between the
+//! passages that are loud enough to keep, such as hiss between sentences.
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::envelope::coefficient;
+use crate::{db_to_linear, CombineError};
+
+/// Turns a track down to `floor_db` while it stays under `threshold_db`.
+///
+/// Both channels share one gain, keyed by the louder of the two, so the
+/// stereo image does not wander as the gate opens and closes.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct NoiseGate {
+    /// Level, in dBFS, at or above which the gate opens.
+    pub threshold_db: f32,
+    /// Time constant for opening once the track crosses the threshold.
+    pub attack_ms: f32,
+    /// How long the gate stays open after the track was last above the
+    /// threshold, so short pauses and the troughs of low notes do not make
+    /// it chatter.
+    pub hold_ms: f32,
+    /// Time constant for closing once the hold is over.
+    pub release_ms: f32,
+    /// Gain of the closed gate in dB, or `None` for silence.
+    pub floor_db: Option<f32>,
+}
+
+#[wasm_bindgen]
+impl NoiseGate {
+    /// Silences anything under -50 dBFS for more than 100 ms.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Default for NoiseGate {
+    fn default() -> Self {
+        Self {
+            threshold_db: -50.0,
+            attack_ms: 1.0,
+            hold_ms: 100.0,
+            release_ms: 150.0,
+            floor_db: None,
+        }
+    }
+}
+
+impl NoiseGate {
+    pub(crate) fn validate(&self, index: usize) -> Result<(), CombineError> {
+        let invalid = |detail: String| Err(CombineError::InvalidTrack { index, detail });
+        if !self.threshold_db.is_finite() {
+            return invalid(format!(
+                "gate threshold_db must be a number, got {}",
+                self.threshold_db
+            ));
+        }
+        for &(name, value) in &[
+            ("attack_ms", self.attack_ms),
+            ("hold_ms", self.hold_ms),
+            ("release_ms", self.release_ms),
+        ] {
+            if !value.is_finite() || value < 0.0 {
+                return invalid(format!("gate {} must not be negative, got {}", name, value));
+            }
+        }
+        match self.floor_db {
+            Some(floor) if !floor.is_finite() || floor > 0.0 => invalid(format!(
+                "gate floor_db must be a number of dB up to 0, got {}",
+                floor
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Gates interleaved stereo `samples` at `sample_rate` in place.
+    pub(crate) fn apply(&self, samples: &mut [f32], sample_rate: u32) {
+        GateState::new(self, sample_rate).process(samples);
+    }
+}
+
+/// A gate part way through a track. Feeding it consecutive blocks gives the
+/// same result as feeding it the whole track at once.
+struct GateState {
+    threshold: f32,
+    floor: f32,
+    attack: f32,
+    release: f32,
+    hold_frames: usize,
+    /// Gain of the last frame.
+    gain: f32,
+    /// Frames left before the gate starts closing.
+    held: usize,
+}
+
+impl GateState {
+    /// A gate that starts closed, so a track that opens quietly is gated
+    /// from its first frame.
+    fn new(gate: &NoiseGate, sample_rate: u32) -> Self {
+        let floor = gate.floor_db.map_or(0.0, db_to_linear);
+        Self {
+            threshold: db_to_linear(gate.threshold_db),
+            floor,
+            attack: coefficient(gate.attack_ms, sample_rate) as f32,
+            release: coefficient(gate.release_ms, sample_rate) as f32,
+            hold_frames: (gate.hold_ms as f64 * sample_rate as f64 / 1000.0).round() as usize,
+            gain: floor,
+            held: 0,
+        }
+    }
+
+    /// Gates the next frames of the track, interleaved stereo, in place.
+    fn process(&mut self, samples: &mut [f32]) {
+        for frame in samples.chunks_exact_mut(2) {
+            let open = if frame[0].abs().max(frame[1].abs()) >= self.threshold {
+                self.held = self.hold_frames;
+                true
+            } else if self.held > 0 {
+                self.held -= 1;
+                true
+            } else {
+                false
+            };
+            let (target, coefficient) = match open {
+                true => (1.0, self.attack),
+                false => (self.floor, self.release),
+            };
+            self.gain = target + (self.gain - target) * coefficient;
+            frame[0] *= self.gain;
+            frame[1] *= self.gain;
+        }
+    }
+}
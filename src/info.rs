@@ -0,0 +1,271 @@
+use symphonia::core::meta::{MetadataRevision, StandardTagKey, StandardVisualKey};
+use wasm_bindgen::prelude::*;
+
+use crate::CombineError;
+
+/// Properties of a file read by `SingleAudioFile::probe`.
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileInfo {
+    pub(crate) duration_ms: Option<f64>,
+    pub(crate) sample_rate: u32,
+    pub(crate) channels: u16,
+    pub(crate) codec: String,
+}
+
+#[wasm_bindgen]
+impl FileInfo {
+    /// Length from the container headers, or `undefined` when the container
+    /// does not say, as with some streamed MP3 and Ogg files.
+    #[wasm_bindgen(getter)]
+    pub fn duration_ms(&self) -> Option<f64> {
+        self.duration_ms
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Channels in the source, before any downmix to stereo.
+    #[wasm_bindgen(getter)]
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    /// Symphonia's short codec name, such as `"mp3"` or `"vorbis"`, or the
+    /// name in `UNSUPPORTED_CODEC` errors for streams that cannot be decoded.
+    #[wasm_bindgen(getter)]
+    pub fn codec(&self) -> String {
+        self.codec.clone()
+    }
+}
+
+/// Outcome of checking one file with `AudioCombiner::validate`.
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileValidation {
+    pub(crate) index: usize,
+    pub(crate) error: Option<CombineError>,
+}
+
+#[wasm_bindgen]
+impl FileValidation {
+    #[wasm_bindgen(getter)]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn ok(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// `CombineError::code` of the failure, or `undefined` when the file is ok.
+    #[wasm_bindgen(getter)]
+    pub fn error_code(&self) -> Option<String> {
+        self.error.as_ref().map(|e| e.code().into())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn message(&self) -> Option<String> {
+        self.error.as_ref().map(|e| e.to_string())
+    }
+}
+
+/// Step at which a file left out by `CombineOptions::skip_failed_tracks`
+/// failed.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecodeStage {
+    /// Opening the container and finding an audio track, or reading the
+    /// spec of raw PCM.
+    Probe,
+    /// Decoding the track's packets.
+    Decode,
+}
+
+/// A file left out of the mix by `CombineOptions::skip_failed_tracks`.
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SkippedTrack {
+    pub(crate) index: usize,
+    pub(crate) stage: DecodeStage,
+    pub(crate) error: CombineError,
+}
+
+#[wasm_bindgen]
+impl SkippedTrack {
+    #[wasm_bindgen(getter)]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn stage(&self) -> DecodeStage {
+        self.stage
+    }
+
+    /// `CombineError::code` of the failure.
+    #[wasm_bindgen(getter)]
+    pub fn code(&self) -> String {
+        self.error.code().into()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn message(&self) -> String {
+        self.error.to_string()
+    }
+}
+
+/// One tag from a file's metadata, with its key as the container spells it,
+/// such as `"TIT2"` in ID3v2 or `"TITLE"` in a Vorbis comment.
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq)]
+pub struct MetadataTag {
+    pub(crate) key: String,
+    pub(crate) value: String,
+}
+
+#[wasm_bindgen]
+impl MetadataTag {
+    #[wasm_bindgen(getter)]
+    pub fn key(&self) -> String {
+        self.key.clone()
+    }
+
+    /// The value as text. Binary values are written out in hex.
+    #[wasm_bindgen(getter)]
+    pub fn value(&self) -> String {
+        self.value.clone()
+    }
+}
+
+/// An image embedded in a file's metadata.
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq)]
+pub struct CoverArt {
+    pub(crate) mime_type: String,
+    pub(crate) bytes: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl CoverArt {
+    #[wasm_bindgen(getter)]
+    pub fn mime_type(&self) -> String {
+        self.mime_type.clone()
+    }
+
+    /// The encoded image. Each read copies it out of wasm memory.
+    #[wasm_bindgen(getter)]
+    pub fn bytes(&self) -> Vec<u8> {
+        self.bytes.clone()
+    }
+}
+
+/// Tags and cover art read by `SingleAudioFile::metadata`. Every field is
+/// empty for a file without tags.
+#[wasm_bindgen]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FileMetadata {
+    pub(crate) title: Option<String>,
+    pub(crate) artist: Option<String>,
+    pub(crate) album: Option<String>,
+    pub(crate) tags: Vec<MetadataTag>,
+    pub(crate) cover_art: Option<CoverArt>,
+}
+
+impl FileMetadata {
+    /// Adds the tags and images of one metadata revision. Earlier revisions
+    /// keep their title, artist, album and cover.
+    pub(crate) fn add(&mut self, revision: &MetadataRevision) {
+        for tag in revision.tags() {
+            // RIFF INFO strings keep their NUL terminator.
+            let value = tag.value.to_string().trim_end_matches('\0').to_string();
+            let field = match tag.std_key {
+                Some(StandardTagKey::TrackTitle) => Some(&mut self.title),
+                Some(StandardTagKey::Artist) => Some(&mut self.artist),
+                Some(StandardTagKey::Album) => Some(&mut self.album),
+                _ => None,
+            };
+            if let Some(field) = field {
+                field.get_or_insert_with(|| value.clone());
+            }
+            self.tags.push(MetadataTag {
+                key: tag.key.clone(),
+                value,
+            });
+        }
+        // The front cover when it is marked, otherwise the first image.
+        let visuals = revision.visuals();
+        let cover = visuals
+            .iter()
+            .find(|v| v.usage == Some(StandardVisualKey::FrontCover))
+            .or_else(|| visuals.first());
+        if let (None, Some(cover)) = (&self.cover_art, cover) {
+            self.cover_art = Some(CoverArt {
+                mime_type: cover.media_type.clone(),
+                bytes: cover.data.to_vec(),
+            });
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl FileMetadata {
+    #[wasm_bindgen(getter)]
+    pub fn title(&self) -> Option<String> {
+        self.title.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn artist(&self) -> Option<String> {
+        self.artist.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn album(&self) -> Option<String> {
+        self.album.clone()
+    }
+
+    /// Every tag in the order the file lists them, including the title,
+    /// artist and album.
+    #[wasm_bindgen(getter)]
+    pub fn tags(&self) -> Vec<MetadataTag> {
+        self.tags.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn cover_art(&self) -> Option<CoverArt> {
+        self.cover_art.clone()
+    }
+}
+
+/// Corrupt data in a file that sets `tolerate_decode_errors`, passed over
+/// instead of failing the decode, or a file with no audio at all.
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecodeWarning {
+    pub(crate) index: usize,
+    pub(crate) timestamp_ms: f64,
+    pub(crate) message: String,
+}
+
+#[wasm_bindgen]
+impl DecodeWarning {
+    #[wasm_bindgen(getter)]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Where in the file the bad data starts.
+    #[wasm_bindgen(getter)]
+    pub fn timestamp_ms(&self) -> f64 {
+        self.timestamp_ms
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn message(&self) -> String {
+        self.message.clone()
+    }
+}
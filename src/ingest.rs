@@ -0,0 +1,66 @@
+//! Reads a JS `ReadableStream` of bytes into wasm memory a chunk at a time,
+//! for `SingleAudioFile::from_stream` and the body of `from_url`.
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+
+use crate::{now, yield_to_event_loop, CombineError, ASYNC_SLICE_MS};
+
+fn aborted(signal: Option<&web_sys::AbortSignal>) -> bool {
+    signal.is_some_and(|s| s.aborted())
+}
+
+/// Every byte of `stream`, with room for `size_hint` reserved up front.
+/// Hands back to the event loop every `ASYNC_SLICE_MS` of reading.
+///
+/// A read that rejects or a chunk that is not a `Uint8Array` becomes
+/// `failed(thrown)`, and an aborted `signal` cancels the stream and gives
+/// `CANCELLED`. Either way the bytes read so far are dropped.
+pub async fn read_to_end(
+    stream: &web_sys::ReadableStream,
+    size_hint: Option<usize>,
+    signal: Option<&web_sys::AbortSignal>,
+    failed: impl Fn(&JsValue) -> CombineError,
+) -> Result<Vec<u8>, CombineError> {
+    let mut bytes = Vec::new();
+    if let Some(size) = size_hint {
+        // Only a hint: a wrong or huge size must not abort the read.
+        let _ = bytes.try_reserve_exact(size);
+    }
+    let reader: web_sys::ReadableStreamDefaultReader = stream.get_reader().unchecked_into();
+    let mut slice_start = now();
+    loop {
+        if aborted(signal) {
+            // The source may still be producing; tell it to stop.
+            let _ = reader.cancel();
+            return Err(CombineError::Cancelled);
+        }
+        let result: web_sys::ReadableStreamReadResult = match JsFuture::from(reader.read()).await {
+            Ok(result) => result.unchecked_into(),
+            Err(_) if aborted(signal) => return Err(CombineError::Cancelled),
+            Err(thrown) => return Err(failed(&thrown)),
+        };
+        if result.get_done().unwrap_or(true) {
+            break;
+        }
+        let chunk = result
+            .get_value()
+            .dyn_into::<js_sys::Uint8Array>()
+            .map_err(|_| {
+                let _ = reader.cancel();
+                failed(&"stream chunk is not a Uint8Array".into())
+            })?;
+        let start = bytes.len();
+        bytes.resize(start + chunk.length() as usize, 0);
+        chunk.copy_to(&mut bytes[start..]);
+        if now() - slice_start >= ASYNC_SLICE_MS {
+            yield_to_event_loop().await;
+            slice_start = now();
+        }
+    }
+    if aborted(signal) {
+        return Err(CombineError::Cancelled);
+    }
+    Ok(bytes)
+}
@@ -0,0 +1,70 @@
+use wasm_bindgen::prelude::*;
+
+/// Peak and average level of a file, from `SingleAudioFile::levels`.
+///
+/// Silent files measure `-Infinity` for both levels.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Levels {
+    pub(crate) peak_dbfs: f32,
+    pub(crate) rms_dbfs: f32,
+    pub(crate) duration_ms: f64,
+}
+
+#[wasm_bindgen]
+impl Levels {
+    #[wasm_bindgen(getter)]
+    pub fn peak_dbfs(&self) -> f32 {
+        self.peak_dbfs
+    }
+
+    /// RMS over both channels of the whole file.
+    #[wasm_bindgen(getter)]
+    pub fn rms_dbfs(&self) -> f32 {
+        self.rms_dbfs
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn duration_ms(&self) -> f64 {
+        self.duration_ms
+    }
+
+    /// Whether every sample is zero, so there is no level to match.
+    #[wasm_bindgen(getter)]
+    pub fn is_silent(&self) -> bool {
+        self.peak_dbfs == f32::NEG_INFINITY
+    }
+}
+
+/// Running peak and mean square, fed interleaved samples a block at a time.
+#[derive(Default)]
+pub struct LevelMeter {
+    peak: f32,
+    sum_squares: f64,
+    samples: u64,
+}
+
+impl LevelMeter {
+    pub fn add(&mut self, samples: &[f32]) {
+        for &sample in samples {
+            self.peak = self.peak.max(sample.abs());
+            self.sum_squares += sample as f64 * sample as f64;
+        }
+        self.samples += samples.len() as u64;
+    }
+
+    /// Levels of everything added so far, taken as `channels` interleaved
+    /// channels at `sample_rate`.
+    pub fn levels(&self, sample_rate: u32, channels: u16) -> Levels {
+        let mean_square = match self.samples {
+            0 => 0.0,
+            n => self.sum_squares / n as f64,
+        };
+        let frames = self.samples / channels as u64;
+        Levels {
+            peak_dbfs: 20.0 * self.peak.log10(),
+            rms_dbfs: (10.0 * mean_square.log10()) as f32,
+            duration_ms: frames as f64 * 1000.0 / sample_rate as f64,
+        }
+    }
+}
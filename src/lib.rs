@@ -1,178 +1,1972 @@
+mod base64;
+mod bext;
+mod cache;
+mod capabilities;
+mod config;
+mod cue;
+mod deadline;
+mod decode;
+mod dither;
+mod ducking;
+mod envelope;
+mod error;
+mod estimate;
+mod fetch;
+mod filter;
+mod gate;
+mod info;
+mod ingest;
+mod levels;
+mod log;
+mod loudness;
+mod master;
+mod mp3;
+mod ogg;
+mod options;
+#[cfg(feature = "opus")]
+mod opus;
+mod project;
+mod replaygain;
+mod resample;
+mod silence;
+mod simd;
+mod smpl;
+mod stats;
+mod stream;
+mod sum;
+mod track;
 mod utils;
+mod wav;
+mod waveform;
+
+use std::sync::{Arc, OnceLock};
 
 use wasm_bindgen::prelude::*;
 
+use deadline::Deadline;
+
+pub use bext::BroadcastExtension;
+pub use capabilities::{capabilities, Capabilities};
+pub use config::MixConfig;
+pub use cue::Marker;
+pub use ducking::Ducking;
+pub use error::CombineError;
+pub use estimate::{DurationEstimate, SizeEstimate};
+pub use filter::Equalizer;
+pub use gate::NoiseGate;
+pub use info::{
+    CoverArt, DecodeStage, DecodeWarning, FileInfo, FileMetadata, FileValidation, MetadataTag,
+    SkippedTrack,
+};
+pub use levels::Levels;
+pub use log::{set_log_level, LogLevel};
+pub use master::Compressor;
+pub use options::{
+    BitDepth, CombineMode, CombineOptions, Mp3Bitrate, OutputFormat, OutputMetadata,
+};
+pub use project::MixProject;
+pub use replaygain::ReplayGain;
+pub use silence::SilentRange;
+pub use smpl::SampleLoop;
+pub use stats::{CombineResult, CombineStats};
+pub use track::{AutomationPoint, FadeCurve, MixTrack};
+/// Starts `num_threads` workers for decoding and mixing in parallel. Await
+/// it once, before creating any combiner.
+#[cfg(feature = "threads")]
+pub use wasm_bindgen_rayon::init_thread_pool;
+
 #[wasm_bindgen]
 extern "C" {
-    fn alert(s: &str);
     #[wasm_bindgen(js_namespace = Date)]
     fn now() -> f64;
+    #[wasm_bindgen(js_name = setTimeout)]
+    fn set_timeout(handler: &js_sys::Function, timeout: i32);
+}
+
+/// Installs the panic hook, so a panic logs its message to the console
+/// instead of only trapping with "unreachable executed". Combiners install
+/// it themselves, so this is only needed to cover other calls made first.
+///
+/// Panics inside symphonia while probing, demuxing or decoding become
+/// `INTERNAL_ERROR` errors when the module is built to unwind. With the
+/// default `panic = "abort"` those, and any panic while mixing or encoding,
+/// still trap, and the instance that trapped should be thrown away.
+#[wasm_bindgen]
+pub fn init() {
+    utils::set_panic_hook();
+}
+
+/// True peak of a file in dBTP per ITU-R BS.1770, the highest level of the
+/// waveform between its samples as a DAC would rebuild it, found by 4x
+/// oversampling. Never below the sample peak; silence measures `-Infinity`.
+#[wasm_bindgen]
+pub fn measure_true_peak(file: SingleAudioFile) -> Result<f64, CombineError> {
+    let decoded = decode::decode_file(0, file, None)?;
+    Ok(20.0 * (loudness::true_peak(&decoded.samples) as f64).log10())
+}
+
+/// Converts a level change in decibels to a linear gain for
+/// `AudioCombiner::combine_with_gains`, e.g. `-6.0` to about `0.5`.
+#[wasm_bindgen]
+pub fn db_to_linear(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
 }
 
+/// Integrated loudness of a file in LUFS per ITU-R BS.1770, for metering
+/// before mixing. Silent or sub-400 ms files measure `-Infinity`.
 #[wasm_bindgen]
-pub fn greet() {
-    alert("Hello, wasm!");
+pub fn measure_loudness(file: SingleAudioFile) -> Result<f64, CombineError> {
+    let decoded = decode::decode_file(0, file, None)?;
+    Ok(loudness::integrated_loudness(
+        &decoded.samples,
+        decoded.sample_rate,
+        decoded.mono,
+    ))
 }
 
+/// Decodes each of `buffers` as the matching entry of `types` and mixes them
+/// at `volumes` as `AudioCombiner::combine` does, returning the WAV bytes.
+/// Nothing is left behind for JS to `free()`.
+///
+/// `buffers` and `types` of different lengths are `TYPE_COUNT_MISMATCH`.
 #[wasm_bindgen]
-#[derive(Clone, Copy)]
+pub fn combine_files(
+    buffers: Vec<js_sys::Uint8Array>,
+    types: Vec<SingleAudioFileType>,
+    volumes: Vec<u8>,
+) -> Result<Vec<u8>, CombineError> {
+    if buffers.len() != types.len() {
+        return Err(CombineError::TypeCountMismatch {
+            buffers: buffers.len(),
+            types: types.len(),
+        });
+    }
+    let files = buffers
+        .iter()
+        .zip(types)
+        .map(|(buffer, r#type)| SingleAudioFile::from_uint8array(buffer, r#type))
+        .collect();
+    let output = AudioCombiner::new(files)?.combine(volumes)?;
+    Ok(output.into_bytes())
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum SingleAudioFileType {
     Wav,
     Mpeg,
     Ogg,
+    Flac,
+    Aac,
+    Webm,
+    Aiff,
+    /// Let the probe sniff the container from its magic bytes.
+    Auto,
+    /// Interleaved f32 samples, see `SingleAudioFile::from_pcm_f32`.
+    Pcm,
+}
+
+impl SingleAudioFileType {
+    /// File extension handed to symphonia's probe as a hint.
+    fn extension(self) -> Option<&'static str> {
+        match self {
+            SingleAudioFileType::Wav => Some("wav"),
+            SingleAudioFileType::Mpeg => Some("mp3"),
+            SingleAudioFileType::Ogg => Some("ogg"),
+            SingleAudioFileType::Flac => Some("flac"),
+            SingleAudioFileType::Aac => Some("m4a"),
+            SingleAudioFileType::Webm => Some("webm"),
+            SingleAudioFileType::Aiff => Some("aiff"),
+            SingleAudioFileType::Auto | SingleAudioFileType::Pcm => None,
+        }
+    }
+
+    /// The name errors give this type's codec and the cargo feature that
+    /// builds it in, or `None` for types that need no codec of their own.
+    fn codec_feature(self) -> Option<(&'static str, &'static str)> {
+        match self {
+            SingleAudioFileType::Wav => Some(("WAV", "wav")),
+            SingleAudioFileType::Aiff => Some(("AIFF", "wav")),
+            SingleAudioFileType::Mpeg => Some(("MP3", "mp3")),
+            SingleAudioFileType::Ogg => Some(("Ogg Vorbis", "ogg-vorbis")),
+            SingleAudioFileType::Webm => Some(("WebM Vorbis", "ogg-vorbis")),
+            SingleAudioFileType::Flac => Some(("FLAC", "flac")),
+            SingleAudioFileType::Aac => Some(("AAC", "aac")),
+            SingleAudioFileType::Auto | SingleAudioFileType::Pcm => None,
+        }
+    }
+
+    /// Whether files of this type can be decoded by this build.
+    fn built_in(self) -> bool {
+        match self {
+            SingleAudioFileType::Wav | SingleAudioFileType::Aiff => cfg!(feature = "wav"),
+            SingleAudioFileType::Mpeg => cfg!(feature = "mp3"),
+            SingleAudioFileType::Ogg | SingleAudioFileType::Webm => {
+                cfg!(any(feature = "ogg-vorbis", feature = "opus"))
+            }
+            SingleAudioFileType::Flac => cfg!(feature = "flac"),
+            SingleAudioFileType::Aac => cfg!(feature = "aac"),
+            SingleAudioFileType::Auto | SingleAudioFileType::Pcm => true,
+        }
+    }
+
+    /// MIME type for a `Blob` of the file. Types with no container of their
+    /// own are plain bytes.
+    fn mime_type(self) -> &'static str {
+        match self {
+            SingleAudioFileType::Wav => "audio/wav",
+            SingleAudioFileType::Mpeg => "audio/mpeg",
+            SingleAudioFileType::Ogg => "audio/ogg",
+            SingleAudioFileType::Flac => "audio/flac",
+            SingleAudioFileType::Aac => "audio/mp4",
+            SingleAudioFileType::Webm => "audio/webm",
+            SingleAudioFileType::Aiff => "audio/aiff",
+            SingleAudioFileType::Auto | SingleAudioFileType::Pcm => "application/octet-stream",
+        }
+    }
+
+    /// The type for a MIME type such as `"audio/x-wav"`, without parameters.
+    /// Names that browsers and servers commonly use are recognised.
+    fn from_mime(mime: &str) -> Option<Self> {
+        let file_type = match mime.trim().to_ascii_lowercase().as_str() {
+            "audio/wav" | "audio/wave" | "audio/x-wav" | "audio/vnd.wave" => {
+                SingleAudioFileType::Wav
+            }
+            "audio/mpeg" | "audio/mp3" | "audio/mpeg3" | "audio/x-mpeg-3" => {
+                SingleAudioFileType::Mpeg
+            }
+            "audio/ogg" | "application/ogg" | "audio/vorbis" => SingleAudioFileType::Ogg,
+            "audio/flac" | "audio/x-flac" => SingleAudioFileType::Flac,
+            "audio/mp4" | "audio/m4a" | "audio/x-m4a" | "audio/aac" | "audio/aacp" => {
+                SingleAudioFileType::Aac
+            }
+            "audio/webm" | "video/webm" => SingleAudioFileType::Webm,
+            "audio/aiff" | "audio/x-aiff" => SingleAudioFileType::Aiff,
+            _ => return None,
+        };
+        Some(file_type)
+    }
+
+    /// Identifies the container from the leading bytes of a file.
+    fn detect(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', ..] => {
+                Some(SingleAudioFileType::Wav)
+            }
+            [b'F', b'O', b'R', b'M', _, _, _, _, b'A', b'I', b'F', b'F' | b'C', ..] => {
+                Some(SingleAudioFileType::Aiff)
+            }
+            [b'O', b'g', b'g', b'S', ..] => Some(SingleAudioFileType::Ogg),
+            [b'f', b'L', b'a', b'C', ..] => Some(SingleAudioFileType::Flac),
+            [_, _, _, _, b'f', b't', b'y', b'p', ..] => Some(SingleAudioFileType::Aac),
+            [0x1a, 0x45, 0xdf, 0xa3, ..] => Some(SingleAudioFileType::Webm),
+            [b'I', b'D', b'3', ..] => Some(SingleAudioFileType::Mpeg),
+            [0xff, b, ..] if b & 0xe0 == 0xe0 => Some(SingleAudioFileType::Mpeg),
+            _ => None,
+        }
+    }
 }
 
 #[wasm_bindgen]
+#[derive(Clone)]
 pub struct SingleAudioFile {
-    #[wasm_bindgen(getter_with_clone)]
-    pub bytes: Vec<u8>,
+    /// Shared between clones, and replaced rather than changed in place.
+    bytes: Arc<Vec<u8>>,
     pub r#type: SingleAudioFileType,
+    /// Replace packets that fail to decode with silence, noting each in
+    /// `CombineResult::warnings`, instead of failing. A broken container
+    /// still ends the file early. Off by default.
+    pub tolerate_decode_errors: bool,
+    /// Sample rate and channel count of `Pcm` bytes.
+    pcm_spec: Option<(u32, u16)>,
 }
 
 #[wasm_bindgen]
 impl SingleAudioFile {
     pub fn new(bytes: Vec<u8>, r#type: SingleAudioFileType) -> Self {
-        Self { bytes, r#type }
+        Self {
+            bytes: Arc::new(bytes),
+            r#type,
+            tolerate_decode_errors: false,
+            pcm_spec: None,
+        }
     }
-}
 
-fn create_wav_container(samples: &[f32], sample_rate: u32) -> Vec<u8> {
-    let mut wav = Vec::new();
-    let data_size = (samples.len() * 2) as u32; // 2 bytes per sample (i16)
+    /// Copies a view, such as a `subarray` of a larger buffer, into wasm
+    /// memory once. Only the bytes inside the view are copied.
+    pub fn from_uint8array(data: &js_sys::Uint8Array, r#type: SingleAudioFileType) -> Self {
+        Self::new(data.to_vec(), r#type)
+    }
 
-    // RIFF Header
-    wav.extend_from_slice(b"RIFF");
-    wav.extend_from_slice(&(36 + data_size).to_le_bytes());
-    wav.extend_from_slice(b"WAVE");
+    /// Copies the whole of `buffer` into wasm memory once, e.g. straight from
+    /// `Response.arrayBuffer()` or `File.arrayBuffer()`.
+    pub fn from_array_buffer(buffer: &js_sys::ArrayBuffer, r#type: SingleAudioFileType) -> Self {
+        Self::from_uint8array(&js_sys::Uint8Array::new(buffer), r#type)
+    }
 
-    // fmt chunk
-    wav.extend_from_slice(b"fmt ");
-    wav.extend_from_slice(&16u32.to_le_bytes());
-    wav.extend_from_slice(&1u16.to_le_bytes());
-    wav.extend_from_slice(&2u16.to_le_bytes()); // Hardcoded Stereo
-    wav.extend_from_slice(&sample_rate.to_le_bytes());
-    wav.extend_from_slice(&(sample_rate * 4).to_le_bytes());
-    wav.extend_from_slice(&4u16.to_le_bytes());
-    wav.extend_from_slice(&16u16.to_le_bytes());
+    /// The file's bytes. Each read copies them out of wasm memory.
+    #[wasm_bindgen(getter)]
+    pub fn bytes(&self) -> Vec<u8> {
+        self.bytes.to_vec()
+    }
 
-    // data chunk
-    wav.extend_from_slice(b"data");
-    wav.extend_from_slice(&data_size.to_le_bytes());
+    /// Replaces the file's bytes, leaving any clones with the old ones.
+    #[wasm_bindgen(setter)]
+    pub fn set_bytes(&mut self, bytes: Vec<u8>) {
+        self.bytes = Arc::new(bytes);
+    }
+
+    /// A second file on the same bytes, which are not copied. Changing
+    /// either file afterwards leaves the other alone.
+    #[wasm_bindgen(js_name = clone)]
+    pub fn duplicate(&self) -> SingleAudioFile {
+        self.clone()
+    }
+
+    /// Size of `bytes`, without copying them out as reading `bytes` does.
+    #[wasm_bindgen(getter)]
+    pub fn byte_length(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// MIME type of the file for building a `Blob`, such as `"audio/mpeg"`.
+    /// An `Auto` file is sniffed from its magic bytes; raw PCM and bytes
+    /// that match no container are `"application/octet-stream"`.
+    #[wasm_bindgen(getter)]
+    pub fn mime_type(&self) -> String {
+        self.container().mime_type().into()
+    }
+
+    /// Extension for a download name, such as `"mp3"`, worked out like
+    /// `mime_type`. Raw PCM is `"pcm"` and unrecognised bytes `"bin"`.
+    #[wasm_bindgen(getter)]
+    pub fn suggested_extension(&self) -> String {
+        match self.container() {
+            SingleAudioFileType::Pcm => "pcm",
+            file_type => file_type.extension().unwrap_or("bin"),
+        }
+        .into()
+    }
+
+    /// Creates a file whose `type` is detected from the container's magic bytes.
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<SingleAudioFile, CombineError> {
+        let r#type = SingleAudioFileType::detect(&bytes).ok_or(CombineError::UnknownFormat)?;
+        Ok(Self::new(bytes, r#type))
+    }
+
+    /// Decodes base64, such as a file embedded in JSON, straight into wasm
+    /// memory. Padding is optional and whitespace, including line breaks, is
+    /// skipped; the URL-safe alphabet is accepted too. Anything else is
+    /// `INVALID_BASE64`.
+    pub fn from_base64(
+        data: &str,
+        r#type: SingleAudioFileType,
+    ) -> Result<SingleAudioFile, CombineError> {
+        Ok(Self::new(base64::decode(data)?, r#type))
+    }
+
+    /// Decodes a `data:audio/mpeg;base64,...` URL as `from_base64` does,
+    /// taking the type from its MIME type, or `Auto` when that is missing or
+    /// not an audio type this knows. URLs without `;base64` are
+    /// `INVALID_DATA_URL`.
+    pub fn from_data_url(url: &str) -> Result<SingleAudioFile, CombineError> {
+        let invalid = |detail: &str| CombineError::InvalidDataUrl {
+            detail: detail.into(),
+        };
+        let scheme = "data:".len();
+        if !url
+            .get(..scheme)
+            .is_some_and(|s| s.eq_ignore_ascii_case("data:"))
+        {
+            return Err(invalid("does not start with \"data:\""));
+        }
+        let comma = url
+            .find(',')
+            .ok_or_else(|| invalid("no \",\" before the data"))?;
+        let mut header = url[scheme..comma].split(';');
+        let mime = header.next().unwrap_or_default();
+        if !header.any(|param| param.trim().eq_ignore_ascii_case("base64")) {
+            return Err(invalid("only base64 data URLs are supported"));
+        }
+        let body = comma + 1;
+        let bytes = base64::decode(&url[body..]).map_err(|error| match error {
+            CombineError::InvalidBase64 { offset, detail } => CombineError::InvalidBase64 {
+                offset: body + offset,
+                detail,
+            },
+            error => error,
+        })?;
+        let r#type = SingleAudioFileType::from_mime(mime).unwrap_or(SingleAudioFileType::Auto);
+        Ok(Self::new(bytes, r#type))
+    }
+
+    /// Downloads `url` into wasm memory as it arrives, resolving to the file.
+    /// An `Auto` type is taken from the response's `Content-Type` when that
+    /// names an audio type, as for `from_data_url`.
+    ///
+    /// A response outside 200-299 rejects with `HTTP_STATUS` and the
+    /// `status`, and a request that never got a response with
+    /// `FETCH_FAILED`, which is also what a server without CORS headers
+    /// for this page gives. Aborting `signal` rejects with `CANCELLED`.
+    pub async fn from_url(
+        url: String,
+        r#type: SingleAudioFileType,
+        signal: Option<web_sys::AbortSignal>,
+    ) -> Result<SingleAudioFile, CombineError> {
+        let download = fetch::download(&url, signal.as_ref()).await?;
+        let r#type = match (r#type, download.content_type) {
+            (SingleAudioFileType::Auto, Some(content_type)) => {
+                let mime = content_type.split(';').next().unwrap_or_default();
+                SingleAudioFileType::from_mime(mime).unwrap_or(SingleAudioFileType::Auto)
+            }
+            (r#type, _) => r#type,
+        };
+        Ok(Self::new(download.bytes, r#type))
+    }
+
+    /// Reads `stream`, such as `File.stream()`, into wasm memory a chunk at
+    /// a time, resolving to the file once it ends. Nothing is copied on the
+    /// JS side, so this suits files too large to hold twice. `size`, such as
+    /// `File.size`, reserves the space up front; without it the buffer grows
+    /// as chunks arrive. Reading yields to the event loop every few
+    /// milliseconds.
+    ///
+    /// A stream that errors, or gives chunks other than `Uint8Array`s,
+    /// rejects with `READ_FAILED`, and aborting `signal` cancels the stream
+    /// and rejects with `CANCELLED`. The bytes read so far are dropped.
+    pub async fn from_stream(
+        stream: web_sys::ReadableStream,
+        r#type: SingleAudioFileType,
+        size: Option<f64>,
+        signal: Option<web_sys::AbortSignal>,
+    ) -> Result<SingleAudioFile, CombineError> {
+        let size = size.filter(|size| *size >= 0.0).map(|size| size as usize);
+        let bytes = ingest::read_to_end(&stream, size, signal.as_ref(), |thrown| {
+            CombineError::ReadFailed {
+                detail: decode::thrown_message(thrown),
+            }
+        })
+        .await?;
+        Ok(Self::new(bytes, r#type))
+    }
+
+    /// Wraps already decoded, interleaved samples so they skip decoding.
+    ///
+    /// Mono input is upmixed to stereo and any sample rate is resampled to
+    /// the output rate when mixing.
+    pub fn from_pcm_f32(
+        samples: Vec<f32>,
+        sample_rate: u32,
+        channels: u16,
+    ) -> Result<SingleAudioFile, CombineError> {
+        if channels != 1 && channels != 2 {
+            return Err(CombineError::InvalidPcm {
+                detail: format!("unsupported PCM channel count: {}", channels),
+            });
+        }
+        if sample_rate == 0 {
+            return Err(CombineError::InvalidPcm {
+                detail: "PCM sample rate must not be zero".into(),
+            });
+        }
+        if !samples.len().is_multiple_of(channels as usize) {
+            return Err(CombineError::InvalidPcm {
+                detail: "PCM sample count is not a multiple of the channel count".into(),
+            });
+        }
+        Ok(Self {
+            bytes: Arc::new(samples.iter().flat_map(|s| s.to_le_bytes()).collect()),
+            r#type: SingleAudioFileType::Pcm,
+            tolerate_decode_errors: false,
+            pcm_spec: Some((sample_rate, channels)),
+        })
+    }
+
+    /// Reads the duration, sample rate, channel count and codec from the
+    /// container headers without decoding the whole file.
+    pub fn probe(&self) -> Result<FileInfo, CombineError> {
+        decode::probe(self)
+    }
 
-    for &sample in samples {
-        let clamped = sample.clamp(-1.0, 1.0);
-        let s = (clamped * i16::MAX as f32) as i16;
-        wav.extend_from_slice(&s.to_le_bytes());
+    /// Title, artist, album, every other tag and any cover art, from ID3v2
+    /// tags, Vorbis comments, RIFF INFO chunks and the like. Files without
+    /// tags give an empty result.
+    pub fn metadata(&self) -> Result<FileMetadata, CombineError> {
+        decode::metadata(self)
+    }
+
+    /// Decodes this file alone into a WAV at `options.sample_rate` and
+    /// `options.bit_depth`, with its tags and dither, but without any of the
+    /// mixing. Mono files stay mono; anything with more channels comes out
+    /// stereo, downmixed as for `combine`.
+    pub fn to_wav(&self, options: &CombineOptions) -> Result<SingleAudioFile, CombineError> {
+        options.validate()?;
+        decode::decode_file(0, self.clone(), None)?.into_wav(options)
+    }
+
+    /// Cuts `start_ms` to `end_ms` out of this file, rounded to frames as
+    /// `MixTrack::trim_start_ms` and `trim_end_ms` round, into a new file:
+    /// a WAV as `to_wav` writes it when `options` are given, or else raw PCM
+    /// at the source rate.
+    ///
+    /// Decoding stops at `end_ms`. It starts shortly before `start_ms` when
+    /// the container can seek there with the same samples as a decode from
+    /// the top, and at the top otherwise, as for Vorbis. A window that ends
+    /// past the end of the file is `SLICE_OUT_OF_RANGE`.
+    pub fn slice(
+        &self,
+        start_ms: f64,
+        end_ms: f64,
+        options: Option<CombineOptions>,
+    ) -> Result<SingleAudioFile, CombineError> {
+        let ordered = 0.0 <= start_ms && start_ms <= end_ms;
+        if !ordered || !end_ms.is_finite() {
+            return Err(CombineError::InvalidOption {
+                detail: format!(
+                    "slice needs 0 <= start_ms <= end_ms, got {} to {} ms",
+                    start_ms, end_ms
+                ),
+            });
+        }
+        if let Some(options) = &options {
+            options.validate()?;
+        }
+        let decoded = decode::decode_window(0, self.clone(), start_ms, end_ms)?;
+        match options {
+            Some(options) => decoded.into_wav(&options),
+            None => Ok(decoded.into_pcm()),
+        }
     }
-    wav
+
+    /// Peak and RMS level and length, from a decode that keeps no more than a
+    /// packet of the file in memory.
+    pub fn levels(&self) -> Result<Levels, CombineError> {
+        decode::levels(self.clone())
+    }
+
+    /// Absolute peak of each of `buckets` equal stretches of the file, for
+    /// drawing it. Stereo is folded to the louder channel unless
+    /// `per_channel` is set, which interleaves a left and a right peak per
+    /// bucket. A file shorter than `buckets` frames gets one per frame.
+    pub fn waveform(&self, buckets: u32, per_channel: bool) -> Result<Vec<f32>, CombineError> {
+        if buckets == 0 {
+            return Err(CombineError::InvalidOption {
+                detail: "waveform needs at least one bucket".into(),
+            });
+        }
+        let decoded = decode::decode_file(0, self.clone(), None)?;
+        Ok(waveform::peaks(&decoded.samples, buckets, per_channel))
+    }
+
+    /// Stretches of at least `min_duration_ms` whose RMS level stays below
+    /// `threshold_dbfs`, judged over 10 ms windows.
+    pub fn detect_silence(
+        &self,
+        threshold_dbfs: f32,
+        min_duration_ms: f64,
+    ) -> Result<Vec<SilentRange>, CombineError> {
+        if threshold_dbfs.is_nan() || !min_duration_ms.is_finite() || min_duration_ms < 0.0 {
+            return Err(CombineError::InvalidOption {
+                detail: format!(
+                    "silence needs a threshold and a non-negative duration, got {} dBFS for {} ms",
+                    threshold_dbfs, min_duration_ms
+                ),
+            });
+        }
+        let decoded = decode::decode_file(0, self.clone(), None)?;
+        let rate = decoded.sample_rate as f64;
+        let min_frames = (min_duration_ms * rate / 1000.0).round() as usize;
+        Ok(silence::silent_ranges(
+            &decoded.samples,
+            decoded.sample_rate,
+            threshold_dbfs,
+            min_frames,
+        )
+        .into_iter()
+        .map(|range| SilentRange {
+            start_ms: range.start as f64 * 1000.0 / rate,
+            end_ms: range.end as f64 * 1000.0 / rate,
+        })
+        .collect())
+    }
+
+    /// `duration_ms` of mono digital silence, e.g. to pad a concatenation.
+    pub fn silence(duration_ms: f64, sample_rate: u32) -> Result<SingleAudioFile, CombineError> {
+        let frames = pcm_frames(duration_ms, sample_rate)?;
+        Self::from_pcm_f32(vec![0.0; frames], sample_rate, 1)
+    }
+
+    /// `duration_ms` of a mono sine at `freq_hz`, peaking at `amplitude`
+    /// (at most 1.0).
+    pub fn sine(
+        freq_hz: f64,
+        duration_ms: f64,
+        sample_rate: u32,
+        amplitude: f32,
+    ) -> Result<SingleAudioFile, CombineError> {
+        let frames = pcm_frames(duration_ms, sample_rate)?;
+        if !(0.0..=1.0).contains(&amplitude) {
+            return Err(CombineError::InvalidPcm {
+                detail: format!(
+                    "sine amplitude must be between 0.0 and 1.0, got {}",
+                    amplitude
+                ),
+            });
+        }
+        if !(freq_hz > 0.0 && freq_hz <= sample_rate as f64 / 2.0) {
+            return Err(CombineError::InvalidPcm {
+                detail: format!(
+                    "sine frequency must be above 0 and at most {} Hz, got {}",
+                    sample_rate / 2,
+                    freq_hz
+                ),
+            });
+        }
+        let step = 2.0 * std::f64::consts::PI * freq_hz / sample_rate as f64;
+        let samples = (0..frames)
+            .map(|n| amplitude * (step * n as f64).sin() as f32)
+            .collect();
+        Self::from_pcm_f32(samples, sample_rate, 1)
+    }
+}
+
+impl SingleAudioFile {
+    /// `type`, or for `Auto` the container its magic bytes match, if any.
+    fn container(&self) -> SingleAudioFileType {
+        match self.r#type {
+            SingleAudioFileType::Auto => {
+                SingleAudioFileType::detect(&self.bytes).unwrap_or(SingleAudioFileType::Auto)
+            }
+            file_type => file_type,
+        }
+    }
+
+    /// The bytes, copied only if a clone still shares them.
+    fn into_bytes(self) -> Vec<u8> {
+        Arc::try_unwrap(self.bytes).unwrap_or_else(|bytes| bytes.to_vec())
+    }
+}
+
+/// Frames in `duration_ms` of a generated source at `sample_rate`.
+fn pcm_frames(duration_ms: f64, sample_rate: u32) -> Result<usize, CombineError> {
+    if !duration_ms.is_finite() || duration_ms < 0.0 {
+        return Err(CombineError::InvalidPcm {
+            detail: format!("duration must not be negative, got {} ms", duration_ms),
+        });
+    }
+    Ok((duration_ms * sample_rate as f64 / 1000.0).round() as usize)
+}
+
+/// Output sample rates accepted by `CombineOptions::sample_rate`.
+const MIN_SAMPLE_RATE: u32 = 8000;
+const MAX_SAMPLE_RATE: u32 = 192_000;
+
+/// Sample rate of the mixed output unless `CombineOptions::sample_rate` says
+/// otherwise.
+const MASTER_SAMPLE_RATE: u32 = 44100;
+
+/// Frames `AudioCombiner::mix` works out fades for at a time.
+const MIX_BLOCK_FRAMES: usize = 1024;
+
+/// Converts percent volumes to tracks at the matching linear gain.
+fn volume_tracks(volumes: &[u8]) -> Vec<MixTrack> {
+    volumes
+        .iter()
+        .map(|&v| MixTrack::with_gain(v as f32 / 100.0))
+        .collect()
+}
+
+/// `decode::decode_file`, recording how long it took.
+fn timed_decode(
+    index: usize,
+    file: SingleAudioFile,
+    progress: Option<&js_sys::Function>,
+) -> Result<AudioCombinerSingleFile, CombineError> {
+    let start = now();
+    let mut file = decode::decode_file(index, file, progress)?;
+    file.decode_ms = now() - start;
+    file.log_decoded(index);
+    Ok(file)
+}
+
+/// How long `AudioCombiner::new_async` decodes, or
+/// `SingleAudioFile::from_stream` reads, before yielding, in ms.
+const ASYNC_SLICE_MS: f64 = 10.0;
+
+/// Resolves on a later macrotask, letting the browser render and run input
+/// handlers (including an abort) in between.
+async fn yield_to_event_loop() {
+    let tick = js_sys::Promise::new(&mut |resolve, _| set_timeout(&resolve, 0));
+    // setTimeout never rejects.
+    let _ = wasm_bindgen_futures::JsFuture::from(tick).await;
 }
 
 struct AudioCombinerSingleFile {
     samples: Vec<f32>,
+    sample_rate: u32,
+    /// The source had one channel, so both sides of `samples` are equal.
+    mono: bool,
+    /// Time spent decoding, for `CombineStats::decode_ms`.
+    decode_ms: f64,
+    /// Corrupt stretches skipped while decoding, or a note that there was
+    /// no audio.
+    warnings: Vec<DecodeWarning>,
+    /// Measured on first use. The samples never change once decoded, so it
+    /// holds for as long as the file, in every combiner sharing it.
+    replaygain: OnceLock<ReplayGain>,
+}
+
+impl AudioCombinerSingleFile {
+    /// Stands in for a file left out by `CombineOptions::skip_failed_tracks`.
+    fn empty() -> Self {
+        Self {
+            samples: Vec::new(),
+            sample_rate: MASTER_SAMPLE_RATE,
+            mono: false,
+            decode_ms: 0.0,
+            warnings: Vec::new(),
+            replaygain: OnceLock::new(),
+        }
+    }
+
+    fn replaygain(&self) -> ReplayGain {
+        *self
+            .replaygain
+            .get_or_init(|| replaygain::analyze(&self.samples, self.sample_rate, self.mono))
+    }
+
+    /// A WAV of the samples at the rate, bit depth, tags and dither of
+    /// `options`. Mono sources are written as one channel.
+    fn into_wav(self, options: &CombineOptions) -> Result<SingleAudioFile, CombineError> {
+        let samples = if self.sample_rate == options.sample_rate {
+            self.samples
+        } else {
+            let ratio = options.sample_rate as f64 / self.sample_rate as f64;
+            resample::resample(&self.samples, ratio)
+        };
+        // Marked as a mix of this one file.
+        let frames = samples.len() / 2;
+        let cues = cue::cue_points(options, &[(0, 0)], frames);
+        let sample_loop = options.sample_loop.map(|l| l.points(frames)).transpose()?;
+        let (samples, channels) = Self::channels(samples, self.mono);
+        Ok(SingleAudioFile::new(
+            wav::create_wav_container(
+                &samples,
+                options.sample_rate,
+                channels,
+                options.bit_depth,
+                wav::WavTags {
+                    bext: options.bext.as_ref(),
+                    sample_loop,
+                    metadata: options.metadata.as_ref(),
+                    cues: &cues,
+                },
+                options.rf64,
+                options.dither().as_mut(),
+            )?,
+            SingleAudioFileType::Wav,
+        ))
+    }
+
+    /// The samples as a `Pcm` file, one channel for mono sources.
+    fn into_pcm(self) -> SingleAudioFile {
+        let (samples, channels) = Self::channels(self.samples, self.mono);
+        SingleAudioFile {
+            bytes: Arc::new(samples.iter().flat_map(|s| s.to_le_bytes()).collect()),
+            r#type: SingleAudioFileType::Pcm,
+            tolerate_decode_errors: false,
+            pcm_spec: Some((self.sample_rate, channels)),
+        }
+    }
+
+    /// Interleaved stereo `samples` and their channel count, cut down to the
+    /// left channel for a mono source, whose two sides are equal.
+    fn channels(samples: Vec<f32>, mono: bool) -> (Vec<f32>, u16) {
+        if mono {
+            (samples.into_iter().step_by(2).collect(), 1)
+        } else {
+            (samples, 2)
+        }
+    }
+
+    fn duration_ms(&self) -> f64 {
+        (self.samples.len() / 2) as f64 * 1000.0 / self.sample_rate as f64
+    }
+
+    /// Logs the decode time and any warnings of the file at `index`.
+    fn log_decoded(&self, index: usize) {
+        log::debug(|| {
+            format!(
+                "decoded file {} in {:.1} ms: {}",
+                index,
+                self.decode_ms,
+                self.describe()
+            )
+        });
+        for warning in &self.warnings {
+            log::debug(|| {
+                format!(
+                    "file {} at {:.1} ms: {}",
+                    warning.index, warning.timestamp_ms, warning.message
+                )
+            });
+        }
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "{} Hz {}, {:.1} ms",
+            self.sample_rate,
+            if self.mono { "mono" } else { "stereo" },
+            self.duration_ms()
+        )
+    }
 }
+
+/// Holds every input decoded once, so repeated combines only read the
+/// decoded samples and never the original bytes.
 #[wasm_bindgen]
 pub struct AudioCombiner {
-    files: Vec<AudioCombinerSingleFile>,
+    /// Shared between clones. Adding or removing a file only changes this
+    /// combiner's list.
+    files: Vec<Arc<AudioCombinerSingleFile>>,
+    /// Each file as last prepared for the mix, while `enable_decode_cache`
+    /// is on.
+    cache: Option<cache::PrepareCache>,
+}
+
+/// Shares the decoded files. A clone's cache starts empty, but on if this
+/// one's is.
+impl Clone for AudioCombiner {
+    fn clone(&self) -> Self {
+        Self {
+            files: self.files.clone(),
+            cache: self
+                .cache
+                .as_ref()
+                .map(|_| cache::PrepareCache::new(self.files.len())),
+        }
+    }
 }
 
 #[wasm_bindgen]
 impl AudioCombiner {
-    pub fn new(files: Vec<SingleAudioFile>) -> Result<AudioCombiner, String> {
-        let mut processed_files = Vec::with_capacity(files.len());
+    pub fn new(files: Vec<SingleAudioFile>) -> Result<AudioCombiner, CombineError> {
+        Self::decode_all(files, None)
+    }
 
-        for file in files {
-            let mut decoded_samples = Vec::new();
-            let src = std::io::Cursor::new(file.bytes);
-            let mss =
-                symphonia::core::io::MediaSourceStream::new(Box::new(src), Default::default());
+    /// Like `new`, calling `progress(file_index, fraction)` while decoding.
+    ///
+    /// Each file ends with a call at `1.0`. If the callback throws, decoding
+    /// stops and the error is returned.
+    pub fn new_with_progress(
+        files: Vec<SingleAudioFile>,
+        progress: &js_sys::Function,
+    ) -> Result<AudioCombiner, CombineError> {
+        Self::decode_all(files, Some(progress))
+    }
+
+    /// Decodes like `new` but yields to the event loop every few milliseconds
+    /// so the page stays responsive, resolving to the combiner.
+    ///
+    /// Aborting `signal` rejects with `CANCELLED` before the next packet is
+    /// decoded; everything decoded so far is dropped.
+    pub async fn new_async(
+        files: Vec<SingleAudioFile>,
+        signal: Option<web_sys::AbortSignal>,
+    ) -> Result<AudioCombiner, CombineError> {
+        utils::set_panic_hook();
 
-            let mut hint = symphonia::core::probe::Hint::new();
-            match file.r#type {
-                SingleAudioFileType::Wav => {
-                    hint.with_extension("wav");
+        let cancelled = || signal.as_ref().is_some_and(|s| s.aborted());
+        let mut processed_files = Vec::with_capacity(files.len());
+        let mut slice_start = now();
+
+        for (index, file) in files.into_iter().enumerate() {
+            let start = now();
+            if file.r#type == SingleAudioFileType::Pcm {
+                let mut file = decode::decode_pcm(index, file)?;
+                file.decode_ms = now() - start;
+                file.log_decoded(index);
+                processed_files.push(file);
+                continue;
+            }
+            // Time handed back to the event loop is not decoding.
+            let mut yielded_ms = 0.0;
+            let mut decoder = decode::FileDecoder::open(index, file)?;
+            decoder.reserve_track();
+            loop {
+                if cancelled() {
+                    return Err(CombineError::Cancelled);
                 }
-                SingleAudioFileType::Mpeg => {
-                    hint.with_extension("mp3");
+                if !decoder.decode_packet(None)? {
+                    break;
                 }
-                SingleAudioFileType::Ogg => {
-                    hint.with_extension("ogg");
+                if now() - slice_start >= ASYNC_SLICE_MS {
+                    let yield_start = now();
+                    yield_to_event_loop().await;
+                    slice_start = now();
+                    yielded_ms += slice_start - yield_start;
                 }
             }
+            let mut file = decoder.finish()?;
+            file.decode_ms = now() - start - yielded_ms;
+            file.log_decoded(index);
+            processed_files.push(file);
+        }
+        if cancelled() {
+            return Err(CombineError::Cancelled);
+        }
 
-            let probed = symphonia::default::get_probe()
-                .format(&hint, mss, &Default::default(), &Default::default())
-                .map_err(|e| e.to_string())?;
+        Ok(AudioCombiner {
+            files: processed_files.into_iter().map(Arc::new).collect(),
+            cache: None,
+        })
+    }
 
-            let mut format = probed.format;
-            let track = format.default_track().ok_or("No supported audio track")?;
-            let mut decoder = symphonia::default::get_codecs()
-                .make(&track.codec_params, &Default::default())
-                .map_err(|e| e.to_string())?;
+    /// Checks that each file opens and that its first second decodes, so bad
+    /// uploads can be flagged before paying for a full decode with `new`.
+    pub fn validate(files: Vec<SingleAudioFile>) -> Vec<FileValidation> {
+        files
+            .into_iter()
+            .enumerate()
+            .map(|(index, file)| FileValidation {
+                index,
+                error: decode::validate(index, file).err(),
+            })
+            .collect()
+    }
 
-            let mut sample_buf = None;
+    /// Decodes `files` and mixes them as placed by `tracks` in one go, for
+    /// callers that only combine once. `options.max_ms` bounds the decoding;
+    /// see `TIMED_OUT`.
+    ///
+    /// Each file is decoded only through its track's trim window, starting
+    /// shortly before it where the container can seek there, as
+    /// `SingleAudioFile::slice` does. Loudness matching measures whole
+    /// files, so with `loudness_target_lufs` or `auto_level` they are
+    /// decoded in full.
+    ///
+    /// With `options.skip_failed_tracks`, a file that fails to probe or
+    /// decode is listed in `CombineResult::skipped_tracks` and left out as if
+    /// muted, so every other file keeps its index and its track. Otherwise
+    /// the first failure is returned, as from `new`.
+    pub fn combine_files(
+        files: Vec<SingleAudioFile>,
+        tracks: Vec<MixTrack>,
+        options: &CombineOptions,
+    ) -> Result<CombineResult, CombineError> {
+        utils::set_panic_hook();
+        options.validate()?;
+        for (index, track) in tracks.iter().enumerate() {
+            track.validate(index, files.len())?;
+        }
 
-            while let Ok(packet) = format.next_packet() {
-                let decoded = decoder.decode(&packet).map_err(|e| e.to_string())?;
-                let spec = *decoded.spec();
-                let num_channels = spec.channels.count();
+        let mut deadline = Deadline::start(options.max_ms);
+        let file_count = files.len();
+        let mut tracks = tracks;
+        tracks.resize_with(tracks.len().max(file_count), MixTrack::default);
+        // Loudness matching measures whole files, so those are decoded in
+        // full. Otherwise each file is decoded only through its trim window.
+        let whole = options.loudness_target_lufs.is_some() || options.auto_level;
+        let whole_file = MixTrack::default();
+        let timed_out = |decoded: &[AudioCombinerSingleFile], index, partial_ms, max_ms| {
+            CombineError::TimedOut {
+                index,
+                max_ms,
+                processed_ms: decoded
+                    .iter()
+                    .map(AudioCombinerSingleFile::duration_ms)
+                    .chain(index.map(|_| partial_ms))
+                    .chain(std::iter::repeat(0.0))
+                    .take(file_count)
+                    .collect(),
+            }
+        };
+        let mut skipped = Vec::new();
+        let mut decoded = Vec::with_capacity(file_count);
+        for (index, file) in files.into_iter().enumerate() {
+            let start = now();
+            let window = if whole { &whole_file } else { &tracks[index] };
+            let result = decode::decode_file_staged(index, file, window, |decoded_ms| {
+                let Some(deadline) = &mut deadline else {
+                    return Ok(());
+                };
+                if !deadline.passed_after_packet() {
+                    return Ok(());
+                }
+                Err(timed_out(
+                    &decoded,
+                    Some(index),
+                    decoded_ms,
+                    deadline.max_ms(),
+                ))
+            });
+            match result {
+                Ok(mut file) => {
+                    file.decode_ms = now() - start;
+                    file.log_decoded(index);
+                    decoded.push(file);
+                    // Already cut to the window.
+                    if !whole {
+                        tracks[index].trim_start_ms = 0.0;
+                        tracks[index].trim_end_ms = None;
+                    }
+                }
+                Err((_, error @ CombineError::TimedOut { .. })) => return Err(error),
+                Err((stage, error)) if options.skip_failed_tracks => {
+                    log::log(LogLevel::Warn, || {
+                        format!("skipping file {}: {}", index, error)
+                    });
+                    skipped.push(SkippedTrack {
+                        index,
+                        stage,
+                        error,
+                    });
+                    decoded.push(AudioCombinerSingleFile::empty());
+                }
+                Err((_, error)) => return Err(error),
+            }
+        }
+        if let Some(deadline) = deadline.filter(Deadline::passed) {
+            return Err(timed_out(&decoded, None, 0.0, deadline.max_ms()));
+        }
+
+        for skip in &skipped {
+            let track = &mut tracks[skip.index];
+            track.mute = true;
+            track.solo = false;
+        }
+        let combiner = AudioCombiner {
+            files: decoded.into_iter().map(Arc::new).collect(),
+            cache: None,
+        };
+        let mut result = combiner.combine_with_stats(tracks, options)?;
+        result.skipped_tracks = skipped;
+        Ok(result)
+    }
 
-                let buf = sample_buf.get_or_insert_with(|| {
-                    symphonia::core::audio::SampleBuffer::<f32>::new(
-                        decoded.capacity() as u64,
-                        spec,
+    /// How long `combine_tracks` would make a mix of files with these
+    /// `SingleAudioFile::probe` results, in file order, without decoding
+    /// any audio. Trims, speed, loops, offsets, mute and solo, and the
+    /// options' mode and crossfade are all taken into account.
+    pub fn estimate_duration(
+        infos: Vec<FileInfo>,
+        tracks: Vec<MixTrack>,
+        options: &CombineOptions,
+    ) -> Result<DurationEstimate, CombineError> {
+        estimate::estimate(&infos, tracks, options)
+    }
+
+    /// Bytes `combine_tracks` would write for files with these probe
+    /// results, from the same layout as `estimate_duration`. Exact for WAV
+    /// when every length is known; compressed sizes come from the nominal
+    /// bitrate.
+    pub fn estimate_output_size(
+        infos: Vec<FileInfo>,
+        tracks: Vec<MixTrack>,
+        options: &CombineOptions,
+    ) -> Result<SizeEstimate, CombineError> {
+        estimate::output_size(&infos, tracks, options)
+    }
+
+    /// Mixes `files` at their volumes in percent like `combine`, but decodes
+    /// them side by side a window at a time and hands out the 16-bit WAV in
+    /// pieces, so memory stays proportional to `chunk_frames` rather than the
+    /// length of the mix.
+    ///
+    /// `write(bytes, offset)` gets each piece with its byte offset in the
+    /// output, in order. The header comes first with a zero length and again
+    /// at offset 0 once the length is known. Every file must have the same
+    /// sample rate, which the output keeps.
+    pub fn combine_streamed(
+        files: Vec<SingleAudioFile>,
+        volumes: Vec<u8>,
+        chunk_frames: usize,
+        write: &js_sys::Function,
+    ) -> Result<(), CombineError> {
+        stream::combine_streamed(
+            files,
+            &volume_tracks(&volumes),
+            chunk_frames,
+            |bytes, offset| {
+                write
+                    .call2(
+                        &JsValue::NULL,
+                        &js_sys::Uint8Array::from(bytes),
+                        &JsValue::from(offset as f64),
                     )
-                });
-                buf.copy_interleaved_ref(decoded);
-
-                // Convert everything to Stereo (2 channels) during ingestion
-                for frame in buf.samples().chunks(num_channels) {
-                    if num_channels == 1 {
-                        decoded_samples.push(frame[0]); // Left
-                        decoded_samples.push(frame[0]); // Right
-                    } else {
-                        decoded_samples.push(frame[0]); // Left
-                        decoded_samples.push(frame[1]); // Right
-                    }
+                    .map(|_| ())
+                    .map_err(|e| CombineError::ChunkCallbackFailed {
+                        detail: decode::thrown_message(&e),
+                    })
+            },
+        )
+    }
+
+    /// A second combiner on the same decoded files, which are not copied,
+    /// to mix another way. Adding or removing files on either afterwards
+    /// leaves the other alone. The clone gets a decode cache of its own,
+    /// empty and on if this one's is.
+    #[wasm_bindgen(js_name = clone)]
+    pub fn duplicate(&self) -> AudioCombiner {
+        self.clone()
+    }
+
+    /// Decodes `file` and appends it, returning its index.
+    pub fn add_file(&mut self, file: SingleAudioFile) -> Result<usize, CombineError> {
+        let index = self.files.len();
+        self.files.push(Arc::new(timed_decode(index, file, None)?));
+        if let Some(cache) = &mut self.cache {
+            cache.push();
+        }
+        Ok(index)
+    }
+
+    /// Downloads `url` with `SingleAudioFile::from_url`, then decodes and
+    /// appends it like `add_file`, resolving to its index. A failed
+    /// download leaves the combiner as it was.
+    pub async fn add_file_from_url(
+        &mut self,
+        url: String,
+        r#type: SingleAudioFileType,
+        signal: Option<web_sys::AbortSignal>,
+    ) -> Result<usize, CombineError> {
+        let file = SingleAudioFile::from_url(url, r#type, signal).await?;
+        self.add_file(file)
+    }
+
+    /// Reads `stream` with `SingleAudioFile::from_stream`, then decodes and
+    /// appends it like `add_file`, resolving to its index. A stream that
+    /// fails partway leaves the combiner as it was.
+    pub async fn add_file_from_stream(
+        &mut self,
+        stream: web_sys::ReadableStream,
+        r#type: SingleAudioFileType,
+        size: Option<f64>,
+        signal: Option<web_sys::AbortSignal>,
+    ) -> Result<usize, CombineError> {
+        let file = SingleAudioFile::from_stream(stream, r#type, size, signal).await?;
+        self.add_file(file)
+    }
+
+    /// Appends the files of `other` after this combiner's own, in order,
+    /// without decoding or copying them. A file that was at `i` in `other`
+    /// is at `len() + i` here, so tracks and volumes for it go there.
+    /// `other` is left as it was.
+    pub fn merge(&mut self, other: &AudioCombiner) {
+        self.files.extend(other.files.iter().cloned());
+        if let Some(cache) = &mut self.cache {
+            for _ in &other.files {
+                cache.push();
+            }
+        }
+    }
+
+    /// One combiner with the files of each of `parts` in turn, as `merge`
+    /// would append them. The decode cache starts off.
+    pub fn from_parts(parts: Vec<AudioCombiner>) -> AudioCombiner {
+        AudioCombiner {
+            files: parts.into_iter().flat_map(|part| part.files).collect(),
+            cache: None,
+        }
+    }
+
+    /// Removes the file at `index`, moving later files down by one.
+    pub fn remove_file(&mut self, index: usize) -> Result<(), CombineError> {
+        if index >= self.files.len() {
+            return Err(CombineError::IndexOutOfRange {
+                index,
+                len: self.files.len(),
+            });
+        }
+        self.files.remove(index);
+        if let Some(cache) = &mut self.cache {
+            cache.remove(index);
+        }
+        Ok(())
+    }
+
+    pub fn clear(&mut self) {
+        self.files.clear();
+        if let Some(cache) = &mut self.cache {
+            *cache = cache::PrepareCache::new(0);
+        }
+    }
+
+    /// Keeps each file as trimmed, filtered and resampled for the mix, so
+    /// later combines that only change gains, pans, fades, offsets or the
+    /// master settings reuse it instead of doing that work again. A file is
+    /// prepared afresh whenever the track settings that shape it or the
+    /// output rate change, and forgotten when it is removed.
+    ///
+    /// Off by default, as it holds a second copy of every file; turning it
+    /// off frees that memory.
+    pub fn enable_decode_cache(&mut self, enabled: bool) {
+        if !enabled {
+            self.cache = None;
+        } else if self.cache.is_none() {
+            self.cache = Some(cache::PrepareCache::new(self.files.len()));
+        }
+    }
+
+    /// Bytes held by the cache, 0 when it is off or empty.
+    pub fn cache_memory_bytes(&self) -> usize {
+        self.cache
+            .as_ref()
+            .map_or(0, cache::PrepareCache::memory_bytes)
+    }
+
+    /// Empties the cache, leaving it on.
+    pub fn clear_cache(&mut self) {
+        if let Some(cache) = &mut self.cache {
+            cache.clear();
+        }
+    }
+
+    /// Number of files in the combiner.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// One line per file with its rate, channels, length and any decode
+    /// warnings, after a count of the files.
+    pub fn describe(&self) -> String {
+        let mut summary = format!("{} files", self.files.len());
+        for (index, file) in self.files.iter().enumerate() {
+            summary += &format!("\n{}: {}", index, file.describe());
+            match file.warnings.len() {
+                0 => {}
+                1 => summary += ", 1 decode warning",
+                n => summary += &format!(", {} decode warnings", n),
+            }
+        }
+        summary
+    }
+
+    /// Linear gains for `combine_with_gains` that bring each file to an RMS
+    /// level of `target_rms_dbfs`. Silent files, which no gain can bring
+    /// there, get 1.0.
+    pub fn suggest_gains(&self, target_rms_dbfs: f32) -> Result<Vec<f32>, CombineError> {
+        if !target_rms_dbfs.is_finite() {
+            return Err(CombineError::InvalidOption {
+                detail: format!("target RMS must be finite, got {}", target_rms_dbfs),
+            });
+        }
+        Ok(self
+            .files
+            .iter()
+            .map(|file| {
+                let mut meter = levels::LevelMeter::default();
+                meter.add(&file.samples);
+                let levels = meter.levels(file.sample_rate, 2);
+                if levels.is_silent() {
+                    1.0
+                } else {
+                    db_to_linear(target_rms_dbfs - levels.rms_dbfs)
+                }
+            })
+            .collect())
+    }
+
+    /// Each file's gain to the ReplayGain 2.0 reference of -18 LUFS and its
+    /// true peak, in file order, as `CombineOptions::auto_level` applies
+    /// them. A file is measured the first time it is asked about and
+    /// remembered from then on, by clones and merged combiners too.
+    pub fn analyze_replaygain(&self) -> Vec<ReplayGain> {
+        self.files.iter().map(|file| file.replaygain()).collect()
+    }
+
+    /// Mixes every file at its volume in percent, 100 being unity.
+    ///
+    /// Files without an entry in `volumes`, or all of them when it is empty,
+    /// play at 100.
+    pub fn combine(&self, volumes: Vec<u8>) -> Result<SingleAudioFile, CombineError> {
+        self.combine_with_options(volumes, &CombineOptions::default())
+    }
+
+    pub fn combine_with_options(
+        &self,
+        volumes: Vec<u8>,
+        options: &CombineOptions,
+    ) -> Result<SingleAudioFile, CombineError> {
+        self.combine_tracks(volume_tracks(&volumes), options)
+    }
+
+    /// Mixes every file at a linear gain, 1.0 being unity.
+    ///
+    /// Files without an entry in `gains` play at 1.0. Gains must be finite
+    /// and not negative; see `db_to_linear` for working in decibels.
+    pub fn combine_with_gains(&self, gains: Vec<f32>) -> Result<SingleAudioFile, CombineError> {
+        self.combine_with_gains_and_options(gains, &CombineOptions::default())
+    }
+
+    pub fn combine_with_gains_and_options(
+        &self,
+        gains: Vec<f32>,
+        options: &CombineOptions,
+    ) -> Result<SingleAudioFile, CombineError> {
+        let tracks = gains.into_iter().map(MixTrack::with_gain).collect();
+        self.combine_tracks(tracks, options)
+    }
+
+    /// Mixes every file as placed by its `MixTrack`.
+    ///
+    /// Files without an entry in `tracks` use `MixTrack::new()`. WAV output
+    /// has one channel when every file in the mix is mono, or forced to it,
+    /// and has the same gain on both sides, since the two would be equal.
+    pub fn combine_tracks(
+        &self,
+        tracks: Vec<MixTrack>,
+        options: &CombineOptions,
+    ) -> Result<SingleAudioFile, CombineError> {
+        Ok(self.combine_with_stats(tracks, options)?.file)
+    }
+
+    /// Like `combine_tracks`, with the tracks and output settings given as
+    /// one plain object; see the `MixConfig` typings.
+    ///
+    /// Type errors name the field, as in `tracks[2].gain: invalid type:
+    /// string "loud", expected f32`, and come back as `INVALID_OPTION`.
+    pub fn combine_with_config(&self, config: MixConfig) -> Result<SingleAudioFile, CombineError> {
+        let (tracks, options) = config::parse(config)?;
+        self.combine_tracks(tracks, &options)
+    }
+
+    /// Saves `tracks` and `options` as a JSON project to restore later with
+    /// `import_project`. The files are not saved: `file_ids` names each
+    /// one, in order, by something the caller can check them against, such
+    /// as a content hash.
+    ///
+    /// The project holds one track per file, as `combine_tracks` would
+    /// place them, and both tracks and options are validated first.
+    pub fn export_project(
+        &self,
+        tracks: Vec<MixTrack>,
+        options: &CombineOptions,
+        file_ids: Vec<String>,
+    ) -> Result<String, CombineError> {
+        project::export(self.files.len(), file_ids, tracks, options)
+    }
+
+    /// Restores a project from `export_project` against `files`, which
+    /// `file_ids` must name as the project did, in the same order. The
+    /// files are decoded as `new` decodes them.
+    ///
+    /// Projects from older versions of this library load, with settings
+    /// added since then at their defaults. An unreadable project, or the
+    /// wrong number of files, is `INVALID_PROJECT`; a file whose id differs
+    /// is `PROJECT_FILE_MISMATCH`.
+    pub fn import_project(
+        json: &str,
+        files: Vec<SingleAudioFile>,
+        file_ids: Vec<String>,
+    ) -> Result<MixProject, CombineError> {
+        project::import(json, files, file_ids)
+    }
+
+    /// Like `combine_tracks`, but writes the mix as consecutive WAVs of
+    /// `segment_ms` each, rounded to a whole number of frames, with the last
+    /// one shorter as needed. Each plays on its own, and their sample data
+    /// end to end is the WAV `combine_tracks` gives, dither included.
+    ///
+    /// Each segment is encoded straight from the mix, which is never copied
+    /// in full. Only `OutputFormat::Wav` can be segmented.
+    pub fn combine_segmented(
+        &self,
+        tracks: Vec<MixTrack>,
+        segment_ms: f64,
+        options: &CombineOptions,
+    ) -> Result<Vec<SingleAudioFile>, CombineError> {
+        if options.format != OutputFormat::Wav {
+            return Err(CombineError::InvalidOption {
+                detail: format!("only WAV output can be segmented, not {:?}", options.format),
+            });
+        }
+        if options.sample_loop.is_some() {
+            return Err(CombineError::InvalidOption {
+                detail: "a sample_loop cannot span segmented output".into(),
+            });
+        }
+        let segment_frames = (segment_ms * options.sample_rate as f64 / 1000.0).round();
+        if segment_frames.is_nan() || segment_frames < 1.0 {
+            return Err(CombineError::InvalidOption {
+                detail: format!(
+                    "segments must be at least a frame long, got {} ms",
+                    segment_ms
+                ),
+            });
+        }
+        let mut stats = CombineStats::default();
+        let master_buffer = self.master(tracks, options, &mut stats)?;
+        let frames = master_buffer.len() / 2;
+        let cues = cue::cue_points(options, &stats.file_starts, frames);
+        let mut dither = options.dither();
+        let segment_frames = segment_frames as usize;
+        let mut segments: Vec<&[f32]> = master_buffer
+            .chunks(segment_frames.saturating_mul(2))
+            .collect();
+        // An empty mix still gets one empty segment.
+        if segments.is_empty() {
+            segments.push(&[]);
+        }
+        segments
+            .into_iter()
+            .enumerate()
+            .map(|(i, segment)| {
+                // Each segment holds the cues that fall in it, counted from
+                // its own start. One at the very end goes in the last.
+                let start = i.saturating_mul(segment_frames);
+                let end = start + segment.len() / 2;
+                let segment_cues: Vec<cue::CuePoint> = cues
+                    .iter()
+                    .filter(|c| start <= c.frame && (c.frame < end || c.frame == frames))
+                    .map(|c| cue::CuePoint {
+                        frame: c.frame - start,
+                        label: c.label.clone(),
+                    })
+                    .collect();
+                let bext = options.bext.as_ref().map(|bext| bext.advanced(start));
+                // One channel when both sides are the same, as in `combine_with_stats`.
+                let (segment, channels) = if stats.mono {
+                    let left = segment.iter().step_by(2).copied().collect();
+                    (std::borrow::Cow::Owned(left), 1)
+                } else {
+                    (std::borrow::Cow::Borrowed(segment), 2)
+                };
+                wav::create_wav_container(
+                    &segment,
+                    options.sample_rate,
+                    channels,
+                    options.bit_depth,
+                    wav::WavTags {
+                        bext: bext.as_ref(),
+                        sample_loop: None,
+                        metadata: options.metadata.as_ref(),
+                        cues: &segment_cues,
+                    },
+                    options.rf64,
+                    dither.as_mut(),
+                )
+                .map(|wav| SingleAudioFile::new(wav, SingleAudioFileType::Wav))
+            })
+            .collect()
+    }
+
+    /// Like `combine_tracks`, also reporting what was done to the mix.
+    pub fn combine_with_stats(
+        &self,
+        tracks: Vec<MixTrack>,
+        options: &CombineOptions,
+    ) -> Result<CombineResult, CombineError> {
+        let mut stats = CombineStats {
+            decode_ms: self.files.iter().map(|f| f.decode_ms).collect(),
+            ..CombineStats::default()
+        };
+        let mut master_buffer = self.master(tracks, options, &mut stats)?;
+        let target_sample_rate = options.sample_rate;
+        let peak = master_buffer
+            .iter()
+            .fold(0.0f32, |peak, s| peak.max(s.abs()));
+        stats.peak_dbfs = 20.0 * peak.log10();
+        if options.true_peak {
+            stats.true_peak_dbtp = Some(20.0 * loudness::true_peak(&master_buffer).log10());
+        }
+        stats.clipped_samples = master_buffer.iter().filter(|s| s.abs() > 1.0).count();
+        let frames = master_buffer.len() / 2;
+        stats.duration_ms = frames as f64 * 1000.0 / target_sample_rate as f64;
+        let waveform = match options.waveform_buckets {
+            0 => None,
+            buckets => Some(waveform::peaks(
+                &master_buffer,
+                buckets,
+                options.waveform_per_channel,
+            )),
+        };
+
+        // Encode into the requested container
+        let encode_start = now();
+        // A mix with the same samples on both sides is written as one channel.
+        let channels = if stats.mono && options.format == OutputFormat::Wav {
+            master_buffer.keep_left();
+            1
+        } else {
+            2
+        };
+        let file = match options.format {
+            OutputFormat::Wav => SingleAudioFile::new(
+                master_buffer.into_wav(
+                    target_sample_rate,
+                    channels,
+                    options.bit_depth,
+                    wav::WavTags {
+                        bext: options.bext.as_ref(),
+                        sample_loop: options.sample_loop.map(|l| l.points(frames)).transpose()?,
+                        metadata: options.metadata.as_ref(),
+                        cues: &cue::cue_points(options, &stats.file_starts, frames),
+                    },
+                    options.rf64,
+                    options.dither().as_mut(),
+                )?,
+                SingleAudioFileType::Wav,
+            ),
+            OutputFormat::Ogg => SingleAudioFile::new(
+                ogg::encode_ogg_vorbis(
+                    &master_buffer,
+                    target_sample_rate,
+                    options.quality,
+                    options.metadata.as_ref(),
+                )?,
+                SingleAudioFileType::Ogg,
+            ),
+            OutputFormat::Mpeg => SingleAudioFile::new(
+                mp3::encode_mp3(
+                    &master_buffer,
+                    target_sample_rate,
+                    options.mp3_bitrate,
+                    options.metadata.as_ref(),
+                )?,
+                SingleAudioFileType::Mpeg,
+            ),
+        };
+        stats.encode_ms = now() - encode_start;
+        log::debug(|| format!("encoded {:?} in {:.1} ms", options.format, stats.encode_ms));
+        Ok(CombineResult {
+            file,
+            stats,
+            waveform,
+            // Numbered by where each file is now, which `remove_file` and
+            // `merge` can change since it was decoded.
+            warnings: self
+                .files
+                .iter()
+                .enumerate()
+                .flat_map(|(index, file)| {
+                    file.warnings.iter().map(move |warning| DecodeWarning {
+                        index,
+                        ..warning.clone()
+                    })
+                })
+                .collect(),
+            skipped_tracks: Vec::new(),
+        })
+    }
+
+    /// Mixes without encoding, for playback straight through Web Audio.
+    pub fn combine_raw(&self, volumes: Vec<u8>) -> CombinedPcm {
+        CombinedPcm {
+            samples: self.mix(
+                &volume_tracks(&volumes),
+                &CombineOptions::default(),
+                &mut CombineStats::default(),
+            ),
+            sample_rate: MASTER_SAMPLE_RATE,
+        }
+    }
+
+    /// Mixes without encoding and splits the channels for `AudioBuffer.copyToChannel`.
+    pub fn combine_planar(&self, volumes: Vec<u8>) -> PlanarPcm {
+        let master_buffer = self.mix(
+            &volume_tracks(&volumes),
+            &CombineOptions::default(),
+            &mut CombineStats::default(),
+        );
+        let frames = master_buffer.len() / 2;
+        let mut left = Vec::with_capacity(frames);
+        let mut right = Vec::with_capacity(frames);
+        for frame in master_buffer.chunks_exact(2) {
+            left.push(frame[0]);
+            right.push(frame[1]);
+        }
+        PlanarPcm {
+            left,
+            right,
+            sample_rate: MASTER_SAMPLE_RATE,
+        }
+    }
+}
+
+impl AudioCombiner {
+    /// Mixes and masters the files into interleaved stereo at
+    /// `options.sample_rate`, noting the gains and timings in `stats`.
+    fn master(
+        &self,
+        tracks: Vec<MixTrack>,
+        options: &CombineOptions,
+        stats: &mut CombineStats,
+    ) -> Result<wav::MixBuffer, CombineError> {
+        options.validate()?;
+        if options.simulate_delay_ms > 0 && !options.deterministic {
+            // There is no sleeping on the wasm main thread, so spin.
+            let until = now() + options.simulate_delay_ms as f64;
+            while now() < until {}
+        }
+
+        if self.files.is_empty() {
+            return Err(CombineError::EmptyInput);
+        }
+        let target_sample_rate = options.sample_rate;
+
+        if options.require_explicit_volumes && tracks.len() < self.files.len() {
+            return Err(CombineError::MissingVolume {
+                index: tracks.len(),
+            });
+        }
+        for (index, track) in tracks.iter().enumerate() {
+            track.validate(index, self.files.len())?;
+        }
+
+        // Extra tracks have no file to place, so drop them.
+        let mut tracks = tracks;
+        tracks.resize_with(self.files.len(), MixTrack::default);
+        let soloing = tracks.iter().any(|t| t.solo);
+        stats.mono = track::is_mono_mix(&tracks, |i| self.files[i].mono);
+        let mix_start = now();
+        if let Some(target) = options.loudness_target_lufs {
+            for (track, file) in tracks.iter_mut().zip(&self.files) {
+                if !track.is_audible(soloing) {
+                    continue;
+                }
+                let lufs = file.replaygain().loudness_lufs;
+                // Silence has no loudness to correct.
+                if lufs.is_finite() {
+                    track.scale_gain(db_to_linear(target - lufs as f32));
                 }
             }
-            processed_files.push(AudioCombinerSingleFile {
-                samples: decoded_samples,
+        }
+        if options.auto_level {
+            for (track, file) in tracks.iter_mut().zip(&self.files) {
+                if track.is_audible(soloing) {
+                    track.scale_gain(db_to_linear(file.replaygain().track_gain_db()));
+                }
+            }
+        }
+
+        let mut master_buffer = self.mix(&tracks, options, stats);
+        if options.mono {
+            master::downmix(&mut master_buffer);
+        }
+        if let Some(compressor) = &options.compressor {
+            stats.max_gain_reduction_db = compressor.apply(&mut master_buffer, target_sample_rate);
+        }
+        if options.normalize {
+            stats.normalize_gain = master::normalize_peak(
+                &mut master_buffer,
+                options.normalize_peak_db,
+                options.true_peak,
+            );
+        }
+        if options.master_gain != 1.0 {
+            for sample in master_buffer.iter_mut() {
+                *sample *= options.master_gain;
+            }
+        }
+        let master_gain = stats.normalize_gain * options.master_gain;
+        stats.track_gains = tracks
+            .iter()
+            .map(|t| {
+                if t.is_audible(soloing) {
+                    let [left, right] = t.gains();
+                    left.max(right) * master_gain
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+        if options.limiter {
+            master::limit(
+                &mut master_buffer,
+                target_sample_rate,
+                options.limiter_release_ms,
+                options.true_peak,
+            );
+        }
+        stats.mix_ms = now() - mix_start;
+        log::debug(|| format!("mixed {} files in {:.1} ms", self.files.len(), stats.mix_ms));
+        Ok(master_buffer)
+    }
+
+    fn decode_all(
+        files: Vec<SingleAudioFile>,
+        progress: Option<&js_sys::Function>,
+    ) -> Result<AudioCombiner, CombineError> {
+        utils::set_panic_hook();
+
+        // Progress callbacks are JS functions, which cannot leave this thread.
+        #[cfg(feature = "threads")]
+        if progress.is_none() && utils::threads_available() {
+            use rayon::prelude::*;
+
+            let decoded: Vec<_> = files
+                .into_par_iter()
+                .enumerate()
+                .map(|(index, file)| timed_decode(index, file, None))
+                .collect();
+            // Collected in input order, so the first failing file is reported
+            // however the work was split.
+            return Ok(AudioCombiner {
+                files: decoded
+                    .into_iter()
+                    .map(|file| file.map(Arc::new))
+                    .collect::<Result<_, _>>()?,
+                cache: None,
             });
         }
 
+        let mut processed_files = Vec::with_capacity(files.len());
+
+        for (index, file) in files.into_iter().enumerate() {
+            processed_files.push(timed_decode(index, file, progress)?);
+            if let Some(progress) = progress {
+                decode::report_progress(progress, index, 1.0)?;
+            }
+        }
+
         Ok(AudioCombiner {
-            files: processed_files,
+            files: processed_files.into_iter().map(Arc::new).collect(),
+            cache: None,
         })
     }
 
-    pub fn combine(&self, volumes: Vec<u8>) -> Result<SingleAudioFile, String> {
-        let target_sample_rate = 44100u32;
+    /// Cuts file `index` to its trim window and any silence at its ends,
+    /// rewires its channels, removes DC, reverses, filters and gates it if
+    /// asked, resamples it for the output rate and its speed, and tiles any
+    /// loops.
+    fn prepare<'a>(
+        f: &'a AudioCombinerSingleFile,
+        track: &MixTrack,
+        index: usize,
+        sample_rate: u32,
+    ) -> std::borrow::Cow<'a, [f32]> {
+        let mut window = track.trim(f.samples.len() / 2, f.sample_rate);
+        if let Some(threshold) = track.auto_trim_silence {
+            let trimmed = &f.samples[window.start * 2..window.end * 2];
+            let audible = silence::audible(trimmed, f.sample_rate, threshold);
+            window = window.start + audible.start..window.start + audible.end;
+        }
+        let samples = &f.samples[window.start * 2..window.end * 2];
+        let mut samples = track.rewired(std::borrow::Cow::Borrowed(samples), f.mono);
+        if track.remove_dc {
+            filter::remove_dc(samples.to_mut(), f.sample_rate);
+        }
+        if track.reversed {
+            // Frame by frame, so left and right stay in place
+            samples = samples.chunks_exact(2).rev().flatten().copied().collect();
+        }
+        if let Some(cutoff) = track.highpass_hz {
+            filter::highpass(samples.to_mut(), cutoff, f.sample_rate);
+        }
+        if let Some(cutoff) = track.lowpass_hz {
+            filter::lowpass(samples.to_mut(), cutoff, f.sample_rate);
+        }
+        if let Some(eq) = track.eq.filter(|eq| !eq.is_flat()) {
+            eq.apply(samples.to_mut(), f.sample_rate);
+        }
+        if let Some(gate) = &track.gate {
+            gate.apply(samples.to_mut(), f.sample_rate);
+        }
+        if f.sample_rate != sample_rate || track.speed != 1.0 {
+            // Playing faster is resampling from a higher rate.
+            let ratio = sample_rate as f64 / (f.sample_rate as f64 * track.speed as f64);
+            log::debug(|| {
+                format!(
+                    "resampling file {} from {} Hz at speed {} to {} Hz",
+                    index, f.sample_rate, track.speed, sample_rate
+                )
+            });
+            samples = std::borrow::Cow::Owned(resample::resample(&samples, ratio));
+        }
+        track.looped(samples, sample_rate)
+    }
+
+    /// Sums every file into one interleaved stereo buffer as placed by its
+    /// track, at the options' sample rate and laid out by their mode.
+    ///
+    /// Files past the end of `tracks` use the default track; extra tracks are
+    /// ignored. Files taken from the cache are counted in `stats`.
+    fn mix(
+        &self,
+        tracks: &[MixTrack],
+        options: &CombineOptions,
+        stats: &mut CombineStats,
+    ) -> wav::MixBuffer {
+        let sample_rate = options.sample_rate;
+        let tracks: Vec<MixTrack> = (0..self.files.len())
+            .map(|i| tracks.get(i).cloned().unwrap_or_default())
+            .collect();
 
-        // 1. Determine final length
-        let max_len = self
+        // 0. Leave out muted files, or everything but the soloed ones
+        let soloing = tracks.iter().any(|t| t.solo);
+        let (indices, (sources, tracks)): (
+            Vec<usize>,
+            (Vec<&AudioCombinerSingleFile>, Vec<MixTrack>),
+        ) = self
             .files
             .iter()
-            .map(|f| f.samples.len())
-            .max()
-            .unwrap_or(0);
+            .map(Arc::as_ref)
+            .zip(tracks)
+            .enumerate()
+            .filter(|(_, (_, track))| track.is_audible(soloing))
+            .unzip();
 
-        // 2. Pre-allocate master buffer with zeros
-        let mut master_buffer = vec![0.0f32; max_len];
+        // 1. Prepare each file for the output rate, or take it from the cache
+        let files: Vec<std::borrow::Cow<[f32]>> = sources
+            .iter()
+            .zip(&tracks)
+            .zip(&indices)
+            .map(|((f, track), &index)| {
+                let Some(cache) = &self.cache else {
+                    return Self::prepare(f, track, index, sample_rate);
+                };
+                let key = cache::PrepareKey::new(track, sample_rate);
+                if let Some(samples) = cache.get(index, &key) {
+                    stats.cached_files += 1;
+                    return std::borrow::Cow::Owned(samples);
+                }
+                let samples = Self::prepare(f, track, index, sample_rate);
+                cache.insert(index, key, &samples);
+                samples
+            })
+            .collect();
+
+        // 2. Place the files and determine the final length, including any
+        //    leading silence
+        let lengths: Vec<usize> = files.iter().map(|f| f.len() / 2).collect();
+        let track::Layout {
+            starts,
+            overlaps,
+            frames: max_frames,
+        } = track::Layout::new(&lengths, &tracks, options);
+        stats.file_starts = indices
+            .iter()
+            .copied()
+            .zip(starts.iter().copied())
+            .collect();
 
-        // 3. Simple addition mix
-        for (i, file) in self.files.iter().enumerate() {
-            let volume_factor = *volumes.get(i).unwrap_or(&100) as f32 / 100.0;
+        // 3. Pre-allocate master buffer with zeros
+        let mut master_buffer = wav::MixBuffer::zeroed(max_frames * 2);
 
-            // Zip allows the compiler to use SIMD optimizations
-            for (m_sample, &f_sample) in master_buffer.iter_mut().zip(file.samples.iter()) {
-                *m_sample += f_sample * volume_factor;
+        // 4. Simple addition mix, in file order
+        let spans: Vec<sum::Span> = starts
+            .iter()
+            .zip(&lengths)
+            .map(|(&start, &frames)| sum::Span {
+                start,
+                frames: frames.min(max_frames - start),
+            })
+            .collect();
+        let weigh = |i: usize, out: &mut [f32]| {
+            let file = &files[i];
+            let mono = sources[i].mono || tracks[i].force_mono;
+            let [left, right] = tracks[i].channel_gains(mono);
+            let frames = file.len() / 2;
+            let envelope = tracks[i].envelope(frames, sample_rate);
+            let (head, tail) = (overlaps[i], overlaps[i + 1]);
+            // A source left out by mute or solo leaves the track alone.
+            let ducking = tracks[i].ducking.and_then(|ducking| {
+                let source = indices.iter().position(|&j| j == ducking.source as usize)?;
+                Some(ducking.gains(
+                    &files[source],
+                    starts[source],
+                    tracks[source].gains(),
+                    max_frames,
+                    sample_rate,
+                ))
+            });
+
+            // Fades are worked out a block at a time so the gain and sum can
+            // run over whole blocks
+            let mut fades = vec![0.0f32; MIX_BLOCK_FRAMES];
+            for (block, (m_block, f_block)) in out
+                .chunks_mut(MIX_BLOCK_FRAMES * 2)
+                .zip(file.chunks(MIX_BLOCK_FRAMES * 2))
+                .enumerate()
+            {
+                let first = block * MIX_BLOCK_FRAMES;
+                let fades = &mut fades[..f_block.len() / 2];
+                for (k, fade) in fades.iter_mut().enumerate() {
+                    let n = first + k;
+                    *fade = envelope(n) * track::crossfade_gain(n, frames, head, tail);
+                    if let Some(ducking) = &ducking {
+                        *fade *= ducking[starts[i] + n];
+                    }
+                }
+                simd::accumulate(m_block, f_block, [left, right], fades);
             }
+        };
+        #[cfg(feature = "threads")]
+        if utils::threads_available() {
+            sum::parallel(&mut master_buffer, &spans, weigh);
+            return master_buffer;
         }
+        sum::serial(&mut master_buffer, &spans, weigh);
+        master_buffer
+    }
+}
 
-        // 4. Wrap in WAV container
-        Ok(SingleAudioFile {
-            bytes: create_wav_container(&master_buffer, target_sample_rate),
-            r#type: SingleAudioFileType::Wav,
-        })
+/// Unencoded mix returned by `AudioCombiner::combine_raw`.
+#[wasm_bindgen]
+pub struct CombinedPcm {
+    samples: wav::MixBuffer,
+    sample_rate: u32,
+}
+
+#[wasm_bindgen]
+impl CombinedPcm {
+    /// Interleaved samples, copied into a `Float32Array` owned by JS so it
+    /// stays valid after wasm memory grows or this struct is freed.
+    #[wasm_bindgen(getter)]
+    pub fn samples(&self) -> js_sys::Float32Array {
+        js_sys::Float32Array::from(&*self.samples)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn channels(&self) -> u16 {
+        2
+    }
+}
+
+/// Unencoded mix returned by `AudioCombiner::combine_planar`, one array per
+/// channel.
+///
+/// The master is always stereo: mono sources are upmixed while decoding, so
+/// `left` and `right` are both populated even when every input was mono.
+#[wasm_bindgen]
+pub struct PlanarPcm {
+    left: Vec<f32>,
+    right: Vec<f32>,
+    sample_rate: u32,
+}
+
+#[wasm_bindgen]
+impl PlanarPcm {
+    /// JS-owned copy of the left channel.
+    #[wasm_bindgen(getter)]
+    pub fn left(&self) -> js_sys::Float32Array {
+        js_sys::Float32Array::from(self.left.as_slice())
+    }
+
+    /// JS-owned copy of the right channel.
+    #[wasm_bindgen(getter)]
+    pub fn right(&self) -> js_sys::Float32Array {
+        js_sys::Float32Array::from(self.right.as_slice())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn channels(&self) -> u16 {
+        2
     }
 }
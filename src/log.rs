@@ -0,0 +1,48 @@
+//! Diagnostics on the browser console, silent unless `set_log_level` turns
+//! them on.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use wasm_bindgen::prelude::*;
+
+/// How much `set_log_level` lets through, each level including the ones
+/// before it.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    /// Decode warnings, resampling and timings as they happen.
+    Debug,
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Off as u8);
+
+/// Sets what is logged to the console for every combiner in this module.
+/// Nothing is logged until this is called.
+#[wasm_bindgen]
+pub fn set_log_level(level: LogLevel) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// Logs `message()` at `level` if that level is on, only building the
+/// message when it is.
+pub(crate) fn log(level: LogLevel, message: impl FnOnce() -> String) {
+    if level == LogLevel::Off || level as u8 > LEVEL.load(Ordering::Relaxed) {
+        return;
+    }
+    let message = JsValue::from(message());
+    match level {
+        LogLevel::Off => {}
+        LogLevel::Error => web_sys::console::error_1(&message),
+        LogLevel::Warn => web_sys::console::warn_1(&message),
+        LogLevel::Info => web_sys::console::info_1(&message),
+        LogLevel::Debug => web_sys::console::debug_1(&message),
+    }
+}
+
+pub(crate) fn debug(message: impl FnOnce() -> String) {
+    log(LogLevel::Debug, message);
+}
@@ -0,0 +1,179 @@
+use crate::filter::Biquad;
+
+/// Gating block length and hop from ITU-R BS.1770-4, in ms.
+const BLOCK_MS: u32 = 400;
+const HOP_MS: u32 = 100;
+/// Blocks quieter than this are ignored outright.
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+/// Blocks this far below the absolutely gated loudness are ignored too.
+const RELATIVE_GATE_LU: f64 = -10.0;
+
+/// The K-weighting pre-filter and RLB high-pass, designed for `sample_rate`
+/// from the analogue prototypes so any input rate is measured correctly.
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+    use std::f64::consts::PI;
+
+    let rate = sample_rate as f64;
+
+    let (f0, gain_db, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+    let k = (PI * f0 / rate).tan();
+    let vh = 10f64.powf(gain_db / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let shelf = Biquad::normalized(
+        [
+            vh + vb * k / q + k * k,
+            2.0 * (k * k - vh),
+            vh - vb * k / q + k * k,
+        ],
+        [
+            1.0 + k / q + k * k,
+            2.0 * (k * k - 1.0),
+            1.0 - k / q + k * k,
+        ],
+    );
+
+    let (f0, q) = (38.13547087602444, 0.5003270373238773);
+    let k = (PI * f0 / rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+    // BS.1770 keeps the numerator of the RLB high-pass at 1, -2, 1 rather
+    // than dividing it by `a0` too.
+    let high_pass = Biquad::normalized(
+        [a0, -2.0 * a0, a0],
+        [a0, 2.0 * (k * k - 1.0), 1.0 - k / q + k * k],
+    );
+    [shelf, high_pass]
+}
+
+fn block_loudness(power: f64) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+/// Gated integrated loudness of decoded interleaved stereo, in LUFS.
+///
+/// A `mono` source was copied to both channels while decoding, so only one
+/// of them is measured. Returns negative infinity for silence and for audio
+/// shorter than one 400 ms block.
+pub fn integrated_loudness(samples: &[f32], sample_rate: u32, mono: bool) -> f64 {
+    let channels = if mono { 1 } else { 2 };
+    let [shelf, high_pass] = k_weighting(sample_rate);
+    let mut states = vec![[[0.0; 2]; 2]; channels];
+
+    // Sum weighted power per hop, then add four hops up into each block.
+    let hop = (sample_rate * HOP_MS / 1000) as usize;
+    let mut hops = Vec::new();
+    let mut energy = 0.0;
+    for (n, frame) in samples.chunks_exact(2).enumerate() {
+        for (sample, [shelf_state, high_pass_state]) in frame.iter().zip(states.iter_mut()) {
+            let weighted =
+                high_pass.process(shelf.process(*sample as f64, shelf_state), high_pass_state);
+            energy += weighted * weighted;
+        }
+        if (n + 1) % hop == 0 {
+            hops.push(energy);
+            energy = 0.0;
+        }
+    }
+
+    let hops_per_block = (BLOCK_MS / HOP_MS) as usize;
+    let block_frames = (hop * hops_per_block) as f64;
+    let blocks: Vec<f64> = hops
+        .windows(hops_per_block)
+        .map(|w| w.iter().sum::<f64>() / block_frames)
+        .filter(|&power| block_loudness(power) > ABSOLUTE_GATE_LUFS)
+        .collect();
+    if blocks.is_empty() {
+        return f64::NEG_INFINITY;
+    }
+
+    let mean = |powers: &[f64]| powers.iter().sum::<f64>() / powers.len() as f64;
+    let gate = block_loudness(mean(&blocks)) + RELATIVE_GATE_LU;
+    // The loudest block is always above the relative gate, so this is never
+    // empty.
+    let gated: Vec<f64> = blocks
+        .into_iter()
+        .filter(|&power| block_loudness(power) > gate)
+        .collect();
+    block_loudness(mean(&gated))
+}
+
+/// The 48-tap interpolating FIR of ITU-R BS.1770-4 Annex 2, split into the
+/// four phases of 4x oversampling, in units of `TRUE_PEAK_TAP_UNIT`. The
+/// standard gives every tap as a multiple of it.
+const TRUE_PEAK_PHASES: [[i16; 12]; 4] = [
+    [
+        14, 90, -161, 272, -487, 1125, 7964, -838, 390, -218, 122, -68,
+    ],
+    [
+        -239, 240, -424, 730, -1364, 3810, 6388, -1641, 832, -477, 271, -155,
+    ],
+    [
+        -155, 271, -477, 832, -1641, 6388, 3810, -1364, 730, -424, 240, -239,
+    ],
+    [
+        -68, 122, -218, 390, -838, 7964, 1125, -487, 272, -161, 90, 14,
+    ],
+];
+
+const TRUE_PEAK_TAP_UNIT: f32 = 1.0 / 8192.0;
+
+/// Input samples the oversampled values lag by: those computed on taking
+/// in frame `n` fall between frames `n - 6` and `n - 5`.
+const TRUE_PEAK_DELAY: usize = 6;
+
+/// Calls `found(n, peak)` with the peak over both channels of the four
+/// values oversampled on taking in frame `n`, for every `n` until the
+/// filter is flushed. Only the taps' worth of input is read at a time, so
+/// nothing the length of `samples` is built.
+fn oversampled_peaks(samples: &[f32], mut found: impl FnMut(usize, f32)) {
+    let frames = samples.len() / 2;
+    let phases = TRUE_PEAK_PHASES.map(|phase| phase.map(|tap| tap as f32 * TRUE_PEAK_TAP_UNIT));
+    let taps = phases[0].len();
+    // Zeros before the first frame and after the last flush the filter.
+    let sample = |frame: usize, channel: usize| match frame.checked_sub(taps - 1) {
+        Some(frame) if frame < frames => samples[frame * 2 + channel],
+        _ => 0.0,
+    };
+    for n in 0..frames + TRUE_PEAK_DELAY {
+        let mut peak = 0.0f32;
+        for channel in 0..2 {
+            for phase in &phases {
+                let value: f32 = phase
+                    .iter()
+                    .enumerate()
+                    .map(|(j, h)| h * sample(n + taps - 1 - j, channel))
+                    .sum();
+                peak = peak.max(value.abs());
+            }
+        }
+        found(n, peak);
+    }
+}
+
+/// Peak of each frame of interleaved stereo in the waveform between its
+/// samples, oversampled 4x as in BS.1770-4: the largest of its own samples
+/// and the values rebuilt within a sample either side, over both channels.
+pub fn frame_true_peaks(samples: &[f32]) -> Vec<f32> {
+    let mut peaks: Vec<f32> = samples
+        .chunks_exact(2)
+        .map(|frame| frame[0].abs().max(frame[1].abs()))
+        .collect();
+    oversampled_peaks(samples, |n, peak| {
+        for frame in [
+            n.checked_sub(TRUE_PEAK_DELAY),
+            n.checked_sub(TRUE_PEAK_DELAY - 1),
+        ] {
+            if let Some(peak_of_frame) = frame.and_then(|frame| peaks.get_mut(frame)) {
+                *peak_of_frame = peak_of_frame.max(peak);
+            }
+        }
+    });
+    peaks
+}
+
+/// True peak of interleaved stereo as a linear level, never below its
+/// sample peak. Silence is 0.
+pub fn true_peak(samples: &[f32]) -> f32 {
+    let mut max = samples.iter().fold(0.0f32, |max, s| max.max(s.abs()));
+    oversampled_peaks(samples, |_, peak| max = max.max(peak));
+    max
+}
@@ -0,0 +1,229 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::envelope::coefficient;
+use crate::{db_to_linear, loudness, CombineError};
+
+/// Scales the mix so its absolute peak sits at `target_db` dBFS, or its
+/// true peak at `target_db` dBTP, and returns the gain applied.
+///
+/// Silence has no peak to scale, so it is left alone with a gain of 1.0.
+pub fn normalize_peak(samples: &mut [f32], target_db: f32, true_peak: bool) -> f32 {
+    let peak = match true_peak {
+        true => loudness::true_peak(samples),
+        false => samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs())),
+    };
+    if peak == 0.0 {
+        return 1.0;
+    }
+    let gain = db_to_linear(target_db) / peak;
+    for sample in samples.iter_mut() {
+        *sample *= gain;
+    }
+    gain
+}
+
+/// Replaces both channels of interleaved stereo with their average, so a
+/// sound in the centre keeps its level.
+pub fn downmix(samples: &mut [f32]) {
+    for frame in samples.chunks_exact_mut(2) {
+        let mid = (frame[0] + frame[1]) / 2.0;
+        frame[0] = mid;
+        frame[1] = mid;
+    }
+}
+
+/// Highest level the limiter lets through, in dBFS.
+const LIMITER_CEILING_DB: f32 = -0.3;
+/// How far ahead the limiter looks, and so how long it takes to pull the
+/// gain down before an over.
+const LIMITER_LOOKAHEAD_MS: f64 = 2.0;
+
+/// Brick-wall limits interleaved stereo at `sample_rate` to
+/// `LIMITER_CEILING_DB`, recovering to unity with a `release_ms` time
+/// constant. With `true_peak` the ceiling is in dBTP: a frame counts as
+/// loud as the waveform rebuilt on either side of it, so the frames around
+/// an inter-sample peak are both turned down under it.
+///
+/// The whole mix is in memory, so the look-ahead reads future frames
+/// directly instead of delaying the output, and the length is unchanged.
+pub fn limit(samples: &mut [f32], sample_rate: u32, release_ms: f32, true_peak: bool) {
+    let ceiling = db_to_linear(LIMITER_CEILING_DB);
+    let lookahead = (LIMITER_LOOKAHEAD_MS * sample_rate as f64 / 1000.0).round() as usize;
+    let release = coefficient(release_ms, sample_rate) as f32;
+
+    let peaks = match true_peak {
+        true => loudness::frame_true_peaks(samples),
+        false => samples
+            .chunks_exact(2)
+            .map(|frame| frame[0].abs().max(frame[1].abs()))
+            .collect(),
+    };
+    // Gain each frame needs on its own to stay under the ceiling.
+    let required: Vec<f32> = peaks
+        .into_iter()
+        .map(|peak| if peak > ceiling { ceiling / peak } else { 1.0 })
+        .collect();
+    let frames = required.len();
+
+    // Lowest requirement over each frame and the `lookahead` after it, via a
+    // monotonic queue of candidate minimums.
+    let mut upcoming = Vec::with_capacity(frames);
+    let mut queue = std::collections::VecDeque::new();
+    for i in 0..frames + lookahead {
+        if i < frames {
+            while queue.back().is_some_and(|&j| required[j] >= required[i]) {
+                queue.pop_back();
+            }
+            queue.push_back(i);
+        }
+        if i >= lookahead {
+            let start = i - lookahead;
+            while queue.front().is_some_and(|&j| j < start) {
+                queue.pop_front();
+            }
+            upcoming.push(required[queue[0]]);
+        }
+    }
+
+    // Averaging that over the look-ahead turns each step down into a ramp
+    // that still reaches the required gain by the time the over arrives.
+    let window = lookahead + 1;
+    let mut sum = 0.0f64;
+    let mut gain = 1.0f32;
+    for i in 0..frames {
+        sum += upcoming[i] as f64;
+        if i >= window {
+            sum -= upcoming[i - window] as f64;
+        }
+        // Frames before the start repeat the first one, so an over right at
+        // the start is already attenuated rather than clipped.
+        let padding = window.saturating_sub(i + 1) as f64 * upcoming[0] as f64;
+        let attack = ((sum + padding) / window as f64) as f32;
+
+        gain = 1.0 - (1.0 - gain) * release;
+        gain = gain.min(attack).min(required[i]);
+        samples[i * 2] *= gain;
+        samples[i * 2 + 1] *= gain;
+    }
+}
+
+/// Evens out the level of the whole mix, set through
+/// `CombineOptions::compressor`. Both channels share one gain, so the stereo
+/// image does not shift.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Compressor {
+    /// Level, in dBFS, above which the mix is turned down.
+    pub threshold_db: f32,
+    /// How many dB over the threshold go in for each dB that comes out.
+    pub ratio: f32,
+    /// Time constant for the detector rising to a louder level.
+    pub attack_ms: f32,
+    /// Time constant for it falling back.
+    pub release_ms: f32,
+    /// Gain added after compression to win back the level, in dB.
+    pub makeup_db: f32,
+    /// Follow the RMS level of the mix instead of its peaks.
+    pub rms: bool,
+}
+
+#[wasm_bindgen]
+impl Compressor {
+    /// 3:1 above -18 dBFS on peaks, with no makeup gain.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self {
+            threshold_db: -18.0,
+            ratio: 3.0,
+            attack_ms: 10.0,
+            release_ms: 150.0,
+            makeup_db: 0.0,
+            rms: false,
+        }
+    }
+}
+
+impl Default for Compressor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Compressor {
+    pub(crate) fn validate(&self) -> Result<(), CombineError> {
+        let invalid = |detail: String| Err(CombineError::InvalidOption { detail });
+        for (name, value) in [
+            ("threshold_db", self.threshold_db),
+            ("makeup_db", self.makeup_db),
+        ] {
+            if !value.is_finite() {
+                return invalid(format!(
+                    "compressor {} must be a number, got {}",
+                    name, value
+                ));
+            }
+        }
+        if !self.ratio.is_finite() || self.ratio < 1.0 {
+            return invalid(format!(
+                "compressor ratio must be at least 1, got {}",
+                self.ratio
+            ));
+        }
+        for (name, value) in [
+            ("attack_ms", self.attack_ms),
+            ("release_ms", self.release_ms),
+        ] {
+            if !value.is_finite() || value < 0.0 {
+                return invalid(format!(
+                    "compressor {} must not be negative, got {}",
+                    name, value
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Compresses interleaved stereo at `sample_rate` in place and returns
+    /// the most gain reduction applied, in dB, before makeup.
+    ///
+    /// The detector follows the louder channel's peak, or the RMS of both
+    /// channels, through a one-pole smoother that rises with `attack_ms`
+    /// and falls with `release_ms`. The gain follows it with no further
+    /// smoothing.
+    pub(crate) fn apply(&self, samples: &mut [f32], sample_rate: u32) -> f32 {
+        let attack = coefficient(self.attack_ms, sample_rate);
+        let release = coefficient(self.release_ms, sample_rate);
+        let threshold = self.threshold_db as f64;
+        let slope = 1.0 - 1.0 / self.ratio as f64;
+        let makeup = self.makeup_db as f64;
+
+        // Squared level for RMS, so the smoother averages power.
+        let mut envelope = 0.0f64;
+        let mut max_reduction = 0.0f64;
+        for frame in samples.chunks_exact_mut(2) {
+            let (left, right) = (frame[0] as f64, frame[1] as f64);
+            let level = if self.rms {
+                (left * left + right * right) / 2.0
+            } else {
+                left.abs().max(right.abs())
+            };
+            let coeff = if level > envelope { attack } else { release };
+            envelope = level + coeff * (envelope - level);
+
+            let level_db = if self.rms {
+                10.0 * envelope.log10()
+            } else {
+                20.0 * envelope.log10()
+            };
+            // Silence gives -inf, which is never over the threshold.
+            let reduction = ((level_db - threshold) * slope).max(0.0);
+            max_reduction = max_reduction.max(reduction);
+            let gain = 10f64.powf((makeup - reduction) / 20.0);
+            frame[0] = (left * gain) as f32;
+            frame[1] = (right * gain) as f32;
+        }
+        max_reduction as f32
+    }
+}
@@ -0,0 +1,92 @@
+use crate::options::{Mp3Bitrate, OutputMetadata};
+use crate::CombineError;
+
+/// An ID3v2.4 tag of UTF-8 text frames for the fields set in `metadata`, or
+/// nothing when none are.
+#[cfg(feature = "mp3-output")]
+fn id3v2_tag(metadata: &OutputMetadata) -> Vec<u8> {
+    let syncsafe = |n: usize| [21, 14, 7, 0].map(|shift| (n >> shift) as u8 & 0x7f);
+    let fields = [
+        (b"TIT2", &metadata.title),
+        (b"TPE1", &metadata.artist),
+        (b"TDRC", &metadata.creation_date),
+    ];
+    let mut frames = Vec::new();
+    for (id, value) in fields {
+        if let Some(value) = value {
+            frames.extend_from_slice(id);
+            frames.extend_from_slice(&syncsafe(value.len() + 1));
+            frames.extend_from_slice(&[0, 0]);
+            // Text encoding 3 is UTF-8.
+            frames.push(3);
+            frames.extend_from_slice(value.as_bytes());
+        }
+    }
+    if frames.is_empty() {
+        return Vec::new();
+    }
+    let mut tag = b"ID3\x04\x00\x00".to_vec();
+    tag.extend_from_slice(&syncsafe(frames.len()));
+    tag.extend(frames);
+    tag
+}
+
+/// Encodes interleaved stereo samples as a constant bitrate MP3 stream,
+/// behind an ID3v2 tag when there is `metadata`.
+#[cfg(feature = "mp3-output")]
+pub fn encode_mp3(
+    samples: &[f32],
+    sample_rate: u32,
+    bitrate: Mp3Bitrate,
+    metadata: Option<&OutputMetadata>,
+) -> Result<Vec<u8>, CombineError> {
+    use mp3lame_encoder::{Bitrate, Builder, FlushNoGap, InterleavedPcm, Quality};
+
+    const CHANNELS: usize = 2;
+    const BLOCK_FRAMES: usize = 4096;
+
+    let mut builder = Builder::new()
+        .ok_or_else(|| CombineError::encode_failed("could not allocate mp3 encoder"))?;
+    builder
+        .set_num_channels(CHANNELS as u8)
+        .map_err(CombineError::encode_failed)?;
+    builder
+        .set_sample_rate(sample_rate)
+        .map_err(CombineError::encode_failed)?;
+    builder
+        .set_brate(match bitrate {
+            Mp3Bitrate::Kbps128 => Bitrate::Kbps128,
+            Mp3Bitrate::Kbps192 => Bitrate::Kbps192,
+            Mp3Bitrate::Kbps320 => Bitrate::Kbps320,
+        })
+        .map_err(CombineError::encode_failed)?;
+    builder
+        .set_quality(Quality::Good)
+        .map_err(CombineError::encode_failed)?;
+    let mut encoder = builder.build().map_err(CombineError::encode_failed)?;
+
+    let mut mp3 = metadata.map(id3v2_tag).unwrap_or_default();
+    for block in samples.chunks(BLOCK_FRAMES * CHANNELS) {
+        mp3.reserve(mp3lame_encoder::max_required_buffer_size(
+            block.len() / CHANNELS,
+        ));
+        encoder
+            .encode_to_vec(InterleavedPcm(block), &mut mp3)
+            .map_err(CombineError::encode_failed)?;
+    }
+    mp3.reserve(mp3lame_encoder::max_required_buffer_size(0));
+    encoder
+        .flush_to_vec::<FlushNoGap>(&mut mp3)
+        .map_err(CombineError::encode_failed)?;
+    Ok(mp3)
+}
+
+#[cfg(not(feature = "mp3-output"))]
+pub fn encode_mp3(
+    _samples: &[f32],
+    _sample_rate: u32,
+    _bitrate: Mp3Bitrate,
+    _metadata: Option<&OutputMetadata>,
+) -> Result<Vec<u8>, CombineError> {
+    Err(CombineError::OutputDisabled { format: "mp3" })
+}
@@ -0,0 +1,95 @@
+use crate::options::OutputMetadata;
+use crate::CombineError;
+
+/// Nominal bitrate of libvorbis stereo at 44.1 kHz for each step of the
+/// 0 to 10 quality scale, in kbps.
+const NOMINAL_KBPS: [f64; 11] = [
+    64.0, 80.0, 96.0, 112.0, 128.0, 160.0, 192.0, 224.0, 256.0, 320.0, 500.0,
+];
+
+/// Rough average bitrate of `encode_ogg_vorbis` at `quality`, in kbps. The
+/// real rate depends on the material, as with any VBR encoder.
+pub fn nominal_kbps(quality: f32) -> f64 {
+    let step = (quality.clamp(0.0, 1.0) * 10.0) as f64;
+    let below = step.floor() as usize;
+    let above = (below + 1).min(10);
+    let t = step - below as f64;
+    NOMINAL_KBPS[below] * (1.0 - t) + NOMINAL_KBPS[above] * t
+}
+
+/// Encodes interleaved stereo samples as an Ogg Vorbis stream.
+///
+/// `quality` is the Vorbis VBR quality in `0.0..=1.0`. Any `metadata` is
+/// written as Vorbis comments.
+#[cfg(feature = "ogg-output")]
+pub fn encode_ogg_vorbis(
+    samples: &[f32],
+    sample_rate: u32,
+    quality: f32,
+    metadata: Option<&OutputMetadata>,
+) -> Result<Vec<u8>, CombineError> {
+    use std::num::{NonZeroU32, NonZeroU8};
+
+    const CHANNELS: usize = 2;
+    const BLOCK_FRAMES: usize = 4096;
+    // A fixed serial keeps identical mixes byte-identical.
+    const STREAM_SERIAL: i32 = 0x5741_4143;
+
+    let sample_rate = NonZeroU32::new(sample_rate)
+        .ok_or_else(|| CombineError::encode_failed("sample rate must not be zero"))?;
+    let mut ogg = Vec::new();
+    let mut builder = vorbis_rs::VorbisEncoderBuilder::new_with_serial(
+        sample_rate,
+        NonZeroU8::new(CHANNELS as u8).unwrap(),
+        &mut ogg,
+        STREAM_SERIAL,
+    );
+    builder.bitrate_management_strategy(vorbis_rs::VorbisBitrateManagementStrategy::QualityVbr {
+        target_quality: quality,
+    });
+    if let Some(metadata) = metadata {
+        let fields = [
+            ("TITLE", &metadata.title),
+            ("ARTIST", &metadata.artist),
+            ("DATE", &metadata.creation_date),
+        ];
+        for (tag, value) in fields {
+            if let Some(value) = value {
+                builder
+                    .comment_tag(tag, value.as_str())
+                    .map_err(CombineError::encode_failed)?;
+            }
+        }
+    }
+    let mut encoder = builder.build().map_err(CombineError::encode_failed)?;
+
+    // libvorbis takes planar blocks.
+    let mut planar = [
+        Vec::with_capacity(BLOCK_FRAMES),
+        Vec::with_capacity(BLOCK_FRAMES),
+    ];
+    for block in samples.chunks(BLOCK_FRAMES * CHANNELS) {
+        for channel in planar.iter_mut() {
+            channel.clear();
+        }
+        for frame in block.chunks_exact(CHANNELS) {
+            planar[0].push(frame[0]);
+            planar[1].push(frame[1]);
+        }
+        encoder
+            .encode_audio_block(&planar)
+            .map_err(CombineError::encode_failed)?;
+    }
+    encoder.finish().map_err(CombineError::encode_failed)?;
+    Ok(ogg)
+}
+
+#[cfg(not(feature = "ogg-output"))]
+pub fn encode_ogg_vorbis(
+    _samples: &[f32],
+    _sample_rate: u32,
+    _quality: f32,
+    _metadata: Option<&OutputMetadata>,
+) -> Result<Vec<u8>, CombineError> {
+    Err(CombineError::OutputDisabled { format: "ogg" })
+}
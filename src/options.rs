@@ -0,0 +1,353 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::dither::Dither;
+use crate::{
+    BroadcastExtension, CombineError, Compressor, Marker, SampleLoop, MAX_SAMPLE_RATE,
+    MIN_SAMPLE_RATE,
+};
+
+/// Sample encoding of the combined WAV output.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum BitDepth {
+    /// 16-bit integer PCM, clamped to full scale.
+    Int16,
+    /// 24-bit integer PCM, clamped to full scale.
+    Int24,
+    /// 32-bit IEEE float, written without clamping.
+    Float32,
+}
+
+impl BitDepth {
+    pub(crate) fn bytes_per_sample(self) -> u16 {
+        match self {
+            BitDepth::Int16 => 2,
+            BitDepth::Int24 => 3,
+            BitDepth::Float32 => 4,
+        }
+    }
+}
+
+/// Container/codec of the combined output.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum OutputFormat {
+    Wav,
+    /// Ogg Vorbis, requires the `ogg-output` feature.
+    Ogg,
+    /// MP3, requires the `mp3-output` feature.
+    Mpeg,
+}
+
+/// Constant bitrate of the combined MP3 output.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Mp3Bitrate {
+    Kbps128,
+    Kbps192,
+    Kbps320,
+}
+
+impl Mp3Bitrate {
+    pub(crate) fn kbps(self) -> u32 {
+        match self {
+            Mp3Bitrate::Kbps128 => 128,
+            Mp3Bitrate::Kbps192 => 192,
+            Mp3Bitrate::Kbps320 => 320,
+        }
+    }
+}
+
+/// How the files are laid out in time.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum CombineMode {
+    /// Overlay every file from the start.
+    Mix,
+    /// Play the files back to back in order. Track offsets become gaps after
+    /// the previous file.
+    Concat,
+}
+
+/// Tags written into the combined output: a LIST/INFO chunk in WAV, Vorbis
+/// comments in Ogg and an ID3v2 tag in MP3. Unset fields are left out.
+#[wasm_bindgen]
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct OutputMetadata {
+    #[wasm_bindgen(getter_with_clone)]
+    pub title: Option<String>,
+    #[wasm_bindgen(getter_with_clone)]
+    pub artist: Option<String>,
+    /// Free text, conventionally an ISO 8601 date such as `"2024-05-01"`.
+    #[wasm_bindgen(getter_with_clone)]
+    pub creation_date: Option<String>,
+}
+
+#[wasm_bindgen]
+impl OutputMetadata {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Settings for `AudioCombiner::combine_with_options`.
+#[wasm_bindgen]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CombineOptions {
+    pub mode: CombineMode,
+    /// In `Concat` mode, how long each file overlaps the next with an
+    /// equal-power crossfade. Clamped to the shorter of the two files.
+    pub crossfade_ms: f64,
+    pub format: OutputFormat,
+    /// Sample encoding for WAV output.
+    pub bit_depth: BitDepth,
+    /// Write WAV output past the 4 GiB that RIFF sizes can hold as RF64
+    /// instead of failing with `TooLarge`. Smaller output stays plain RIFF.
+    pub rf64: bool,
+    /// Add triangular noise of up to 1 LSB before rounding 16-bit output,
+    /// trading the distortion of quiet passages and fades for a steady
+    /// noise floor. Other bit depths are never dithered.
+    pub dither: bool,
+    /// Seed for the dither noise. The same seed gives the same bytes.
+    pub dither_seed: u32,
+    /// Promise byte-identical output for identical inputs and options, run
+    /// after run and machine to machine, as for caching mixes by a hash of
+    /// them. `max_ms` is `INVALID_OPTION` with this on, since whether a
+    /// budget runs out depends on how fast the machine is, and
+    /// `simulate_delay_ms` is skipped so nothing reads the clock.
+    ///
+    /// Nothing else needs changing: dither comes from `dither_seed`, the
+    /// `threads` feature decodes and weighs each file on its own and sums
+    /// them in input order, and the `simd` feature does the same float
+    /// operations in the same order as the scalar code. Across machines this holds for
+    /// the wasm build, whose float math is the same everywhere; a native
+    /// build uses the platform's math library.
+    pub deterministic: bool,
+    /// Encoder quality for compressed output, from 0.0 to 1.0.
+    pub quality: f32,
+    /// Bitrate for MP3 output.
+    pub mp3_bitrate: Mp3Bitrate,
+    /// Output sample rate in Hz; inputs at other rates are resampled.
+    pub sample_rate: u32,
+    /// Busy-waits this long before combining, to make loading states visible
+    /// in demos. Blocks the calling thread; leave at 0 in real use. Skipped
+    /// when `deterministic`.
+    pub simulate_delay_ms: u32,
+    /// Reject volume lists shorter than the file list instead of mixing the
+    /// missing files at 100.
+    pub require_explicit_volumes: bool,
+    /// In `AudioCombiner::combine_files`, leave out any file that fails to
+    /// probe or decode, listing it in `CombineResult::skipped_tracks`,
+    /// instead of failing the whole combine. Only applies there, as every
+    /// other method mixes files that are already decoded.
+    pub skip_failed_tracks: bool,
+    /// Give up on `AudioCombiner::combine_files` with `TIMED_OUT` once it
+    /// has run this long, checking every few packets while decoding and
+    /// again before mixing, or `None` to take as long as it takes.
+    pub max_ms: Option<f64>,
+    /// Scale the finished mix so its peak lands on `normalize_peak_db`.
+    pub normalize: bool,
+    /// Target peak in dBFS for `normalize`.
+    pub normalize_peak_db: f32,
+    /// Measure the peaks `normalize` and the limiter work to as true peak,
+    /// in dBTP, rather than sample peak. Peaks between samples can reach
+    /// past 0 dBFS once a DAC rebuilds the waveform, which is what loudness
+    /// specs such as EBU R 128 cap. Also reports the mix's true peak in
+    /// `CombineStats::true_peak_dbtp`.
+    pub true_peak: bool,
+    /// Average the two channels of the summed mix into both, before the
+    /// compressor and everything after it. The output stays two-channel.
+    pub mono: bool,
+    /// Compress the summed mix before `normalize`, the master gain and the
+    /// limiter, or `None` to leave its dynamics alone.
+    pub compressor: Option<Compressor>,
+    /// Linear gain applied to the whole mix, after `normalize` and before the
+    /// limiter and quantization.
+    pub master_gain: f32,
+    /// Run the mix through a look-ahead limiter with a -0.3 dBFS ceiling,
+    /// or -0.3 dBTP with `true_peak`, instead of letting overs clip.
+    pub limiter: bool,
+    /// How quickly the limiter recovers after an over.
+    pub limiter_release_ms: f32,
+    /// Set each file's gain so it measures this integrated loudness, in
+    /// LUFS, before the track gain is applied on top.
+    pub loudness_target_lufs: Option<f32>,
+    /// Start each file at the gain `AudioCombiner::analyze_replaygain`
+    /// suggests, bringing it to -18 LUFS, with the track gain applied on top.
+    /// Measurements are kept on the combiner, so only the first combine
+    /// pays for them. Cannot be combined with `loudness_target_lufs`.
+    pub auto_level: bool,
+    /// Buckets in `CombineResult::waveform`, or 0 to leave it out.
+    pub waveform_buckets: u32,
+    /// Give a left and a right peak per waveform bucket instead of the louder
+    /// of the two.
+    pub waveform_per_channel: bool,
+    /// Tags for the output file, or `None` for an untagged file.
+    #[wasm_bindgen(getter_with_clone)]
+    pub metadata: Option<OutputMetadata>,
+    /// Make WAV output a Broadcast Wave file with these details, written
+    /// alongside any `metadata`. Segments of `combine_segmented` each get a
+    /// `time_reference` moved on to where they start.
+    #[wasm_bindgen(getter_with_clone)]
+    pub bext: Option<BroadcastExtension>,
+    /// Write a `smpl` chunk into WAV output with this loop, for samplers and
+    /// game engines. A loop that runs past the end of the output is
+    /// `INVALID_OPTION`, and so is one given to `combine_segmented`.
+    pub sample_loop: Option<SampleLoop>,
+    /// Cue points to write into WAV output, each with a label, such as
+    /// chapter marks. Markers past the end of the mix are left out.
+    #[wasm_bindgen(getter_with_clone)]
+    pub markers: Vec<Marker>,
+    /// Also mark where each file starts in WAV output, labelled
+    /// `"Track 1"` and on by file index. In `Concat` mode these are the
+    /// boundaries between files, at the start of any crossfade.
+    pub mark_file_starts: bool,
+}
+
+#[wasm_bindgen]
+impl CombineOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Default for CombineOptions {
+    fn default() -> Self {
+        Self {
+            mode: CombineMode::Mix,
+            crossfade_ms: 0.0,
+            format: OutputFormat::Wav,
+            bit_depth: BitDepth::Int16,
+            rf64: false,
+            dither: true,
+            dither_seed: 0,
+            deterministic: false,
+            quality: 0.5,
+            mp3_bitrate: Mp3Bitrate::Kbps128,
+            sample_rate: crate::MASTER_SAMPLE_RATE,
+            simulate_delay_ms: 0,
+            require_explicit_volumes: false,
+            skip_failed_tracks: false,
+            max_ms: None,
+            normalize: false,
+            normalize_peak_db: -1.0,
+            true_peak: false,
+            mono: false,
+            compressor: None,
+            master_gain: 1.0,
+            limiter: false,
+            limiter_release_ms: 100.0,
+            loudness_target_lufs: None,
+            auto_level: false,
+            waveform_buckets: 0,
+            waveform_per_channel: false,
+            metadata: None,
+            bext: None,
+            sample_loop: None,
+            markers: Vec::new(),
+            mark_file_starts: false,
+        }
+    }
+}
+
+impl CombineOptions {
+    /// The noise source for 16-bit WAV output, if it is dithered.
+    pub(crate) fn dither(&self) -> Option<Dither> {
+        self.dither.then(|| Dither::new(self.dither_seed))
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), CombineError> {
+        if !(MIN_SAMPLE_RATE..=MAX_SAMPLE_RATE).contains(&self.sample_rate) {
+            return Err(CombineError::InvalidOption {
+                detail: format!(
+                    "sample rate must be between {} and {}, got {}",
+                    MIN_SAMPLE_RATE, MAX_SAMPLE_RATE, self.sample_rate
+                ),
+            });
+        }
+        if !self.crossfade_ms.is_finite() || self.crossfade_ms < 0.0 {
+            return Err(CombineError::InvalidOption {
+                detail: format!(
+                    "crossfade_ms must not be negative, got {}",
+                    self.crossfade_ms
+                ),
+            });
+        }
+        if !(0.0..=1.0).contains(&self.quality) {
+            return Err(CombineError::InvalidOption {
+                detail: format!("quality must be between 0.0 and 1.0, got {}", self.quality),
+            });
+        }
+        if !self.normalize_peak_db.is_finite() {
+            return Err(CombineError::InvalidOption {
+                detail: format!(
+                    "normalize_peak_db must be a number, got {}",
+                    self.normalize_peak_db
+                ),
+            });
+        }
+        if let Some(compressor) = &self.compressor {
+            compressor.validate()?;
+        }
+        if !self.master_gain.is_finite() || self.master_gain < 0.0 {
+            return Err(CombineError::InvalidOption {
+                detail: format!(
+                    "master_gain must be a non-negative number, got {}",
+                    self.master_gain
+                ),
+            });
+        }
+        if !self.limiter_release_ms.is_finite() || self.limiter_release_ms < 0.0 {
+            return Err(CombineError::InvalidOption {
+                detail: format!(
+                    "limiter_release_ms must not be negative, got {}",
+                    self.limiter_release_ms
+                ),
+            });
+        }
+        if self.deterministic && self.max_ms.is_some() {
+            return Err(CombineError::InvalidOption {
+                detail: "max_ms depends on the clock, so cannot be used with deterministic".into(),
+            });
+        }
+        if let Some(max_ms) = self.max_ms {
+            if !max_ms.is_finite() || max_ms <= 0.0 {
+                return Err(CombineError::InvalidOption {
+                    detail: format!("max_ms must be a positive number, got {}", max_ms),
+                });
+            }
+        }
+        if let Some(target) = self.loudness_target_lufs {
+            if !target.is_finite() {
+                return Err(CombineError::InvalidOption {
+                    detail: format!("loudness_target_lufs must be a number, got {}", target),
+                });
+            }
+            if self.auto_level {
+                return Err(CombineError::InvalidOption {
+                    detail: "auto_level and loudness_target_lufs both set each file's level".into(),
+                });
+            }
+        }
+        if let Some(bext) = &self.bext {
+            bext.validate()?;
+        }
+        if let Some(sample_loop) = &self.sample_loop {
+            sample_loop.validate()?;
+        }
+        for marker in &self.markers {
+            if !marker.time_ms.is_finite() || marker.time_ms < 0.0 {
+                return Err(CombineError::InvalidOption {
+                    detail: format!(
+                        "marker time_ms must not be negative, got {}",
+                        marker.time_ms
+                    ),
+                });
+            }
+        }
+        Ok(())
+    }
+}
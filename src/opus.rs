@@ -0,0 +1,187 @@
+//! An Opus decoder for symphonia, which demuxes Opus from Ogg and WebM but
+//! ships no decoder for it. Decoding is done by `unsafe-libopus`, a Rust
+//! translation of libopus that builds for wasm without a C toolchain.
+
+use std::ptr::NonNull;
+
+use symphonia::core::audio::{
+    AsAudioBufferRef, AudioBuffer, AudioBufferRef, Channels, Signal, SignalSpec,
+};
+use symphonia::core::codecs::{
+    CodecDescriptor, CodecParameters, Decoder, DecoderOptions, FinalizeResult, CODEC_TYPE_OPUS,
+};
+use symphonia::core::errors::{decode_error, unsupported_error, Result};
+use symphonia::core::formats::Packet;
+use symphonia::core::support_codec;
+use unsafe_libopus::varargs::VarArgs;
+
+/// Opus always decodes at 48 kHz, whatever rate the source was recorded at.
+const SAMPLE_RATE: u32 = 48000;
+/// Longest a packet can be, 120 ms.
+const MAX_PACKET_FRAMES: usize = 5760;
+
+/// The fields of an OpusHead packet that decoding needs. Ogg carries it as
+/// the first packet of the stream and Matroska as the track's private data.
+struct OpusHead {
+    channels: usize,
+    pre_skip: usize,
+    /// Gain to apply to the output, as a linear factor.
+    gain: f32,
+}
+
+impl OpusHead {
+    fn read(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 19 || &bytes[..8] != b"OpusHead" {
+            return decode_error("opus: missing OpusHead");
+        }
+        // Higher mapping families split the channels over several streams.
+        if bytes[18] != 0 {
+            return unsupported_error("multichannel Opus");
+        }
+        let channels = match bytes[9] {
+            channels @ 1..=2 => channels as usize,
+            _ => return decode_error("opus: invalid channel count"),
+        };
+        let gain_q8 = i16::from_le_bytes([bytes[16], bytes[17]]);
+        Ok(Self {
+            channels,
+            pre_skip: u16::from_le_bytes([bytes[10], bytes[11]]) as usize,
+            gain: 10f32.powf(gain_q8 as f32 / (20.0 * 256.0)),
+        })
+    }
+}
+
+/// Owns a libopus decoder state.
+struct State(NonNull<unsafe_libopus::OpusDecoder>);
+
+// SAFETY: the state is only reached through `&mut self`, and libopus keeps
+// nothing tied to the thread that created it.
+unsafe impl Send for State {}
+unsafe impl Sync for State {}
+
+impl State {
+    fn new(channels: usize) -> Result<Self> {
+        let mut error = 0;
+        // SAFETY: the rate and channel count are ones libopus accepts, and it
+        // reports any other failure through `error`.
+        let state = unsafe {
+            unsafe_libopus::opus_decoder_create(SAMPLE_RATE as i32, channels as i32, &mut error)
+        };
+        match NonNull::new(state) {
+            Some(state) if error == unsafe_libopus::OPUS_OK => Ok(Self(state)),
+            _ => decode_error("opus: could not create a decoder"),
+        }
+    }
+}
+
+impl Drop for State {
+    fn drop(&mut self) {
+        // SAFETY: the pointer came from `opus_decoder_create` and is freed
+        // only here.
+        unsafe { unsafe_libopus::opus_decoder_destroy(self.0.as_ptr()) }
+    }
+}
+
+/// Decodes Opus streams of one or two channels to 48 kHz.
+pub struct OpusDecoder {
+    params: CodecParameters,
+    head: OpusHead,
+    state: State,
+    /// Interleaved output of the last packet.
+    pcm: Vec<f32>,
+    buf: AudioBuffer<f32>,
+    /// Frames still to drop from the start of the stream, which the encoder
+    /// filled with its lookahead.
+    skip: usize,
+}
+
+impl Decoder for OpusDecoder {
+    fn try_new(params: &CodecParameters, _options: &DecoderOptions) -> Result<Self> {
+        if params.codec != CODEC_TYPE_OPUS {
+            return unsupported_error("opus: not an Opus stream");
+        }
+        let head = OpusHead::read(params.extra_data.as_deref().unwrap_or_default())?;
+        let layout = match head.channels {
+            1 => Channels::FRONT_LEFT,
+            _ => Channels::FRONT_LEFT | Channels::FRONT_RIGHT,
+        };
+        let spec = SignalSpec::new(SAMPLE_RATE, layout);
+        Ok(Self {
+            params: params.clone(),
+            state: State::new(head.channels)?,
+            pcm: vec![0.0; MAX_PACKET_FRAMES * head.channels],
+            buf: AudioBuffer::new(MAX_PACKET_FRAMES as u64, spec),
+            skip: head.pre_skip,
+            head,
+        })
+    }
+
+    fn supported_codecs() -> &'static [CodecDescriptor] {
+        &[support_codec!(CODEC_TYPE_OPUS, "opus", "Opus")]
+    }
+
+    fn reset(&mut self) {
+        // SAFETY: the state is live, and resetting takes no arguments.
+        unsafe {
+            unsafe_libopus::opus_decoder_ctl_impl(
+                self.state.0.as_ptr(),
+                unsafe_libopus::OPUS_RESET_STATE,
+                VarArgs::new(Vec::new()),
+            );
+        }
+        // The pre-skip only covers the start of the stream, and a reset
+        // comes after a seek away from it.
+        self.skip = 0;
+    }
+
+    fn codec_params(&self) -> &CodecParameters {
+        &self.params
+    }
+
+    fn decode(&mut self, packet: &Packet) -> Result<AudioBufferRef<'_>> {
+        let data = packet.buf();
+        // SAFETY: `data` and `pcm` are live for the call, and `pcm` holds the
+        // longest packet libopus can give for the channel count.
+        let frames = unsafe {
+            unsafe_libopus::opus_decode_float(
+                self.state.0.as_ptr(),
+                data.as_ptr(),
+                data.len() as i32,
+                self.pcm.as_mut_ptr(),
+                MAX_PACKET_FRAMES as i32,
+                0,
+            )
+        };
+        if frames < 0 {
+            self.buf.clear();
+            return decode_error("opus: invalid packet");
+        }
+        let frames = frames as usize;
+
+        self.buf.clear();
+        self.buf.render_reserved(Some(frames));
+        let channels = self.head.channels;
+        for channel in 0..channels {
+            let out = self.buf.chan_mut(channel);
+            for (sample, frame) in out.iter_mut().zip(self.pcm.chunks(channels)) {
+                *sample = frame[channel] * self.head.gain;
+            }
+        }
+
+        let skipped = self.skip.min(frames);
+        self.skip -= skipped;
+        self.buf.trim(
+            skipped + packet.trim_start() as usize,
+            packet.trim_end() as usize,
+        );
+        Ok(self.buf.as_audio_buffer_ref())
+    }
+
+    fn finalize(&mut self) -> FinalizeResult {
+        Default::default()
+    }
+
+    fn last_decoded(&self) -> AudioBufferRef<'_> {
+        self.buf.as_audio_buffer_ref()
+    }
+}
@@ -0,0 +1,168 @@
+//! Saved mixes for `AudioCombiner::export_project` and `import_project`:
+//! every track's settings and the output options as JSON, with each file
+//! named by an id the caller picks rather than stored.
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::{AudioCombiner, CombineError, CombineOptions, MixTrack, SingleAudioFile};
+
+/// Schema version `export_project` writes. Fields that are only added need
+/// no new version, since a project that lacks them loads with their
+/// defaults; a field that changes meaning does, with a step in `upgrade`.
+const PROJECT_VERSION: u32 = 1;
+
+/// The JSON of a project. Track `i` places file `i`.
+#[derive(Debug, Serialize, Deserialize)]
+struct Project {
+    version: u32,
+    files: Vec<ProjectFile>,
+    #[serde(default)]
+    tracks: Vec<MixTrack>,
+    #[serde(default)]
+    options: CombineOptions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ProjectFile {
+    /// The caller's name for the file, such as a content hash.
+    id: String,
+}
+
+fn invalid(detail: impl Into<String>) -> CombineError {
+    CombineError::InvalidProject {
+        detail: detail.into(),
+    }
+}
+
+/// The JSON of `tracks` and `options` for files named by `file_ids`, with
+/// one track per file. Both are validated first, so what is saved loads.
+pub fn export(
+    files: usize,
+    file_ids: Vec<String>,
+    mut tracks: Vec<MixTrack>,
+    options: &CombineOptions,
+) -> Result<String, CombineError> {
+    if file_ids.len() != files {
+        return Err(invalid(format!(
+            "expected {} file ids, got {}",
+            files,
+            file_ids.len()
+        )));
+    }
+    options.validate()?;
+    for (index, track) in tracks.iter().enumerate() {
+        track.validate(index, files)?;
+    }
+    tracks.resize_with(files, MixTrack::default);
+    let project = Project {
+        version: PROJECT_VERSION,
+        files: file_ids.into_iter().map(|id| ProjectFile { id }).collect(),
+        tracks,
+        options: options.clone(),
+    };
+    serde_json::to_string(&project).map_err(|e| invalid(e.to_string()))
+}
+
+/// Brings a project written as `version` up to `PROJECT_VERSION`. There has
+/// only been the one so far.
+fn upgrade(project: serde_json::Value, version: u64) -> Result<serde_json::Value, CombineError> {
+    match version {
+        1 => Ok(project),
+        v if v > PROJECT_VERSION as u64 => Err(invalid(format!(
+            "project version {} is newer than this build reads ({})",
+            v, PROJECT_VERSION
+        ))),
+        v => Err(invalid(format!("unknown project version {}", v))),
+    }
+}
+
+/// Reads `json` back into its tracks and options after checking that
+/// `file_ids` names the same files in the same order.
+fn parse(json: &str, file_ids: &[String]) -> Result<(Vec<MixTrack>, CombineOptions), CombineError> {
+    let value: serde_json::Value =
+        serde_json::from_str(json).map_err(|e| invalid(e.to_string()))?;
+    let version = value
+        .get("version")
+        .and_then(serde_json::Value::as_u64)
+        .ok_or_else(|| invalid("version: missing or not a whole number"))?;
+    let project: Project = serde_path_to_error::deserialize(upgrade(value, version)?)
+        .map_err(|e| invalid(format!("{}: {}", e.path(), e.inner())))?;
+
+    if project.files.len() != file_ids.len() {
+        return Err(invalid(format!(
+            "project has {} files, got {}",
+            project.files.len(),
+            file_ids.len()
+        )));
+    }
+    for (index, (saved, found)) in project.files.iter().zip(file_ids).enumerate() {
+        if saved.id != *found {
+            return Err(CombineError::ProjectFileMismatch {
+                index,
+                expected: saved.id.clone(),
+                found: found.clone(),
+            });
+        }
+    }
+    let mut tracks = project.tracks;
+    tracks.resize_with(file_ids.len(), MixTrack::default);
+    Ok((tracks, project.options))
+}
+
+/// Checks `json` against `file_ids`, then decodes `files` as
+/// `AudioCombiner::new` does.
+pub fn import(
+    json: &str,
+    files: Vec<SingleAudioFile>,
+    file_ids: Vec<String>,
+) -> Result<MixProject, CombineError> {
+    if files.len() != file_ids.len() {
+        return Err(invalid(format!(
+            "got {} files but {} file ids",
+            files.len(),
+            file_ids.len()
+        )));
+    }
+    let (tracks, options) = parse(json, &file_ids)?;
+    Ok(MixProject {
+        combiner: AudioCombiner::new(files)?,
+        tracks,
+        options,
+    })
+}
+
+/// Output of `AudioCombiner::import_project`: the decoded files with the
+/// tracks and options they were saved with.
+#[wasm_bindgen]
+pub struct MixProject {
+    combiner: AudioCombiner,
+    tracks: Vec<MixTrack>,
+    options: CombineOptions,
+}
+
+#[wasm_bindgen]
+impl MixProject {
+    /// One track per file, in file order.
+    #[wasm_bindgen(getter)]
+    pub fn tracks(&self) -> Vec<MixTrack> {
+        self.tracks.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn options(&self) -> CombineOptions {
+        self.options.clone()
+    }
+
+    /// Mixes the files as saved, as `AudioCombiner::combine_tracks` does.
+    pub fn combine(&self) -> Result<SingleAudioFile, CombineError> {
+        self.combiner
+            .combine_tracks(self.tracks.clone(), &self.options)
+    }
+
+    /// Hands over the combiner, to mix the files some other way. The project
+    /// cannot be used afterwards.
+    pub fn into_combiner(self) -> AudioCombiner {
+        self.combiner
+    }
+}
@@ -0,0 +1,53 @@
+//! The per-file analysis behind `AudioCombiner::analyze_replaygain` and
+//! `CombineOptions::auto_level`.
+
+use wasm_bindgen::prelude::*;
+
+use crate::loudness;
+
+/// Loudness every file is brought to, in LUFS, as in ReplayGain 2.0.
+pub const REFERENCE_LUFS: f64 = -18.0;
+
+/// How far a file is from the ReplayGain reference loudness, from
+/// `AudioCombiner::analyze_replaygain`.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReplayGain {
+    pub(crate) loudness_lufs: f64,
+    pub(crate) track_peak: f32,
+}
+
+#[wasm_bindgen]
+impl ReplayGain {
+    /// Gain in dB that brings the file to -18 LUFS, or 0 for a file with
+    /// no loudness to match, such as silence.
+    #[wasm_bindgen(getter)]
+    pub fn track_gain_db(&self) -> f32 {
+        if self.loudness_lufs.is_finite() {
+            (REFERENCE_LUFS - self.loudness_lufs) as f32
+        } else {
+            0.0
+        }
+    }
+
+    /// True peak of the file as a linear amplitude, as ReplayGain tags
+    /// store it, for checking whether `track_gain_db` would clip.
+    #[wasm_bindgen(getter)]
+    pub fn track_peak(&self) -> f32 {
+        self.track_peak
+    }
+
+    /// Integrated loudness per ITU-R BS.1770, as `measure_loudness` gives.
+    #[wasm_bindgen(getter)]
+    pub fn loudness_lufs(&self) -> f64 {
+        self.loudness_lufs
+    }
+}
+
+/// Measures interleaved stereo `samples` at `sample_rate`.
+pub fn analyze(samples: &[f32], sample_rate: u32, mono: bool) -> ReplayGain {
+    ReplayGain {
+        loudness_lufs: loudness::integrated_loudness(samples, sample_rate, mono),
+        track_peak: loudness::true_peak(samples),
+    }
+}
@@ -0,0 +1,75 @@
+const CHANNELS: usize = 2;
+/// Zero crossings of the sinc kernel on each side of an output sample.
+const HALF_TAPS: usize = 16;
+/// Kernel table entries per zero crossing; lookups interpolate between them.
+const TABLE_STEPS: usize = 512;
+/// Fraction of the lower Nyquist frequency kept, leaving room for the
+/// window's transition band so it does not alias.
+const ROLLOFF: f64 = 0.95;
+
+/// Stretches interleaved stereo samples to `ratio` times as many frames with
+/// a Blackman-windowed sinc filter, e.g. `to / from` to convert sample rates.
+///
+/// The output holds `frames * ratio` frames rounded to the nearest one, so
+/// durations are preserved to within half a sample.
+pub fn resample(samples: &[f32], ratio: f64) -> Vec<f32> {
+    let frames = samples.len() / CHANNELS;
+    let out_frames = (frames as f64 * ratio).round() as usize;
+
+    // When downsampling the kernel is stretched so it also band-limits the
+    // input to the new Nyquist frequency.
+    let cutoff = ratio.min(1.0) * ROLLOFF;
+    let kernel = kernel_table(cutoff);
+    let reach = HALF_TAPS as f64 / cutoff;
+
+    let mut out = Vec::with_capacity(out_frames * CHANNELS);
+    for n in 0..out_frames {
+        let center = n as f64 / ratio;
+        let first = (center - reach).ceil().max(0.0) as usize;
+        let last = ((center + reach).floor() as usize).min(frames - 1);
+
+        let (mut left, mut right) = (0.0f32, 0.0f32);
+        for k in first..=last {
+            let weight = lookup(&kernel, (center - k as f64).abs() * cutoff);
+            left += samples[k * CHANNELS] * weight;
+            right += samples[k * CHANNELS + 1] * weight;
+        }
+        out.push(left);
+        out.push(right);
+    }
+    out
+}
+
+/// Samples the windowed sinc, scaled by `cutoff` for unity gain, from zero
+/// to `HALF_TAPS` zero crossings.
+fn kernel_table(cutoff: f64) -> Vec<f32> {
+    use std::f64::consts::PI;
+
+    (0..=HALF_TAPS * TABLE_STEPS + 1)
+        .map(|i| {
+            let x = i as f64 / TABLE_STEPS as f64;
+            if x >= HALF_TAPS as f64 {
+                return 0.0;
+            }
+            let sinc = if i == 0 {
+                1.0
+            } else {
+                (PI * x).sin() / (PI * x)
+            };
+            let u = x / HALF_TAPS as f64;
+            let window = 0.42 + 0.5 * (PI * u).cos() + 0.08 * (2.0 * PI * u).cos();
+            (cutoff * sinc * window) as f32
+        })
+        .collect()
+}
+
+/// Linearly interpolates the kernel at `x` zero crossings from its center.
+fn lookup(kernel: &[f32], x: f64) -> f32 {
+    let position = x * TABLE_STEPS as f64;
+    let index = position as usize;
+    if index + 1 >= kernel.len() {
+        return 0.0;
+    }
+    let fraction = (position - index as f64) as f32;
+    kernel[index] + (kernel[index + 1] - kernel[index]) * fraction
+}
@@ -0,0 +1,79 @@
+use std::ops::Range;
+
+use wasm_bindgen::prelude::*;
+
+use crate::db_to_linear;
+
+/// Length of the RMS window silence is judged over. It spans a few cycles of
+/// anything audible, so a zero crossing is never mistaken for silence.
+const WINDOW_MS: f64 = 10.0;
+
+/// A stretch of a file quieter than the threshold given to
+/// `SingleAudioFile::detect_silence`.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SilentRange {
+    pub start_ms: f64,
+    pub end_ms: f64,
+}
+
+fn window_frames(sample_rate: u32) -> usize {
+    ((WINDOW_MS * sample_rate as f64 / 1000.0).round() as usize).max(1)
+}
+
+/// Whether each window of interleaved stereo has an RMS level below
+/// `threshold_dbfs`. The last window may be short.
+fn quiet_windows(samples: &[f32], window: usize, threshold_dbfs: f32) -> Vec<bool> {
+    let threshold = db_to_linear(threshold_dbfs) as f64;
+    samples
+        .chunks(window * 2)
+        .map(|chunk| {
+            let sum: f64 = chunk.iter().map(|&s| s as f64 * s as f64).sum();
+            (sum / chunk.len() as f64).sqrt() < threshold
+        })
+        .collect()
+}
+
+/// Frame ranges of interleaved stereo at `sample_rate` quieter than
+/// `threshold_dbfs` for at least `min_frames`, to the nearest window.
+pub fn silent_ranges(
+    samples: &[f32],
+    sample_rate: u32,
+    threshold_dbfs: f32,
+    min_frames: usize,
+) -> Vec<Range<usize>> {
+    let frames = samples.len() / 2;
+    let window = window_frames(sample_rate);
+    let quiet = quiet_windows(samples, window, threshold_dbfs);
+    let mut ranges = Vec::new();
+    let mut start = None;
+    for (i, &quiet) in quiet.iter().chain(&[false]).enumerate() {
+        match (quiet, start) {
+            (true, None) => start = Some(i * window),
+            (false, Some(from)) => {
+                let to = (i * window).min(frames);
+                if to - from >= min_frames.max(1) {
+                    ranges.push(from..to);
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    ranges
+}
+
+/// Frames of interleaved stereo at `sample_rate` between the first and last
+/// window at or above `threshold_dbfs`, empty when it is all quieter.
+pub fn audible(samples: &[f32], sample_rate: u32, threshold_dbfs: f32) -> Range<usize> {
+    let frames = samples.len() / 2;
+    let window = window_frames(sample_rate);
+    let quiet = quiet_windows(samples, window, threshold_dbfs);
+    match (
+        quiet.iter().position(|&q| !q),
+        quiet.iter().rposition(|&q| !q),
+    ) {
+        (Some(first), Some(last)) => first * window..((last + 1) * window).min(frames),
+        _ => 0..0,
+    }
+}
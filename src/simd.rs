@@ -0,0 +1,69 @@
+//! Hot loops with a wasm `simd128` version behind the `simd` feature. Both
+//! versions do the same float operations in the same order, so they give
+//! bit-identical results.
+
+#[cfg(all(feature = "simd", target_arch = "wasm32", target_feature = "simd128"))]
+use core::arch::wasm32::*;
+
+/// Adds `samples * gains * fade` into `out` frame by frame, with one fade
+/// factor per interleaved stereo frame in `fades`.
+pub fn accumulate(out: &mut [f32], samples: &[f32], gains: [f32; 2], fades: &[f32]) {
+    let frames = fades.len().min(out.len() / 2).min(samples.len() / 2);
+    #[cfg(all(feature = "simd", target_arch = "wasm32", target_feature = "simd128"))]
+    let done = {
+        let [left, right] = gains;
+        let gains = f32x4(left, right, left, right);
+        let pairs = frames / 2;
+        for pair in 0..pairs {
+            let (i, n) = (pair * 4, pair * 2);
+            let sample = f32x4(samples[i], samples[i + 1], samples[i + 2], samples[i + 3]);
+            let fade = f32x4(fades[n], fades[n], fades[n + 1], fades[n + 1]);
+            let mixed = f32x4(out[i], out[i + 1], out[i + 2], out[i + 3]);
+            let sum = f32x4_add(mixed, f32x4_mul(f32x4_mul(sample, gains), fade));
+            out[i] = f32x4_extract_lane::<0>(sum);
+            out[i + 1] = f32x4_extract_lane::<1>(sum);
+            out[i + 2] = f32x4_extract_lane::<2>(sum);
+            out[i + 3] = f32x4_extract_lane::<3>(sum);
+        }
+        pairs * 2
+    };
+    #[cfg(not(all(feature = "simd", target_arch = "wasm32", target_feature = "simd128")))]
+    let done = 0;
+
+    let [left, right] = gains;
+    for n in done..frames {
+        out[n * 2] += samples[n * 2] * left * fades[n];
+        out[n * 2 + 1] += samples[n * 2 + 1] * right * fades[n];
+    }
+}
+
+/// Appends samples to `out` as little-endian 16-bit PCM, clamped to full
+/// scale.
+pub fn quantize_i16(samples: &[f32], out: &mut Vec<u8>) {
+    #[cfg(all(feature = "simd", target_arch = "wasm32", target_feature = "simd128"))]
+    let samples = {
+        let (low, high, scale) = (
+            f32x4_splat(-1.0),
+            f32x4_splat(1.0),
+            f32x4_splat(i16::MAX as f32),
+        );
+        let mut quads = samples.chunks_exact(4);
+        for quad in &mut quads {
+            let sample = f32x4(quad[0], quad[1], quad[2], quad[3]);
+            // NaN stays NaN through min and max and truncates to 0, as `as` does.
+            let clamped = f32x4_min(f32x4_max(sample, low), high);
+            let s = i32x4_trunc_sat_f32x4(f32x4_mul(clamped, scale));
+            out.extend_from_slice(&(i32x4_extract_lane::<0>(s) as i16).to_le_bytes());
+            out.extend_from_slice(&(i32x4_extract_lane::<1>(s) as i16).to_le_bytes());
+            out.extend_from_slice(&(i32x4_extract_lane::<2>(s) as i16).to_le_bytes());
+            out.extend_from_slice(&(i32x4_extract_lane::<3>(s) as i16).to_le_bytes());
+        }
+        quads.remainder()
+    };
+
+    for &sample in samples {
+        let clamped = sample.clamp(-1.0, 1.0);
+        let s = (clamped * i16::MAX as f32) as i16;
+        out.extend_from_slice(&s.to_le_bytes());
+    }
+}
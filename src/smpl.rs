@@ -0,0 +1,123 @@
+//! The sampler `smpl` chunk of `CombineOptions::sample_loop`, which game
+//! engines and samplers read for loop points.
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::CombineError;
+
+/// Bytes of a `smpl` chunk with one loop, header included.
+pub(crate) const SMPL_CHUNK_SIZE: usize = 8 + 36 + 24;
+
+/// MIDI note the output plays back at its own pitch: middle C.
+const UNITY_NOTE: u32 = 60;
+
+/// One forward loop for WAV output to repeat forever.
+///
+/// Both ends are in frames of the output, one sample per channel at the
+/// output sample rate, counted from the start of the sample data. They are
+/// not bytes. The loop includes the frame at `end_frame`, as `smpl` chunks
+/// count it.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SampleLoop {
+    /// First frame of the loop.
+    pub start_frame: u32,
+    /// Last frame of the loop, or `None` for the last frame of the output.
+    pub end_frame: Option<u32>,
+}
+
+#[wasm_bindgen]
+impl SampleLoop {
+    /// A loop over the whole output.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A loop from `start_frame` to `end_frame`, both included.
+    pub fn between(start_frame: u32, end_frame: u32) -> Self {
+        Self {
+            start_frame,
+            end_frame: Some(end_frame),
+        }
+    }
+}
+
+/// A loop checked against the output it goes in.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct LoopPoints {
+    start: u32,
+    end: u32,
+}
+
+impl SampleLoop {
+    pub(crate) fn validate(&self) -> Result<(), CombineError> {
+        match self.end_frame {
+            Some(end) if end < self.start_frame => Err(CombineError::InvalidOption {
+                detail: format!(
+                    "sample_loop ends at frame {} before it starts at {}",
+                    end, self.start_frame
+                ),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// The loop in output `frames` long, which must hold both ends.
+    pub(crate) fn points(&self, frames: usize) -> Result<LoopPoints, CombineError> {
+        let last = frames.checked_sub(1);
+        let end = self.end_frame.map(|end| end as usize).or(last);
+        match (end, last) {
+            (Some(end), Some(last)) if self.start_frame as usize <= end && end <= last => {
+                Ok(LoopPoints {
+                    start: self.start_frame,
+                    end: end as u32,
+                })
+            }
+            _ => Err(CombineError::InvalidOption {
+                detail: format!(
+                    "sample_loop frames {} to {} run past the {} frames of the output",
+                    self.start_frame,
+                    end.map_or("the end".into(), |end| end.to_string()),
+                    frames
+                ),
+            }),
+        }
+    }
+}
+
+impl LoopPoints {
+    /// The whole `smpl` chunk for output at `sample_rate`, header included.
+    pub(crate) fn chunk(&self, sample_rate: u32) -> Vec<u8> {
+        // Nanoseconds per frame.
+        let sample_period = (1e9 / sample_rate as f64).round() as u32;
+        let fields = [
+            0, // manufacturer
+            0, // product
+            sample_period,
+            UNITY_NOTE,
+            0, // pitch fraction
+            0, // SMPTE format
+            0, // SMPTE offset
+            1, // loops
+            0, // sampler data
+            // The loop: cue point id, forward, its ends, no fraction, and
+            // a play count of 0 for forever.
+            0,
+            0,
+            self.start,
+            self.end,
+            0,
+            0,
+        ];
+        let mut chunk = Vec::with_capacity(SMPL_CHUNK_SIZE);
+        chunk.extend_from_slice(b"smpl");
+        chunk.extend_from_slice(&(SMPL_CHUNK_SIZE as u32 - 8).to_le_bytes());
+        for field in fields {
+            chunk.extend_from_slice(&field.to_le_bytes());
+        }
+        chunk
+    }
+}
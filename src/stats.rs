@@ -0,0 +1,172 @@
+use wasm_bindgen::prelude::*;
+
+use crate::{DecodeWarning, SingleAudioFile, SkippedTrack};
+
+/// What `AudioCombiner::combine_with_stats` did to the mix.
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct CombineStats {
+    pub(crate) normalize_gain: f32,
+    pub(crate) max_gain_reduction_db: f32,
+    pub(crate) track_gains: Vec<f32>,
+    pub(crate) decode_ms: Vec<f64>,
+    pub(crate) mix_ms: f64,
+    pub(crate) encode_ms: f64,
+    pub(crate) peak_dbfs: f32,
+    pub(crate) true_peak_dbtp: Option<f32>,
+    pub(crate) clipped_samples: usize,
+    pub(crate) duration_ms: f64,
+    pub(crate) cached_files: usize,
+    /// (file index, first frame) of each audible file in the mix.
+    pub(crate) file_starts: Vec<(usize, usize)>,
+    /// Whether both sides of the mix are the same, as `track::is_mono_mix`
+    /// works out, so WAV output needs only one channel.
+    pub(crate) mono: bool,
+}
+
+impl Default for CombineStats {
+    fn default() -> Self {
+        Self {
+            normalize_gain: 1.0,
+            max_gain_reduction_db: 0.0,
+            track_gains: Vec::new(),
+            decode_ms: Vec::new(),
+            mix_ms: 0.0,
+            encode_ms: 0.0,
+            peak_dbfs: f32::NEG_INFINITY,
+            true_peak_dbtp: None,
+            clipped_samples: 0,
+            duration_ms: 0.0,
+            cached_files: 0,
+            file_starts: Vec::new(),
+            mono: false,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl CombineStats {
+    /// Linear gain applied by `CombineOptions::normalize`, 1.0 when it is off.
+    #[wasm_bindgen(getter)]
+    pub fn normalize_gain(&self) -> f32 {
+        self.normalize_gain
+    }
+
+    /// Most the `CombineOptions::compressor` turned the mix down at any
+    /// point, in dB before makeup gain. 0 when there is no compressor.
+    #[wasm_bindgen(getter)]
+    pub fn max_gain_reduction_db(&self) -> f32 {
+        self.max_gain_reduction_db
+    }
+
+    /// Overall linear gain each file was mixed at: its track gain, any
+    /// loudness correction, normalization and the master gain, but not
+    /// the compressor, whose gain changes over time. Files left out
+    /// by mute or solo report 0, and files with `gain_left` and
+    /// `gain_right` report the louder channel.
+    #[wasm_bindgen(getter)]
+    pub fn track_gains(&self) -> Vec<f32> {
+        self.track_gains.clone()
+    }
+
+    /// How long each file took to decode when it was added, in ms.
+    #[wasm_bindgen(getter)]
+    pub fn decode_ms(&self) -> Vec<f64> {
+        self.decode_ms.clone()
+    }
+
+    /// Time from the first track being placed to the finished master,
+    /// including loudness matching, compression, normalization and the
+    /// limiter, in ms.
+    #[wasm_bindgen(getter)]
+    pub fn mix_ms(&self) -> f64 {
+        self.mix_ms
+    }
+
+    /// Time spent writing the output container, in ms.
+    #[wasm_bindgen(getter)]
+    pub fn encode_ms(&self) -> f64 {
+        self.encode_ms
+    }
+
+    /// Absolute peak of the master before encoding, in dBFS. Silence
+    /// reports `-Infinity`.
+    #[wasm_bindgen(getter)]
+    pub fn peak_dbfs(&self) -> f32 {
+        self.peak_dbfs
+    }
+
+    /// True peak of the master before encoding, in dBTP: the highest level
+    /// between its samples after 4x oversampling as in BS.1770. Silence
+    /// reports `-Infinity`. Only measured with `CombineOptions::true_peak`,
+    /// as it takes a pass over the whole mix, and `undefined` otherwise.
+    #[wasm_bindgen(getter)]
+    pub fn true_peak_dbtp(&self) -> Option<f32> {
+        self.true_peak_dbtp
+    }
+
+    /// Samples beyond full scale, which integer WAV and compressed output
+    /// clip. Float WAV keeps them as they are.
+    #[wasm_bindgen(getter)]
+    pub fn clipped_samples(&self) -> usize {
+        self.clipped_samples
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn duration_ms(&self) -> f64 {
+        self.duration_ms
+    }
+
+    /// Files taken ready prepared from the cache turned on by
+    /// `AudioCombiner::enable_decode_cache`, rather than trimmed, filtered
+    /// and resampled again.
+    #[wasm_bindgen(getter)]
+    pub fn cached_files(&self) -> usize {
+        self.cached_files
+    }
+}
+
+/// Output of `AudioCombiner::combine_with_stats`.
+#[wasm_bindgen]
+pub struct CombineResult {
+    pub(crate) file: SingleAudioFile,
+    pub(crate) stats: CombineStats,
+    pub(crate) waveform: Option<Vec<f32>>,
+    pub(crate) warnings: Vec<DecodeWarning>,
+    pub(crate) skipped_tracks: Vec<SkippedTrack>,
+}
+
+#[wasm_bindgen]
+impl CombineResult {
+    /// The encoded mix. Each read is a new file on the same bytes.
+    #[wasm_bindgen(getter)]
+    pub fn file(&self) -> SingleAudioFile {
+        self.file.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn stats(&self) -> CombineStats {
+        self.stats.clone()
+    }
+
+    /// Peaks of the mix as `SingleAudioFile::waveform` lays them out, when
+    /// `CombineOptions::waveform_buckets` asked for them.
+    #[wasm_bindgen(getter)]
+    pub fn waveform(&self) -> Option<Vec<f32>> {
+        self.waveform.clone()
+    }
+
+    /// Corrupt stretches of inputs that were replaced with silence, in file
+    /// order. Empty unless a file sets `tolerate_decode_errors`.
+    #[wasm_bindgen(getter)]
+    pub fn warnings(&self) -> Vec<DecodeWarning> {
+        self.warnings.clone()
+    }
+
+    /// Files left out because they failed to probe or decode, in file order.
+    /// Empty unless `CombineOptions::skip_failed_tracks` is set.
+    #[wasm_bindgen(getter)]
+    pub fn skipped_tracks(&self) -> Vec<SkippedTrack> {
+        self.skipped_tracks.clone()
+    }
+}
@@ -0,0 +1,183 @@
+use crate::decode::{self, FileDecoder};
+use crate::options::{BitDepth, CombineOptions};
+use crate::{
+    track, wav, AudioCombinerSingleFile, CombineError, MixTrack, SingleAudioFile,
+    SingleAudioFileType, MASTER_SAMPLE_RATE,
+};
+
+/// One input of a streamed combine, read a window at a time.
+enum Source {
+    /// A compressed file, decoded only as far as the current window needs.
+    Decoder { decoder: FileDecoder, ended: bool },
+    /// Raw PCM, which is in memory in full anyway.
+    Pcm {
+        file: AudioCombinerSingleFile,
+        position: usize,
+    },
+}
+
+impl Source {
+    fn open(index: usize, file: SingleAudioFile) -> Result<Self, CombineError> {
+        if file.r#type == SingleAudioFileType::Pcm {
+            return Ok(Source::Pcm {
+                file: decode::decode_pcm(index, file)?,
+                position: 0,
+            });
+        }
+        let mut decoder = FileDecoder::open(index, file)?;
+        // The first packet settles the rate when the headers leave it out.
+        let ended = !decoder.decode_packet(None)?;
+        Ok(Source::Decoder { decoder, ended })
+    }
+
+    fn mono(&self) -> bool {
+        match self {
+            Source::Decoder { decoder, .. } => decoder.mono(),
+            Source::Pcm { file, .. } => file.mono,
+        }
+    }
+
+    fn sample_rate(&self) -> Option<u32> {
+        match self {
+            Source::Decoder { decoder, .. } => decoder.sample_rate(),
+            Source::Pcm { file, .. } => Some(file.sample_rate),
+        }
+    }
+
+    /// Adds the next `out.len() / 2` frames into `out` at the track's gains,
+    /// returning how many frames there were before the source ran out.
+    fn mix_into(&mut self, out: &mut [f32], track: &MixTrack) -> Result<usize, CombineError> {
+        let frames = out.len() / 2;
+        match self {
+            Source::Decoder { decoder, ended } => {
+                while !*ended && decoder.decoded_frames() < frames {
+                    *ended = !decoder.decode_packet(None)?;
+                }
+                let gains = track.channel_gains(decoder.mono());
+                Ok(add_frames(
+                    out,
+                    decoder.take_frames(frames).as_slice(),
+                    gains,
+                ))
+            }
+            Source::Pcm { file, position } => {
+                let end = (*position + frames * 2).min(file.samples.len());
+                let samples = &file.samples[*position..end];
+                *position = end;
+                Ok(add_frames(out, samples, track.channel_gains(file.mono)))
+            }
+        }
+    }
+}
+
+/// Adds interleaved stereo `samples` into `out`, returning the frame count.
+fn add_frames(out: &mut [f32], samples: &[f32], [left, right]: [f32; 2]) -> usize {
+    for (m_frame, f_frame) in out.chunks_exact_mut(2).zip(samples.chunks_exact(2)) {
+        m_frame[0] += f_frame[0] * left;
+        m_frame[1] += f_frame[1] * right;
+    }
+    samples.len() / 2
+}
+
+/// Mixes `files` from the start, `chunk_frames` at a time, passing each piece
+/// of a 16-bit WAV to `write` with its byte offset in the output.
+///
+/// The header is written first with a zero length and again at offset 0 once
+/// the length is known. Only one window per file is decoded at any time.
+pub fn combine_streamed(
+    files: Vec<SingleAudioFile>,
+    tracks: &[MixTrack],
+    chunk_frames: usize,
+    mut write: impl FnMut(&[u8], u64) -> Result<(), CombineError>,
+) -> Result<(), CombineError> {
+    if chunk_frames == 0 {
+        return Err(CombineError::InvalidOption {
+            detail: "chunk_frames must be at least 1".into(),
+        });
+    }
+    if files.is_empty() {
+        return Err(CombineError::EmptyInput);
+    }
+    let mut sources = files
+        .into_iter()
+        .enumerate()
+        .map(|(index, file)| Source::open(index, file))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Streaming has no room to resample, so every file must agree with the
+    // first whose rate is known.
+    let rates: Vec<Option<u32>> = sources.iter().map(Source::sample_rate).collect();
+    let sample_rate = rates.iter().flatten().next().copied();
+    for (index, &rate) in rates.iter().enumerate() {
+        if let (Some(found), Some(expected)) = (rate, sample_rate) {
+            if found != expected {
+                return Err(CombineError::SampleRateMismatch {
+                    index,
+                    found,
+                    expected,
+                });
+            }
+        }
+    }
+    let sample_rate = sample_rate.unwrap_or(MASTER_SAMPLE_RATE);
+    let mut tracks = tracks.to_vec();
+    tracks.resize_with(sources.len(), MixTrack::default);
+    // One channel when both sides are the same, as `combine` writes it.
+    let mono = track::is_mono_mix(&tracks, |i| sources[i].mono());
+    let channels = if mono { 1 } else { 2 };
+
+    let header = wav::wav_header(
+        0,
+        sample_rate,
+        channels,
+        BitDepth::Int16,
+        wav::WavTags::default(),
+        false,
+    )?;
+    let header_len = header.len() as u64;
+    write(&header, 0)?;
+
+    // Dithered as `combine` does by default, so the two give the same bytes.
+    let mut dither = CombineOptions::default().dither();
+    let mut mix = vec![0.0f32; chunk_frames * 2];
+    let mut bytes = Vec::with_capacity(chunk_frames * 4);
+    let mut offset = header_len;
+    loop {
+        mix.fill(0.0);
+        let mut frames = 0;
+        for (source, track) in sources.iter_mut().zip(&tracks) {
+            frames = frames.max(source.mix_into(&mut mix, track)?);
+        }
+        if frames == 0 {
+            break;
+        }
+        bytes.clear();
+        if mono {
+            for n in 0..frames {
+                mix[n] = mix[n * 2];
+            }
+        }
+        wav::encode_samples(
+            &mix[..frames * channels as usize],
+            BitDepth::Int16,
+            dither.as_mut(),
+            &mut bytes,
+        );
+        write(&bytes, offset)?;
+        offset += bytes.len() as u64;
+    }
+
+    // RIFF sizes are 32-bit, so anything longer is left at the maximum.
+    let data_size = (offset - header_len).min((u32::MAX as u64) - header_len);
+    write(
+        &wav::wav_header(
+            data_size,
+            sample_rate,
+            channels,
+            BitDepth::Int16,
+            wav::WavTags::default(),
+            false,
+        )?,
+        0,
+    )
+}
@@ -0,0 +1,89 @@
+//! Adding each file's share into the master, spread over the thread pool
+//! behind the `threads` feature.
+
+/// Frames of the master one file covers.
+pub struct Span {
+    pub start: usize,
+    pub frames: usize,
+}
+
+/// Lets `weigh(i, out)` add file `i`, with its gains and fades, straight
+/// into the master at `spans[i]`, one file after another.
+pub fn serial(master: &mut [f32], spans: &[Span], weigh: impl Fn(usize, &mut [f32])) {
+    for (i, span) in spans.iter().enumerate() {
+        weigh(
+            i,
+            &mut master[span.start * 2..(span.start + span.frames) * 2],
+        );
+    }
+}
+
+/// Gives `serial`'s result with the weighing of every file run on the pool,
+/// each into a buffer of its own, then sums the buffers in file order.
+/// Adding to a zeroed buffer is exact, so the sums are the same float
+/// operations in the same order whatever the number of threads.
+#[cfg(feature = "threads")]
+pub fn parallel(master: &mut [f32], spans: &[Span], weigh: impl Fn(usize, &mut [f32]) + Sync) {
+    use rayon::prelude::*;
+
+    let weighed: Vec<Vec<f32>> = spans
+        .par_iter()
+        .enumerate()
+        .map(|(i, span)| {
+            let mut out = vec![0.0; span.frames * 2];
+            weigh(i, &mut out);
+            out
+        })
+        .collect();
+    for (span, samples) in spans.iter().zip(weighed) {
+        for (sample, weighed) in master[span.start * 2..].iter_mut().zip(samples) {
+            *sample += weighed;
+        }
+    }
+}
+
+#[cfg(all(test, feature = "threads"))]
+mod tests {
+    use super::*;
+    use crate::simd;
+
+    #[test]
+    fn parallel_sums_match_serial_ones() {
+        let files: Vec<Vec<f32>> = (0..6)
+            .map(|f| {
+                (0..(3000 + f * 517) * 2)
+                    .map(|n| ((n * (f + 3)) as f32 * 0.013).sin() * 0.7)
+                    .collect()
+            })
+            .collect();
+        let spans: Vec<Span> = files
+            .iter()
+            .enumerate()
+            .map(|(f, file)| Span {
+                start: f * 401,
+                frames: file.len() / 2,
+            })
+            .collect();
+        let frames = spans.iter().map(|s| s.start + s.frames).max().unwrap();
+        let weigh = |i: usize, out: &mut [f32]| {
+            let fades: Vec<f32> = (0..spans[i].frames)
+                .map(|n| (n as f32 / 1000.0).min(1.0))
+                .collect();
+            let gains = [0.3 + i as f32 * 0.11, 0.9 - i as f32 * 0.07];
+            simd::accumulate(out, &files[i], gains, &fades);
+        };
+
+        let mut expected = vec![0.0; frames * 2];
+        serial(&mut expected, &spans, weigh);
+        for threads in [1, 2, 5] {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            let mut master = vec![0.0; frames * 2];
+            pool.install(|| parallel(&mut master, &spans, weigh));
+            let bits = |s: &[f32]| s.iter().map(|x| x.to_bits()).collect::<Vec<_>>();
+            assert_eq!(bits(&master), bits(&expected), "{} threads", threads);
+        }
+    }
+}
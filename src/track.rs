@@ -0,0 +1,535 @@
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::{CombineError, CombineMode, CombineOptions, Ducking, Equalizer, NoiseGate};
+
+/// Shape of a fade, from silence at the start of a fade-in to unity at its end.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum FadeCurve {
+    Linear,
+    /// Rises 60 dB evenly in decibels, so the fade sounds steady.
+    Exponential,
+    /// Quarter sine, so overlapping fades in and out keep constant power.
+    EqualPower,
+}
+
+impl FadeCurve {
+    /// Gain at `x` of the way through a fade-in.
+    fn gain(self, x: f32) -> f32 {
+        match self {
+            FadeCurve::Linear => x,
+            FadeCurve::Exponential => (1000f32.powf(x) - 1.0) / 999.0,
+            FadeCurve::EqualPower => (x * std::f32::consts::FRAC_PI_2).sin(),
+        }
+    }
+}
+
+/// Linear interpolation between `(frame, gain)` keyframes sorted by frame,
+/// holding the first and last gains outside them.
+fn automation_gain(keyframes: &[(f64, f32)], frame: f64) -> f32 {
+    let next = keyframes.partition_point(|&(at, _)| at <= frame);
+    match (
+        next.checked_sub(1).map(|i| keyframes[i]),
+        keyframes.get(next),
+    ) {
+        (None, None) => 1.0,
+        (Some((_, gain)), None) | (None, Some(&(_, gain))) => gain,
+        (Some((from, a)), Some(&(to, b))) => a + (b - a) * ((frame - from) / (to - from)) as f32,
+    }
+}
+
+/// A gain keyframe for `MixTrack::automation`.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct AutomationPoint {
+    /// Position in the trimmed and looped file, in milliseconds.
+    pub time_ms: f64,
+    /// Linear gain at that position.
+    pub gain: f32,
+}
+
+#[wasm_bindgen]
+impl AutomationPoint {
+    #[wasm_bindgen(constructor)]
+    pub fn new(time_ms: f64, gain: f32) -> Self {
+        Self { time_ms, gain }
+    }
+}
+
+/// Equal-power gain at frame `n` of a file that crossfades with the previous
+/// file over its first `head` frames and with the next over its last `tail`.
+///
+/// The fade-out at the end of a file is the exact complement of the fade-in
+/// it overlaps, so the two always sum to constant power.
+pub(crate) fn crossfade_gain(n: usize, frames: usize, head: usize, tail: usize) -> f32 {
+    use std::f32::consts::FRAC_PI_2;
+
+    let mut gain = 1.0;
+    if n < head {
+        gain *= (n as f32 / head as f32 * FRAC_PI_2).sin();
+    }
+    if n + tail >= frames {
+        gain *= ((n + tail - frames) as f32 / tail as f32 * FRAC_PI_2).cos();
+    }
+    gain
+}
+
+/// Range of `MixTrack::speed`.
+const MIN_SPEED: f32 = 0.5;
+const MAX_SPEED: f32 = 2.0;
+
+/// How one input file is placed in the mix by `AudioCombiner::combine_tracks`.
+#[wasm_bindgen]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MixTrack {
+    /// Linear gain, 1.0 being unity.
+    pub gain: f32,
+    /// Linear gain of the output's left channel in place of `gain`, as for
+    /// a stereo file with one side louder than the other. Applied after
+    /// `swap_channels` and to both sides of a mono file, and multiplied by
+    /// the pan like `gain` is.
+    pub gain_left: Option<f32>,
+    /// Like `gain_left`, for the right channel.
+    pub gain_right: Option<f32>,
+    /// Stereo position from -1.0 (hard left) to 1.0 (hard right).
+    ///
+    /// Mono sources are panned with a constant-power law normalised so the
+    /// centre is unity. Stereo sources are balanced instead: the opposite
+    /// channel is attenuated along the same curve and the near one kept.
+    /// Either way the pan multiplies each channel's gain.
+    pub pan: f32,
+    /// Delay before the file starts, in frames at the output sample rate.
+    pub offset_frames: u32,
+    /// Further delay in milliseconds, rounded to the nearest frame and added
+    /// to `offset_frames`.
+    pub offset_ms: f64,
+    /// Milliseconds cut from the start of the file before it is mixed.
+    pub trim_start_ms: f64,
+    /// Where in the file to stop, in milliseconds from its start. `None`
+    /// keeps everything up to the end.
+    pub trim_end_ms: Option<f64>,
+    /// Drop leading and trailing stretches quieter than this many dBFS RMS
+    /// from the trimmed file, before it is placed, looped or faded.
+    pub auto_trim_silence: Option<f32>,
+    /// Play the trimmed file backwards.
+    pub reversed: bool,
+    /// Take out any DC offset with a 5 Hz high-pass, after the channels are
+    /// rewired and before anything else.
+    pub remove_dc: bool,
+    /// Play the left channel on the right and the right on the left.
+    pub swap_channels: bool,
+    /// Flip the polarity of both channels.
+    pub invert_phase: bool,
+    /// Sum both channels to the centre, 3 dB down so the level holds. Files
+    /// that are already mono are left alone.
+    pub force_mono: bool,
+    /// Cut rumble below this many Hz from the trimmed file, at 24 dB per
+    /// octave. Applied at the file's own sample rate, before speed and
+    /// looping.
+    pub highpass_hz: Option<f32>,
+    /// Cut hiss above this many Hz, like `highpass_hz`.
+    pub lowpass_hz: Option<f32>,
+    /// Shelf and peak gains, applied after the filters at the file's own
+    /// sample rate.
+    pub eq: Option<Equalizer>,
+    /// Silence the file while it is quiet, as between the sentences of a
+    /// speaker. Applied after the EQ at the file's own sample rate.
+    pub gate: Option<NoiseGate>,
+    /// Playback speed from 0.5 to 2.0, applied after trimming. Pitch moves
+    /// with it.
+    pub speed: f32,
+    /// How many times the trimmed file plays back to back.
+    pub loop_count: u32,
+    /// Repeat the trimmed file until it lasts this long, cutting the last
+    /// repetition short. Takes precedence over `loop_count`.
+    pub loop_to_ms: Option<f64>,
+    /// Length of the ramp up from silence at the start of the trimmed file.
+    pub fade_in_ms: f64,
+    /// Length of the ramp down to silence at its end, after any looping.
+    /// Fades longer than the file are clamped to it.
+    pub fade_out_ms: f64,
+    pub fade_curve: FadeCurve,
+    /// Gain keyframes, interpolated linearly and multiplied onto `gain`.
+    /// Before the first and after the last the nearest keyframe holds; an
+    /// empty list leaves the gain alone.
+    #[wasm_bindgen(getter_with_clone)]
+    pub automation: Vec<AutomationPoint>,
+    /// Turn this file down while another one plays.
+    pub ducking: Option<Ducking>,
+    /// Leave the file out of the mix, so it neither sounds nor adds length.
+    pub mute: bool,
+    /// Mix only the soloed files when any file is soloed, muted or not.
+    pub solo: bool,
+}
+
+#[wasm_bindgen]
+impl MixTrack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Default for MixTrack {
+    fn default() -> Self {
+        Self {
+            gain: 1.0,
+            gain_left: None,
+            gain_right: None,
+            pan: 0.0,
+            offset_frames: 0,
+            offset_ms: 0.0,
+            trim_start_ms: 0.0,
+            trim_end_ms: None,
+            auto_trim_silence: None,
+            reversed: false,
+            remove_dc: false,
+            swap_channels: false,
+            invert_phase: false,
+            force_mono: false,
+            highpass_hz: None,
+            lowpass_hz: None,
+            eq: None,
+            gate: None,
+            speed: 1.0,
+            loop_count: 1,
+            loop_to_ms: None,
+            fade_in_ms: 0.0,
+            fade_out_ms: 0.0,
+            fade_curve: FadeCurve::Linear,
+            automation: Vec::new(),
+            ducking: None,
+            mute: false,
+            solo: false,
+        }
+    }
+}
+
+impl MixTrack {
+    pub(crate) fn with_gain(gain: f32) -> Self {
+        Self {
+            gain,
+            ..Self::default()
+        }
+    }
+
+    /// Checks the track for the file at `index` out of `files`.
+    pub(crate) fn validate(&self, index: usize, files: usize) -> Result<(), CombineError> {
+        if let Some(ducking) = &self.ducking {
+            ducking.validate(index, files)?;
+        }
+        if let Some(eq) = &self.eq {
+            eq.validate(index)?;
+        }
+        if let Some(gate) = &self.gate {
+            gate.validate(index)?;
+        }
+        for gain in [Some(self.gain), self.gain_left, self.gain_right] {
+            match gain {
+                Some(gain) if !gain.is_finite() || gain < 0.0 => {
+                    return Err(CombineError::InvalidGain { index, gain });
+                }
+                _ => {}
+            }
+        }
+        if !(-1.0..=1.0).contains(&self.pan) {
+            return Err(CombineError::InvalidTrack {
+                index,
+                detail: format!("pan must be between -1.0 and 1.0, got {}", self.pan),
+            });
+        }
+        if !(MIN_SPEED..=MAX_SPEED).contains(&self.speed) {
+            return Err(CombineError::InvalidTrack {
+                index,
+                detail: format!(
+                    "speed must be between {} and {}, got {}",
+                    MIN_SPEED, MAX_SPEED, self.speed
+                ),
+            });
+        }
+        for &(name, value) in &[
+            ("offset_ms", self.offset_ms),
+            ("trim_start_ms", self.trim_start_ms),
+            ("fade_in_ms", self.fade_in_ms),
+            ("fade_out_ms", self.fade_out_ms),
+        ] {
+            if !value.is_finite() || value < 0.0 {
+                return Err(CombineError::InvalidTrack {
+                    index,
+                    detail: format!("{} must not be negative, got {}", name, value),
+                });
+            }
+        }
+        if let Some(end) = self.trim_end_ms {
+            if end.is_nan() || end < self.trim_start_ms {
+                return Err(CombineError::InvalidTrack {
+                    index,
+                    detail: format!(
+                        "trim_end_ms must not be before trim_start_ms, got {} and {}",
+                        end, self.trim_start_ms
+                    ),
+                });
+            }
+        }
+        for (name, cutoff) in [
+            ("highpass_hz", self.highpass_hz),
+            ("lowpass_hz", self.lowpass_hz),
+        ] {
+            if let Some(hz) = cutoff.filter(|hz| !hz.is_finite() || *hz <= 0.0) {
+                return Err(CombineError::InvalidTrack {
+                    index,
+                    detail: format!("{} must be a positive number, got {}", name, hz),
+                });
+            }
+        }
+        if self.auto_trim_silence.is_some_and(f32::is_nan) {
+            return Err(CombineError::InvalidTrack {
+                index,
+                detail: "auto_trim_silence must be a number".into(),
+            });
+        }
+        for point in &self.automation {
+            if !point.time_ms.is_finite() || !point.gain.is_finite() || point.gain < 0.0 {
+                return Err(CombineError::InvalidTrack {
+                    index,
+                    detail: format!(
+                        "automation point must have a finite time and non-negative gain, got {} ms at {}",
+                        point.time_ms, point.gain
+                    ),
+                });
+            }
+        }
+        if self.loop_count == 0 {
+            return Err(CombineError::InvalidTrack {
+                index,
+                detail: "loop_count must be at least 1".into(),
+            });
+        }
+        match self.loop_to_ms {
+            Some(ms) if !ms.is_finite() || ms < 0.0 => Err(CombineError::InvalidTrack {
+                index,
+                detail: format!("loop_to_ms must not be negative, got {}", ms),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the file is mixed, given whether any file is soloed.
+    pub(crate) fn is_audible(&self, soloing: bool) -> bool {
+        if soloing {
+            self.solo
+        } else {
+            !self.mute
+        }
+    }
+
+    /// Total start offset in frames at `sample_rate`.
+    pub(crate) fn offset(&self, sample_rate: u32) -> usize {
+        let from_ms = (self.offset_ms * sample_rate as f64 / 1000.0).round() as usize;
+        self.offset_frames as usize + from_ms
+    }
+
+    /// Frames of a `frames`-long file at `sample_rate` kept by the trim,
+    /// clamped to the file.
+    pub(crate) fn trim(&self, frames: usize, sample_rate: u32) -> std::ops::Range<usize> {
+        let to_frame = |ms: f64| ((ms * sample_rate as f64 / 1000.0).round() as usize).min(frames);
+        let start = to_frame(self.trim_start_ms);
+        let end = self.trim_end_ms.map_or(frames, to_frame);
+        start..end.max(start)
+    }
+
+    /// Applies `swap_channels`, `invert_phase` and `force_mono` to a file,
+    /// given whether it was decoded from mono.
+    pub(crate) fn rewired<'a>(&self, samples: Cow<'a, [f32]>, mono: bool) -> Cow<'a, [f32]> {
+        let force_mono = self.force_mono && !mono;
+        if !self.swap_channels && !self.invert_phase && !force_mono {
+            return samples;
+        }
+        let sign = if self.invert_phase { -1.0 } else { 1.0 };
+        let mut samples = samples.into_owned();
+        for frame in samples.chunks_exact_mut(2) {
+            let [mut left, mut right] = [frame[0] * sign, frame[1] * sign];
+            if self.swap_channels {
+                std::mem::swap(&mut left, &mut right);
+            }
+            if force_mono {
+                left = (left + right) * std::f32::consts::FRAC_1_SQRT_2;
+                right = left;
+            }
+            frame[0] = left;
+            frame[1] = right;
+        }
+        Cow::Owned(samples)
+    }
+
+    /// Tiles a trimmed file at `sample_rate` out to its looped length.
+    pub(crate) fn looped<'a>(&self, samples: Cow<'a, [f32]>, sample_rate: u32) -> Cow<'a, [f32]> {
+        let frames = match self.loop_to_ms {
+            Some(ms) => (ms * sample_rate as f64 / 1000.0).round() as usize,
+            None => samples.len() / 2 * self.loop_count as usize,
+        };
+        if frames * 2 == samples.len() || samples.is_empty() {
+            return samples;
+        }
+        Cow::Owned(samples.iter().copied().cycle().take(frames * 2).collect())
+    }
+
+    /// Length in frames at `output_rate` of a `frames`-long file at
+    /// `sample_rate` once trimmed, resampled and looped, as `looped` and the
+    /// steps before it leave it. `auto_trim_silence` needs the samples, so
+    /// it is not taken into account.
+    pub(crate) fn prepared_frames(
+        &self,
+        frames: usize,
+        sample_rate: u32,
+        output_rate: u32,
+    ) -> usize {
+        let mut frames = self.trim(frames, sample_rate).len();
+        if sample_rate != output_rate || self.speed != 1.0 {
+            let ratio = output_rate as f64 / (sample_rate as f64 * self.speed as f64);
+            frames = (frames as f64 * ratio).round() as usize;
+        }
+        match self.loop_to_ms {
+            _ if frames == 0 => 0,
+            Some(ms) => (ms * output_rate as f64 / 1000.0).round() as usize,
+            None => frames * self.loop_count as usize,
+        }
+    }
+
+    /// Fade and automation gain for each frame of a trimmed file `frames`
+    /// long at `sample_rate`.
+    pub(crate) fn envelope(&self, frames: usize, sample_rate: u32) -> impl Fn(usize) -> f32 {
+        let to_frames = |ms: f64| ((ms * sample_rate as f64 / 1000.0).round() as usize).min(frames);
+        let fade_in = to_frames(self.fade_in_ms);
+        let fade_out = to_frames(self.fade_out_ms);
+        let curve = self.fade_curve;
+        let mut keyframes: Vec<(f64, f32)> = self
+            .automation
+            .iter()
+            .map(|p| (p.time_ms * sample_rate as f64 / 1000.0, p.gain))
+            .collect();
+        keyframes.sort_by(|a, b| a.0.total_cmp(&b.0));
+        move |n| {
+            let mut gain = automation_gain(&keyframes, n as f64);
+            if n < fade_in {
+                gain *= curve.gain(n as f32 / fade_in as f32);
+            }
+            let remaining = frames - 1 - n;
+            if remaining < fade_out {
+                gain *= curve.gain(remaining as f32 / fade_out as f32);
+            }
+            gain
+        }
+    }
+
+    /// The gain of each output channel before the pan: `gain_left` and
+    /// `gain_right`, or `gain` where they are not set.
+    pub(crate) fn gains(&self) -> [f32; 2] {
+        [
+            self.gain_left.unwrap_or(self.gain),
+            self.gain_right.unwrap_or(self.gain),
+        ]
+    }
+
+    /// Multiplies every gain of the track by `factor`.
+    pub(crate) fn scale_gain(&mut self, factor: f32) {
+        self.gain *= factor;
+        for gain in self.gain_left.iter_mut().chain(&mut self.gain_right) {
+            *gain *= factor;
+        }
+    }
+
+    /// Left and right multipliers for a source, folding in the gains.
+    pub(crate) fn channel_gains(&self, mono: bool) -> [f32; 2] {
+        // Scaled by sqrt(2) so both sides are exactly 1.0 at the centre.
+        let angle = (self.pan as f64 + 1.0) * std::f64::consts::FRAC_PI_4;
+        let left = std::f64::consts::SQRT_2 * angle.cos();
+        let right = std::f64::consts::SQRT_2 * angle.sin();
+        let [left, right] = match (mono, self.pan) {
+            (true, _) => [left, right],
+            (false, pan) if pan < 0.0 => [1.0, right],
+            (false, _) => [left, 1.0],
+        };
+        let [gain_left, gain_right] = self.gains();
+        [gain_left * left as f32, gain_right * right as f32]
+    }
+}
+
+/// Whether a mix of `tracks` has the same samples on both sides, so one
+/// channel holds it: there is a file in it, and every file in it is mono,
+/// or forced to it, and has the same gain on both sides. `mono(i)` says
+/// whether file `i` is.
+pub(crate) fn is_mono_mix(tracks: &[MixTrack], mono: impl Fn(usize) -> bool) -> bool {
+    let soloing = tracks.iter().any(|t| t.solo);
+    let mut audible = tracks
+        .iter()
+        .enumerate()
+        .filter(|(_, track)| track.is_audible(soloing))
+        .peekable();
+    audible.peek().is_some()
+        && audible.all(|(i, track)| {
+            let [left, right] = track.channel_gains(true);
+            (mono(i) || track.force_mono) && left == right
+        })
+}
+
+/// Where `AudioCombiner::mix` places each file, in frames at the output rate.
+pub(crate) struct Layout {
+    /// First frame of each file in the mix.
+    pub(crate) starts: Vec<usize>,
+    /// Frames each file shares with the one before it, and a 0 after the
+    /// last file.
+    pub(crate) overlaps: Vec<usize>,
+    /// Length of the whole mix, including any leading silence.
+    pub(crate) frames: usize,
+}
+
+impl Layout {
+    /// Places files of `frames` each, as prepared for the output, at their
+    /// tracks' offsets: all from the start in `Mix` mode, or one after
+    /// another with `crossfade_ms` overlaps in `Concat` mode.
+    pub(crate) fn new(frames: &[usize], tracks: &[MixTrack], options: &CombineOptions) -> Self {
+        let sample_rate = options.sample_rate;
+        let crossfade = (options.crossfade_ms * sample_rate as f64 / 1000.0).round() as usize;
+        let overlaps: Vec<usize> = (0..frames.len())
+            .map(|i| match options.mode {
+                CombineMode::Concat if i > 0 => crossfade.min(frames[i - 1]).min(frames[i]),
+                _ => 0,
+            })
+            .chain(std::iter::once(0))
+            .collect();
+
+        let mut end = 0;
+        let starts: Vec<usize> = tracks
+            .iter()
+            .zip(frames)
+            .zip(&overlaps)
+            .map(|((track, &frames), overlap)| {
+                let offset = track.offset(sample_rate);
+                match options.mode {
+                    CombineMode::Mix => offset,
+                    CombineMode::Concat => {
+                        let start = end - overlap + offset;
+                        end = start + frames;
+                        start
+                    }
+                }
+            })
+            .collect();
+
+        let total = frames
+            .iter()
+            .zip(&starts)
+            .map(|(frames, start)| start + frames)
+            .max()
+            .unwrap_or(0);
+        Self {
+            starts,
+            overlaps,
+            frames: total,
+        }
+    }
+}
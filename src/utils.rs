@@ -1,3 +1,5 @@
+use crate::CombineError;
+
 pub fn set_panic_hook() {
     // When the `console_error_panic_hook` feature is enabled, we can call the
     // `set_panic_hook` function at least once during initialization, and then
@@ -8,3 +10,32 @@ pub fn set_panic_hook() {
     #[cfg(feature = "console_error_panic_hook")]
     console_error_panic_hook::set_once();
 }
+
+/// Runs `f`, turning a panic inside it into `CombineError::Internal` with
+/// `context` in front of the panic message.
+///
+/// This only works where panics unwind. A wasm build with the default
+/// `panic = "abort"` still traps, though the panic hook logs the message
+/// first.
+pub fn catch_panic<T>(
+    context: impl FnOnce() -> String,
+    f: impl FnOnce() -> T,
+) -> Result<T, CombineError> {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).map_err(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".into());
+        CombineError::Internal {
+            detail: format!("{}: {}", context(), message),
+        }
+    })
+}
+
+/// Whether the page can share memory with workers, which the thread pool
+/// needs. Without cross-origin isolation browsers hide `SharedArrayBuffer`.
+#[cfg(feature = "threads")]
+pub fn threads_available() -> bool {
+    js_sys::Reflect::has(&js_sys::global(), &"SharedArrayBuffer".into()).unwrap_or(false)
+}
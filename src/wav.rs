@@ -0,0 +1,434 @@
+use crate::bext::BroadcastExtension;
+use crate::cue::{self, CuePoint};
+use crate::dither::Dither;
+use crate::options::{BitDepth, OutputMetadata};
+use crate::smpl::LoopPoints;
+use crate::{simd, CombineError};
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+
+/// Bytes in an RF64 ds64 chunk, header included.
+const DS64_CHUNK_SIZE: u32 = 36;
+
+const I24_MAX: i32 = (1 << 23) - 1;
+
+/// The optional chunks that go ahead of the sample data.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WavTags<'a> {
+    /// Written as a Broadcast Wave `bext` chunk.
+    pub bext: Option<&'a BroadcastExtension>,
+    /// Written as a `smpl` chunk.
+    pub sample_loop: Option<LoopPoints>,
+    /// Written as a LIST/INFO chunk.
+    pub metadata: Option<&'a OutputMetadata>,
+    /// Written as a `cue ` chunk and a LIST/adtl chunk of their labels.
+    pub cues: &'a [CuePoint],
+}
+
+/// Samples `MixBuffer::into_wav` reads and encodes at a time.
+const IN_PLACE_BLOCK: usize = 16 * 1024;
+
+/// Interleaved samples kept in a byte buffer, so `into_wav` can write the
+/// WAV over them instead of into a second buffer alongside them.
+pub struct MixBuffer {
+    bytes: Vec<u8>,
+    /// Where the samples start in `bytes`, aligned for `f32`.
+    start: usize,
+    len: usize,
+}
+
+impl MixBuffer {
+    /// `len` samples of silence.
+    pub fn zeroed(len: usize) -> Self {
+        // Spare bytes to align the samples, wherever the allocator put them.
+        let bytes = vec![0u8; len * 4 + 3];
+        let (prefix, _, _) = bytemuck::pod_align_to::<u8, f32>(&bytes);
+        Self {
+            start: prefix.len(),
+            bytes,
+            len,
+        }
+    }
+
+    /// Keeps the left sample of each stereo frame and drops the right, for
+    /// a mix whose two sides are the same.
+    pub fn keep_left(&mut self) {
+        let frames = self.len / 2;
+        let samples: &mut [f32] = self;
+        for n in 0..frames {
+            samples[n] = samples[n * 2];
+        }
+        self.len = frames;
+    }
+
+    /// Wraps samples interleaved across `channels` in a RIFF/WAVE container
+    /// as `create_wav_container` does, giving the same bytes.
+    ///
+    /// Each block of samples is encoded over the ones already read, and the
+    /// data is then moved up past the header, so for integer output the WAV
+    /// ends up in the mix's own allocation. Float output is as wide as the
+    /// mix, so the buffer has to grow by the header.
+    pub fn into_wav(
+        self,
+        sample_rate: u32,
+        channels: u16,
+        bit_depth: BitDepth,
+        tags: WavTags,
+        rf64: bool,
+        mut dither: Option<&mut Dither>,
+    ) -> Result<Vec<u8>, CombineError> {
+        let Self {
+            mut bytes,
+            start,
+            len,
+        } = self;
+        let data_size = len as u64 * bit_depth.bytes_per_sample() as u64;
+        let header = wav_header(data_size, sample_rate, channels, bit_depth, tags, rf64)?;
+        let file_len = file_len(header.len(), data_size)?;
+
+        let mut block = Vec::with_capacity(IN_PLACE_BLOCK * 4);
+        let mut written = start;
+        for first in (0..len).step_by(IN_PLACE_BLOCK) {
+            let end = (first + IN_PLACE_BLOCK).min(len);
+            let samples: &[f32] = bytemuck::cast_slice(&bytes[start + first * 4..start + end * 4]);
+            block.clear();
+            encode_samples(samples, bit_depth, dither.as_deref_mut(), &mut block);
+            // No wider than the samples, so this stops short of the next block.
+            bytes[written..written + block.len()].copy_from_slice(&block);
+            written += block.len();
+        }
+
+        if bytes.len() < file_len {
+            bytes.resize(file_len, 0);
+        }
+        bytes.copy_within(start..written, header.len());
+        bytes[..header.len()].copy_from_slice(&header);
+        bytes.truncate(file_len);
+        bytes.shrink_to_fit();
+        Ok(bytes)
+    }
+}
+
+impl std::ops::Deref for MixBuffer {
+    type Target = [f32];
+
+    fn deref(&self) -> &[f32] {
+        bytemuck::cast_slice(&self.bytes[self.start..self.start + self.len * 4])
+    }
+}
+
+impl std::ops::DerefMut for MixBuffer {
+    fn deref_mut(&mut self) -> &mut [f32] {
+        bytemuck::cast_slice_mut(&mut self.bytes[self.start..self.start + self.len * 4])
+    }
+}
+
+/// Wraps samples interleaved across `channels` in a RIFF/WAVE container.
+///
+/// Integer output is clamped to full scale; float output is written as-is so
+/// overs survive for later processing. Any `tags` go ahead of the samples,
+/// and any `dither` is added to 16-bit output, carrying on from wherever it
+/// was left.
+pub fn create_wav_container(
+    samples: &[f32],
+    sample_rate: u32,
+    channels: u16,
+    bit_depth: BitDepth,
+    tags: WavTags,
+    rf64: bool,
+    dither: Option<&mut Dither>,
+) -> Result<Vec<u8>, CombineError> {
+    let data_size = samples.len() as u64 * bit_depth.bytes_per_sample() as u64;
+    let mut wav = wav_header(data_size, sample_rate, channels, bit_depth, tags, rf64)?;
+    wav.reserve(file_len(wav.len(), data_size)? - wav.len());
+    encode_samples(samples, bit_depth, dither, &mut wav);
+    Ok(wav)
+}
+
+/// Length of a WAV of `header_len` bytes of header and `data_size` of
+/// samples, or `TooLarge` when no `Vec` can hold it, as on wasm32 past
+/// 2 GiB.
+fn file_len(header_len: usize, data_size: u64) -> Result<usize, CombineError> {
+    let len = header_len as u64 + data_size;
+    if len > isize::MAX as u64 {
+        return Err(CombineError::TooLarge { bytes: len });
+    }
+    Ok(len as usize)
+}
+
+/// A LIST/INFO chunk of the fields set in `metadata`, or nothing when none
+/// are.
+fn info_chunk(metadata: &OutputMetadata) -> Vec<u8> {
+    let fields = [
+        (b"INAM", &metadata.title),
+        (b"IART", &metadata.artist),
+        (b"ICRD", &metadata.creation_date),
+    ];
+    let mut info = b"INFO".to_vec();
+    for (id, value) in fields {
+        if let Some(value) = value {
+            // NUL-terminated, and padded to keep chunks word aligned.
+            let size = value.len() as u32 + 1;
+            info.extend_from_slice(id);
+            info.extend_from_slice(&size.to_le_bytes());
+            info.extend_from_slice(value.as_bytes());
+            info.push(0);
+            if size % 2 == 1 {
+                info.push(0);
+            }
+        }
+    }
+    if info.len() == 4 {
+        return Vec::new();
+    }
+    let mut list = b"LIST".to_vec();
+    list.extend_from_slice(&(info.len() as u32).to_le_bytes());
+    list.extend(info);
+    list
+}
+
+/// Everything before the sample data of a WAV of `channels` holding
+/// `data_size` bytes of samples.
+///
+/// The bext, smpl, INFO and cue chunks go ahead of the data, in that
+/// order, where readers that stop at the samples, symphonia among them,
+/// still find them. Sizes too big for RIFF's 32 bits go in an RF64 ds64
+/// chunk when `rf64` is set and are `TooLarge` otherwise.
+pub fn wav_header(
+    data_size: u64,
+    sample_rate: u32,
+    channels: u16,
+    bit_depth: BitDepth,
+    tags: WavTags,
+    rf64: bool,
+) -> Result<Vec<u8>, CombineError> {
+    let bytes_per_sample = bit_depth.bytes_per_sample();
+    let block_align = channels * bytes_per_sample;
+
+    // Non-PCM formats need the extended fmt chunk and a fact chunk.
+    let (format_tag, fmt_size, fact_size) = match bit_depth {
+        BitDepth::Int16 | BitDepth::Int24 => (WAVE_FORMAT_PCM, 16u32, 0u32),
+        BitDepth::Float32 => (WAVE_FORMAT_IEEE_FLOAT, 18, 12),
+    };
+
+    let mut tag_chunks = tags.bext.map(BroadcastExtension::chunk).unwrap_or_default();
+    if let Some(sample_loop) = tags.sample_loop {
+        tag_chunks.extend(sample_loop.chunk(sample_rate));
+    }
+    tag_chunks.extend(tags.metadata.map(info_chunk).unwrap_or_default());
+    tag_chunks.extend(cue::chunks(tags.cues));
+    let tags_size = tag_chunks.len() as u32;
+
+    let riff_size = (20 + fmt_size + fact_size + tags_size) as u64 + data_size;
+    let frames = data_size / block_align as u64;
+    let large = riff_size > u32::MAX as u64;
+    if large && !rf64 {
+        return Err(CombineError::TooLarge {
+            bytes: riff_size + 8,
+        });
+    }
+    // RF64 marks every 32-bit size it moves into ds64 as all ones.
+    let size32 = |size: u64| if large { u32::MAX } else { size as u32 };
+
+    let mut wav =
+        Vec::with_capacity((28 + DS64_CHUNK_SIZE + fmt_size + fact_size + tags_size) as usize);
+
+    // RIFF Header
+    wav.extend_from_slice(if large { b"RF64" } else { b"RIFF" });
+    wav.extend_from_slice(&size32(riff_size).to_le_bytes());
+    wav.extend_from_slice(b"WAVE");
+
+    // ds64 chunk, without a table of other large chunks
+    if large {
+        wav.extend_from_slice(b"ds64");
+        wav.extend_from_slice(&(DS64_CHUNK_SIZE - 8).to_le_bytes());
+        wav.extend_from_slice(&(riff_size + DS64_CHUNK_SIZE as u64).to_le_bytes());
+        wav.extend_from_slice(&data_size.to_le_bytes());
+        wav.extend_from_slice(&frames.to_le_bytes());
+        wav.extend_from_slice(&0u32.to_le_bytes());
+    }
+
+    // fmt chunk
+    wav.extend_from_slice(b"fmt ");
+    wav.extend_from_slice(&fmt_size.to_le_bytes());
+    wav.extend_from_slice(&format_tag.to_le_bytes());
+    wav.extend_from_slice(&channels.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+    wav.extend_from_slice(&block_align.to_le_bytes());
+    wav.extend_from_slice(&(bytes_per_sample * 8).to_le_bytes());
+    if fmt_size == 18 {
+        wav.extend_from_slice(&0u16.to_le_bytes());
+    }
+
+    // fact chunk
+    if fact_size > 0 {
+        wav.extend_from_slice(b"fact");
+        wav.extend_from_slice(&4u32.to_le_bytes());
+        wav.extend_from_slice(&size32(frames).to_le_bytes());
+    }
+
+    wav.extend(tag_chunks);
+
+    // data chunk
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&size32(data_size).to_le_bytes());
+    Ok(wav)
+}
+
+/// Appends samples to `out` in the sample encoding of `bit_depth`, with
+/// `dither` added if it is 16-bit.
+pub fn encode_samples(
+    samples: &[f32],
+    bit_depth: BitDepth,
+    dither: Option<&mut Dither>,
+    out: &mut Vec<u8>,
+) {
+    match bit_depth {
+        BitDepth::Int16 => match dither {
+            Some(dither) => dither.quantize_i16(samples, out),
+            None => simd::quantize_i16(samples, out),
+        },
+        BitDepth::Int24 => {
+            for &sample in samples {
+                let clamped = sample.clamp(-1.0, 1.0);
+                let s = (clamped * I24_MAX as f32) as i32;
+                out.extend_from_slice(&s.to_le_bytes()[..3]);
+            }
+        }
+        BitDepth::Float32 => {
+            for &sample in samples {
+                out.extend_from_slice(&sample.to_le_bytes());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use super::*;
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+        u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+    }
+
+    #[test]
+    fn riff_sizes_stop_at_u32_max() {
+        let largest = u32::MAX as u64 - 36;
+        let header = wav_header(
+            largest,
+            44100,
+            2,
+            BitDepth::Int16,
+            WavTags::default(),
+            false,
+        )
+        .unwrap();
+        assert_eq!(&header[..4], b"RIFF");
+        assert_eq!(u32_at(&header, 4), u32::MAX);
+        assert_eq!(u32_at(&header, 40), largest as u32);
+
+        assert_eq!(
+            wav_header(
+                largest + 1,
+                44100,
+                2,
+                BitDepth::Int16,
+                WavTags::default(),
+                false
+            ),
+            Err(CombineError::TooLarge {
+                bytes: u32::MAX as u64 + 9
+            })
+        );
+        // Only output that needs it becomes RF64.
+        let header =
+            wav_header(largest, 44100, 2, BitDepth::Int16, WavTags::default(), true).unwrap();
+        assert_eq!(&header[..4], b"RIFF");
+    }
+
+    #[test]
+    fn rf64_carries_64_bit_sizes_in_ds64() {
+        // Five hours of 32-bit float stereo at 48 kHz.
+        let frames = 5 * 3600 * 48000u64;
+        let data_size = frames * 8;
+        let header = wav_header(
+            data_size,
+            48000,
+            2,
+            BitDepth::Float32,
+            WavTags::default(),
+            true,
+        )
+        .unwrap();
+        assert_eq!(&header[..4], b"RF64");
+        assert_eq!(u32_at(&header, 4), u32::MAX);
+        assert_eq!(&header[12..16], b"ds64");
+        assert_eq!(u32_at(&header, 16), 28);
+        assert_eq!(u64_at(&header, 20), header.len() as u64 - 8 + data_size);
+        assert_eq!(u64_at(&header, 28), data_size);
+        assert_eq!(u64_at(&header, 36), frames);
+
+        let fact = header.len() - 20;
+        assert_eq!(&header[fact..fact + 4], b"fact");
+        assert_eq!(u32_at(&header, fact + 8), u32::MAX);
+        assert_eq!(&header[header.len() - 8..header.len() - 4], b"data");
+        assert_eq!(u32_at(&header, header.len() - 4), u32::MAX);
+    }
+
+    #[test]
+    fn wav_written_over_the_mix_matches_a_separate_one() {
+        let len = IN_PLACE_BLOCK * 2 + 6;
+        let cues = [CuePoint {
+            frame: 5,
+            label: "intro".into(),
+        }];
+        let tags = WavTags {
+            sample_loop: Some(LoopPoints::default()),
+            cues: &cues,
+            ..WavTags::default()
+        };
+        for bit_depth in [BitDepth::Int16, BitDepth::Int24, BitDepth::Float32] {
+            let mut mix = MixBuffer::zeroed(len);
+            for (n, sample) in mix.iter_mut().enumerate() {
+                *sample = (n as f32 * 0.01).sin() * 1.2;
+            }
+            let expected = create_wav_container(
+                &mix,
+                48000,
+                2,
+                bit_depth,
+                tags,
+                false,
+                Some(&mut Dither::new(7)),
+            )
+            .unwrap();
+            let mix_at = mix.bytes.as_ptr();
+            let wav = mix
+                .into_wav(48000, 2, bit_depth, tags, false, Some(&mut Dither::new(7)))
+                .unwrap();
+            assert!(wav == expected, "{:?}", bit_depth);
+            if bit_depth != BitDepth::Float32 {
+                assert_eq!(wav.as_ptr(), mix_at, "{:?}", bit_depth);
+            }
+        }
+    }
+
+    #[test]
+    fn files_past_isize_max_are_too_large() {
+        assert_eq!(file_len(44, 1000), Ok(1044));
+        assert_eq!(
+            file_len(44, isize::MAX as u64),
+            Err(CombineError::TooLarge {
+                bytes: isize::MAX as u64 + 44
+            })
+        );
+    }
+}
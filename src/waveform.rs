@@ -0,0 +1,26 @@
+/// Absolute peak of each of `buckets` equal stretches of interleaved stereo.
+///
+/// With `per_channel` each bucket gives a left and a right peak, interleaved;
+/// otherwise the louder of the two. Asking for more buckets than there are
+/// frames gives one bucket per frame.
+pub fn peaks(samples: &[f32], buckets: u32, per_channel: bool) -> Vec<f32> {
+    let frames = samples.len() / 2;
+    let buckets = (buckets as usize).min(frames);
+    let mut peaks = Vec::with_capacity(if per_channel { buckets * 2 } else { buckets });
+    for bucket in 0..buckets {
+        let start = bucket * frames / buckets;
+        let end = (bucket + 1) * frames / buckets;
+        let (left, right) = samples[start * 2..end * 2]
+            .chunks_exact(2)
+            .fold((0.0f32, 0.0f32), |(left, right), frame| {
+                (left.max(frame[0].abs()), right.max(frame[1].abs()))
+            });
+        if per_channel {
+            peaks.push(left);
+            peaks.push(right);
+        } else {
+            peaks.push(left.max(right));
+        }
+    }
+    peaks
+}
@@ -3,11 +3,5245 @@
 #![cfg(target_arch = "wasm32")]
 
 extern crate wasm_bindgen_test;
+use std::convert::TryInto;
+
+use wasm_audio_combiner::{
+    capabilities, combine_files, db_to_linear, measure_loudness, measure_true_peak, set_log_level,
+    AudioCombiner, AutomationPoint, BitDepth, BroadcastExtension, CombineError, CombineMode,
+    CombineOptions, Compressor, DecodeStage, Ducking, Equalizer, FadeCurve, LogLevel, Marker,
+    MixConfig, MixProject, MixTrack, Mp3Bitrate, NoiseGate, OutputFormat, OutputMetadata,
+    SampleLoop, SingleAudioFile, SingleAudioFileType,
+};
+use wasm_bindgen::{JsCast, JsValue};
 use wasm_bindgen_test::*;
 
 wasm_bindgen_test_configure!(run_in_browser);
 
+const SINE_MP3: &[u8] = include_bytes!("fixtures/sine_440_stereo_44100.mp3");
+const SINE_M4A: &[u8] = include_bytes!("fixtures/sine_440_stereo_44100.m4a");
+const SINE_HE_AAC_M4A: &[u8] = include_bytes!("fixtures/sine_440_stereo_44100_he.m4a");
+const SINE_VORBIS_WEBM: &[u8] = include_bytes!("fixtures/sine_440_stereo_44100_vorbis.webm");
+const OPUS_SILENCE: &[u8] = include_bytes!("fixtures/silence_48000.opus");
+const SINE_OPUS: &[u8] = include_bytes!("fixtures/sine_440_stereo_48000.opus");
+/// `SINE_OPUS` with one packet cut off after its first byte.
+const CORRUPT_OPUS: &[u8] = include_bytes!("fixtures/sine_440_stereo_48000_corrupt.opus");
+/// The packets of `SINE_OPUS` laid out as Chrome's MediaRecorder writes
+/// WebM, with a segment and clusters of unknown size.
+const SINE_OPUS_WEBM: &[u8] = include_bytes!("fixtures/sine_440_stereo_48000_opus.webm");
+const SINE_FLAC_24: &[u8] = include_bytes!("fixtures/sine_440_stereo_44100_24bit.flac");
+const SINE_MONO_MP3: &[u8] = include_bytes!("fixtures/sine_440_mono_44100.mp3");
+const SINE_OGG: &[u8] = include_bytes!("fixtures/sine_440_stereo_44100.ogg");
+const SINE_OGG_48000: &[u8] = include_bytes!("fixtures/sine_440_stereo_48000.ogg");
+/// `SINE_OGG` followed by `SINE_OGG_48000` as a second link.
+const CHAINED_OGG: &[u8] = include_bytes!("fixtures/chained_44100_48000.ogg");
+
+/// Sample encodings understood by `wav_bytes`.
+#[derive(Clone, Copy)]
+enum WavEncoding {
+    Pcm16,
+    Pcm24,
+    Float32,
+}
+
+/// Builds a minimal WAV file from interleaved samples in `-1.0..=1.0`.
+fn wav_bytes(encoding: WavEncoding, channels: u16, sample_rate: u32, samples: &[f32]) -> Vec<u8> {
+    let (format_tag, bytes_per_sample) = match encoding {
+        WavEncoding::Pcm16 => (1u16, 2u16),
+        WavEncoding::Pcm24 => (1, 3),
+        WavEncoding::Float32 => (3, 4),
+    };
+    let block_align = channels * bytes_per_sample;
+    let data_size = samples.len() as u32 * bytes_per_sample as u32;
+
+    let mut wav = Vec::new();
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_size).to_le_bytes());
+    wav.extend_from_slice(b"WAVE");
+    wav.extend_from_slice(b"fmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&format_tag.to_le_bytes());
+    wav.extend_from_slice(&channels.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+    wav.extend_from_slice(&block_align.to_le_bytes());
+    wav.extend_from_slice(&(bytes_per_sample * 8).to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_size.to_le_bytes());
+    for &sample in samples {
+        match encoding {
+            WavEncoding::Pcm16 => {
+                wav.extend_from_slice(&((sample * i16::MAX as f32) as i16).to_le_bytes())
+            }
+            WavEncoding::Pcm24 => {
+                let s = (sample * 8_388_607.0) as i32;
+                wav.extend_from_slice(&s.to_le_bytes()[..3]);
+            }
+            WavEncoding::Float32 => wav.extend_from_slice(&sample.to_le_bytes()),
+        }
+    }
+    wav
+}
+
+/// Builds a 32-bit float WAVE_FORMAT_EXTENSIBLE file with a speaker mask.
+fn extensible_wav_bytes(channel_mask: u32, sample_rate: u32, samples: &[f32]) -> Vec<u8> {
+    let channels = channel_mask.count_ones() as u16;
+    let block_align = channels * 4;
+    let data_size = samples.len() as u32 * 4;
+
+    let mut wav = Vec::new();
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(60 + data_size).to_le_bytes());
+    wav.extend_from_slice(b"WAVE");
+    wav.extend_from_slice(b"fmt ");
+    wav.extend_from_slice(&40u32.to_le_bytes());
+    wav.extend_from_slice(&0xfffeu16.to_le_bytes());
+    wav.extend_from_slice(&channels.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+    wav.extend_from_slice(&block_align.to_le_bytes());
+    wav.extend_from_slice(&32u16.to_le_bytes());
+    wav.extend_from_slice(&22u16.to_le_bytes());
+    wav.extend_from_slice(&32u16.to_le_bytes());
+    wav.extend_from_slice(&channel_mask.to_le_bytes());
+    // KSDATAFORMAT_SUBTYPE_IEEE_FLOAT
+    wav.extend_from_slice(&[
+        0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xaa, 0x00, 0x38, 0x9b,
+        0x71,
+    ]);
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_size.to_le_bytes());
+    for &sample in samples {
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+    wav
+}
+
+/// Builds a big-endian PCM AIFF file from interleaved samples in `-1.0..=1.0`.
+fn aiff_bytes(bits: u16, channels: u16, sample_rate: u32, samples: &[f32]) -> Vec<u8> {
+    let bytes_per_sample = (bits / 8) as usize;
+    let frames = samples.len() / channels as usize;
+    let data_size = samples.len() * bytes_per_sample;
+
+    // The COMM chunk stores the rate as an 80-bit extended float.
+    let exponent = 31 - sample_rate.leading_zeros();
+    let mut rate = [0u8; 10];
+    rate[..2].copy_from_slice(&(16383 + exponent as u16).to_be_bytes());
+    rate[2..].copy_from_slice(&((sample_rate as u64) << (63 - exponent)).to_be_bytes());
+
+    let mut aiff = Vec::new();
+    aiff.extend_from_slice(b"FORM");
+    aiff.extend_from_slice(&(4 + 26 + 16 + data_size as u32).to_be_bytes());
+    aiff.extend_from_slice(b"AIFF");
+    aiff.extend_from_slice(b"COMM");
+    aiff.extend_from_slice(&18u32.to_be_bytes());
+    aiff.extend_from_slice(&channels.to_be_bytes());
+    aiff.extend_from_slice(&(frames as u32).to_be_bytes());
+    aiff.extend_from_slice(&bits.to_be_bytes());
+    aiff.extend_from_slice(&rate);
+    aiff.extend_from_slice(b"SSND");
+    aiff.extend_from_slice(&(8 + data_size as u32).to_be_bytes());
+    aiff.extend_from_slice(&[0; 8]);
+    for &sample in samples {
+        let s = (sample * ((1i32 << (bits - 1)) - 1) as f32) as i32;
+        aiff.extend_from_slice(&s.to_be_bytes()[4 - bytes_per_sample..]);
+    }
+    aiff
+}
+
+/// Returns the body of the first RIFF chunk with the given id.
+fn riff_chunk<'a>(wav: &'a [u8], id: &[u8; 4]) -> Option<&'a [u8]> {
+    let mut pos = 12;
+    while pos + 8 <= wav.len() {
+        let size = u32::from_le_bytes([wav[pos + 4], wav[pos + 5], wav[pos + 6], wav[pos + 7]]);
+        let body = &wav[pos + 8..(pos + 8 + size as usize).min(wav.len())];
+        if &wav[pos..pos + 4] == id {
+            return Some(body);
+        }
+        pos += 8 + size as usize + (size as usize & 1);
+    }
+    None
+}
+
+/// Returns the 16-bit samples from the data chunk of a combined output.
+fn output_samples(wav: &[u8]) -> Vec<i16> {
+    riff_chunk(wav, b"data")
+        .unwrap()
+        .chunks_exact(2)
+        .map(|b| i16::from_le_bytes([b[0], b[1]]))
+        .collect()
+}
+
+/// Interleaved stereo sine with both channels identical.
+fn stereo_sine(freq: f32, sample_rate: u32, frames: usize, amplitude: f32) -> Vec<f32> {
+    (0..frames)
+        .flat_map(|i| {
+            let t = i as f64 / sample_rate as f64;
+            let s = amplitude * (2.0 * std::f64::consts::PI * freq as f64 * t).sin() as f32;
+            [s, s]
+        })
+        .collect()
+}
+
+fn ramp(len: usize) -> Vec<f32> {
+    (0..len)
+        .map(|i| (i as f32 / len as f32) * 1.6 - 0.8)
+        .collect()
+}
+
+/// 16-bit samples of the mix, undithered so they can be compared closely.
+fn combine(files: Vec<SingleAudioFile>, volumes: Vec<u8>) -> Vec<i16> {
+    let combiner = AudioCombiner::new(files).unwrap();
+    let mut options = CombineOptions::new();
+    options.dither = false;
+    output_samples(
+        &combiner
+            .combine_with_options(volumes, &options)
+            .unwrap()
+            .bytes(),
+    )
+}
+
+fn assert_close(actual: &[i16], expected: &[i16], tolerance: i32) {
+    assert_eq!(actual.len(), expected.len());
+    for (i, (&a, &e)) in actual.iter().zip(expected).enumerate() {
+        assert!(
+            (a as i32 - e as i32).abs() <= tolerance,
+            "sample {}: {} vs {}",
+            i,
+            a,
+            e
+        );
+    }
+}
+
 #[wasm_bindgen_test]
 fn pass() {
     assert_eq!(1 + 1, 2);
 }
+
+#[wasm_bindgen_test]
+fn wav_input_decodes_at_every_bit_depth() {
+    let samples = ramp(2048);
+    let expected: Vec<i16> = samples
+        .iter()
+        .map(|&s| (s * i16::MAX as f32) as i16)
+        .collect();
+
+    for encoding in [WavEncoding::Pcm16, WavEncoding::Pcm24, WavEncoding::Float32] {
+        let wav = wav_bytes(encoding, 2, 44100, &samples);
+        let output = combine(
+            vec![SingleAudioFile::new(wav, SingleAudioFileType::Wav)],
+            vec![100],
+        );
+        assert_close(&output, &expected, 1);
+    }
+}
+
+#[wasm_bindgen_test]
+fn wav_mixes_like_the_mp3_it_was_made_from() {
+    let mp3 = || SingleAudioFile::new(SINE_MP3.to_vec(), SingleAudioFileType::Mpeg);
+    let as_wav = AudioCombiner::new(vec![mp3()])
+        .unwrap()
+        .combine(vec![100])
+        .unwrap();
+
+    let two_mp3s = combine(vec![mp3(), mp3()], vec![50, 50]);
+    let wav_and_mp3 = combine(
+        vec![SingleAudioFile::new(as_wav.bytes(), as_wav.r#type), mp3()],
+        vec![50, 50],
+    );
+    assert_close(&wav_and_mp3, &two_mp3s, 2);
+}
+
+#[wasm_bindgen_test]
+fn malformed_riff_header_is_an_error() {
+    let mut wav = wav_bytes(WavEncoding::Pcm16, 2, 44100, &ramp(64));
+    wav[..4].copy_from_slice(b"RIFX");
+    let error = AudioCombiner::new(vec![SingleAudioFile::new(wav, SingleAudioFileType::Wav)])
+        .err()
+        .unwrap();
+    assert_eq!(error.index(), Some(0));
+    assert!(error.to_string().starts_with("file 0:"), "{}", error);
+}
+
+#[wasm_bindgen_test]
+fn flac_24bit_decodes_without_truncation() {
+    let output = combine(
+        vec![SingleAudioFile::new(
+            SINE_FLAC_24.to_vec(),
+            SingleAudioFileType::Flac,
+        )],
+        vec![100],
+    );
+    let expected: Vec<i16> = stereo_sine(440.0, 44100, 44100, 0.5)
+        .iter()
+        .map(|&s| (s * i16::MAX as f32) as i16)
+        .collect();
+    assert_close(&output, &expected, 1);
+}
+
+#[wasm_bindgen_test]
+fn flac_mixes_with_mp3() {
+    let flac = || SingleAudioFile::new(SINE_FLAC_24.to_vec(), SingleAudioFileType::Flac);
+    let mp3 = || SingleAudioFile::new(SINE_MP3.to_vec(), SingleAudioFileType::Mpeg);
+
+    let flac_len = combine(vec![flac()], vec![100]).len();
+    let mp3_len = combine(vec![mp3()], vec![100]).len();
+    assert_eq!(flac_len, 44100 * 2);
+
+    let mixed = combine(vec![flac(), mp3()], vec![50, 50]);
+    assert_eq!(mixed.len(), flac_len.max(mp3_len));
+}
+
+#[wasm_bindgen_test]
+fn m4a_decodes_the_aac_track() {
+    let output = combine(
+        vec![SingleAudioFile::new(
+            SINE_M4A.to_vec(),
+            SingleAudioFileType::Aac,
+        )],
+        vec![100],
+    );
+    assert!(output.len() >= 44100 * 2);
+    let peak = output.iter().map(|s| s.unsigned_abs()).max().unwrap();
+    assert!(peak > i16::MAX as u16 / 3, "peak {}", peak);
+}
+
+#[wasm_bindgen_test]
+fn he_aac_reports_unsupported_codec() {
+    let error = AudioCombiner::new(vec![SingleAudioFile::new(
+        SINE_HE_AAC_M4A.to_vec(),
+        SingleAudioFileType::Aac,
+    )])
+    .err()
+    .unwrap();
+    assert_eq!(error.code(), "UNSUPPORTED_CODEC");
+    assert!(
+        error.to_string().contains("codec not supported"),
+        "{}",
+        error
+    );
+}
+
+#[wasm_bindgen_test]
+fn ogg_opus_at_48000_mixes_with_an_mp3_at_44100() {
+    let opus = || SingleAudioFile::new(SINE_OPUS.to_vec(), SingleAudioFileType::Ogg);
+    let mp3 = || SingleAudioFile::new(SINE_MP3.to_vec(), SingleAudioFileType::Mpeg);
+    let flac = || SingleAudioFile::new(SINE_FLAC_24.to_vec(), SingleAudioFileType::Flac);
+    let info = opus().probe().unwrap();
+    assert_eq!((info.sample_rate(), info.channels()), (48000, 2));
+    assert_eq!(info.codec(), "opus");
+    assert_eq!(info.duration_ms(), Some(1000.0));
+
+    // The pre-skip and end trim leave exactly the second that was encoded,
+    // which comes out at 44.1 kHz like the lossless FLAC of the same sine.
+    let alone = combine(vec![opus()], vec![100]);
+    let expected = combine(vec![flac()], vec![100]);
+    assert_eq!(alone.len(), expected.len());
+    // Opus is lossy, so only check it tracks the FLAC in time, away from
+    // the edges where the resampler runs out of input.
+    let mean_squared_error = |a: &[i16], b: &[i16]| {
+        let edge = 2 * 256;
+        a[edge..a.len() - edge]
+            .iter()
+            .zip(&b[edge..])
+            .map(|(&a, &b)| ((a as f64 - b as f64) / 32768.0).powi(2))
+            .sum::<f64>()
+            / (a.len() - 2 * edge) as f64
+    };
+    let error = mean_squared_error(&alone, &expected);
+    assert!(error < 1e-4, "mean squared error {}", error);
+
+    // Mixed with the shorter MP3, each lands at the start of the mix at
+    // half its level.
+    let mp3_alone = combine(vec![mp3()], vec![100]);
+    let mixed = combine(vec![opus(), mp3()], vec![50, 50]);
+    assert_eq!(mixed.len(), alone.len());
+    let halves: Vec<i16> = alone
+        .iter()
+        .zip(mp3_alone.iter().chain(std::iter::repeat(&0)))
+        .map(|(&a, &b)| ((a as i32 + b as i32) / 2) as i16)
+        .collect();
+    assert_close(&mixed, &halves, 2);
+}
+
+#[wasm_bindgen_test]
+fn corrupt_opus_packets_are_reported_with_the_file_index() {
+    let error = AudioCombiner::new(vec![
+        SingleAudioFile::new(SINE_MP3.to_vec(), SingleAudioFileType::Mpeg),
+        SingleAudioFile::new(CORRUPT_OPUS.to_vec(), SingleAudioFileType::Ogg),
+    ])
+    .err()
+    .unwrap();
+    assert_eq!((error.code(), error.index()), ("CORRUPT_PACKET", Some(1)));
+    assert!(error.to_string().starts_with("file 1: "), "{}", error);
+}
+
+#[wasm_bindgen_test]
+fn webm_skips_the_video_track() {
+    let webm = combine(
+        vec![SingleAudioFile::new(
+            SINE_VORBIS_WEBM.to_vec(),
+            SingleAudioFileType::Webm,
+        )],
+        vec![100],
+    );
+    let expected: Vec<i16> = stereo_sine(440.0, 44100, 44100, 0.5)
+        .iter()
+        .map(|&s| (s * i16::MAX as f32) as i16)
+        .collect();
+    // Matroska carries no end trim, so the final Vorbis block may add padding.
+    assert!(webm.len() >= expected.len() && webm.len() < expected.len() + 4096);
+    // Vorbis is lossy, so only check the decoded signal tracks the source.
+    let error: f64 = webm
+        .iter()
+        .zip(&expected)
+        .map(|(&a, &e)| ((a as f64 - e as f64) / i16::MAX as f64).powi(2))
+        .sum::<f64>()
+        / expected.len() as f64;
+    assert!(error < 1e-4, "mean squared error {}", error);
+}
+
+#[wasm_bindgen_test]
+fn webm_opus_from_media_recorder_decodes() {
+    let webm = || SingleAudioFile::new(SINE_OPUS_WEBM.to_vec(), SingleAudioFileType::Webm);
+    let info = webm().probe().unwrap();
+    assert_eq!((info.sample_rate(), info.channels()), (48000, 2));
+    assert_eq!(info.codec(), "opus");
+    assert_eq!(
+        SingleAudioFile::from_bytes(SINE_OPUS_WEBM.to_vec())
+            .unwrap()
+            .r#type,
+        SingleAudioFileType::Webm
+    );
+
+    let mut options = CombineOptions::new();
+    options.sample_rate = 48000;
+    options.dither = false;
+    let render = |file: SingleAudioFile| {
+        let combiner = AudioCombiner::new(vec![file]).unwrap();
+        output_samples(
+            &combiner
+                .combine_with_options(vec![100], &options)
+                .unwrap()
+                .bytes(),
+        )
+    };
+    // The same packets as the Ogg file decode to the same samples. Matroska
+    // carries no end trim, so the last packet's padding is kept.
+    let webm = render(webm());
+    let ogg = render(SingleAudioFile::new(
+        SINE_OPUS.to_vec(),
+        SingleAudioFileType::Ogg,
+    ));
+    assert_eq!(ogg.len(), 48000 * 2);
+    assert!(webm.len() >= ogg.len() && webm.len() < ogg.len() + 960 * 2);
+    assert_eq!(webm[..ogg.len()], ogg[..]);
+}
+
+#[wasm_bindgen_test]
+fn from_bytes_detects_the_container() {
+    let wav = wav_bytes(WavEncoding::Pcm16, 2, 44100, &ramp(64));
+    let cases: [(&[u8], SingleAudioFileType); 6] = [
+        (&wav, SingleAudioFileType::Wav),
+        (SINE_MP3, SingleAudioFileType::Mpeg),
+        (OPUS_SILENCE, SingleAudioFileType::Ogg),
+        (SINE_FLAC_24, SingleAudioFileType::Flac),
+        (SINE_M4A, SingleAudioFileType::Aac),
+        (SINE_VORBIS_WEBM, SingleAudioFileType::Webm),
+    ];
+    for (bytes, expected) in cases {
+        let file = SingleAudioFile::from_bytes(bytes.to_vec()).unwrap();
+        assert_eq!(file.r#type, expected);
+    }
+    assert!(SingleAudioFile::from_bytes(vec![0; 64]).is_err());
+}
+
+/// Standard base64 of `bytes`, with or without `=` padding.
+fn base64(bytes: &[u8], padded: bool) -> String {
+    const DIGITS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let group = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |group, (i, &b)| group | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(DIGITS[(group >> (18 - 6 * i) & 63) as usize] as char);
+            } else if padded {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[wasm_bindgen_test]
+fn base64_decodes_padded_or_not() {
+    let cases = [
+        ("", ""),
+        ("TWFu", "Man"),
+        ("TWE=", "Ma"),
+        ("TWE", "Ma"),
+        ("TQ==", "M"),
+        ("TQ", "M"),
+        (" TW\r\nFu T Q = = \n", "ManM"),
+    ];
+    for (data, expected) in cases {
+        let file = SingleAudioFile::from_base64(data, SingleAudioFileType::Auto).unwrap();
+        assert_eq!(file.bytes(), expected.as_bytes(), "{:?}", data);
+    }
+
+    // Lengths that end in a full group, two digits and three digits.
+    for bytes in [SINE_MP3, &SINE_MP3[1..], &SINE_MP3[2..]] {
+        let padded = base64(bytes, true);
+        let wrapped = padded
+            .as_bytes()
+            .chunks(76)
+            .map(|line| std::str::from_utf8(line).unwrap())
+            .collect::<Vec<_>>()
+            .join("\n");
+        for data in [padded.clone(), base64(bytes, false), wrapped] {
+            let file = SingleAudioFile::from_base64(&data, SingleAudioFileType::Mpeg).unwrap();
+            assert_eq!(file.bytes(), bytes);
+            assert_eq!(file.r#type, SingleAudioFileType::Mpeg);
+        }
+    }
+    let url_safe = base64(&[0xfb, 0xff], true)
+        .replace('+', "-")
+        .replace('/', "_");
+    let file = SingleAudioFile::from_base64(&url_safe, SingleAudioFileType::Auto).unwrap();
+    assert_eq!(file.bytes(), [0xfb, 0xff]);
+}
+
+#[wasm_bindgen_test]
+fn data_urls_take_their_type_from_the_mime_type() {
+    let wav = wav_bytes(WavEncoding::Pcm16, 2, 44100, &ramp(64));
+    let cases: [(&str, &[u8], SingleAudioFileType); 6] = [
+        (
+            "data:audio/mpeg;base64,",
+            SINE_MP3,
+            SingleAudioFileType::Mpeg,
+        ),
+        ("data:audio/x-wav;base64,", &wav, SingleAudioFileType::Wav),
+        ("DATA:Audio/MP4;Base64,", SINE_M4A, SingleAudioFileType::Aac),
+        (
+            "data:audio/ogg;codecs=opus;base64,",
+            OPUS_SILENCE,
+            SingleAudioFileType::Ogg,
+        ),
+        ("data:;base64,", SINE_FLAC_24, SingleAudioFileType::Auto),
+        (
+            "data:application/octet-stream;base64,",
+            SINE_FLAC_24,
+            SingleAudioFileType::Auto,
+        ),
+    ];
+    for (prefix, bytes, expected) in cases {
+        let url = format!("{}{}", prefix, base64(bytes, false));
+        let file = SingleAudioFile::from_data_url(&url).unwrap();
+        assert_eq!(file.bytes(), bytes, "{}", prefix);
+        assert_eq!(file.r#type, expected, "{}", prefix);
+    }
+
+    let url = format!("data:audio/mpeg;base64,{}", base64(SINE_MP3, true));
+    let file = SingleAudioFile::from_data_url(&url).unwrap();
+    let from_bytes = SingleAudioFile::new(SINE_MP3.to_vec(), SingleAudioFileType::Mpeg);
+    assert_eq!(
+        combine(vec![file], vec![100]),
+        combine(vec![from_bytes], vec![100])
+    );
+}
+
+#[wasm_bindgen_test]
+fn malformed_base64_is_rejected() {
+    let cases = [
+        ("TW!u", "byte 2"),
+        ("TWFuT", "byte 5"),
+        ("TQ=", "byte 3"),
+        ("TQ===", "byte 4"),
+        ("T===", "byte 1"),
+        ("TQ==TWFu", "byte 4"),
+    ];
+    for (data, position) in cases {
+        let error = SingleAudioFile::from_base64(data, SingleAudioFileType::Auto)
+            .err()
+            .unwrap();
+        assert_eq!(error.code(), "INVALID_BASE64", "{:?}", data);
+        assert!(
+            error.to_string().contains(position),
+            "{:?}: {}",
+            data,
+            error
+        );
+    }
+
+    // Offsets count from the start of the URL.
+    let error = SingleAudioFile::from_data_url("data:audio/mpeg;base64,TW!u")
+        .err()
+        .unwrap();
+    assert_eq!(error.code(), "INVALID_BASE64");
+    assert!(error.to_string().contains("byte 25"), "{}", error);
+
+    for url in [
+        "audio/mpeg;base64,TWFu",
+        "data:audio/mpeg;base64",
+        "data:audio/mpeg,Man",
+        "",
+    ] {
+        let error = SingleAudioFile::from_data_url(url).err().unwrap();
+        assert_eq!(error.code(), "INVALID_DATA_URL", "{:?}", url);
+    }
+}
+
+#[wasm_bindgen_test]
+async fn files_download_from_urls() {
+    // A data: URL is fetched like any other, with its MIME type as the
+    // response's Content-Type.
+    let url = format!("data:audio/mpeg;base64,{}", base64(SINE_MP3, true));
+    let file = SingleAudioFile::from_url(url.clone(), SingleAudioFileType::Auto, None)
+        .await
+        .unwrap();
+    assert_eq!(file.bytes(), SINE_MP3);
+    assert_eq!(file.r#type, SingleAudioFileType::Mpeg);
+    let file = SingleAudioFile::from_url(url.clone(), SingleAudioFileType::Wav, None)
+        .await
+        .unwrap();
+    assert_eq!(file.r#type, SingleAudioFileType::Wav);
+
+    let mut combiner = AudioCombiner::new(vec![SingleAudioFile::new(
+        SINE_FLAC_24.to_vec(),
+        SingleAudioFileType::Flac,
+    )])
+    .unwrap();
+    let index = combiner
+        .add_file_from_url(url, SingleAudioFileType::Auto, None)
+        .await
+        .unwrap();
+    assert_eq!(index, 1);
+    let expected = AudioCombiner::new(vec![
+        SingleAudioFile::new(SINE_FLAC_24.to_vec(), SingleAudioFileType::Flac),
+        SingleAudioFile::new(SINE_MP3.to_vec(), SingleAudioFileType::Mpeg),
+    ])
+    .unwrap();
+    assert_eq!(
+        combiner.combine(vec![50, 50]).unwrap().bytes(),
+        expected.combine(vec![50, 50]).unwrap().bytes()
+    );
+}
+
+#[wasm_bindgen_test]
+async fn failed_downloads_say_why() {
+    // Nothing listens on the discard port.
+    let url = "http://127.0.0.1:9/sine.mp3";
+    let error = SingleAudioFile::from_url(url.into(), SingleAudioFileType::Auto, None)
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(error.code(), "FETCH_FAILED");
+    let message = error.to_string();
+    assert!(
+        message.contains(url) && message.contains("CORS"),
+        "{}",
+        message
+    );
+
+    let controller = web_sys::AbortController::new().unwrap();
+    controller.abort();
+    let url = format!("data:audio/mpeg;base64,{}", base64(SINE_MP3, true));
+    let error =
+        SingleAudioFile::from_url(url, SingleAudioFileType::Auto, Some(controller.signal()))
+            .await
+            .err()
+            .unwrap();
+    assert_eq!(error, CombineError::Cancelled);
+
+    // Stand in for a server that answers 404.
+    let global = js_sys::global();
+    let fetch = js_sys::Reflect::get(&global, &"fetch".into()).unwrap();
+    let not_found = js_sys::Function::new_with_args(
+        "url",
+        "return Promise.resolve(new Response('gone', { status: 404, statusText: 'Not Found' }))",
+    );
+    js_sys::Reflect::set(&global, &"fetch".into(), &not_found).unwrap();
+    let result = SingleAudioFile::from_url(
+        "https://example.com/sine.mp3".into(),
+        SingleAudioFileType::Auto,
+        None,
+    )
+    .await;
+    js_sys::Reflect::set(&global, &"fetch".into(), &fetch).unwrap();
+    let error = result.err().unwrap();
+    assert_eq!(error.code(), "HTTP_STATUS");
+    assert_eq!(
+        error.to_string(),
+        "fetching https://example.com/sine.mp3 gave HTTP 404 Not Found"
+    );
+    let js = JsValue::from(error);
+    let status = js_sys::Reflect::get(&js, &"status".into()).unwrap();
+    assert_eq!(status.as_f64(), Some(404.0));
+}
+
+/// A stream of `bytes` in `chunk`-byte pieces, ending with the JS statement
+/// `end`, which closes or errors `controller`.
+fn chunked_stream(bytes: &[u8], chunk: usize, end: &str) -> web_sys::ReadableStream {
+    let body = format!(
+        "let offset = 0;
+        return new ReadableStream({{
+            pull(controller) {{
+                if (offset >= bytes.length) {{ {}; return; }}
+                controller.enqueue(bytes.slice(offset, offset + chunk));
+                offset += chunk;
+            }},
+        }});",
+        end
+    );
+    js_sys::Function::new_with_args("bytes, chunk", &body)
+        .call2(
+            &JsValue::NULL,
+            &js_sys::Uint8Array::from(bytes),
+            &JsValue::from(chunk as u32),
+        )
+        .unwrap()
+        .unchecked_into()
+}
+
+#[wasm_bindgen_test]
+async fn files_read_from_streams() {
+    for size in [None, Some(SINE_FLAC_24.len() as f64), Some(1.0)] {
+        let stream = chunked_stream(SINE_FLAC_24, 1000, "controller.close()");
+        let file = SingleAudioFile::from_stream(stream, SingleAudioFileType::Flac, size, None)
+            .await
+            .unwrap();
+        assert_eq!(file.bytes(), SINE_FLAC_24, "{:?}", size);
+        assert_eq!(file.r#type, SingleAudioFileType::Flac);
+    }
+
+    let mut combiner = AudioCombiner::new(vec![SingleAudioFile::new(
+        SINE_MP3.to_vec(),
+        SingleAudioFileType::Mpeg,
+    )])
+    .unwrap();
+    let stream = chunked_stream(SINE_FLAC_24, 4096, "controller.close()");
+    let index = combiner
+        .add_file_from_stream(stream, SingleAudioFileType::Auto, None, None)
+        .await
+        .unwrap();
+    assert_eq!(index, 1);
+    let expected = AudioCombiner::new(vec![
+        SingleAudioFile::new(SINE_MP3.to_vec(), SingleAudioFileType::Mpeg),
+        SingleAudioFile::new(SINE_FLAC_24.to_vec(), SingleAudioFileType::Flac),
+    ])
+    .unwrap();
+    assert_eq!(
+        combiner.combine(vec![50, 50]).unwrap().bytes(),
+        expected.combine(vec![50, 50]).unwrap().bytes()
+    );
+}
+
+#[wasm_bindgen_test]
+async fn failed_streams_add_nothing() {
+    let mut combiner = AudioCombiner::new(vec![SingleAudioFile::new(
+        SINE_MP3.to_vec(),
+        SingleAudioFileType::Mpeg,
+    )])
+    .unwrap();
+
+    let stream = chunked_stream(
+        SINE_FLAC_24,
+        1000,
+        "controller.error(new Error('disk went away'))",
+    );
+    let error = combiner
+        .add_file_from_stream(stream, SingleAudioFileType::Flac, None, None)
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(error.code(), "READ_FAILED");
+    assert_eq!(
+        error.to_string(),
+        "reading the stream failed: disk went away"
+    );
+
+    let stream = chunked_stream(SINE_FLAC_24, 1000, "controller.enqueue('text')");
+    let error = combiner
+        .add_file_from_stream(stream, SingleAudioFileType::Flac, None, None)
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(error.code(), "READ_FAILED");
+
+    let controller = web_sys::AbortController::new().unwrap();
+    controller.abort();
+    let stream = chunked_stream(SINE_FLAC_24, 1000, "controller.close()");
+    let error = combiner
+        .add_file_from_stream(
+            stream,
+            SingleAudioFileType::Flac,
+            None,
+            Some(controller.signal()),
+        )
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(error, CombineError::Cancelled);
+
+    assert_eq!(combiner.len(), 1);
+}
+
+#[wasm_bindgen_test]
+fn combine_files_mixes_in_one_call() {
+    let buffers = || {
+        [SINE_MP3, SINE_FLAC_24, SINE_OGG]
+            .iter()
+            .map(|&bytes| js_sys::Uint8Array::from(bytes))
+            .collect::<Vec<_>>()
+    };
+    let types = vec![
+        SingleAudioFileType::Mpeg,
+        SingleAudioFileType::Auto,
+        SingleAudioFileType::Ogg,
+    ];
+    let expected = AudioCombiner::new(vec![
+        SingleAudioFile::new(SINE_MP3.to_vec(), SingleAudioFileType::Mpeg),
+        SingleAudioFile::new(SINE_FLAC_24.to_vec(), SingleAudioFileType::Auto),
+        SingleAudioFile::new(SINE_OGG.to_vec(), SingleAudioFileType::Ogg),
+    ])
+    .unwrap()
+    .combine(vec![80, 50])
+    .unwrap()
+    .bytes();
+    assert_eq!(
+        combine_files(buffers(), types.clone(), vec![80, 50]).unwrap(),
+        expected
+    );
+
+    let error = combine_files(buffers(), types[..2].to_vec(), vec![])
+        .err()
+        .unwrap();
+    assert_eq!(error.code(), "TYPE_COUNT_MISMATCH");
+    assert_eq!(error.to_string(), "got 3 buffers but 2 types");
+    let error = combine_files(vec![], vec![], vec![]).err().unwrap();
+    assert_eq!(error, CombineError::EmptyInput);
+}
+
+#[wasm_bindgen_test]
+fn auto_type_decodes_like_an_explicit_one() {
+    let auto = combine(
+        vec![SingleAudioFile::new(
+            SINE_FLAC_24.to_vec(),
+            SingleAudioFileType::Auto,
+        )],
+        vec![100],
+    );
+    let explicit = combine(
+        vec![SingleAudioFile::new(
+            SINE_FLAC_24.to_vec(),
+            SingleAudioFileType::Flac,
+        )],
+        vec![100],
+    );
+    assert_eq!(auto, explicit);
+
+    let error = AudioCombiner::new(vec![
+        SingleAudioFile::new(SINE_MP3.to_vec(), SingleAudioFileType::Auto),
+        SingleAudioFile::new(vec![0; 64], SingleAudioFileType::Auto),
+    ])
+    .err()
+    .unwrap();
+    assert_eq!(error.code(), "UNSUPPORTED_FORMAT");
+    assert!(
+        error
+            .to_string()
+            .starts_with("could not detect format for file 1"),
+        "{}",
+        error
+    );
+}
+
+#[wasm_bindgen_test]
+fn pcm_input_mixes_like_decoded_audio() {
+    let stereo = ramp(2048);
+    let mono: Vec<f32> = stereo.iter().step_by(2).copied().collect();
+    let as_wav = || {
+        let upmixed: Vec<f32> = mono.iter().flat_map(|&s| [s, s]).collect();
+        SingleAudioFile::new(
+            wav_bytes(WavEncoding::Float32, 2, 44100, &upmixed),
+            SingleAudioFileType::Wav,
+        )
+    };
+
+    let decoded = combine(vec![as_wav(), as_wav()], vec![30, 60]);
+    let pcm = combine(
+        vec![
+            SingleAudioFile::from_pcm_f32(mono.clone(), 44100, 1).unwrap(),
+            as_wav(),
+        ],
+        vec![30, 60],
+    );
+    assert_eq!(pcm, decoded);
+
+    let stereo_pcm = combine(
+        vec![SingleAudioFile::from_pcm_f32(stereo.clone(), 44100, 2).unwrap()],
+        vec![100],
+    );
+    let stereo_wav = combine(
+        vec![SingleAudioFile::new(
+            wav_bytes(WavEncoding::Float32, 2, 44100, &stereo),
+            SingleAudioFileType::Wav,
+        )],
+        vec![100],
+    );
+    assert_eq!(stereo_pcm, stereo_wav);
+}
+
+#[wasm_bindgen_test]
+fn pcm_input_rejects_bad_specs() {
+    assert!(SingleAudioFile::from_pcm_f32(vec![0.0; 6], 44100, 3).is_err());
+    assert!(SingleAudioFile::from_pcm_f32(vec![0.0; 3], 44100, 2).is_err());
+    assert!(AudioCombiner::new(vec![SingleAudioFile::new(
+        vec![0; 8],
+        SingleAudioFileType::Pcm
+    )])
+    .is_err());
+}
+
+#[wasm_bindgen_test]
+fn aiff_input_decodes_8_and_24_bit() {
+    let samples = ramp(2048);
+    let mp3 = || SingleAudioFile::new(SINE_MP3.to_vec(), SingleAudioFileType::Mpeg);
+
+    // 24-bit AIFF and WAV hold identical samples, so the mixes must match.
+    let aiff = SingleAudioFile::new(
+        aiff_bytes(24, 2, 44100, &samples),
+        SingleAudioFileType::Aiff,
+    );
+    let wav = SingleAudioFile::new(
+        wav_bytes(WavEncoding::Pcm24, 2, 44100, &samples),
+        SingleAudioFileType::Wav,
+    );
+    assert_eq!(
+        combine(vec![aiff, mp3()], vec![50, 50]),
+        combine(vec![wav, mp3()], vec![50, 50])
+    );
+
+    let expected: Vec<i16> = samples
+        .iter()
+        .map(|&s| (s * i16::MAX as f32) as i16)
+        .collect();
+    let eight_bit = combine(
+        vec![SingleAudioFile::new(
+            aiff_bytes(8, 2, 44100, &samples),
+            SingleAudioFileType::Aiff,
+        )],
+        vec![100],
+    );
+    // One 8-bit step spans 256 16-bit steps.
+    assert_close(&eight_bit, &expected, 512);
+    assert_eq!(
+        SingleAudioFile::from_bytes(aiff_bytes(8, 1, 44100, &samples))
+            .unwrap()
+            .r#type,
+        SingleAudioFileType::Aiff
+    );
+}
+
+#[wasm_bindgen_test]
+fn combined_output_round_trips_as_wav_input() {
+    let first = AudioCombiner::new(vec![
+        SingleAudioFile::new(SINE_MP3.to_vec(), SingleAudioFileType::Mpeg),
+        SingleAudioFile::new(SINE_FLAC_24.to_vec(), SingleAudioFileType::Flac),
+    ])
+    .unwrap()
+    .combine(vec![50, 50])
+    .unwrap();
+    assert_eq!(first.r#type, SingleAudioFileType::Wav);
+
+    let expected = output_samples(&first.bytes());
+    let second = combine(
+        vec![SingleAudioFile::new(first.bytes(), first.r#type)],
+        vec![100],
+    );
+    assert_close(&second, &expected, 1);
+}
+
+#[wasm_bindgen_test]
+fn float_output_keeps_overs_unclamped() {
+    let samples = ramp(2048);
+    let file = || {
+        SingleAudioFile::new(
+            wav_bytes(WavEncoding::Float32, 2, 44100, &samples),
+            SingleAudioFileType::Wav,
+        )
+    };
+    let mut options = CombineOptions::new();
+    options.bit_depth = BitDepth::Float32;
+    let output = AudioCombiner::new(vec![file(), file()])
+        .unwrap()
+        .combine_with_options(vec![100, 100], &options)
+        .unwrap();
+
+    let bytes = output.bytes();
+    let fmt = riff_chunk(&bytes, b"fmt ").unwrap();
+    assert_eq!(u16::from_le_bytes([fmt[0], fmt[1]]), 3);
+    assert_eq!(
+        u32::from_le_bytes([fmt[8], fmt[9], fmt[10], fmt[11]]),
+        44100 * 8
+    );
+    assert_eq!(u16::from_le_bytes([fmt[12], fmt[13]]), 8);
+    assert_eq!(u16::from_le_bytes([fmt[14], fmt[15]]), 32);
+    let riff_size = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+    assert_eq!(riff_size as usize, output.bytes().len() - 8);
+
+    let mixed: Vec<f32> = riff_chunk(&output.bytes(), b"data")
+        .unwrap()
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect();
+    let expected: Vec<f32> = samples.iter().map(|s| s * 2.0).collect();
+    assert_eq!(mixed, expected);
+    assert!(mixed.iter().any(|s| s.abs() > 1.0));
+
+    // The float output is valid input again.
+    let round_trip = combine(
+        vec![SingleAudioFile::new(output.bytes(), output.r#type)],
+        vec![50],
+    );
+    assert_eq!(round_trip.len(), samples.len());
+}
+
+#[wasm_bindgen_test]
+fn dithered_silence_is_a_seeded_one_lsb_floor() {
+    let combiner =
+        AudioCombiner::new(vec![SingleAudioFile::silence(1000.0, 44100).unwrap()]).unwrap();
+    let render = |options: &CombineOptions| {
+        combiner
+            .combine_with_options(vec![], options)
+            .unwrap()
+            .bytes()
+    };
+
+    let mut options = CombineOptions::new();
+    let dithered = render(&options);
+    assert_eq!(render(&options), dithered);
+    options.dither_seed = 7;
+    assert_ne!(render(&options), dithered);
+
+    // Triangular noise of up to 1 LSB rounds to -1 or 1 a quarter of the
+    // time, and to nothing louder.
+    let samples = output_samples(&dithered);
+    assert!(samples.iter().all(|s| (-1..=1).contains(s)));
+    let nonzero = samples.iter().filter(|&&s| s != 0).count() as f64 / samples.len() as f64;
+    assert!((nonzero - 0.25).abs() < 0.01, "{}", nonzero);
+    let mean = samples.iter().map(|&s| s as f64).sum::<f64>() / samples.len() as f64;
+    assert!(mean.abs() < 0.01, "{}", mean);
+
+    options.dither = false;
+    assert!(output_samples(&render(&options)).iter().all(|&s| s == 0));
+    options.dither = true;
+    options.bit_depth = BitDepth::Float32;
+    let float = render(&options);
+    assert!(riff_chunk(&float, b"data").unwrap().iter().all(|&b| b == 0));
+}
+
+/// The SHA-256 digest of `bytes`, from Web Crypto.
+async fn sha256(bytes: &[u8]) -> Vec<u8> {
+    let crypto: web_sys::Crypto = js_sys::Reflect::get(&js_sys::global(), &"crypto".into())
+        .unwrap()
+        .unchecked_into();
+    let digest = crypto
+        .subtle()
+        .digest_with_str_and_u8_array("SHA-256", bytes)
+        .unwrap();
+    let digest = wasm_bindgen_futures::JsFuture::from(digest).await.unwrap();
+    js_sys::Uint8Array::new(&digest).to_vec()
+}
+
+#[wasm_bindgen_test]
+async fn deterministic_output_hashes_the_same_every_run() {
+    let render = |options: &CombineOptions| {
+        AudioCombiner::new(vec![
+            SingleAudioFile::new(SINE_MP3.to_vec(), SingleAudioFileType::Mpeg),
+            SingleAudioFile::new(SINE_OGG_48000.to_vec(), SingleAudioFileType::Ogg),
+            SingleAudioFile::new(SINE_FLAC_24.to_vec(), SingleAudioFileType::Flac),
+        ])
+        .and_then(|combiner| combiner.combine_with_options(vec![70, 50, 30], options))
+        .map(|output| output.bytes())
+    };
+    let mut options = CombineOptions::new();
+    options.deterministic = true;
+    options.dither_seed = 42;
+    options.normalize = true;
+    options.limiter = true;
+    options.simulate_delay_ms = 60_000;
+    let first = sha256(&render(&options).unwrap()).await;
+    assert_eq!(sha256(&render(&options).unwrap()).await, first);
+    options.dither_seed = 43;
+    assert_ne!(sha256(&render(&options).unwrap()).await, first);
+
+    // A time budget makes the outcome depend on the machine.
+    options.max_ms = Some(60_000.0);
+    let error = render(&options).unwrap_err();
+    assert_eq!(error.code(), "INVALID_OPTION");
+    assert!(error.to_string().contains("deterministic"), "{}", error);
+}
+
+#[wasm_bindgen_test]
+fn int24_output_layout_and_precision() {
+    let to_options = |bit_depth| {
+        let mut options = CombineOptions::new();
+        options.bit_depth = bit_depth;
+        options
+    };
+    for frames in [1usize, 2, 3, 255, 1024, 4097] {
+        let samples = ramp(frames * 2);
+        let input = SingleAudioFile::new(
+            wav_bytes(WavEncoding::Float32, 2, 44100, &samples),
+            SingleAudioFileType::Wav,
+        );
+        let output = AudioCombiner::new(vec![input])
+            .unwrap()
+            .combine_with_options(vec![100], &to_options(BitDepth::Int24))
+            .unwrap();
+
+        let bytes = output.bytes();
+        let fmt = riff_chunk(&bytes, b"fmt ").unwrap();
+        assert_eq!(
+            u32::from_le_bytes([fmt[8], fmt[9], fmt[10], fmt[11]]),
+            44100 * 6
+        );
+        assert_eq!(u16::from_le_bytes([fmt[12], fmt[13]]), 6);
+        assert_eq!(u16::from_le_bytes([fmt[14], fmt[15]]), 24);
+        assert_eq!(
+            riff_chunk(&output.bytes(), b"data").unwrap().len(),
+            samples.len() * 3
+        );
+
+        let decoded = AudioCombiner::new(vec![SingleAudioFile::new(output.bytes(), output.r#type)])
+            .unwrap()
+            .combine_with_options(vec![100], &to_options(BitDepth::Float32))
+            .unwrap();
+        let decoded: Vec<f32> = riff_chunk(&decoded.bytes(), b"data")
+            .unwrap()
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        assert_eq!(decoded.len(), samples.len());
+        for (&d, &s) in decoded.iter().zip(&samples) {
+            assert!((d - s).abs() <= 2.0 / 8_388_608.0, "{} vs {}", d, s);
+        }
+    }
+}
+
+#[wasm_bindgen_test]
+fn ogg_output_is_gated_by_feature() {
+    let combiner = AudioCombiner::new(vec![SingleAudioFile::new(
+        SINE_FLAC_24.to_vec(),
+        SingleAudioFileType::Flac,
+    )])
+    .unwrap();
+    let mut options = CombineOptions::new();
+    options.format = OutputFormat::Ogg;
+
+    let result = combiner.combine_with_options(vec![100], &options);
+    if cfg!(feature = "ogg-output") {
+        let output = result.unwrap();
+        assert_eq!(output.r#type, SingleAudioFileType::Ogg);
+        let decoded = combine(
+            vec![SingleAudioFile::new(output.bytes(), output.r#type)],
+            vec![100],
+        );
+        assert_eq!(decoded.len(), 44100 * 2);
+    } else {
+        let error = result.err().unwrap();
+        assert_eq!(error, CombineError::OutputDisabled { format: "ogg" });
+        assert_eq!(error.to_string(), "ogg output not enabled");
+    }
+
+    options.quality = 1.5;
+    assert!(combiner.combine_with_options(vec![100], &options).is_err());
+}
+
+#[wasm_bindgen_test]
+fn mp3_output_is_gated_by_feature() {
+    let combiner = AudioCombiner::new(vec![SingleAudioFile::new(
+        SINE_FLAC_24.to_vec(),
+        SingleAudioFileType::Flac,
+    )])
+    .unwrap();
+    let mut options = CombineOptions::new();
+    options.format = OutputFormat::Mpeg;
+
+    for &bitrate in &[
+        Mp3Bitrate::Kbps128,
+        Mp3Bitrate::Kbps192,
+        Mp3Bitrate::Kbps320,
+    ] {
+        options.mp3_bitrate = bitrate;
+        let result = combiner.combine_with_options(vec![100], &options);
+        if cfg!(feature = "mp3-output") {
+            let output = result.unwrap();
+            assert_eq!(output.r#type, SingleAudioFileType::Mpeg);
+            let decoded = combine(
+                vec![SingleAudioFile::new(output.bytes(), output.r#type)],
+                vec![100],
+            );
+            // Encoder delay and frame padding are not trimmed.
+            assert!(decoded.len() >= 44100 * 2);
+            assert!(decoded.len() <= (44100 + 3 * 1152) * 2);
+        } else {
+            let error = result.err().unwrap();
+            assert_eq!(error, CombineError::OutputDisabled { format: "mp3" });
+            assert_eq!(error.to_string(), "mp3 output not enabled");
+        }
+    }
+}
+
+#[wasm_bindgen_test]
+fn raw_output_is_the_unencoded_mix() {
+    let a = stereo_sine(440.0, 44100, 4410, 0.4);
+    let b = ramp(2 * 2205);
+    let combiner = AudioCombiner::new(vec![
+        SingleAudioFile::new(
+            wav_bytes(WavEncoding::Float32, 2, 44100, &a),
+            SingleAudioFileType::Wav,
+        ),
+        SingleAudioFile::new(
+            wav_bytes(WavEncoding::Float32, 2, 44100, &b),
+            SingleAudioFileType::Wav,
+        ),
+    ])
+    .unwrap();
+
+    let raw = combiner.combine_raw(vec![50, 100]);
+    assert_eq!(raw.sample_rate(), 44100);
+    assert_eq!(raw.channels(), 2);
+
+    let samples = raw.samples().to_vec();
+    assert_eq!(samples.len(), a.len());
+    for (i, &s) in samples.iter().enumerate() {
+        let expected = a[i] * 0.5 + b.get(i).copied().unwrap_or(0.0);
+        assert!((s - expected).abs() < 1e-6, "{}: {} vs {}", i, s, expected);
+    }
+
+    // Each call hands out a fresh copy rather than a view into wasm memory.
+    let first = raw.samples();
+    drop(raw);
+    assert_eq!(first.to_vec(), samples);
+}
+
+#[wasm_bindgen_test]
+fn planar_output_splits_the_raw_mix() {
+    let left = ramp(4410);
+    let samples: Vec<f32> = left.iter().flat_map(|&l| [l, -l * 0.5]).collect();
+    let combiner = AudioCombiner::new(vec![
+        SingleAudioFile::new(
+            wav_bytes(WavEncoding::Float32, 2, 44100, &samples),
+            SingleAudioFileType::Wav,
+        ),
+        SingleAudioFile::new(
+            wav_bytes(WavEncoding::Float32, 1, 44100, &ramp(2205)),
+            SingleAudioFileType::Wav,
+        ),
+    ])
+    .unwrap();
+
+    let raw = combiner.combine_raw(vec![100, 30]).samples().to_vec();
+    let planar = combiner.combine_planar(vec![100, 30]);
+    assert_eq!(planar.sample_rate(), 44100);
+    assert_eq!(planar.channels(), 2);
+
+    let (l, r) = (planar.left().to_vec(), planar.right().to_vec());
+    assert_eq!(l.len(), 4410);
+    assert_eq!(r.len(), 4410);
+    for (i, frame) in raw.chunks_exact(2).enumerate() {
+        assert_eq!(l[i], frame[0]);
+        assert_eq!(r[i], frame[1]);
+    }
+}
+
+#[wasm_bindgen_test]
+fn mixed_sample_rates_are_resampled_to_the_output_rate() {
+    let wav = |rate: u32| {
+        SingleAudioFile::new(
+            wav_bytes(
+                WavEncoding::Float32,
+                2,
+                rate,
+                &stereo_sine(440.0, rate, rate as usize, 0.5),
+            ),
+            SingleAudioFileType::Wav,
+        )
+    };
+    let at_48k = combine(vec![wav(48000)], vec![100]);
+    let at_44k = combine(vec![wav(44100)], vec![100]);
+
+    // Both are one second long, well within a millisecond of each other.
+    assert!((at_48k.len() as i32 - at_44k.len() as i32).abs() <= 2 * 44);
+    assert_eq!(
+        combine(vec![wav(48000), wav(44100)], vec![50, 50]).len(),
+        at_44k.len()
+    );
+
+    // Away from the edges, where the filter runs out of input, the resampled
+    // sine matches the one generated at 44.1 kHz.
+    let edge = 2 * 64;
+    let len = at_44k.len();
+    assert_close(&at_48k[edge..len - edge], &at_44k[edge..len - edge], 1);
+}
+
+#[wasm_bindgen_test]
+fn output_sample_rate_is_configurable() {
+    let mut options = CombineOptions::new();
+    options.sample_rate = 48000;
+    let render = |file: SingleAudioFile| {
+        let combiner = AudioCombiner::new(vec![file]).unwrap();
+        combiner
+            .combine_with_options(vec![100], &options)
+            .unwrap()
+            .bytes()
+    };
+
+    let flac = render(SingleAudioFile::new(
+        SINE_FLAC_24.to_vec(),
+        SingleAudioFileType::Flac,
+    ));
+    let fmt = riff_chunk(&flac, b"fmt ").unwrap();
+    assert_eq!(u32::from_le_bytes([fmt[4], fmt[5], fmt[6], fmt[7]]), 48000);
+    assert_eq!(
+        u32::from_le_bytes([fmt[8], fmt[9], fmt[10], fmt[11]]),
+        48000 * 4
+    );
+    assert_eq!(output_samples(&flac).len(), 48000 * 2);
+
+    // The Ogg fixture is already at 48 kHz, so it only has to line up with the
+    // resampled FLAC. Vorbis output is not end-trimmed, hence the allowance.
+    let ogg = render(SingleAudioFile::new(
+        SINE_OGG_48000.to_vec(),
+        SingleAudioFileType::Ogg,
+    ));
+    let ogg = output_samples(&ogg);
+    assert!(ogg.len() >= 48000 * 2 && ogg.len() <= (48000 + 4096) * 2);
+    let flac = output_samples(&flac);
+    let error: f64 = flac[256..96000 - 256]
+        .iter()
+        .zip(&ogg[256..])
+        .map(|(&a, &b)| ((a as f64 - b as f64) / 32768.0).powi(2))
+        .sum::<f64>()
+        / (96000.0 - 512.0);
+    assert!(error < 1e-4, "mean squared error {}", error);
+
+    options.sample_rate = 0;
+    assert!(AudioCombiner::new(vec![])
+        .unwrap()
+        .combine_with_options(vec![], &options)
+        .is_err());
+}
+
+#[wasm_bindgen_test]
+fn mono_input_at_22050_gets_a_matching_mono_header() {
+    let mono: Vec<f32> = stereo_sine(440.0, 22050, 22050, 0.5)
+        .into_iter()
+        .step_by(2)
+        .collect();
+    let file = || {
+        SingleAudioFile::new(
+            wav_bytes(WavEncoding::Pcm16, 1, 22050, &mono),
+            SingleAudioFileType::Wav,
+        )
+    };
+    let fmt_fields = |wav: &[u8]| {
+        let fmt = riff_chunk(wav, b"fmt ").unwrap();
+        let u16_at = |i: usize| u16::from_le_bytes([fmt[i], fmt[i + 1]]) as u32;
+        let u32_at = |i: usize| u32::from_le_bytes([fmt[i], fmt[i + 1], fmt[i + 2], fmt[i + 3]]);
+        // Channels, sample rate, byte rate, block align and bits per sample.
+        [u16_at(2), u32_at(4), u32_at(8), u16_at(12), u16_at(14)]
+    };
+
+    let mut options = CombineOptions::new();
+    options.sample_rate = 22050;
+    options.dither = false;
+    let wav = AudioCombiner::new(vec![file()])
+        .unwrap()
+        .combine_with_options(vec![], &options)
+        .unwrap()
+        .bytes();
+    assert_eq!(fmt_fields(&wav), [1, 22050, 22050 * 2, 2, 16]);
+    // One sample per source sample, rather than mono read as stereo.
+    assert_eq!(output_samples(&wav).len(), 22050);
+
+    // Panned, the two sides differ, so both are written.
+    let mut panned = MixTrack::new();
+    panned.pan = 0.5;
+    let wav = AudioCombiner::new(vec![file()])
+        .unwrap()
+        .combine_tracks(vec![panned], &options)
+        .unwrap()
+        .bytes();
+    assert_eq!(fmt_fields(&wav), [2, 22050, 22050 * 4, 4, 16]);
+    assert_eq!(output_samples(&wav).len(), 22050 * 2);
+
+    // At the default rate the same second is resampled, not played fast.
+    let wav = AudioCombiner::new(vec![file()])
+        .unwrap()
+        .combine(vec![])
+        .unwrap()
+        .bytes();
+    assert_eq!(fmt_fields(&wav), [1, 44100, 44100 * 2, 2, 16]);
+    let frames = output_samples(&wav).len() as i64;
+    assert!((frames - 44100).abs() <= 2, "{} frames", frames);
+}
+
+#[wasm_bindgen_test]
+fn mono_mp3_stays_aligned_with_stereo_ogg() {
+    let mono = || SingleAudioFile::new(SINE_MONO_MP3.to_vec(), SingleAudioFileType::Mpeg);
+    let stereo = || SingleAudioFile::new(SINE_MP3.to_vec(), SingleAudioFileType::Mpeg);
+    let ogg = || SingleAudioFile::new(SINE_OGG.to_vec(), SingleAudioFileType::Ogg);
+
+    // Mono on its own is written as one channel, one sample per source
+    // sample.
+    let mono_only = combine(vec![mono()], vec![100]);
+    assert_eq!(
+        mono_only.len() * 2,
+        combine(vec![stereo()], vec![100]).len()
+    );
+
+    let mixed = combine(vec![mono(), ogg()], vec![50, 50]);
+    let reference = combine(vec![stereo(), ogg()], vec![50, 50]);
+    // The two MP3s differ only by lossy coding noise; a track running at the
+    // wrong speed would be off by up to full scale.
+    assert_close(&mixed, &reference, 1024);
+}
+
+#[wasm_bindgen_test]
+fn multichannel_sources_use_the_itu_downmix() {
+    /// Amplitude of `freq` in one channel of an interleaved stereo mix.
+    fn tone_level(mix: &[i16], channel: usize, freq: f64) -> f64 {
+        let frames = mix.len() / 2;
+        let sum: f64 = (0..frames)
+            .map(|i| {
+                let phase = 2.0 * std::f64::consts::PI * freq * i as f64 / 44100.0;
+                mix[i * 2 + channel] as f64 / 32767.0 * phase.sin()
+            })
+            .sum();
+        2.0 * sum / frames as f64
+    }
+    fn assert_gains(mix: &[i16], tones: &[f64], gains: &[[f64; 2]]) {
+        for (&freq, gains) in tones.iter().zip(gains) {
+            for (channel, &gain) in gains.iter().enumerate() {
+                let level = tone_level(mix, channel, freq);
+                assert!(
+                    (level - 0.1 * gain).abs() < 1e-3,
+                    "{} Hz on channel {}: {}",
+                    freq,
+                    channel,
+                    level
+                );
+            }
+        }
+    }
+    let g = std::f64::consts::FRAC_1_SQRT_2;
+
+    // Each speaker carries its own tone; whole periods fit in one second.
+    let tones = [100.0, 200.0, 300.0, 400.0, 500.0, 600.0];
+    let render = |channel_mask: u32| {
+        let count = channel_mask.count_ones() as usize;
+        let samples: Vec<f32> = (0..44100)
+            .flat_map(|i| {
+                tones[..count].iter().map(move |&freq| {
+                    let phase = 2.0 * std::f64::consts::PI * freq * i as f64 / 44100.0;
+                    (0.1 * phase.sin()) as f32
+                })
+            })
+            .collect();
+        combine(
+            vec![SingleAudioFile::new(
+                extensible_wav_bytes(channel_mask, 44100, &samples),
+                SingleAudioFileType::Wav,
+            )],
+            vec![100],
+        )
+    };
+
+    // 5.1 (side): FL, FR, FC, LFE, SL, SR.
+    assert_gains(
+        &render(0x60f),
+        &tones,
+        &[
+            [1.0, 0.0],
+            [0.0, 1.0],
+            [g, g],
+            [0.0, 0.0],
+            [g, 0.0],
+            [0.0, g],
+        ],
+    );
+
+    // Quad (FL, FR, RL, RR) has no centre or LFE, so its last two channels
+    // must follow the mask rather than their 5.1 positions.
+    assert_gains(
+        &render(0x33),
+        &tones,
+        &[[1.0, 0.0], [0.0, 1.0], [g, 0.0], [0.0, g]],
+    );
+}
+
+#[wasm_bindgen_test]
+fn combine_does_not_stall_unless_asked_to() {
+    let combiner = AudioCombiner::new(vec![
+        SingleAudioFile::new(
+            wav_bytes(WavEncoding::Pcm16, 2, 44100, &ramp(2048)),
+            SingleAudioFileType::Wav,
+        ),
+        SingleAudioFile::new(
+            wav_bytes(WavEncoding::Pcm16, 1, 44100, &ramp(1024)),
+            SingleAudioFileType::Wav,
+        ),
+    ])
+    .unwrap();
+
+    let start = js_sys::Date::now();
+    combiner.combine(vec![100, 100]).unwrap();
+    assert!(js_sys::Date::now() - start < 250.0);
+
+    let mut options = CombineOptions::new();
+    options.simulate_delay_ms = 50;
+    let start = js_sys::Date::now();
+    combiner
+        .combine_with_options(vec![100, 100], &options)
+        .unwrap();
+    assert!(js_sys::Date::now() - start >= 50.0);
+
+    // Bad options are rejected before the wait, not after it.
+    options.simulate_delay_ms = 60_000;
+    options.sample_rate = 0;
+    let start = js_sys::Date::now();
+    let error = combiner
+        .combine_with_options(vec![100, 100], &options)
+        .err()
+        .unwrap();
+    assert_eq!(error.code(), "INVALID_OPTION");
+    assert!(js_sys::Date::now() - start < 250.0);
+}
+
+#[wasm_bindgen_test]
+fn progress_is_reported_per_file_and_throttled() {
+    use std::{cell::RefCell, rc::Rc};
+    use wasm_bindgen::closure::Closure;
+
+    let calls = Rc::new(RefCell::new(Vec::new()));
+    let sink = calls.clone();
+    let progress = Closure::wrap(Box::new(move |index: u32, fraction: f64| {
+        sink.borrow_mut().push((index, fraction));
+    }) as Box<dyn FnMut(u32, f64)>);
+
+    AudioCombiner::new_with_progress(
+        vec![
+            SingleAudioFile::new(SINE_FLAC_24.to_vec(), SingleAudioFileType::Flac),
+            SingleAudioFile::new(SINE_MP3.to_vec(), SingleAudioFileType::Mpeg),
+        ],
+        progress.as_ref().unchecked_ref(),
+    )
+    .unwrap();
+
+    let calls = calls.borrow();
+    for index in 0..2 {
+        let fractions: Vec<f64> = calls
+            .iter()
+            .filter(|(i, _)| *i == index)
+            .map(|&(_, f)| f)
+            .collect();
+        // One second of audio: a handful of updates, then the final 1.0.
+        assert!(fractions.len() > 2, "{:?}", fractions);
+        assert!(fractions.len() <= 31, "{:?}", fractions);
+        assert!(
+            fractions.windows(2).all(|w| w[0] <= w[1]),
+            "{:?}",
+            fractions
+        );
+        assert_eq!(*fractions.last().unwrap(), 1.0);
+    }
+    assert!(calls.windows(2).all(|w| w[0].0 <= w[1].0));
+
+    let throwing = js_sys::Function::new_no_args("throw new Error('cancelled')");
+    let error = AudioCombiner::new_with_progress(
+        vec![SingleAudioFile::new(
+            SINE_FLAC_24.to_vec(),
+            SingleAudioFileType::Flac,
+        )],
+        &throwing,
+    )
+    .err()
+    .unwrap();
+    assert_eq!(error.code(), "CALLBACK_FAILED");
+    assert_eq!(
+        error.to_string(),
+        "file 0: progress callback threw: cancelled"
+    );
+}
+
+#[wasm_bindgen_test]
+async fn async_decode_matches_sync_and_can_be_cancelled() {
+    let files = || {
+        vec![
+            SingleAudioFile::new(SINE_FLAC_24.to_vec(), SingleAudioFileType::Flac),
+            SingleAudioFile::new(SINE_MP3.to_vec(), SingleAudioFileType::Mpeg),
+            SingleAudioFile::from_pcm_f32(ramp(2048), 44100, 2).unwrap(),
+        ]
+    };
+    let combiner = AudioCombiner::new_async(files(), None).await.unwrap();
+    assert_eq!(
+        combiner.combine(vec![40, 40, 40]).unwrap().bytes(),
+        AudioCombiner::new(files())
+            .unwrap()
+            .combine(vec![40, 40, 40])
+            .unwrap()
+            .bytes()
+    );
+
+    let controller = web_sys::AbortController::new().unwrap();
+    controller.abort();
+    let error = AudioCombiner::new_async(files(), Some(controller.signal()))
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(error, CombineError::Cancelled);
+
+    // Abort from a timer while decoding is under way; there is enough input
+    // to span several time slices.
+    let controller = web_sys::AbortController::new().unwrap();
+    let signal = controller.signal();
+    let abort = wasm_bindgen::closure::Closure::once_into_js(move || controller.abort());
+    let set_timeout: js_sys::Function =
+        js_sys::Reflect::get(&js_sys::global(), &"setTimeout".into())
+            .unwrap()
+            .into();
+    set_timeout
+        .call2(&JsValue::NULL, &abort, &JsValue::from(0))
+        .unwrap();
+
+    let many: Vec<SingleAudioFile> = (0..50)
+        .map(|_| SingleAudioFile::new(SINE_FLAC_24.to_vec(), SingleAudioFileType::Flac))
+        .collect();
+    let error = AudioCombiner::new_async(many, Some(signal))
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(error, CombineError::Cancelled);
+}
+
+#[wasm_bindgen_test]
+fn errors_reach_js_with_a_code_and_index() {
+    let error = AudioCombiner::new(vec![
+        SingleAudioFile::new(SINE_MP3.to_vec(), SingleAudioFileType::Mpeg),
+        SingleAudioFile::new(SINE_HE_AAC_M4A.to_vec(), SingleAudioFileType::Aac),
+    ])
+    .err()
+    .unwrap();
+    let js = JsValue::from(error);
+    let get = |key: &str| js_sys::Reflect::get(&js, &key.into()).unwrap();
+    assert!(js.is_instance_of::<js_sys::Error>());
+    assert_eq!(get("name").as_string().unwrap(), "CombineError");
+    assert_eq!(get("code").as_string().unwrap(), "UNSUPPORTED_CODEC");
+    assert_eq!(get("index").as_f64(), Some(1.0));
+    assert_eq!(
+        get("message").as_string().unwrap(),
+        "file 1: codec not supported: HE-AAC"
+    );
+
+    let js = JsValue::from(SingleAudioFile::from_bytes(vec![0; 64]).err().unwrap());
+    let get = |key: &str| js_sys::Reflect::get(&js, &key.into()).unwrap();
+    assert_eq!(get("code").as_string().unwrap(), "UNKNOWN_FORMAT");
+    assert!(get("index").is_undefined());
+}
+
+#[wasm_bindgen_test]
+fn volumes_default_to_unity_unless_required() {
+    let files = || {
+        vec![
+            SingleAudioFile::from_pcm_f32(ramp(512), 44100, 2).unwrap(),
+            SingleAudioFile::from_pcm_f32(stereo_sine(440.0, 44100, 256, 0.3), 44100, 2).unwrap(),
+            SingleAudioFile::from_pcm_f32(ramp(128), 44100, 1).unwrap(),
+        ]
+    };
+    let unity = combine(files(), vec![100, 100, 100]);
+
+    assert_eq!(combine(files(), vec![]), unity);
+    assert_eq!(combine(files(), vec![100]), unity);
+    assert_eq!(combine(files(), vec![100, 100, 100, 0, 7]), unity);
+    assert_eq!(
+        combine(files(), vec![50, 100]),
+        combine(files(), vec![50, 100, 100])
+    );
+
+    let combiner = AudioCombiner::new(files()).unwrap();
+    let mut options = CombineOptions::new();
+    options.require_explicit_volumes = true;
+    options.dither = false;
+    for volumes in [vec![], vec![100, 100]] {
+        let error = combiner
+            .combine_with_options(volumes.clone(), &options)
+            .err()
+            .unwrap();
+        assert_eq!(
+            error,
+            CombineError::MissingVolume {
+                index: volumes.len()
+            }
+        );
+    }
+    let exact = combiner
+        .combine_with_options(vec![100, 100, 100], &options)
+        .unwrap();
+    assert_eq!(output_samples(&exact.bytes()), unity);
+    let long = combiner
+        .combine_with_options(vec![100, 100, 100, 3], &options)
+        .unwrap();
+    assert_eq!(output_samples(&long.bytes()), unity);
+}
+
+#[wasm_bindgen_test]
+fn linear_gains_match_volumes_and_go_beyond_them() {
+    let quiet: Vec<f32> = ramp(1024).iter().map(|s| s * 0.1).collect();
+    let combiner = AudioCombiner::new(vec![
+        SingleAudioFile::from_pcm_f32(quiet.clone(), 44100, 2).unwrap(),
+        SingleAudioFile::from_pcm_f32(ramp(512), 44100, 2).unwrap(),
+    ])
+    .unwrap();
+
+    let by_volume = combiner.combine(vec![100, 50]).unwrap();
+    let by_gain = combiner.combine_with_gains(vec![1.0, 0.5]).unwrap();
+    assert_eq!(by_gain.bytes(), by_volume.bytes());
+    assert_eq!(
+        combiner.combine_with_gains(vec![]).unwrap().bytes(),
+        combiner.combine(vec![]).unwrap().bytes()
+    );
+
+    // 4x is out of reach of a u8 percentage.
+    let mut options = CombineOptions::new();
+    options.bit_depth = BitDepth::Float32;
+    let loud = combiner
+        .combine_with_gains_and_options(vec![4.0, 0.0], &options)
+        .unwrap();
+    let bytes = loud.bytes();
+    let data = riff_chunk(&bytes, b"data").unwrap();
+    for (i, b) in data.chunks_exact(4).enumerate() {
+        let s = f32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+        assert!((s - quiet[i] * 4.0).abs() < 1e-6);
+    }
+
+    assert_eq!(db_to_linear(0.0), 1.0);
+    assert!((db_to_linear(20.0) - 10.0).abs() < 1e-5);
+    assert!((db_to_linear(-6.0) - 0.501_187).abs() < 1e-5);
+    assert_eq!(db_to_linear(f32::NEG_INFINITY), 0.0);
+
+    for gain in [-0.5, f32::NAN, f32::INFINITY] {
+        let error = combiner.combine_with_gains(vec![1.0, gain]).err().unwrap();
+        assert_eq!(error.code(), "INVALID_GAIN");
+        assert_eq!(error.index(), Some(1));
+    }
+}
+
+#[wasm_bindgen_test]
+fn tracks_pan_mono_and_balance_stereo() {
+    let tone = stereo_sine(440.0, 44100, 4410, 0.5);
+    let mono: Vec<f32> = tone.iter().step_by(2).copied().collect();
+    let combiner = AudioCombiner::new(vec![
+        SingleAudioFile::from_pcm_f32(mono.clone(), 44100, 1).unwrap(),
+        SingleAudioFile::from_pcm_f32(tone.clone(), 44100, 2).unwrap(),
+    ])
+    .unwrap();
+    let track = |gain: f32, pan: f32| {
+        let mut track = MixTrack::new();
+        track.gain = gain;
+        track.pan = pan;
+        track
+    };
+    let mut options = CombineOptions::new();
+    options.bit_depth = BitDepth::Float32;
+    let render = |tracks: Vec<MixTrack>| -> Vec<f32> {
+        let wav = combiner.combine_tracks(tracks, &options).unwrap().bytes();
+        riff_chunk(&wav, b"data")
+            .unwrap()
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()
+    };
+
+    // Centred tracks mix exactly like plain volumes.
+    assert_eq!(
+        combiner
+            .combine_tracks(
+                vec![track(0.5, 0.0), track(1.0, 0.0)],
+                &CombineOptions::new()
+            )
+            .unwrap()
+            .bytes(),
+        combiner.combine(vec![50, 100]).unwrap().bytes()
+    );
+
+    // Hard left mono leaves the right channel silent.
+    let left = render(vec![track(1.0, -1.0), track(0.0, 0.0)]);
+    for (frame, &s) in left.chunks_exact(2).zip(&mono) {
+        assert!((frame[0] - s * std::f32::consts::SQRT_2).abs() < 1e-6);
+        assert!(frame[1].abs() < 1e-6);
+    }
+
+    // The pan law keeps the mono source's power constant.
+    for &pan in &[-0.7, -0.2, 0.3, 0.9] {
+        let panned = render(vec![track(1.0, pan), track(0.0, 0.0)]);
+        for (frame, &s) in panned.chunks_exact(2).zip(&mono) {
+            let power = frame[0] * frame[0] + frame[1] * frame[1];
+            assert!((power - 2.0 * s * s).abs() < 1e-5, "pan {}", pan);
+        }
+    }
+
+    // Balancing stereo right only turns the left channel down.
+    let balanced = render(vec![track(0.0, 0.0), track(1.0, 0.5)]);
+    let attenuation = std::f32::consts::SQRT_2 * (3.0 * std::f32::consts::FRAC_PI_8).cos();
+    for (out, src) in balanced.chunks_exact(2).zip(tone.chunks_exact(2)) {
+        assert!((out[0] - src[0] * attenuation).abs() < 1e-6);
+        assert_eq!(out[1], src[1]);
+    }
+
+    for pan in [-1.5, 2.0, f32::NAN] {
+        let error = combiner
+            .combine_tracks(vec![track(1.0, 0.0), track(1.0, pan)], &options)
+            .err()
+            .unwrap();
+        assert_eq!(error.code(), "INVALID_TRACK");
+        assert_eq!(error.index(), Some(1));
+    }
+}
+
+#[wasm_bindgen_test]
+fn tracks_gain_left_and_right_apart() {
+    // A different tone on each side, so each output channel shows which
+    // gain reached it.
+    let tones: Vec<f32> = stereo_sine(440.0, 44100, 4410, 0.5)
+        .chunks_exact(2)
+        .zip(stereo_sine(1000.0, 44100, 4410, 0.5).chunks_exact(2))
+        .flat_map(|(left, right)| [left[0], right[1]])
+        .collect();
+    let mono = stereo_sine(220.0, 44100, 4410, 0.5)
+        .into_iter()
+        .step_by(2)
+        .collect::<Vec<_>>();
+    let combiner = AudioCombiner::new(vec![
+        SingleAudioFile::from_pcm_f32(tones.clone(), 44100, 2).unwrap(),
+        SingleAudioFile::from_pcm_f32(mono.clone(), 44100, 1).unwrap(),
+    ])
+    .unwrap();
+    let track = |gain_left: f32, gain_right: f32, pan: f32| {
+        let mut track = MixTrack::new();
+        track.gain = 0.1;
+        track.gain_left = Some(gain_left);
+        track.gain_right = Some(gain_right);
+        track.pan = pan;
+        track
+    };
+    let mut options = CombineOptions::new();
+    options.bit_depth = BitDepth::Float32;
+    let render = |tracks: Vec<MixTrack>| -> Vec<f32> {
+        let wav = combiner.combine_tracks(tracks, &options).unwrap().bytes();
+        riff_chunk(&wav, b"data")
+            .unwrap()
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()
+    };
+    let mut silent = MixTrack::new();
+    silent.gain = 0.0;
+    let rms = |samples: &[f32], channel: usize| {
+        let power: f32 = samples.iter().skip(channel).step_by(2).map(|s| s * s).sum();
+        (power * 2.0 / samples.len() as f32).sqrt()
+    };
+
+    // The right channel comes out 0.6 of the left, each with its own tone.
+    let out = render(vec![track(1.0, 0.6, 0.0), silent.clone()]);
+    for (out, src) in out.chunks_exact(2).zip(tones.chunks_exact(2)) {
+        assert_eq!(out[0], src[0]);
+        assert_eq!(out[1], src[1] * 0.6);
+    }
+    let ratio = rms(&out, 1) / rms(&out, 0);
+    assert!((ratio - 0.6).abs() < 1e-3, "{}", ratio);
+
+    // The balance multiplies in.
+    let out = render(vec![track(1.0, 0.6, 0.5), silent.clone()]);
+    let attenuation = std::f32::consts::SQRT_2 * (3.0 * std::f32::consts::FRAC_PI_8).cos();
+    for (out, src) in out.chunks_exact(2).zip(tones.chunks_exact(2)) {
+        assert!((out[0] - src[0] * attenuation).abs() < 1e-6);
+        assert_eq!(out[1], src[1] * 0.6);
+    }
+
+    // A mono file is spread to both sides before the gains and pan apply.
+    let out = render(vec![silent.clone(), track(0.5, 1.0, -0.5)]);
+    let [left, right] = [
+        std::f32::consts::SQRT_2 * std::f32::consts::FRAC_PI_8.cos(),
+        std::f32::consts::SQRT_2 * std::f32::consts::FRAC_PI_8.sin(),
+    ];
+    for (out, &s) in out.chunks_exact(2).zip(&mono) {
+        assert!((out[0] - s * 0.5 * left).abs() < 1e-6);
+        assert!((out[1] - s * right).abs() < 1e-6);
+    }
+
+    // Left unset, the track gain applies to that channel.
+    let mut right_only = track(1.0, 0.6, 0.0);
+    right_only.gain_left = None;
+    let out = render(vec![right_only, silent.clone()]);
+    for (out, src) in out.chunks_exact(2).zip(tones.chunks_exact(2)) {
+        assert_eq!(out[0], src[0] * 0.1);
+    }
+
+    for gain in [-0.5, f32::NAN, f32::INFINITY] {
+        let error = combiner
+            .combine_tracks(vec![silent.clone(), track(1.0, gain, 0.0)], &options)
+            .err()
+            .unwrap();
+        assert_eq!(error.code(), "INVALID_GAIN");
+        assert_eq!(error.index(), Some(1));
+    }
+}
+
+#[wasm_bindgen_test]
+fn gates_silence_hiss_between_speech() {
+    // Hiss around -54 dBFS throughout, under bursts of a voiced "speech"
+    // tone whose syllables swell and fade but stay above -30 dBFS.
+    let rate = 44100;
+    let ms = |ms: usize| ms * rate / 1000;
+    let speech = [ms(500)..ms(1000), ms(2000)..ms(2500)];
+    let mut seed = 1u32;
+    let samples: Vec<f32> = (0..ms(3000))
+        .flat_map(|n| {
+            let t = n as f32 / rate as f32;
+            let voice = match speech.iter().any(|range| range.contains(&n)) {
+                true => {
+                    let syllables = 0.2 + 0.8 * (std::f32::consts::PI * 3.0 * t).sin().abs();
+                    let tone = (std::f32::consts::TAU * 180.0 * t).sin()
+                        + 0.5 * (std::f32::consts::TAU * 360.0 * t).sin();
+                    0.3 * syllables * tone
+                }
+                false => 0.0,
+            };
+            let mut hiss = || {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (seed >> 8) as f32 / (1 << 24) as f32 * 0.004 - 0.002
+            };
+            [voice + hiss(), voice + hiss()]
+        })
+        .collect();
+    let combiner = AudioCombiner::new(vec![SingleAudioFile::from_pcm_f32(
+        samples.clone(),
+        rate as u32,
+        2,
+    )
+    .unwrap()])
+    .unwrap();
+    let mut options = CombineOptions::new();
+    options.bit_depth = BitDepth::Float32;
+    let render = |gate: NoiseGate| -> Vec<f32> {
+        let mut track = MixTrack::new();
+        track.gate = Some(gate);
+        let wav = combiner
+            .combine_tracks(vec![track], &options)
+            .unwrap()
+            .bytes();
+        riff_chunk(&wav, b"data")
+            .unwrap()
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()
+    };
+    let rms_db = |samples: &[f32], range: std::ops::Range<usize>| {
+        let samples = &samples[range.start * 2..range.end * 2];
+        let power = samples.iter().map(|&s| s as f64 * s as f64).sum::<f64>();
+        10.0 * (power / samples.len() as f64).log10()
+    };
+
+    let mut gate = NoiseGate::new();
+    gate.threshold_db = -40.0;
+    gate.release_ms = 30.0;
+    gate.floor_db = Some(-30.0);
+    let gated = render(gate);
+    // Before the first word, and once the hold and release after the first
+    // are over.
+    for hiss in [0..ms(500), ms(1500)..ms(2000)] {
+        let drop = rms_db(&gated, hiss.clone()) - rms_db(&samples, hiss.clone());
+        assert!(drop <= -29.9, "{:?}: {} dB", hiss, drop);
+    }
+    for speech in speech.clone() {
+        let change = rms_db(&gated, speech.clone()) - rms_db(&samples, speech.clone());
+        assert!(change.abs() < 0.5, "{:?}: {} dB", speech, change);
+    }
+
+    // With no floor the hiss goes, and a gate closed from the start lets
+    // nothing through before the first word.
+    gate.floor_db = None;
+    let silenced = render(gate);
+    assert!(silenced[..ms(500) * 2].iter().all(|&s| s == 0.0));
+    assert!(rms_db(&silenced, ms(1500)..ms(2000)) < -120.0);
+
+    // Without a hold the gate closes in the troughs between syllables.
+    gate.hold_ms = 0.0;
+    gate.threshold_db = -12.0;
+    let chattering = render(gate);
+    let change = rms_db(&chattering, speech[0].clone()) - rms_db(&samples, speech[0].clone());
+    assert!(change < -0.5, "{} dB", change);
+
+    for (field, value) in [
+        ("hold_ms", -1.0),
+        ("attack_ms", f32::NAN),
+        ("floor_db", 6.0),
+    ] {
+        let mut gate = NoiseGate::new();
+        match field {
+            "hold_ms" => gate.hold_ms = value,
+            "attack_ms" => gate.attack_ms = value,
+            _ => gate.floor_db = Some(value),
+        }
+        let mut track = MixTrack::new();
+        track.gate = Some(gate);
+        let error = combiner
+            .combine_tracks(vec![track], &options)
+            .err()
+            .unwrap();
+        assert_eq!(error.code(), "INVALID_TRACK");
+        assert!(error.to_string().contains(field), "{}", error);
+    }
+}
+
+#[wasm_bindgen_test]
+fn offsets_delay_tracks_with_leading_silence() {
+    let intro = stereo_sine(440.0, 44100, 4410, 0.5);
+    let voice = stereo_sine(220.0, 44100, 441, 0.5);
+    let combiner = AudioCombiner::new(vec![
+        SingleAudioFile::from_pcm_f32(intro.clone(), 44100, 2).unwrap(),
+        SingleAudioFile::from_pcm_f32(voice.clone(), 44100, 2).unwrap(),
+    ])
+    .unwrap();
+    let track = |offset_frames: u32, offset_ms: f64| {
+        let mut track = MixTrack::new();
+        track.offset_frames = offset_frames;
+        track.offset_ms = offset_ms;
+        track
+    };
+    let mut options = CombineOptions::new();
+    options.bit_depth = BitDepth::Float32;
+    let render = |tracks: Vec<MixTrack>| -> Vec<f32> {
+        let wav = combiner.combine_tracks(tracks, &options).unwrap().bytes();
+        riff_chunk(&wav, b"data")
+            .unwrap()
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()
+    };
+
+    // A sample-accurate offset inside the intro lands exactly there.
+    let mixed = render(vec![track(0, 0.0), track(1001, 0.0)]);
+    assert_eq!(mixed.len(), intro.len());
+    assert_eq!(mixed[..2002], intro[..2002]);
+    for (i, &s) in voice.iter().enumerate() {
+        assert_eq!(mixed[2002 + i], intro[2002 + i] + s);
+    }
+
+    // Milliseconds and frames add up, and starting past the end pads with
+    // silence instead of panicking.
+    let mixed = render(vec![track(0, 0.0), track(10, 200.0)]);
+    let start = (8820 + 10) * 2;
+    assert_eq!(mixed.len(), start + voice.len());
+    assert_eq!(mixed[..intro.len()], intro[..]);
+    assert!(mixed[intro.len()..start].iter().all(|&s| s == 0.0));
+    assert_eq!(mixed[start..], voice[..]);
+
+    for offset_ms in [-1.0, f64::NAN] {
+        let error = combiner
+            .combine_tracks(vec![track(0, offset_ms)], &options)
+            .err()
+            .unwrap();
+        assert_eq!(error.code(), "INVALID_TRACK");
+        assert_eq!(error.index(), Some(0));
+    }
+}
+
+#[wasm_bindgen_test]
+fn trims_cut_each_file_to_its_window() {
+    let tone = stereo_sine(440.0, 44100, 44100, 0.5);
+    let tone_48k = stereo_sine(440.0, 48000, 48000, 0.5);
+    let combiner = AudioCombiner::new(vec![
+        SingleAudioFile::from_pcm_f32(tone.clone(), 44100, 2).unwrap(),
+        SingleAudioFile::from_pcm_f32(tone_48k, 48000, 2).unwrap(),
+    ])
+    .unwrap();
+    let track = |gain: f32, trim_start_ms: f64, trim_end_ms: Option<f64>| {
+        let mut track = MixTrack::new();
+        track.gain = gain;
+        track.trim_start_ms = trim_start_ms;
+        track.trim_end_ms = trim_end_ms;
+        track
+    };
+    let mut options = CombineOptions::new();
+    options.bit_depth = BitDepth::Float32;
+    let render = |tracks: Vec<MixTrack>| -> Vec<f32> {
+        let wav = combiner.combine_tracks(tracks, &options).unwrap().bytes();
+        riff_chunk(&wav, b"data")
+            .unwrap()
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()
+    };
+
+    // Same-rate trims are exact slices of the source.
+    let mixed = render(vec![
+        track(1.0, 100.0, Some(450.0)),
+        track(0.0, 0.0, Some(0.0)),
+    ]);
+    assert_eq!(mixed[..], tone[4410 * 2..19845 * 2]);
+
+    // A resampled file lands within a frame of its window, and an end past
+    // the file is clamped to it.
+    let mixed = render(vec![
+        track(0.0, 0.0, Some(0.0)),
+        track(1.0, 250.0, Some(5000.0)),
+    ]);
+    let frames = mixed.len() as i64 / 2;
+    assert!((frames - 33075).abs() <= 1, "{} frames", frames);
+
+    let error = combiner
+        .combine_tracks(
+            vec![track(1.0, 0.0, None), track(1.0, 300.0, Some(200.0))],
+            &options,
+        )
+        .err()
+        .unwrap();
+    assert_eq!(error.code(), "INVALID_TRACK");
+    assert_eq!(error.index(), Some(1));
+}
+
+#[wasm_bindgen_test]
+fn fades_ramp_tracks_in_and_out() {
+    let combiner = AudioCombiner::new(vec![SingleAudioFile::from_pcm_f32(
+        vec![0.5; 44100 * 2],
+        44100,
+        2,
+    )
+    .unwrap()])
+    .unwrap();
+    let mut options = CombineOptions::new();
+    options.bit_depth = BitDepth::Float32;
+    let render = |fade_in_ms: f64, fade_out_ms: f64, fade_curve: FadeCurve| -> Vec<f32> {
+        let mut track = MixTrack::new();
+        track.fade_in_ms = fade_in_ms;
+        track.fade_out_ms = fade_out_ms;
+        track.fade_curve = fade_curve;
+        let wav = combiner
+            .combine_tracks(vec![track], &options)
+            .unwrap()
+            .bytes();
+        riff_chunk(&wav, b"data")
+            .unwrap()
+            .chunks_exact(8)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) / 0.5)
+            .collect()
+    };
+
+    // 20 ms is 882 frames, so frame 441 is halfway through either fade.
+    let halfway = [
+        (FadeCurve::Linear, 0.5),
+        (FadeCurve::Exponential, (1000f32.sqrt() - 1.0) / 999.0),
+        (FadeCurve::EqualPower, std::f32::consts::FRAC_1_SQRT_2),
+    ];
+    for &(curve, expected) in &halfway {
+        let gains = render(20.0, 20.0, curve);
+        assert_eq!(gains.len(), 44100);
+        assert!(gains[0].abs() < 1e-6);
+        assert!((gains[441] - expected).abs() < 1e-5);
+        assert_eq!(gains[882], 1.0);
+        assert_eq!(gains[44100 - 883], 1.0);
+        assert!((gains[44100 - 1 - 441] - expected).abs() < 1e-5);
+        assert!(gains[44099].abs() < 1e-6);
+    }
+
+    // Zero-length fades change nothing; over-long ones span the whole file.
+    assert!(render(0.0, 0.0, FadeCurve::Linear)
+        .iter()
+        .all(|&g| g == 1.0));
+    let gains = render(10_000.0, 0.0, FadeCurve::Linear);
+    assert!(gains.windows(2).all(|w| w[0] < w[1]));
+    assert!((gains[44099] - 44099.0 / 44100.0).abs() < 1e-6);
+}
+
+#[wasm_bindgen_test]
+fn concat_plays_files_back_to_back() {
+    let first = stereo_sine(440.0, 44100, 2 * 44100, 0.5);
+    let second = stereo_sine(440.0, 48000, 3 * 48000, 0.5);
+    let combiner = AudioCombiner::new(vec![
+        SingleAudioFile::from_pcm_f32(first.clone(), 44100, 2).unwrap(),
+        SingleAudioFile::from_pcm_f32(second, 48000, 2).unwrap(),
+    ])
+    .unwrap();
+    let mut options = CombineOptions::new();
+    options.mode = CombineMode::Concat;
+    options.bit_depth = BitDepth::Float32;
+    let wav = combiner
+        .combine_with_gains_and_options(vec![0.5, 1.0], &options)
+        .unwrap()
+        .bytes();
+    let mixed: Vec<f32> = riff_chunk(&wav, b"data")
+        .unwrap()
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect();
+
+    // The 48 kHz file is resampled, so the total is 5 s at the output rate.
+    let frames = mixed.len() as i64 / 2;
+    assert!((frames - 5 * 44100).abs() <= 2, "{} frames", frames);
+    for (out, src) in mixed.iter().zip(&first) {
+        assert_eq!(*out, src * 0.5);
+    }
+}
+
+#[wasm_bindgen_test]
+fn crossfades_overlap_concatenated_files() {
+    let second = 44100 / 2;
+    let combiner = AudioCombiner::new(vec![
+        SingleAudioFile::from_pcm_f32(vec![0.5; 44100 * 2], 44100, 2).unwrap(),
+        SingleAudioFile::from_pcm_f32(vec![0.5; second * 2], 44100, 2).unwrap(),
+        SingleAudioFile::from_pcm_f32(vec![0.5; 44100 * 2], 44100, 2).unwrap(),
+    ])
+    .unwrap();
+    let render = |crossfade_ms: f64| -> Vec<f32> {
+        let mut options = CombineOptions::new();
+        options.mode = CombineMode::Concat;
+        options.crossfade_ms = crossfade_ms;
+        options.bit_depth = BitDepth::Float32;
+        let wav = combiner.combine_tracks(vec![], &options).unwrap().bytes();
+        riff_chunk(&wav, b"data")
+            .unwrap()
+            .chunks_exact(8)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()
+    };
+
+    // Each boundary overlaps by 100 ms, and DC stays at constant power.
+    let mixed = render(100.0);
+    assert_eq!(mixed.len(), 44100 + second + 44100 - 2 * 4410);
+    let midpoint = 44100 - 4410 / 2;
+    assert_eq!(mixed[44100 - 4411], 0.5);
+    assert!((mixed[midpoint] - 0.5 * std::f32::consts::SQRT_2).abs() < 1e-4);
+
+    // Overlong crossfades clamp to the shorter neighbour.
+    assert_eq!(render(5000.0).len(), 44100 + second + 44100 - 2 * second);
+}
+
+#[wasm_bindgen_test]
+fn loops_tile_a_track_to_its_length() {
+    let bed = ramp(1000 * 2);
+    let combiner = AudioCombiner::new(vec![
+        SingleAudioFile::from_pcm_f32(bed.clone(), 44100, 2).unwrap()
+    ])
+    .unwrap();
+    let mut options = CombineOptions::new();
+    options.bit_depth = BitDepth::Float32;
+    let render = |track: MixTrack| -> Vec<f32> {
+        let wav = combiner
+            .combine_tracks(vec![track], &options)
+            .unwrap()
+            .bytes();
+        riff_chunk(&wav, b"data")
+            .unwrap()
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()
+    };
+
+    let mut track = MixTrack::new();
+    track.loop_count = 3;
+    let looped = render(track.clone());
+    assert_eq!(looped.len(), 3 * bed.len());
+    assert!(looped.chunks_exact(bed.len()).all(|tile| tile == &bed[..]));
+
+    // 50 ms is 2205 frames: two full plays and a 205-frame partial one,
+    // delayed and scaled like any other track.
+    track.loop_to_ms = Some(50.0);
+    track.offset_frames = 100;
+    track.gain = 0.5;
+    let looped = render(track.clone());
+    assert_eq!(looped.len(), (100 + 2205) * 2);
+    assert!(looped[..200].iter().all(|&s| s == 0.0));
+    for (i, &s) in looped[200..].iter().enumerate() {
+        assert_eq!(s, bed[i % bed.len()] * 0.5);
+    }
+
+    track.loop_count = 0;
+    let error = combiner
+        .combine_tracks(vec![track], &options)
+        .err()
+        .unwrap();
+    assert_eq!(error.code(), "INVALID_TRACK");
+}
+
+#[wasm_bindgen_test]
+fn normalize_scales_the_mix_to_its_peak_target() {
+    let tone = stereo_sine(440.0, 44100, 4410, 1.0);
+    let combiner = AudioCombiner::new(vec![
+        SingleAudioFile::from_pcm_f32(tone.clone(), 44100, 2).unwrap(),
+        SingleAudioFile::from_pcm_f32(tone.clone(), 44100, 2).unwrap(),
+        SingleAudioFile::from_pcm_f32(vec![0.0; 100], 44100, 2).unwrap(),
+    ])
+    .unwrap();
+    let mut options = CombineOptions::new();
+    options.bit_depth = BitDepth::Float32;
+    options.normalize = true;
+    let render = |gains: Vec<f32>| {
+        let tracks = gains
+            .into_iter()
+            .map(|gain| {
+                let mut track = MixTrack::new();
+                track.gain = gain;
+                track
+            })
+            .collect();
+        let result = combiner.combine_with_stats(tracks, &options).unwrap();
+        let samples: Vec<f32> = riff_chunk(&result.file().bytes(), b"data")
+            .unwrap()
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        (samples, result.stats().normalize_gain())
+    };
+
+    let (samples, gain) = render(vec![1.0, 1.0, 1.0]);
+    let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+    assert!((peak - db_to_linear(-1.0)).abs() < 1e-6);
+    assert!((gain * 2.0 - db_to_linear(-1.0)).abs() < 1e-3);
+    for (out, src) in samples.iter().zip(&tone) {
+        assert!((out - src * 2.0 * gain).abs() < 1e-6);
+    }
+
+    // Silence stays silent rather than dividing by zero.
+    let (samples, gain) = render(vec![0.0, 0.0, 1.0]);
+    assert_eq!(gain, 1.0);
+    assert!(samples.iter().all(|&s| s == 0.0));
+
+    options.normalize_peak_db = f32::NAN;
+    let error = combiner.combine_with_stats(vec![], &options).err().unwrap();
+    assert_eq!(error.code(), "INVALID_OPTION");
+}
+
+#[wasm_bindgen_test]
+fn limiter_keeps_overs_under_the_ceiling() {
+    let tone = stereo_sine(440.0, 44100, 44100, 1.0);
+    let quiet = stereo_sine(440.0, 44100, 44100, 0.1);
+    let combiner = AudioCombiner::new(vec![
+        SingleAudioFile::from_pcm_f32(tone.clone(), 44100, 2).unwrap(),
+        SingleAudioFile::from_pcm_f32(tone, 44100, 2).unwrap(),
+        SingleAudioFile::from_pcm_f32(quiet.clone(), 44100, 2).unwrap(),
+    ])
+    .unwrap();
+    let mut options = CombineOptions::new();
+    options.bit_depth = BitDepth::Float32;
+    options.limiter = true;
+    let render = |gains: Vec<f32>| -> Vec<f32> {
+        let wav = combiner
+            .combine_with_gains_and_options(gains, &options)
+            .unwrap()
+            .bytes();
+        riff_chunk(&wav, b"data")
+            .unwrap()
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()
+    };
+
+    let ceiling = db_to_linear(-0.3);
+    let limited = render(vec![1.0, 1.0, 0.0]);
+    assert_eq!(limited.len(), 44100 * 2);
+    assert!(limited.iter().all(|s| s.abs() <= ceiling + 1e-6));
+    let clipped = |s: f32| s.abs() >= ceiling - 1e-6;
+    assert!(!limited
+        .chunks_exact(2)
+        .collect::<Vec<_>>()
+        .windows(2)
+        .any(|w| clipped(w[0][0]) && clipped(w[1][0])));
+
+    // Material under the ceiling passes through untouched.
+    assert_eq!(render(vec![0.0, 0.0, 1.0]), quiet);
+}
+
+#[wasm_bindgen_test]
+fn compressor_turns_down_overs_with_one_gain_for_both_channels() {
+    // A loud left and a quieter right, -6 and -26 dBFS.
+    let tone: Vec<f32> = stereo_sine(440.0, 44100, 44100, 0.5)
+        .chunks_exact(2)
+        .flat_map(|frame| [frame[0], frame[1] * 0.1])
+        .collect();
+    let combiner = AudioCombiner::new(vec![
+        SingleAudioFile::from_pcm_f32(tone.clone(), 44100, 2).unwrap()
+    ])
+    .unwrap();
+    let mut options = CombineOptions::new();
+    options.bit_depth = BitDepth::Float32;
+    let render = |options: &CombineOptions| {
+        let result = combiner.combine_with_stats(vec![], options).unwrap();
+        let samples: Vec<f32> = riff_chunk(&result.file().bytes(), b"data")
+            .unwrap()
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        (samples, result.stats().max_gain_reduction_db())
+    };
+    assert_eq!(render(&options).1, 0.0);
+
+    let mut compressor = Compressor::new();
+    compressor.threshold_db = -20.0;
+    compressor.ratio = 4.0;
+    compressor.attack_ms = 0.0;
+    compressor.release_ms = 50.0;
+    options.compressor = Some(compressor);
+    let (samples, reduction) = render(&options);
+    // 14 dB over at 4:1 comes out 3.5 dB over, 10.5 dB down.
+    assert!((reduction - 10.5).abs() < 0.1, "{} dB", reduction);
+    let settled = &samples[44100..];
+    let peak = settled.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+    let peak_db = 20.0 * peak.log10();
+    assert!((peak_db + 16.5).abs() < 0.5, "{} dBFS", peak_db);
+    // Linked, so the right channel keeps its level against the left.
+    for frame in settled.chunks_exact(2) {
+        assert!((frame[1] - frame[0] * 0.1).abs() < 1e-6);
+    }
+
+    // Makeup gain lifts everything, and audio under the threshold is
+    // otherwise untouched.
+    compressor.threshold_db = 0.0;
+    compressor.makeup_db = 6.0;
+    options.compressor = Some(compressor);
+    let (samples, reduction) = render(&options);
+    assert_eq!(reduction, 0.0);
+    let makeup = db_to_linear(6.0);
+    for (out, src) in samples.iter().zip(&tone) {
+        assert!((out - src * makeup).abs() < 1e-6);
+    }
+
+    // The RMS detector averages the power of both channels, -12 dBFS here.
+    compressor.threshold_db = -20.0;
+    compressor.makeup_db = 0.0;
+    compressor.attack_ms = 50.0;
+    compressor.rms = true;
+    options.compressor = Some(compressor);
+    let (_, reduction) = render(&options);
+    assert!((reduction - 6.0).abs() < 0.2, "{} dB", reduction);
+
+    compressor.ratio = 0.5;
+    options.compressor = Some(compressor);
+    let error = combiner.combine_with_stats(vec![], &options).err().unwrap();
+    assert_eq!(error.code(), "INVALID_OPTION");
+}
+
+#[wasm_bindgen_test]
+fn channels_can_be_swapped_inverted_and_summed_to_mono() {
+    // A recording with only the left channel populated.
+    let left_only: Vec<f32> = stereo_sine(440.0, 44100, 4410, 0.5)
+        .chunks_exact(2)
+        .flat_map(|frame| [frame[0], 0.0])
+        .collect();
+    let mut options = CombineOptions::new();
+    options.bit_depth = BitDepth::Float32;
+    let render = |samples: &[f32], track: MixTrack, options: &CombineOptions| -> Vec<f32> {
+        let combiner = AudioCombiner::new(vec![SingleAudioFile::from_pcm_f32(
+            samples.to_vec(),
+            44100,
+            2,
+        )
+        .unwrap()])
+        .unwrap();
+        let wav = combiner
+            .combine_tracks(vec![track], options)
+            .unwrap()
+            .bytes();
+        riff_chunk(&wav, b"data")
+            .unwrap()
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()
+    };
+    let energy = |samples: &[f32], channel: usize| -> f32 {
+        samples.iter().skip(channel).step_by(2).map(|s| s * s).sum()
+    };
+
+    let mut track = MixTrack::new();
+    track.force_mono = true;
+    let mono = render(&left_only, track.clone(), &options);
+    // Both sides would be the same, so only one is written.
+    assert_eq!(mono.len(), left_only.len() / 2);
+    // The -3 dB sum on both sides keeps the total energy of the one
+    // populated channel.
+    let sum_energy: f32 = mono.iter().map(|s| s * s).sum();
+    assert!((2.0 * sum_energy / energy(&left_only, 0) - 1.0).abs() < 1e-4);
+
+    let mut track = MixTrack::new();
+    track.swap_channels = true;
+    let swapped = render(&left_only, track, &options);
+    for (out, src) in swapped.chunks_exact(2).zip(left_only.chunks_exact(2)) {
+        assert_eq!(out, [src[1], src[0]]);
+    }
+
+    // Inverting twice gives back the input exactly.
+    let mut track = MixTrack::new();
+    track.invert_phase = true;
+    let inverted = render(&left_only, track.clone(), &options);
+    assert_eq!(inverted[0..2], [-left_only[0], -left_only[1]]);
+    assert_eq!(render(&inverted, track, &options), left_only);
+
+    // The output switch averages the master into both channels.
+    options.mono = true;
+    let downmixed = render(&left_only, MixTrack::new(), &options);
+    for (out, src) in downmixed.chunks_exact(2).zip(left_only.chunks_exact(2)) {
+        assert_eq!(out, [src[0] / 2.0, src[0] / 2.0]);
+    }
+}
+
+#[wasm_bindgen_test]
+fn remove_dc_centres_an_offset_recording_and_keeps_its_bass() {
+    let render = |samples: Vec<f32>| -> Vec<f32> {
+        let combiner = AudioCombiner::new(vec![
+            SingleAudioFile::from_pcm_f32(samples, 44100, 2).unwrap()
+        ])
+        .unwrap();
+        let mut options = CombineOptions::new();
+        options.bit_depth = BitDepth::Float32;
+        let mut track = MixTrack::new();
+        track.remove_dc = true;
+        let wav = combiner
+            .combine_tracks(vec![track], &options)
+            .unwrap()
+            .bytes();
+        riff_chunk(&wav, b"data")
+            .unwrap()
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()
+    };
+
+    let offset: Vec<f32> = stereo_sine(440.0, 44100, 2 * 44100, 0.3)
+        .iter()
+        .map(|s| s + 0.1)
+        .collect();
+    let samples = render(offset);
+    let mean = samples.iter().map(|&s| s as f64).sum::<f64>() / samples.len() as f64;
+    assert!(mean.abs() < 1e-4, "mean {}", mean);
+
+    // 40 Hz comes through within a tenth of a dB once the filter settles.
+    let bass = stereo_sine(40.0, 44100, 2 * 44100, 0.3);
+    let samples = render(bass.clone());
+    let rms = |samples: &[f32]| {
+        (samples.iter().map(|&s| s as f64 * s as f64).sum::<f64>() / samples.len() as f64).sqrt()
+    };
+    let gain_db = 20.0 * (rms(&samples[44100..]) / rms(&bass[44100..])).log10();
+    assert!(gain_db.abs() < 0.1, "{} dB", gain_db);
+}
+
+/// True peak in dBTP by a slower, separate route: 8x band-limited
+/// interpolation with a Blackman-windowed sinc 64 samples wide.
+fn reference_true_peak(samples: &[f32]) -> f64 {
+    use std::f64::consts::PI;
+    const HALF: isize = 32;
+    const STEPS: usize = 8;
+    let frames = samples.len() as isize / 2;
+    let mut peak = 0.0f64;
+    for channel in 0..2 {
+        let x = |n: isize| match n {
+            n if (0..frames).contains(&n) => samples[n as usize * 2 + channel] as f64,
+            _ => 0.0,
+        };
+        for n in 0..frames {
+            peak = peak.max(x(n).abs());
+            for step in 1..STEPS {
+                let t = n as f64 + step as f64 / STEPS as f64;
+                let value: f64 = (n - HALF + 1..=n + HALF)
+                    .map(|k| {
+                        let d = t - k as f64;
+                        let window = 0.42
+                            + 0.5 * (PI * d / HALF as f64).cos()
+                            + 0.08 * (2.0 * PI * d / HALF as f64).cos();
+                        x(k) * (PI * d).sin() / (PI * d) * window
+                    })
+                    .sum();
+                peak = peak.max(value.abs());
+            }
+        }
+    }
+    20.0 * peak.log10()
+}
+
+/// A sine at a quarter of 44.1 kHz whose samples fall halfway between its
+/// crests, so its sample peak is 3 dB under its true peak of `amplitude`.
+fn quarter_rate_sine(amplitude: f32, frames: usize) -> Vec<f32> {
+    (0..frames)
+        .flat_map(|n| {
+            let phase = std::f32::consts::FRAC_PI_2 * n as f32 + std::f32::consts::FRAC_PI_4;
+            let s = amplitude * phase.sin();
+            [s, s]
+        })
+        .collect()
+}
+
+#[wasm_bindgen_test]
+fn true_peak_is_measured_between_samples() {
+    let measure = |samples: Vec<f32>| {
+        measure_true_peak(SingleAudioFile::from_pcm_f32(samples, 44100, 2).unwrap()).unwrap()
+    };
+    let db = |linear: f32| 20.0 * (linear as f64).log10();
+
+    let dbtp = measure(quarter_rate_sine(0.5, 44100));
+    assert!((dbtp - db(0.5)).abs() < 0.3, "{} dBTP", dbtp);
+    // A long sine crests at its amplitude somewhere between its samples.
+    // Faded in and out, since a sine cut off mid-swing really does
+    // overshoot between samples.
+    for freq in [997.0, 5000.0, 12345.0, 17000.0] {
+        let mut sine = stereo_sine(freq, 44100, 44100, 0.5);
+        let fade = 441;
+        for n in 0..fade {
+            let gain = (std::f32::consts::FRAC_PI_2 * n as f32 / fade as f32).sin();
+            let ends = [n, 44100 - 1 - n];
+            for frame in ends {
+                sine[frame * 2] *= gain;
+                sine[frame * 2 + 1] *= gain;
+            }
+        }
+        let dbtp = measure(sine);
+        assert!((dbtp - db(0.5)).abs() < 0.3, "{} Hz: {} dBTP", freq, dbtp);
+    }
+    assert_eq!(measure(vec![0.0; 1000]), f64::NEG_INFINITY);
+
+    // Decoded fixtures agree with the reference.
+    let mut options = CombineOptions::new();
+    options.bit_depth = BitDepth::Float32;
+    for (bytes, r#type) in [
+        (SINE_MP3, SingleAudioFileType::Mpeg),
+        (SINE_MONO_MP3, SingleAudioFileType::Mpeg),
+        (SINE_FLAC_24, SingleAudioFileType::Flac),
+        (SINE_OGG, SingleAudioFileType::Ogg),
+        (SINE_M4A, SingleAudioFileType::Aac),
+    ] {
+        let file = || SingleAudioFile::new(bytes.to_vec(), r#type);
+        let wav = AudioCombiner::new(vec![file()])
+            .unwrap()
+            .combine_with_options(vec![], &options)
+            .unwrap()
+            .bytes();
+        let samples: Vec<f32> = riff_chunk(&wav, b"data")
+            .unwrap()
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        let dbtp = measure_true_peak(file()).unwrap();
+        let reference = reference_true_peak(&samples);
+        assert!(
+            (dbtp - reference).abs() < 0.3,
+            "{:?}: {} against {} dBTP",
+            r#type,
+            dbtp,
+            reference
+        );
+    }
+}
+
+#[wasm_bindgen_test]
+fn normalize_and_limiter_can_work_to_true_peak() {
+    let combiner = AudioCombiner::new(vec![SingleAudioFile::from_pcm_f32(
+        quarter_rate_sine(0.5, 44100),
+        44100,
+        2,
+    )
+    .unwrap()])
+    .unwrap();
+    let stats = |options: &CombineOptions| {
+        let result = combiner.combine_with_stats(vec![], options).unwrap();
+        // Measured from the output when the stats leave it out.
+        let true_peak = measure_true_peak(result.file()).unwrap() as f32;
+        (result.stats(), true_peak)
+    };
+    let mut options = CombineOptions::new();
+    options.bit_depth = BitDepth::Float32;
+    options.normalize = true;
+
+    // Normalized by its samples the sine overshoots between them.
+    let (by_samples, true_peak) = stats(&options);
+    assert!((by_samples.peak_dbfs() + 1.0).abs() < 0.01);
+    assert_eq!(by_samples.true_peak_dbtp(), None);
+    assert!(true_peak > 1.5, "{}", true_peak);
+    options.true_peak = true;
+    let (by_true_peak, true_peak) = stats(&options);
+    assert!((by_true_peak.true_peak_dbtp().unwrap() + 1.0).abs() < 0.01);
+    assert!((true_peak + 1.0).abs() < 0.01, "{}", true_peak);
+    assert!(
+        by_true_peak.peak_dbfs() < -3.5,
+        "{}",
+        by_true_peak.peak_dbfs()
+    );
+
+    // Pushed 12 dB into the limiter, the true peak stays under its ceiling.
+    options.normalize = false;
+    options.master_gain = 4.0;
+    options.limiter = true;
+    let (limited, _) = stats(&options);
+    let true_peak = limited.true_peak_dbtp().unwrap();
+    assert!(true_peak < -0.2, "{}", true_peak);
+    options.true_peak = false;
+    let (limited, true_peak) = stats(&options);
+    assert!(limited.peak_dbfs() <= -0.3 + 1e-4);
+    assert!(true_peak > 2.0, "{}", true_peak);
+}
+
+#[wasm_bindgen_test]
+fn loudness_is_measured_and_normalized_per_track() {
+    let tone = |db: f32| stereo_sine(1000.0, 44100, 2 * 44100, db_to_linear(db));
+    let lufs =
+        measure_loudness(SingleAudioFile::from_pcm_f32(tone(-23.0), 44100, 2).unwrap()).unwrap();
+    assert!((lufs + 23.0).abs() < 0.5, "{} LUFS", lufs);
+    let mono: Vec<f32> = tone(0.0).into_iter().step_by(2).collect();
+    let lufs = measure_loudness(SingleAudioFile::from_pcm_f32(mono, 44100, 1).unwrap()).unwrap();
+    assert!((lufs + 3.0).abs() < 0.5, "{} LUFS", lufs);
+    let silence = SingleAudioFile::from_pcm_f32(vec![0.0; 44100 * 2], 44100, 2).unwrap();
+    assert_eq!(measure_loudness(silence).unwrap(), f64::NEG_INFINITY);
+
+    let combiner = AudioCombiner::new(vec![
+        SingleAudioFile::from_pcm_f32(tone(-30.0), 44100, 2).unwrap(),
+        SingleAudioFile::from_pcm_f32(tone(-10.0), 44100, 2).unwrap(),
+    ])
+    .unwrap();
+    let mut options = CombineOptions::new();
+    options.bit_depth = BitDepth::Float32;
+    options.loudness_target_lufs = Some(-16.0);
+    for gains in [vec![1.0, 0.0], vec![0.0, 1.0]] {
+        let wav = combiner
+            .combine_with_gains_and_options(gains, &options)
+            .unwrap();
+        let lufs = measure_loudness(wav).unwrap();
+        assert!((lufs + 16.0).abs() < 0.5, "{} LUFS", lufs);
+    }
+}
+
+#[wasm_bindgen_test]
+fn auto_level_matches_files_10_db_apart() {
+    let mut options = CombineOptions::new();
+    options.bit_depth = BitDepth::Float32;
+    let quieter = AudioCombiner::new(vec![SingleAudioFile::new(
+        SINE_OGG.to_vec(),
+        SingleAudioFileType::Ogg,
+    )])
+    .unwrap()
+    .combine_with_gains_and_options(vec![db_to_linear(-10.0)], &options)
+    .unwrap();
+    let combiner = AudioCombiner::new(vec![
+        SingleAudioFile::new(SINE_MP3.to_vec(), SingleAudioFileType::Mpeg),
+        quieter,
+    ])
+    .unwrap();
+
+    let analysis = combiner.analyze_replaygain();
+    assert_eq!(analysis.len(), 2);
+    let difference = analysis[1].track_gain_db() - analysis[0].track_gain_db();
+    assert!((difference - 10.0).abs() < 1.0, "{} dB apart", difference);
+    for file in &analysis {
+        let lufs = file.loudness_lufs() + file.track_gain_db() as f64;
+        assert!((lufs + 18.0).abs() < 0.01, "{} LUFS", lufs);
+        assert!(file.track_peak() > 0.0 && file.track_peak() < 1.0);
+    }
+    assert_eq!(combiner.duplicate().analyze_replaygain(), analysis);
+
+    options.auto_level = true;
+    let solo = |gains: Vec<f32>| {
+        let wav = combiner
+            .combine_with_gains_and_options(gains, &options)
+            .unwrap();
+        measure_loudness(wav).unwrap()
+    };
+    let first = solo(vec![1.0, 0.0]);
+    let second = solo(vec![0.0, 1.0]);
+    assert!((first - second).abs() < 1.0, "{} vs {} LUFS", first, second);
+    assert!((first + 18.0).abs() < 1.0, "{} LUFS", first);
+    // The user's gains apply on top of the matched level.
+    let halved = solo(vec![0.0, 0.5]);
+    assert!((second - halved - 6.02).abs() < 0.1, "{} LUFS", halved);
+
+    options.loudness_target_lufs = Some(-16.0);
+    let error = combiner
+        .combine_with_gains_and_options(vec![1.0], &options)
+        .err()
+        .unwrap();
+    assert_eq!(error.code(), "INVALID_OPTION");
+    assert!(error.to_string().contains("auto_level"), "{}", error);
+}
+
+#[wasm_bindgen_test]
+fn mute_and_solo_pick_the_files_that_are_mixed() {
+    let loud = stereo_sine(440.0, 44100, 4410, 0.8);
+    let voice = stereo_sine(220.0, 44100, 2205, 0.5);
+    let combiner = AudioCombiner::new(vec![
+        SingleAudioFile::from_pcm_f32(loud.clone(), 44100, 2).unwrap(),
+        SingleAudioFile::from_pcm_f32(voice.clone(), 44100, 2).unwrap(),
+        SingleAudioFile::from_pcm_f32(vec![0.0; 1000], 44100, 2).unwrap(),
+    ])
+    .unwrap();
+    let mut options = CombineOptions::new();
+    options.bit_depth = BitDepth::Float32;
+    let render = |flags: &[(bool, bool)]| -> Vec<f32> {
+        let tracks = flags
+            .iter()
+            .map(|&(mute, solo)| {
+                let mut track = MixTrack::new();
+                track.mute = mute;
+                track.solo = solo;
+                track
+            })
+            .collect();
+        let wav = combiner.combine_tracks(tracks, &options).unwrap().bytes();
+        riff_chunk(&wav, b"data")
+            .unwrap()
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()
+    };
+
+    assert_eq!(
+        render(&[(true, false), (false, false), (false, false)]),
+        voice
+    );
+
+    // Soloing the silent file silences the mix despite the loud ones.
+    let soloed = render(&[(false, false), (false, false), (false, true)]);
+    assert_eq!(soloed.len(), 1000);
+    assert!(soloed.iter().all(|&s| s == 0.0));
+
+    // Solo wins over mute, and unsoloed files drop out.
+    assert_eq!(render(&[(true, true), (false, false), (true, false)]), loud);
+}
+
+#[wasm_bindgen_test]
+fn master_gain_scales_the_whole_mix() {
+    let tone = stereo_sine(440.0, 44100, 4410, 0.5);
+    let combiner = AudioCombiner::new(vec![
+        SingleAudioFile::from_pcm_f32(tone.clone(), 44100, 2).unwrap(),
+        SingleAudioFile::from_pcm_f32(tone.clone(), 44100, 2).unwrap(),
+    ])
+    .unwrap();
+    let mut options = CombineOptions::new();
+    options.bit_depth = BitDepth::Float32;
+    options.master_gain = db_to_linear(-2.0);
+    let tracks = |gains: &[f32]| -> Vec<MixTrack> {
+        gains
+            .iter()
+            .map(|&gain| {
+                let mut track = MixTrack::new();
+                track.gain = gain;
+                track
+            })
+            .collect()
+    };
+
+    let result = combiner
+        .combine_with_stats(tracks(&[1.0, 0.5]), &options)
+        .unwrap();
+    let samples: Vec<f32> = riff_chunk(&result.file().bytes(), b"data")
+        .unwrap()
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect();
+    for (out, src) in samples.iter().zip(&tone) {
+        assert!((out - src * 1.5 * options.master_gain).abs() < 1e-6);
+    }
+    assert_eq!(
+        result.stats().track_gains(),
+        vec![options.master_gain, 0.5 * options.master_gain]
+    );
+
+    // Normalization comes first, so the master gain still trims its peak.
+    options.normalize = true;
+    let stats = combiner
+        .combine_with_stats(tracks(&[1.0, 1.0]), &options)
+        .unwrap()
+        .stats();
+    let total = stats.normalize_gain() * options.master_gain;
+    assert_eq!(stats.track_gains(), vec![total, total]);
+
+    for master_gain in [-1.0, f32::NAN] {
+        options.master_gain = master_gain;
+        let error = combiner.combine_with_stats(vec![], &options).err().unwrap();
+        assert_eq!(error.code(), "INVALID_OPTION");
+    }
+}
+
+#[wasm_bindgen_test]
+fn automation_interpolates_gain_keyframes() {
+    // Whole cycles in every 10 ms block, so block RMS only moves with gain.
+    let combiner = AudioCombiner::new(vec![SingleAudioFile::from_pcm_f32(
+        stereo_sine(1000.0, 44100, 44100, 0.5),
+        44100,
+        2,
+    )
+    .unwrap()])
+    .unwrap();
+    let mut options = CombineOptions::new();
+    options.bit_depth = BitDepth::Float32;
+    let render = |automation: Vec<AutomationPoint>| -> Vec<f32> {
+        let mut track = MixTrack::new();
+        track.automation = automation;
+        let wav = combiner
+            .combine_tracks(vec![track], &options)
+            .unwrap()
+            .bytes();
+        riff_chunk(&wav, b"data")
+            .unwrap()
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()
+    };
+    let rms =
+        |block: &[f32]| (block.iter().map(|s| s * s).sum::<f32>() / block.len() as f32).sqrt();
+
+    let faded = render(vec![
+        AutomationPoint::new(0.0, 1.0),
+        AutomationPoint::new(1000.0, 0.0),
+    ]);
+    let levels: Vec<f32> = faded.chunks_exact(441 * 2).map(rms).collect();
+    assert!(levels.windows(2).all(|w| w[1] < w[0]));
+
+    // Keyframes outside the file clamp, so this holds 0.2 then recovers
+    // linearly, whatever order they are given in.
+    let ducked = render(vec![
+        AutomationPoint::new(5000.0, 1.0),
+        AutomationPoint::new(-100.0, 0.2),
+        AutomationPoint::new(250.0, 0.2),
+    ]);
+    let plain = render(vec![]);
+    for (i, (out, src)) in ducked
+        .chunks_exact(2)
+        .zip(plain.chunks_exact(2))
+        .enumerate()
+    {
+        let ms = i as f32 / 44.1;
+        let gain = if ms <= 250.0 {
+            0.2
+        } else {
+            0.2 + 0.8 * (ms - 250.0) / 4750.0
+        };
+        assert!((out[0] - src[0] * gain).abs() < 1e-5, "frame {}", i);
+    }
+
+    let mut track = MixTrack::new();
+    track.automation = vec![AutomationPoint::new(0.0, -1.0)];
+    let error = combiner
+        .combine_tracks(vec![track], &options)
+        .err()
+        .unwrap();
+    assert_eq!(error.code(), "INVALID_TRACK");
+}
+
+#[wasm_bindgen_test]
+fn ducking_turns_a_track_down_under_its_source() {
+    let music = stereo_sine(1000.0, 44100, 3 * 44100, 0.25);
+    let tone = stereo_sine(441.0, 44100, 3 * 44100, 0.5);
+    let speaking =
+        |frame: usize| (22050..44100).contains(&frame) || (110250..123480).contains(&frame);
+    let voice: Vec<f32> = tone
+        .chunks_exact(2)
+        .enumerate()
+        .flat_map(|(i, f)| {
+            if speaking(i) {
+                [f[0], f[1]]
+            } else {
+                [0.0, 0.0]
+            }
+        })
+        .collect();
+    let combiner = AudioCombiner::new(vec![
+        SingleAudioFile::from_pcm_f32(music, 44100, 2).unwrap(),
+        SingleAudioFile::from_pcm_f32(voice, 44100, 2).unwrap(),
+    ])
+    .unwrap();
+    let mut options = CombineOptions::new();
+    options.bit_depth = BitDepth::Float32;
+    let render = |music_gain: f32| -> Vec<f32> {
+        let mut bed = MixTrack::new();
+        bed.gain = music_gain;
+        bed.ducking = Some(Ducking::new(1));
+        let wav = combiner
+            .combine_tracks(vec![bed, MixTrack::new()], &options)
+            .unwrap()
+            .bytes();
+        riff_chunk(&wav, b"data")
+            .unwrap()
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()
+    };
+
+    // Take the voice back out to hear what happened to the music.
+    let voice_only = render(0.0);
+    let bed: Vec<f32> = render(1.0)
+        .iter()
+        .zip(&voice_only)
+        .map(|(mix, voice)| mix - voice)
+        .collect();
+    let level_db = |from_s: f32, to_s: f32| {
+        let block = &bed[(from_s * 44100.0) as usize * 2..(to_s * 44100.0) as usize * 2];
+        let rms = (block.iter().map(|s| s * s).sum::<f32>() / block.len() as f32).sqrt();
+        20.0 * (rms / (0.25 * std::f32::consts::FRAC_1_SQRT_2)).log10()
+    };
+
+    assert!(level_db(0.1, 0.4).abs() < 0.1);
+    assert!((level_db(0.7, 0.95) + 12.0).abs() < 0.5);
+    assert!(level_db(2.2, 2.45).abs() < 0.5);
+    assert!((level_db(2.6, 2.75) + 12.0).abs() < 0.5);
+
+    let mut bed = MixTrack::new();
+    bed.ducking = Some(Ducking::new(0));
+    let error = combiner.combine_tracks(vec![bed], &options).err().unwrap();
+    assert_eq!(error.code(), "INVALID_TRACK");
+}
+
+#[wasm_bindgen_test]
+fn speed_scales_a_tracks_length() {
+    let tone = stereo_sine(440.0, 44100, 44100, 0.5);
+    let combiner = AudioCombiner::new(vec![
+        SingleAudioFile::from_pcm_f32(tone.clone(), 44100, 2).unwrap(),
+        SingleAudioFile::from_pcm_f32(tone, 44100, 2).unwrap(),
+    ])
+    .unwrap();
+    let frames = |speeds: &[f32], mode: CombineMode| {
+        let tracks = speeds
+            .iter()
+            .map(|&speed| {
+                let mut track = MixTrack::new();
+                track.speed = speed;
+                track
+            })
+            .collect();
+        let mut options = CombineOptions::new();
+        options.mode = mode;
+        let result = combiner.combine_tracks(tracks, &options);
+        result.map(|file| output_samples(&file.bytes()).len() / 2)
+    };
+
+    assert_eq!(frames(&[2.0, 1.0], CombineMode::Mix), Ok(44100));
+    assert_eq!(frames(&[2.0, 2.0], CombineMode::Mix), Ok(22050));
+    assert_eq!(frames(&[2.0, 0.5], CombineMode::Concat), Ok(22050 + 88200));
+
+    for speed in [0.25, 3.0, f32::NAN] {
+        let error = frames(&[speed], CombineMode::Mix).err().unwrap();
+        assert_eq!(error.code(), "INVALID_TRACK");
+        assert_eq!(error.index(), Some(0));
+    }
+}
+
+#[wasm_bindgen_test]
+fn reversing_plays_frames_backwards_after_trimming() {
+    let mut options = CombineOptions::new();
+    options.bit_depth = BitDepth::Float32;
+    let reverse = |samples: Vec<f32>, trim_start_ms: f64| -> Vec<f32> {
+        let combiner = AudioCombiner::new(vec![
+            SingleAudioFile::from_pcm_f32(samples, 44100, 2).unwrap()
+        ])
+        .unwrap();
+        let mut track = MixTrack::new();
+        track.reversed = true;
+        track.trim_start_ms = trim_start_ms;
+        let wav = combiner
+            .combine_tracks(vec![track], &options)
+            .unwrap()
+            .bytes();
+        riff_chunk(&wav, b"data")
+            .unwrap()
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()
+    };
+
+    // Left and right differ so a sample-wise reverse would swap them.
+    let source: Vec<f32> = ramp(4410 * 2)
+        .chunks_exact(2)
+        .flat_map(|f| [f[0], -f[1] * 0.5])
+        .collect();
+    let reversed = reverse(source.clone(), 0.0);
+    assert_eq!(reversed[..2], source[source.len() - 2..]);
+    assert_eq!(reverse(reversed, 0.0), source);
+
+    // 10 ms is trimmed from the start, so the reversed file ends before it.
+    let trimmed = reverse(source.clone(), 10.0);
+    assert_eq!(trimmed.len(), source.len() - 441 * 2);
+    assert_eq!(trimmed[trimmed.len() - 2..], source[441 * 2..441 * 2 + 2]);
+}
+
+#[wasm_bindgen_test]
+fn generated_sources_mix_like_decoded_files() {
+    let mut options = CombineOptions::new();
+    options.mode = CombineMode::Concat;
+    options.bit_depth = BitDepth::Float32;
+    let combiner = AudioCombiner::new(vec![
+        SingleAudioFile::sine(441.0, 100.0, 44100, 0.5).unwrap(),
+        SingleAudioFile::silence(50.0, 44100).unwrap(),
+        SingleAudioFile::sine(441.0, 100.0, 44100, 0.5).unwrap(),
+    ])
+    .unwrap();
+    let wav = combiner.combine_tracks(vec![], &options).unwrap().bytes();
+    let samples: Vec<f32> = riff_chunk(&wav, b"data")
+        .unwrap()
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect();
+
+    // Generated files are mono, so a mix of nothing else is too.
+    let tone: Vec<f32> = stereo_sine(441.0, 44100, 4410, 0.5)
+        .into_iter()
+        .step_by(2)
+        .collect();
+    assert_eq!(samples.len(), 4410 + 2205 + 4410);
+    assert!(samples[..4410]
+        .iter()
+        .zip(&tone)
+        .all(|(a, b)| (a - b).abs() < 1e-6));
+    assert!(samples[4410..6615].iter().all(|&s| s == 0.0));
+    assert_eq!(samples[6615..], samples[..4410]);
+
+    for error in [
+        SingleAudioFile::sine(441.0, 100.0, 44100, 1.5).err(),
+        SingleAudioFile::sine(30000.0, 100.0, 44100, 0.5).err(),
+        SingleAudioFile::silence(-1.0, 44100).err(),
+    ] {
+        assert_eq!(error.unwrap().code(), "INVALID_PCM");
+    }
+}
+
+#[wasm_bindgen_test]
+fn files_can_be_added_and_removed_between_combines() {
+    let mut options = CombineOptions::new();
+    options.mode = CombineMode::Concat;
+    let frames = |combiner: &AudioCombiner| {
+        let wav = combiner.combine_tracks(vec![], &options).unwrap().bytes();
+        // Silence is mono, so the mix is one channel.
+        output_samples(&wav).len()
+    };
+    let silence = |ms: f64| SingleAudioFile::silence(ms, 44100).unwrap();
+
+    let mut combiner = AudioCombiner::new(vec![silence(10.0)]).unwrap();
+    assert_eq!(combiner.add_file(silence(20.0)), Ok(1));
+    assert_eq!(combiner.add_file(silence(40.0)), Ok(2));
+    assert_eq!(combiner.len(), 3);
+    assert_eq!(frames(&combiner), 441 * 7);
+
+    combiner.remove_file(1).unwrap();
+    assert_eq!(frames(&combiner), 441 * 5);
+
+    // The 40 ms file moved to index 1, so muting it leaves only the first.
+    let mut muted = MixTrack::new();
+    muted.mute = true;
+    let wav = combiner
+        .combine_tracks(vec![MixTrack::new(), muted], &options)
+        .unwrap()
+        .bytes();
+    assert_eq!(output_samples(&wav).len(), 441);
+
+    let error = combiner.remove_file(2).err().unwrap();
+    assert_eq!(error.code(), "INDEX_OUT_OF_RANGE");
+    assert_eq!(error.index(), Some(2));
+
+    combiner.clear();
+    assert!(combiner.is_empty());
+    assert_eq!(combiner.add_file(silence(30.0)), Ok(0));
+    assert_eq!(frames(&combiner), 441 * 3);
+}
+
+#[wasm_bindgen_test]
+fn empty_inputs_are_errors_and_empty_streams_silence() {
+    let error = AudioCombiner::new(vec![
+        SingleAudioFile::new(SINE_MP3.to_vec(), SingleAudioFileType::Mpeg),
+        SingleAudioFile::new(vec![], SingleAudioFileType::Mpeg),
+    ])
+    .err()
+    .unwrap();
+    assert_eq!(error.code(), "EMPTY_FILE");
+    assert_eq!(error.index(), Some(1));
+
+    let mut combiner = AudioCombiner::new(vec![]).unwrap();
+    let error = combiner.combine(vec![]).err().unwrap();
+    assert_eq!(error.code(), "EMPTY_INPUT");
+    assert_eq!(error.index(), None);
+    assert_eq!(
+        combine_streamed(vec![], vec![], 4096).err().unwrap().code(),
+        "EMPTY_INPUT"
+    );
+
+    let error = combiner
+        .add_file(SingleAudioFile::new(vec![], SingleAudioFileType::Auto))
+        .err()
+        .unwrap();
+    assert_eq!(error, CombineError::EmptyFile { index: 0 });
+    assert!(combiner.is_empty());
+
+    // A WAV whose data chunk is empty opens fine but holds no audio.
+    let empty_wav = wav_bytes(WavEncoding::Pcm16, 2, 44100, &[]);
+    assert_eq!(
+        combiner.add_file(SingleAudioFile::new(empty_wav, SingleAudioFileType::Wav)),
+        Ok(0)
+    );
+    let result = combiner
+        .combine_with_stats(vec![], &CombineOptions::new())
+        .unwrap();
+    assert!(output_samples(&result.file().bytes()).is_empty());
+    let warnings = result.warnings();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].index(), 0);
+    assert_eq!(warnings[0].timestamp_ms(), 0.0);
+
+    // Next to other files it is silence that takes no time.
+    combiner
+        .add_file(SingleAudioFile::silence(10.0, 44100).unwrap())
+        .unwrap();
+    let wav = combiner.combine(vec![]).unwrap().bytes();
+    assert_eq!(output_samples(&wav).len(), 441 * 2);
+}
+
+#[wasm_bindgen_test]
+fn probe_reads_properties_without_combining() {
+    let info = SingleAudioFile::new(SINE_FLAC_24.to_vec(), SingleAudioFileType::Flac)
+        .probe()
+        .unwrap();
+    assert_eq!(info.sample_rate(), 44100);
+    assert_eq!(info.channels(), 2);
+    assert_eq!(info.codec(), "flac");
+    assert!(info.duration_ms().unwrap() > 0.0);
+
+    let m4a = SingleAudioFile::new(SINE_M4A.to_vec(), SingleAudioFileType::Aac)
+        .probe()
+        .unwrap();
+    assert_eq!((m4a.channels(), m4a.codec().as_str()), (2, "aac"));
+
+    // HE-AAC cannot be decoded but its headers can still be read.
+    let he = SingleAudioFile::new(SINE_HE_AAC_M4A.to_vec(), SingleAudioFileType::Aac)
+        .probe()
+        .unwrap();
+    assert_eq!(he.codec(), "HE-AAC");
+
+    // Matroska reports its length in milliseconds rather than frames.
+    let webm = SingleAudioFile::new(SINE_VORBIS_WEBM.to_vec(), SingleAudioFileType::Webm)
+        .probe()
+        .unwrap();
+    assert_eq!(webm.duration_ms(), Some(1000.0));
+
+    let silence = SingleAudioFile::silence(250.0, 48000)
+        .unwrap()
+        .probe()
+        .unwrap();
+    assert_eq!(silence.duration_ms(), Some(250.0));
+    assert_eq!((silence.sample_rate(), silence.channels()), (48000, 1));
+
+    let error = SingleAudioFile::new(vec![0; 64], SingleAudioFileType::Auto)
+        .probe()
+        .err()
+        .unwrap();
+    assert_eq!(error.code(), "UNSUPPORTED_FORMAT");
+}
+
+#[wasm_bindgen_test]
+fn validate_flags_corrupt_and_truncated_files() {
+    let flac = |bytes: &[u8]| SingleAudioFile::new(bytes.to_vec(), SingleAudioFileType::Flac);
+    let mut corrupt_header = SINE_FLAC_24.to_vec();
+    corrupt_header[..16].fill(0x55);
+    let truncated = &SINE_FLAC_24[..SINE_FLAC_24.len() / 2];
+
+    let results = AudioCombiner::validate(vec![
+        flac(SINE_FLAC_24),
+        flac(&corrupt_header),
+        flac(truncated),
+        SingleAudioFile::silence(5000.0, 44100).unwrap(),
+    ]);
+    let codes: Vec<_> = results.iter().map(|r| r.error_code()).collect();
+    assert_eq!(
+        codes,
+        [
+            None,
+            Some("UNSUPPORTED_FORMAT".to_string()),
+            Some("TRUNCATED".to_string()),
+            None
+        ]
+    );
+    assert!(results[0].ok() && results[0].message().is_none());
+    assert_eq!(results[2].index(), 2);
+    assert!(results[2].message().unwrap().starts_with("file 2:"));
+}
+
+/// Current size of the wasm linear memory, which only ever grows.
+fn wasm_memory_bytes() -> u32 {
+    wasm_bindgen::memory()
+        .unchecked_into::<js_sys::WebAssembly::Memory>()
+        .buffer()
+        .unchecked_into::<js_sys::ArrayBuffer>()
+        .byte_length()
+}
+
+#[wasm_bindgen_test]
+fn repeated_combines_do_not_grow_memory() {
+    let file = || SingleAudioFile::sine(440.0, 10_000.0, 44100, 0.5).unwrap();
+    let combiner = AudioCombiner::new(vec![file(), file()]).unwrap();
+    let combine = |volume: u8| combiner.combine(vec![volume, 100]).unwrap();
+
+    // Warm the allocator up so later combines can reuse freed blocks.
+    drop(combine(50));
+    let before = wasm_memory_bytes();
+    for volume in 0..20 {
+        drop(combine(volume));
+    }
+    // Keeping anything per call would add twenty outputs' worth.
+    let output_bytes = combine(100).bytes().len() as u32;
+    assert!(wasm_memory_bytes() - before <= 2 * output_bytes);
+}
+
+#[wasm_bindgen_test]
+fn files_from_js_buffers_are_copied_once() {
+    let len = 8 * 1024 * 1024;
+    let buffer = js_sys::ArrayBuffer::new(len + 16);
+    let view = js_sys::Uint8Array::new_with_byte_offset_and_length(&buffer, 16, len);
+    view.fill(7, 0, len);
+
+    let before = wasm_memory_bytes();
+    let file = SingleAudioFile::from_uint8array(&view, SingleAudioFileType::Wav);
+    // A second copy would need another `len` bytes.
+    assert!(wasm_memory_bytes() - before < len + len / 2);
+    assert_eq!(file.byte_length(), len as usize);
+    assert!(file.bytes().iter().all(|&b| b == 7));
+
+    let file = SingleAudioFile::from_array_buffer(&buffer, SingleAudioFileType::Wav);
+    assert_eq!(file.byte_length(), len as usize + 16);
+    assert_eq!(file.r#type, SingleAudioFileType::Wav);
+}
+
+/// A 16-bit stereo WAV of `seconds` of a tone, built a second at a time so
+/// the test itself does not need the decoded length in memory.
+fn long_wav(freq: f32, seconds: u32) -> Vec<u8> {
+    let second = wav_bytes(
+        WavEncoding::Pcm16,
+        2,
+        44100,
+        &stereo_sine(freq, 44100, 44100, 0.2),
+    );
+    let (header, data) = second.split_at(44);
+    let data_size = data.len() as u32 * seconds;
+    let mut wav = Vec::with_capacity(44 + data_size as usize);
+    wav.extend_from_slice(header);
+    wav[4..8].copy_from_slice(&(36 + data_size).to_le_bytes());
+    wav[40..44].copy_from_slice(&data_size.to_le_bytes());
+    for _ in 0..seconds {
+        wav.extend_from_slice(data);
+    }
+    wav
+}
+
+/// Runs `combine_streamed`, writing each chunk at its offset.
+fn combine_streamed(
+    files: Vec<SingleAudioFile>,
+    volumes: Vec<u8>,
+    chunk_frames: usize,
+) -> Result<(Vec<u8>, usize), CombineError> {
+    use std::{cell::RefCell, rc::Rc};
+    use wasm_bindgen::closure::Closure;
+
+    let output = Rc::new(RefCell::new((Vec::new(), 0)));
+    let sink = output.clone();
+    let write = Closure::wrap(Box::new(move |bytes: js_sys::Uint8Array, offset: f64| {
+        let (output, calls) = &mut *sink.borrow_mut();
+        let offset = offset as usize;
+        let end = offset + bytes.length() as usize;
+        if output.len() < end {
+            output.resize(end, 0);
+        }
+        bytes.copy_to(&mut output[offset..end]);
+        *calls += 1;
+    }) as Box<dyn FnMut(js_sys::Uint8Array, f64)>);
+
+    AudioCombiner::combine_streamed(files, volumes, chunk_frames, write.as_ref().unchecked_ref())?;
+    let output = output.borrow().clone();
+    Ok(output)
+}
+
+#[wasm_bindgen_test]
+fn streamed_combine_matches_combine() {
+    let files = || {
+        vec![
+            SingleAudioFile::new(SINE_MP3.to_vec(), SingleAudioFileType::Mpeg),
+            SingleAudioFile::new(SINE_FLAC_24.to_vec(), SingleAudioFileType::Flac),
+            SingleAudioFile::sine(300.0, 1500.0, 44100, 0.3).unwrap(),
+        ]
+    };
+    let expected = AudioCombiner::new(files())
+        .unwrap()
+        .combine(vec![50, 80])
+        .unwrap()
+        .bytes();
+
+    let (streamed, calls) = combine_streamed(files(), vec![50, 80], 4096).unwrap();
+    assert_eq!(streamed, expected);
+    // Header, 1.5 s in 4096-frame chunks, then the patched header.
+    assert_eq!(calls, 1 + 17 + 1);
+
+    // Mono files alone stream as one channel, as they combine.
+    let mono = || {
+        vec![
+            SingleAudioFile::new(SINE_MONO_MP3.to_vec(), SingleAudioFileType::Mpeg),
+            SingleAudioFile::sine(300.0, 500.0, 44100, 0.3).unwrap(),
+        ]
+    };
+    let expected = AudioCombiner::new(mono())
+        .unwrap()
+        .combine(vec![50, 80])
+        .unwrap()
+        .bytes();
+    assert_eq!(u16::from_le_bytes([expected[22], expected[23]]), 1);
+    assert_eq!(
+        combine_streamed(mono(), vec![50, 80], 4096).unwrap().0,
+        expected
+    );
+
+    let error = combine_streamed(
+        vec![
+            SingleAudioFile::silence(10.0, 44100).unwrap(),
+            SingleAudioFile::silence(10.0, 48000).unwrap(),
+        ],
+        vec![],
+        4096,
+    )
+    .err()
+    .unwrap();
+    assert_eq!(
+        error,
+        CombineError::SampleRateMismatch {
+            index: 1,
+            found: 48000,
+            expected: 44100
+        }
+    );
+    assert_eq!(error.code(), "SAMPLE_RATE_MISMATCH");
+}
+
+#[wasm_bindgen_test]
+fn streamed_combine_keeps_memory_to_a_window_per_file() {
+    // Decoded in full, these would need over 90 MB on top of the inputs.
+    let files: Vec<_> = [220.0, 330.0, 440.0]
+        .iter()
+        .map(|&freq| SingleAudioFile::new(long_wav(freq, 60), SingleAudioFileType::Wav))
+        .collect();
+
+    // Only the length is kept, so the output does not land in wasm memory.
+    let length = std::rc::Rc::new(std::cell::Cell::new(0.0));
+    let sink = length.clone();
+    let write = wasm_bindgen::closure::Closure::wrap(Box::new(
+        move |bytes: js_sys::Uint8Array, offset: f64| {
+            sink.set(f64::max(sink.get(), offset + bytes.length() as f64));
+        },
+    )
+        as Box<dyn FnMut(js_sys::Uint8Array, f64)>);
+
+    let before = wasm_memory_bytes();
+    AudioCombiner::combine_streamed(files, vec![], 4096, write.as_ref().unchecked_ref()).unwrap();
+    assert!(wasm_memory_bytes() - before < 8 * 1024 * 1024);
+    assert_eq!(length.get(), 44.0 + 60.0 * 44100.0 * 4.0);
+}
+
+#[wasm_bindgen_test]
+fn wav_output_holds_no_extra_copy_of_the_mix() {
+    let combiner = AudioCombiner::new(vec![
+        SingleAudioFile::sine(440.0, 120_000.0, 44100, 0.5).unwrap()
+    ])
+    .unwrap();
+    let mix_bytes = 120 * 44100 * 2 * 4;
+    // Panned, so the WAV is stereo like the mix.
+    let mut panned = MixTrack::new();
+    panned.pan = 0.5;
+
+    let before = wasm_memory_bytes();
+    let wav = combiner
+        .combine_tracks(vec![panned], &CombineOptions::new())
+        .unwrap()
+        .bytes();
+    assert_eq!(wav.len(), 44 + mix_bytes / 2);
+    // The WAV is written over the mix, so the mix is all that has to fit.
+    assert!(((wasm_memory_bytes() - before) as usize) < mix_bytes * 9 / 8);
+}
+
+#[wasm_bindgen_test]
+fn stats_report_timings_peak_and_clipping() {
+    let loud = || SingleAudioFile::sine(441.0, 250.0, 44100, 0.8).unwrap();
+    let combiner = AudioCombiner::new(vec![loud(), loud()]).unwrap();
+
+    let stats = combiner
+        .combine_with_stats(vec![], &CombineOptions::new())
+        .unwrap()
+        .stats();
+    assert_eq!(stats.decode_ms().len(), 2);
+    assert!(stats.decode_ms().iter().all(|&ms| ms >= 0.0));
+    assert!(stats.mix_ms() >= 0.0 && stats.encode_ms() >= 0.0);
+    assert_eq!(stats.duration_ms(), 250.0);
+    // Two in-phase sines at 0.8 peak at 1.6, about +4.1 dBFS.
+    assert!((stats.peak_dbfs() - 20.0 * 1.6f32.log10()).abs() < 0.01);
+    assert!(stats.clipped_samples() > 0);
+
+    let mut quiet = MixTrack::new();
+    quiet.gain = 0.5;
+    let stats = combiner
+        .combine_with_stats(vec![quiet.clone(), quiet], &CombineOptions::new())
+        .unwrap()
+        .stats();
+    assert_eq!(stats.clipped_samples(), 0);
+
+    let mut muted = MixTrack::new();
+    muted.mute = true;
+    let stats = combiner
+        .combine_with_stats(vec![muted.clone(), muted], &CombineOptions::new())
+        .unwrap()
+        .stats();
+    assert_eq!(stats.peak_dbfs(), f32::NEG_INFINITY);
+}
+
+#[wasm_bindgen_test]
+fn waveforms_give_peaks_per_bucket() {
+    // Loud left channel for the first half, quiet right channel for the second.
+    let mut samples = vec![0.0; 8];
+    samples[0] = -0.9;
+    samples[2] = 0.5;
+    samples[5] = 0.25;
+    let file = SingleAudioFile::from_pcm_f32(samples, 44100, 2).unwrap();
+
+    assert_eq!(file.waveform(2, false).unwrap(), [0.9, 0.25]);
+    assert_eq!(file.waveform(1, true).unwrap(), [0.9, 0.25]);
+    assert_eq!(file.waveform(2, true).unwrap(), [0.9, 0.0, 0.0, 0.25]);
+    // More buckets than frames gives one per frame.
+    assert_eq!(file.waveform(100, false).unwrap(), [0.9, 0.5, 0.25, 0.0]);
+    assert_eq!(
+        file.waveform(0, false).err().unwrap().code(),
+        "INVALID_OPTION"
+    );
+
+    let mp3 = SingleAudioFile::new(SINE_MP3.to_vec(), SingleAudioFileType::Mpeg);
+    let peaks = mp3.waveform(10, false).unwrap();
+    assert_eq!(peaks.len(), 10);
+    assert!(peaks[2..8].iter().all(|&p| p > 0.1 && p <= 1.0));
+
+    let combiner = AudioCombiner::new(vec![file]).unwrap();
+    let mut options = CombineOptions::new();
+    assert_eq!(
+        combiner
+            .combine_with_stats(vec![], &options)
+            .unwrap()
+            .waveform(),
+        None
+    );
+    options.waveform_buckets = 2;
+    options.waveform_per_channel = true;
+    let result = combiner.combine_with_stats(vec![], &options).unwrap();
+    assert_eq!(result.waveform().unwrap(), [0.9, 0.0, 0.0, 0.25]);
+}
+
+#[wasm_bindgen_test]
+fn silence_is_detected_and_trimmed_from_track_ends() {
+    let ms = |ms: usize| ms * 441 / 10;
+    // 200 ms of noise at -60 dBFS, a tone with a 30 ms gap, then 300 ms of
+    // digital silence.
+    let tone = stereo_sine(441.0, 44100, ms(250), 0.5);
+    let mut samples: Vec<f32> = (0..ms(200) * 2)
+        .map(|i| if i % 4 < 2 { 0.001 } else { -0.001 })
+        .collect();
+    samples.extend(&tone);
+    samples.extend(vec![0.0; ms(30) * 2]);
+    samples.extend(&tone[..ms(220) * 2]);
+    samples.extend(vec![0.0; ms(300) * 2]);
+    let file = || SingleAudioFile::from_pcm_f32(samples.clone(), 44100, 2).unwrap();
+
+    let spans = |min_ms: f64| -> Vec<(f64, f64)> {
+        file()
+            .detect_silence(-40.0, min_ms)
+            .unwrap()
+            .iter()
+            .map(|range| (range.start_ms, range.end_ms))
+            .collect()
+    };
+    assert_eq!(spans(100.0), [(0.0, 200.0), (700.0, 1000.0)]);
+    assert_eq!(spans(20.0), [(0.0, 200.0), (450.0, 480.0), (700.0, 1000.0)]);
+    // Below the noise floor only the digital silence counts.
+    assert_eq!(
+        file()
+            .detect_silence(-80.0, 0.0)
+            .unwrap()
+            .iter()
+            .map(|range| (range.start_ms, range.end_ms))
+            .collect::<Vec<_>>(),
+        [(450.0, 480.0), (700.0, 1000.0)]
+    );
+
+    let combiner = AudioCombiner::new(vec![file()]).unwrap();
+    let mut options = CombineOptions::new();
+    options.bit_depth = BitDepth::Float32;
+    let render = |track: MixTrack| -> (f64, Vec<f32>) {
+        let result = combiner.combine_with_stats(vec![track], &options).unwrap();
+        let left = riff_chunk(&result.file().bytes(), b"data")
+            .unwrap()
+            .chunks_exact(8)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        (result.stats().duration_ms(), left)
+    };
+    let mut track = MixTrack::new();
+    track.auto_trim_silence = Some(-40.0);
+    let (duration, trimmed) = render(track.clone());
+    assert_eq!(duration, 500.0);
+    assert_eq!(
+        trimmed[..100],
+        tone.iter()
+            .step_by(2)
+            .take(100)
+            .copied()
+            .collect::<Vec<_>>()[..]
+    );
+
+    // Offsets place the audible part, and fades start where it does.
+    track.offset_ms = 100.0;
+    track.fade_in_ms = 10.0;
+    let (duration, placed) = render(track);
+    assert_eq!(duration, 600.0);
+    assert!(placed[..ms(100)].iter().all(|&s| s == 0.0));
+    let n = ms(5);
+    assert!((placed[ms(100) + n] / trimmed[n] - 0.5).abs() < 0.01);
+}
+
+#[wasm_bindgen_test]
+fn levels_measure_peak_and_rms_and_suggest_gains() {
+    // A square wave at 0.5 has its peak and RMS both at -6 dBFS.
+    let square: Vec<f32> = (0..44100)
+        .flat_map(|i| {
+            let s = if i % 100 < 50 { 0.5 } else { -0.5 };
+            [s, s]
+        })
+        .collect();
+    let levels = SingleAudioFile::from_pcm_f32(square.clone(), 44100, 2)
+        .unwrap()
+        .levels()
+        .unwrap();
+    assert!((levels.peak_dbfs() - 20.0 * 0.5f32.log10()).abs() < 1e-4);
+    assert!((levels.rms_dbfs() - 20.0 * 0.5f32.log10()).abs() < 1e-4);
+    assert_eq!(levels.duration_ms(), 1000.0);
+    assert!(!levels.is_silent());
+
+    // Mono PCM measures the same as its stereo upmix.
+    let mono: Vec<f32> = square.iter().step_by(2).copied().collect();
+    assert_eq!(
+        SingleAudioFile::from_pcm_f32(mono, 44100, 1)
+            .unwrap()
+            .levels()
+            .unwrap(),
+        levels
+    );
+
+    let silent = SingleAudioFile::silence(500.0, 44100)
+        .unwrap()
+        .levels()
+        .unwrap();
+    assert!(silent.is_silent());
+    assert_eq!(silent.peak_dbfs(), f32::NEG_INFINITY);
+    assert_eq!(silent.rms_dbfs(), f32::NEG_INFINITY);
+    assert_eq!(silent.duration_ms(), 500.0);
+
+    // A sine's RMS sits 3 dB under its peak.
+    let mp3 = SingleAudioFile::new(SINE_MP3.to_vec(), SingleAudioFileType::Mpeg)
+        .levels()
+        .unwrap();
+    assert!((mp3.peak_dbfs() - mp3.rms_dbfs() - 3.0).abs() < 0.5);
+    assert!(mp3.duration_ms() > 900.0);
+
+    let combiner = AudioCombiner::new(vec![
+        SingleAudioFile::from_pcm_f32(square, 44100, 2).unwrap(),
+        SingleAudioFile::silence(500.0, 44100).unwrap(),
+    ])
+    .unwrap();
+    let gains = combiner.suggest_gains(-12.0).unwrap();
+    assert!((gains[0] - db_to_linear(-12.0 - 20.0 * 0.5f32.log10())).abs() < 1e-4);
+    assert_eq!(gains[1], 1.0);
+    assert_eq!(
+        combiner.suggest_gains(f32::NAN).err().unwrap().code(),
+        "INVALID_OPTION"
+    );
+}
+
+/// An ID3v2.3 tag holding latin-1 text `frames` and a front cover.
+fn id3v2_tag(frames: &[(&[u8; 4], &str)], cover: &[u8]) -> Vec<u8> {
+    let frame = |id: &[u8; 4], content: Vec<u8>| {
+        let mut frame = id.to_vec();
+        frame.extend_from_slice(&(content.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&[0, 0]);
+        frame.extend(content);
+        frame
+    };
+    let mut body = Vec::new();
+    for (id, text) in frames {
+        body.extend(frame(id, [&[0][..], text.as_bytes()].concat()));
+    }
+    // Latin-1 text, the MIME type, picture type 3 (front cover), an empty
+    // description, then the image.
+    body.extend(frame(
+        b"APIC",
+        [&[0][..], b"image/png\0\x03\0", cover].concat(),
+    ));
+
+    let mut tag = b"ID3\x03\x00\x00".to_vec();
+    let size = body.len() as u32;
+    tag.extend([size >> 21, size >> 14, size >> 7, size].map(|b| (b & 0x7f) as u8));
+    tag.extend(body);
+    tag
+}
+
+#[wasm_bindgen_test]
+fn metadata_reads_tags_and_cover_art() {
+    let cover = b"\x89PNG\r\n\x1a\nnot really";
+    let mut mp3 = id3v2_tag(
+        &[
+            (b"TIT2", "Sine"),
+            (b"TPE1", "Oscillator"),
+            (b"TALB", "Test Tones"),
+            (b"TCON", "Electronic"),
+        ],
+        cover,
+    );
+    mp3.extend_from_slice(SINE_MP3);
+    let metadata = SingleAudioFile::new(mp3, SingleAudioFileType::Mpeg)
+        .metadata()
+        .unwrap();
+    assert_eq!(metadata.title().as_deref(), Some("Sine"));
+    assert_eq!(metadata.artist().as_deref(), Some("Oscillator"));
+    assert_eq!(metadata.album().as_deref(), Some("Test Tones"));
+    let tags: Vec<(String, String)> = metadata
+        .tags()
+        .iter()
+        .map(|tag| (tag.key(), tag.value()))
+        .collect();
+    assert!(tags.contains(&("TCON".into(), "Electronic".into())));
+    let art = metadata.cover_art().unwrap();
+    assert_eq!(art.mime_type(), "image/png");
+    assert_eq!(art.bytes(), cover);
+
+    // RIFF INFO values are NUL-terminated, which is not part of the value.
+    let wav = wav_bytes(WavEncoding::Pcm16, 2, 44100, &[0.0; 8]);
+    let mut info = b"INFO".to_vec();
+    for (id, value) in [(b"INAM", &b"Song\0\0"[..]), (b"IART", b"Band\0\0")] {
+        info.extend_from_slice(id);
+        info.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        info.extend_from_slice(value);
+    }
+    let data = wav.windows(4).position(|w| w == b"data").unwrap();
+    let mut tagged = wav[..data].to_vec();
+    tagged.extend_from_slice(b"LIST");
+    tagged.extend_from_slice(&(info.len() as u32).to_le_bytes());
+    tagged.extend(info);
+    tagged.extend_from_slice(&wav[data..]);
+    let riff_size = tagged.len() as u32 - 8;
+    tagged[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    let metadata = SingleAudioFile::new(tagged, SingleAudioFileType::Wav)
+        .metadata()
+        .unwrap();
+    assert_eq!(metadata.title().as_deref(), Some("Song"));
+    assert_eq!(metadata.artist().as_deref(), Some("Band"));
+    assert_eq!(metadata.album(), None);
+    assert_eq!(metadata.cover_art(), None);
+
+    for untagged in [
+        SingleAudioFile::new(SINE_MP3.to_vec(), SingleAudioFileType::Mpeg),
+        SingleAudioFile::from_pcm_f32(vec![0.0; 4], 44100, 2).unwrap(),
+    ] {
+        let metadata = untagged.metadata().unwrap();
+        assert_eq!(metadata.title(), None);
+        assert!(metadata.tags().is_empty());
+        assert_eq!(metadata.cover_art(), None);
+    }
+}
+
+#[wasm_bindgen_test]
+fn output_metadata_is_written_as_riff_info() {
+    let combiner = AudioCombiner::new(vec![
+        SingleAudioFile::sine(441.0, 100.0, 44100, 0.5).unwrap()
+    ])
+    .unwrap();
+    let mut options = CombineOptions::new();
+    let untagged = combiner.combine_tracks(vec![], &options).unwrap();
+
+    let mut metadata = OutputMetadata::new();
+    metadata.title = Some("Mix".into());
+    metadata.artist = Some("Combiner".into());
+    metadata.creation_date = Some("2024-05-01".into());
+    for bit_depth in [BitDepth::Int16, BitDepth::Float32] {
+        options.bit_depth = bit_depth;
+        options.metadata = Some(metadata.clone());
+        let wav = combiner.combine_tracks(vec![], &options).unwrap().bytes();
+        let riff_size = u32::from_le_bytes([wav[4], wav[5], wav[6], wav[7]]);
+        assert_eq!(riff_size as usize, wav.len() - 8);
+
+        let read = SingleAudioFile::new(wav.clone(), SingleAudioFileType::Wav)
+            .metadata()
+            .unwrap();
+        assert_eq!(read.title().as_deref(), Some("Mix"));
+        assert_eq!(read.artist().as_deref(), Some("Combiner"));
+        let tags: Vec<(String, String)> =
+            read.tags().iter().map(|t| (t.key(), t.value())).collect();
+        assert!(tags.contains(&("ICRD".into(), "2024-05-01".into())));
+
+        // The samples are untouched by the tags.
+        options.metadata = None;
+        let plain = combiner.combine_tracks(vec![], &options).unwrap().bytes();
+        assert_eq!(riff_chunk(&wav, b"data"), riff_chunk(&plain, b"data"));
+    }
+
+    // Metadata with nothing set writes no INFO chunk.
+    options.bit_depth = BitDepth::Int16;
+    options.metadata = Some(OutputMetadata::new());
+    assert_eq!(
+        combiner.combine_tracks(vec![], &options).unwrap().bytes(),
+        untagged.bytes()
+    );
+    assert!(
+        SingleAudioFile::new(untagged.bytes(), SingleAudioFileType::Wav)
+            .metadata()
+            .unwrap()
+            .tags()
+            .is_empty()
+    );
+}
+
+#[wasm_bindgen_test]
+fn corrupt_packets_fail_or_become_warnings() {
+    // Scrambles 400 bytes 30% of the way in, past the headers.
+    let corrupt = |bytes: &[u8], r#type| {
+        let mut bytes = bytes.to_vec();
+        let start = bytes.len() * 3 / 10;
+        for (i, b) in bytes[start..start + 400].iter_mut().enumerate() {
+            *b = (i * 37 % 251) as u8;
+        }
+        SingleAudioFile::new(bytes, r#type)
+    };
+    let clean = |bytes: &[u8], r#type| {
+        AudioCombiner::new(vec![SingleAudioFile::new(bytes.to_vec(), r#type)])
+            .unwrap()
+            .combine_with_stats(vec![], &CombineOptions::new())
+            .unwrap()
+    };
+
+    for (bytes, r#type) in [
+        (SINE_MP3, SingleAudioFileType::Mpeg),
+        (SINE_M4A, SingleAudioFileType::Aac),
+    ] {
+        let sine = || SingleAudioFile::sine(441.0, 100.0, 44100, 0.5).unwrap();
+        let error = AudioCombiner::new(vec![sine(), corrupt(bytes, r#type)])
+            .err()
+            .unwrap();
+        assert_eq!(error.code(), "CORRUPT_PACKET");
+        assert_eq!(error.index(), Some(1));
+
+        let mut tolerant = corrupt(bytes, r#type);
+        tolerant.tolerate_decode_errors = true;
+        let result = AudioCombiner::new(vec![sine(), tolerant])
+            .unwrap()
+            .combine_with_stats(vec![], &CombineOptions::new())
+            .unwrap();
+        let warnings = result.warnings();
+        assert!(!warnings.is_empty());
+        for warning in &warnings {
+            assert_eq!(warning.index(), 1);
+            assert!(warning.timestamp_ms() > 200.0 && warning.timestamp_ms() < 500.0);
+            assert!(!warning.message().is_empty());
+        }
+        // Skipped packets are filled with silence. MP4 indexes every packet
+        // so nothing after them moves, whereas the MP3 reader silently
+        // resyncs past bytes it cannot frame.
+        let expected = clean(bytes, r#type).stats().duration_ms();
+        if r#type == SingleAudioFileType::Aac {
+            assert_eq!(result.stats().duration_ms(), expected);
+        } else {
+            assert!(result.stats().duration_ms() <= expected);
+        }
+    }
+    assert!(clean(SINE_MP3, SingleAudioFileType::Mpeg)
+        .warnings()
+        .is_empty());
+}
+
+#[wasm_bindgen_test]
+fn chained_ogg_decodes_every_link_at_the_first_rate() {
+    let duration = |bytes: &[u8]| {
+        AudioCombiner::new(vec![SingleAudioFile::new(
+            bytes.to_vec(),
+            SingleAudioFileType::Ogg,
+        )])
+        .unwrap()
+        .combine_with_stats(vec![], &CombineOptions::new())
+        .unwrap()
+        .stats()
+        .duration_ms()
+    };
+    let links = duration(SINE_OGG) + duration(SINE_OGG_48000);
+    // The second link is resampled from 48 kHz, so allow a frame either way.
+    assert!((duration(CHAINED_OGG) - links).abs() <= 1000.0 / 44100.0);
+
+    // The 48 kHz link is not left playing 9% slow at 44.1 kHz: the sine
+    // still crosses zero 880 times a second.
+    let combiner = AudioCombiner::new(vec![SingleAudioFile::new(
+        CHAINED_OGG.to_vec(),
+        SingleAudioFileType::Ogg,
+    )])
+    .unwrap();
+    let samples = output_samples(&combiner.combine(vec![]).unwrap().bytes());
+    let second_link = (duration(SINE_OGG) * 44.1) as usize + 4410;
+    let left: Vec<i16> = samples[second_link * 2..(second_link + 22050) * 2]
+        .iter()
+        .step_by(2)
+        .copied()
+        .collect();
+    let crossings = left.windows(2).filter(|w| (w[0] < 0) != (w[1] < 0)).count();
+    assert!(
+        (crossings as i32 - 440).abs() <= 2,
+        "{} crossings",
+        crossings
+    );
+}
+
+/// An MPEG-1 layer III Info frame at 128 kbps and 44.1 kHz, like `SINE_MP3`'s
+/// frames, whose LAME extension records `delay` and `padding` frames around
+/// `frames` MPEG frames of audio.
+fn lame_info_frame(frames: u32, delay: u32, padding: u32) -> Vec<u8> {
+    // Header, then side information left zeroed.
+    let mut frame = vec![0xff, 0xfb, 0x90, 0x64];
+    frame.resize(4 + 32, 0);
+    frame.extend_from_slice(b"Info");
+    frame.extend_from_slice(&1u32.to_be_bytes());
+    frame.extend_from_slice(&frames.to_be_bytes());
+    // Encoder, revision, lowpass, peak, both gains, flags and bitrate, then
+    // the encoder's delay and padding as two 12-bit values. Decoders add
+    // their own 529 frames on top.
+    frame.extend_from_slice(b"Lavf58.76");
+    frame.resize(frame.len() + 12, 0);
+    let trim = delay << 12 | (padding + 529);
+    frame.extend_from_slice(&trim.to_be_bytes()[1..]);
+    frame.resize(417, 0);
+    frame
+}
+
+#[wasm_bindgen_test]
+fn encoder_delay_and_padding_are_dropped_from_mp3s() {
+    // The tone in `SINE_MP3` starts after LAME's usual 576 frames plus the
+    // decoder's 529 and runs past the last frame. Cutting its 38 frames of
+    // 1152 down to 19 cycles of 2205 gives a piece that ends where it
+    // starts, as though split from one longer tone.
+    let piece = 19 * 2205;
+    let padding = 38 * 1152 - 1105 - piece as u32;
+    let mut mp3 = lame_info_frame(38, 576, padding);
+    mp3.extend_from_slice(SINE_MP3);
+    let file = || SingleAudioFile::new(mp3.clone(), SingleAudioFileType::Mpeg);
+
+    let mixed = AudioCombiner::new(vec![file()])
+        .unwrap()
+        .combine(vec![])
+        .unwrap();
+    assert_eq!(output_samples(&mixed.bytes()).len(), piece * 2);
+
+    let combiner = AudioCombiner::new(vec![file(), file()]).unwrap();
+    let mut options = CombineOptions::new();
+    options.mode = CombineMode::Concat;
+    let samples = output_samples(
+        &combiner
+            .combine_with_gains_and_options(vec![1.0, 1.0], &options)
+            .unwrap()
+            .bytes(),
+    );
+    assert_eq!(samples.len(), piece * 4);
+
+    // No gap or click at the joint: every 10 ms keeps the tone's level, and
+    // no step between samples is much bigger than the tone makes on its own.
+    // A cut mid-cycle would jump by up to the tone's full amplitude.
+    let left: Vec<f64> = samples.iter().step_by(2).map(|&s| s as f64).collect();
+    let rms =
+        |window: &[f64]| (window.iter().map(|s| s * s).sum::<f64>() / window.len() as f64).sqrt();
+    let max_step = |window: &[f64]| {
+        window
+            .windows(2)
+            .map(|w| (w[1] - w[0]).abs())
+            .fold(0.0, f64::max)
+    };
+    let steady = &left[piece / 2..piece / 2 + 4410];
+    let joint = &left[piece - 2205..piece + 2205];
+    for window in joint.chunks_exact(441) {
+        assert!(
+            (rms(window) / rms(steady) - 1.0).abs() < 0.05,
+            "{} against {}",
+            rms(window),
+            rms(steady)
+        );
+    }
+    assert!(
+        max_step(joint) <= max_step(steady) * 1.25,
+        "{} against {}",
+        max_step(joint),
+        max_step(steady)
+    );
+}
+
+/// Power near `freq` in `signal`, summed over Hann-windowed blocks so a
+/// noise signal gives a steady estimate.
+fn band_power(signal: &[f32], sample_rate: u32, freq: f64) -> f64 {
+    const BLOCK: usize = 4096;
+    let coeff = 2.0 * (2.0 * std::f64::consts::PI * freq / sample_rate as f64).cos();
+    signal
+        .chunks_exact(BLOCK)
+        .map(|block| {
+            // Goertzel's algorithm for the one frequency.
+            let (mut s1, mut s2) = (0.0, 0.0);
+            for (i, &x) in block.iter().enumerate() {
+                let window =
+                    0.5 - 0.5 * (2.0 * std::f64::consts::PI * i as f64 / BLOCK as f64).cos();
+                let s = x as f64 * window + coeff * s1 - s2;
+                s2 = s1;
+                s1 = s;
+            }
+            s1 * s1 + s2 * s2 - coeff * s1 * s2
+        })
+        .sum()
+}
+
+#[wasm_bindgen_test]
+fn highpass_and_lowpass_cut_an_octave_past_the_cutoff() {
+    // Two seconds of white noise at 48 kHz, the same on both sides.
+    let mut state = 1u32;
+    let noise: Vec<f32> = (0..2 * 48000)
+        .flat_map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            let s = (state as f64 / u32::MAX as f64 * 2.0 - 1.0) as f32 * 0.5;
+            [s, s]
+        })
+        .collect();
+    let mut options = CombineOptions::new();
+    options.sample_rate = 48000;
+    options.bit_depth = BitDepth::Float32;
+    let render = |track: MixTrack| {
+        let combiner =
+            AudioCombiner::new(vec![
+                SingleAudioFile::from_pcm_f32(noise.clone(), 48000, 2).unwrap()
+            ])
+            .unwrap();
+        let wav = combiner
+            .combine_tracks(vec![track], &options)
+            .unwrap()
+            .bytes();
+        riff_chunk(&wav, b"data")
+            .unwrap()
+            .chunks_exact(8)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect::<Vec<f32>>()
+    };
+
+    let dry = render(MixTrack::new());
+    let mut track = MixTrack::new();
+    track.highpass_hz = Some(200.0);
+    track.lowpass_hz = Some(4000.0);
+    let wet = render(track);
+    let gain_db =
+        |freq: f64| 10.0 * (band_power(&wet, 48000, freq) / band_power(&dry, 48000, freq)).log10();
+
+    assert!(gain_db(100.0) < -20.0, "{} dB at 100 Hz", gain_db(100.0));
+    assert!(gain_db(8000.0) < -20.0, "{} dB at 8 kHz", gain_db(8000.0));
+    assert!(
+        gain_db(1000.0).abs() < 0.5,
+        "{} dB at 1 kHz",
+        gain_db(1000.0)
+    );
+    // Butterworth filters are 3 dB down at the cutoff, which lands there
+    // only if the file's own rate was used.
+    assert!(
+        (gain_db(4000.0) + 3.0).abs() < 0.5,
+        "{} dB at 4 kHz",
+        gain_db(4000.0)
+    );
+
+    let mut track = MixTrack::new();
+    track.lowpass_hz = Some(0.0);
+    let error = AudioCombiner::new(vec![SingleAudioFile::silence(10.0, 48000).unwrap()])
+        .unwrap()
+        .combine_tracks(vec![track], &options)
+        .err()
+        .unwrap();
+    assert_eq!(error.code(), "INVALID_TRACK");
+}
+
+#[wasm_bindgen_test]
+fn eq_bands_set_the_gain_at_their_centres() {
+    // Gain in dB of a sine at `freq_hz` through `eq`, once the filters have
+    // settled. A second of sine at multiples of 1.25 Hz leaves whole cycles.
+    let gain_db = |eq: Option<Equalizer>, freq_hz: f64, sample_rate: u32| {
+        let mut options = CombineOptions::new();
+        options.sample_rate = sample_rate;
+        options.bit_depth = BitDepth::Float32;
+        let mut track = MixTrack::new();
+        track.eq = eq;
+        let sine = SingleAudioFile::sine(freq_hz, 1000.0, sample_rate, 0.25).unwrap();
+        let wav = AudioCombiner::new(vec![sine])
+            .unwrap()
+            .combine_tracks(vec![track], &options)
+            .unwrap()
+            .bytes();
+        let left: Vec<f64> = riff_chunk(&wav, b"data")
+            .unwrap()
+            .chunks_exact(8)
+            .skip(sample_rate as usize / 5)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64)
+            .collect();
+        let rms = (left.iter().map(|s| s * s).sum::<f64>() / left.len() as f64).sqrt();
+        20.0 * (rms / (0.25 / 2f64.sqrt())).log10()
+    };
+    let assert_gain = |eq: Equalizer, freq_hz: f64, sample_rate: u32, expected: f64| {
+        let gain = gain_db(Some(eq), freq_hz, sample_rate);
+        assert!(
+            (gain - expected).abs() < 0.1,
+            "{} dB at {} Hz, expected {}",
+            gain,
+            freq_hz,
+            expected
+        );
+    };
+
+    // Shelves are half way at their frequency and all the way well past it.
+    let mut low = Equalizer::new();
+    low.low_gain_db = 6.0;
+    assert_gain(low, 100.0, 44100, 3.0);
+    assert!(gain_db(Some(low), 20.0, 44100) > 5.7);
+    assert_gain(low, 5000.0, 44100, 0.0);
+
+    let mut mid = Equalizer::new();
+    mid.mid_gain_db = -9.0;
+    assert_gain(mid, 1000.0, 44100, -9.0);
+    assert_gain(mid, 10_000.0, 44100, 0.0);
+    // Worked out again for a file at another rate.
+    assert_gain(mid, 1000.0, 48000, -9.0);
+
+    let mut high = Equalizer::new();
+    high.high_gain_db = 4.0;
+    high.high_freq = 8000.0;
+    assert_gain(high, 8000.0, 44100, 2.0);
+    assert!(gain_db(Some(high), 18_000.0, 44100) > 3.8);
+    assert_gain(high, 500.0, 44100, 0.0);
+
+    // A flat EQ is skipped outright.
+    assert_eq!(
+        gain_db(Some(Equalizer::new()), 440.0, 44100),
+        gain_db(None, 440.0, 44100)
+    );
+
+    let mut track = MixTrack::new();
+    let mut eq = Equalizer::new();
+    eq.mid_q = 0.0;
+    track.eq = Some(eq);
+    let error = AudioCombiner::new(vec![SingleAudioFile::silence(10.0, 44100).unwrap()])
+        .unwrap()
+        .combine_tracks(vec![track], &CombineOptions::new())
+        .err()
+        .unwrap();
+    assert_eq!(error.code(), "INVALID_TRACK");
+}
+
+#[wasm_bindgen_test]
+fn capabilities_list_what_this_build_supports() {
+    let capabilities = capabilities();
+    let inputs = capabilities.input_types();
+    // This suite runs with every codec built in.
+    for file_type in [
+        SingleAudioFileType::Wav,
+        SingleAudioFileType::Mpeg,
+        SingleAudioFileType::Ogg,
+        SingleAudioFileType::Flac,
+        SingleAudioFileType::Aac,
+        SingleAudioFileType::Webm,
+        SingleAudioFileType::Aiff,
+        SingleAudioFileType::Auto,
+        SingleAudioFileType::Pcm,
+    ] {
+        assert!(inputs.contains(&file_type), "{:?} missing", file_type);
+    }
+    let outputs = capabilities.output_formats();
+    assert!(outputs.contains(&OutputFormat::Wav));
+    assert_eq!(
+        outputs.contains(&OutputFormat::Mpeg),
+        cfg!(feature = "mp3-output")
+    );
+    assert_eq!(capabilities.threads(), cfg!(feature = "threads"));
+    assert_eq!(capabilities.version(), env!("CARGO_PKG_VERSION"));
+}
+
+#[wasm_bindgen_test]
+fn describe_summarizes_files_and_debug_logging_runs() {
+    let mono: Vec<f32> = stereo_sine(440.0, 22050, 5512, 0.5)
+        .into_iter()
+        .step_by(2)
+        .collect();
+    let combiner = AudioCombiner::new(vec![
+        SingleAudioFile::from_pcm_f32(stereo_sine(440.0, 44100, 44100, 0.5), 44100, 2).unwrap(),
+        SingleAudioFile::from_pcm_f32(mono, 22050, 1).unwrap(),
+    ])
+    .unwrap();
+    assert_eq!(
+        combiner.describe(),
+        "2 files\n0: 44100 Hz stereo, 1000.0 ms\n1: 22050 Hz mono, 250.0 ms"
+    );
+
+    // Resampling the second file and the timings go to the console.
+    set_log_level(LogLevel::Debug);
+    let result = combiner.combine_with_stats(vec![], &CombineOptions::new());
+    set_log_level(LogLevel::Off);
+    assert!(result.is_ok());
+}
+
+#[wasm_bindgen_test]
+fn config_objects_set_tracks_and_output() {
+    let combiner = AudioCombiner::new(vec![
+        SingleAudioFile::from_pcm_f32(stereo_sine(440.0, 48000, 4800, 0.5), 48000, 2).unwrap(),
+        SingleAudioFile::from_pcm_f32(ramp(4800 * 2), 48000, 2).unwrap(),
+    ])
+    .unwrap();
+    let config = |json: &str| -> MixConfig { js_sys::JSON::parse(json).unwrap().unchecked_into() };
+
+    let from_config = combiner
+        .combine_with_config(config(
+            r#"{
+                "tracks": [
+                    { "gain": 0.5, "fade_curve": "EqualPower", "fade_in_ms": 10 },
+                    { "pan": -0.5, "offset_ms": 5, "ducking": { "source": 0 } }
+                ],
+                "output": { "sample_rate": 48000, "bit_depth": "Float32", "format": "Wav" },
+                "mode": "Mix",
+                "comment": "unknown fields are ignored"
+            }"#,
+        ))
+        .unwrap();
+    let mut first = MixTrack::new();
+    first.gain = 0.5;
+    first.fade_curve = FadeCurve::EqualPower;
+    first.fade_in_ms = 10.0;
+    let mut second = MixTrack::new();
+    second.pan = -0.5;
+    second.offset_ms = 5.0;
+    second.ducking = Some(Ducking::new(0));
+    let mut options = CombineOptions::new();
+    options.sample_rate = 48000;
+    options.bit_depth = BitDepth::Float32;
+    let expected = combiner
+        .combine_tracks(vec![first, second], &options)
+        .unwrap();
+    assert_eq!(from_config.bytes(), expected.bytes());
+
+    // Everything is optional.
+    assert_eq!(
+        combiner.combine_with_config(config("{}")).unwrap().bytes(),
+        combiner
+            .combine_tracks(vec![], &CombineOptions::new())
+            .unwrap()
+            .bytes()
+    );
+
+    let error = combiner
+        .combine_with_config(config(r#"{ "tracks": [{}, { "gain": "loud" }] }"#))
+        .err()
+        .unwrap();
+    assert_eq!(error.code(), "INVALID_OPTION");
+    assert!(
+        error.to_string().starts_with("tracks[1].gain: "),
+        "{}",
+        error
+    );
+    let error = combiner
+        .combine_with_config(config(r#"{ "output": { "format": "Flac" } }"#))
+        .err()
+        .unwrap();
+    assert!(
+        error.to_string().starts_with("output.format: "),
+        "{}",
+        error
+    );
+}
+
+#[wasm_bindgen_test]
+fn files_report_a_mime_type_and_extension() {
+    let describe = |file: SingleAudioFile| (file.mime_type(), file.suggested_extension());
+    let pair = |mime: &str, extension: &str| (mime.to_string(), extension.to_string());
+
+    let combiner =
+        AudioCombiner::new(vec![SingleAudioFile::silence(10.0, 44100).unwrap()]).unwrap();
+    let wav = combiner.combine(vec![]).unwrap();
+    assert_eq!(wav.byte_length(), wav.bytes().len());
+    assert_eq!(describe(wav), pair("audio/wav", "wav"));
+    assert_eq!(
+        describe(SingleAudioFile::new(
+            SINE_M4A.to_vec(),
+            SingleAudioFileType::Aac
+        )),
+        pair("audio/mp4", "m4a")
+    );
+    // `Auto` goes by the magic bytes, whatever they turn out to be.
+    assert_eq!(
+        describe(SingleAudioFile::new(
+            SINE_MP3.to_vec(),
+            SingleAudioFileType::Auto
+        )),
+        pair("audio/mpeg", "mp3")
+    );
+    assert_eq!(
+        describe(SingleAudioFile::new(
+            SINE_OGG.to_vec(),
+            SingleAudioFileType::Auto
+        )),
+        pair("audio/ogg", "ogg")
+    );
+    assert_eq!(
+        describe(SingleAudioFile::new(vec![0; 16], SingleAudioFileType::Auto)),
+        pair("application/octet-stream", "bin")
+    );
+    assert_eq!(
+        describe(SingleAudioFile::from_pcm_f32(vec![0.0; 4], 44100, 2).unwrap()),
+        pair("application/octet-stream", "pcm")
+    );
+}
+
+#[wasm_bindgen_test]
+fn single_files_transcode_to_wav_keeping_their_channels() {
+    let ogg = SingleAudioFile::new(SINE_OGG.to_vec(), SingleAudioFileType::Ogg);
+    let wav = ogg.to_wav(&CombineOptions::new()).unwrap();
+    assert_eq!(wav.r#type, SingleAudioFileType::Wav);
+    let info = wav.probe().unwrap();
+    assert_eq!((info.sample_rate(), info.channels()), (44100, 2));
+    // Decoded as `combine` decodes, so a lone file comes out the same.
+    let combiner = AudioCombiner::new(vec![ogg]).unwrap();
+    assert_eq!(wav.bytes(), combiner.combine(vec![]).unwrap().bytes());
+
+    let mono = SingleAudioFile::new(SINE_MONO_MP3.to_vec(), SingleAudioFileType::Mpeg);
+    let mut options = CombineOptions::new();
+    options.sample_rate = 48000;
+    options.bit_depth = BitDepth::Float32;
+    let wav = mono.to_wav(&options).unwrap();
+    let info = wav.probe().unwrap();
+    assert_eq!((info.sample_rate(), info.channels()), (48000, 1));
+    let source_ms = mono.probe().unwrap().duration_ms().unwrap();
+    assert!((info.duration_ms().unwrap() - source_ms).abs() < 1.0);
+
+    options.sample_rate = 1;
+    assert_eq!(
+        mono.to_wav(&options).err().unwrap().code(),
+        "INVALID_OPTION"
+    );
+}
+
+#[wasm_bindgen_test]
+fn slicing_then_mixing_matches_a_trimmed_mix() {
+    let ogg = || SingleAudioFile::new(SINE_OGG.to_vec(), SingleAudioFileType::Ogg);
+    let mut options = CombineOptions::new();
+    options.bit_depth = BitDepth::Float32;
+    let render = |file: SingleAudioFile, track: MixTrack| -> Vec<u8> {
+        AudioCombiner::new(vec![file])
+            .unwrap()
+            .combine_tracks(vec![track], &options)
+            .unwrap()
+            .bytes()
+    };
+    let mut trimmed = MixTrack::new();
+    trimmed.trim_start_ms = 250.3;
+    trimmed.trim_end_ms = Some(750.6);
+
+    // Vorbis pages hold many frames, yet both cuts land on the same frame.
+    let slice = ogg().slice(250.3, 750.6, None).unwrap();
+    assert_eq!(slice.r#type, SingleAudioFileType::Pcm);
+    assert_eq!(
+        render(slice, MixTrack::new()),
+        render(ogg(), trimmed.clone())
+    );
+
+    let wav = ogg().slice(250.3, 750.6, Some(options.clone())).unwrap();
+    assert_eq!(wav.r#type, SingleAudioFileType::Wav);
+    assert_eq!(wav.bytes(), render(ogg(), trimmed));
+
+    let empty = ogg().slice(100.0, 100.0, None).unwrap();
+    assert!(empty.bytes().is_empty());
+
+    let code =
+        |start_ms: f64, end_ms: f64| ogg().slice(start_ms, end_ms, None).err().unwrap().code();
+    assert_eq!(code(500.0, 250.0), "INVALID_OPTION");
+    assert_eq!(code(-1.0, 250.0), "INVALID_OPTION");
+    assert_eq!(code(0.0, f64::NAN), "INVALID_OPTION");
+    assert_eq!(code(0.0, 60_000.0), "SLICE_OUT_OF_RANGE");
+}
+
+#[wasm_bindgen_test]
+fn segments_concatenate_to_the_full_mix() {
+    let combiner = AudioCombiner::new(vec![
+        SingleAudioFile::new(SINE_OGG.to_vec(), SingleAudioFileType::Ogg),
+        SingleAudioFile::from_pcm_f32(stereo_sine(220.0, 44100, 30000, 0.3), 44100, 2).unwrap(),
+    ])
+    .unwrap();
+    let options = CombineOptions::new();
+    let full = combiner.combine_tracks(vec![], &options).unwrap();
+    let segments = combiner.combine_segmented(vec![], 300.0, &options).unwrap();
+
+    let frames = output_samples(&full.bytes()).len() / 2;
+    assert_eq!(segments.len(), frames.div_ceil(13230));
+    let mut joined = Vec::new();
+    for (index, segment) in segments.iter().enumerate() {
+        let info = segment.probe().unwrap();
+        assert_eq!((info.sample_rate(), info.channels()), (44100, 2));
+        let bytes = segment.bytes();
+        let data = riff_chunk(&bytes, b"data").unwrap();
+        if index + 1 < segments.len() {
+            assert_eq!(data.len(), 13230 * 4);
+        }
+        joined.extend_from_slice(data);
+    }
+    // Dither runs on across segments, so the bytes match exactly.
+    assert_eq!(joined, riff_chunk(&full.bytes(), b"data").unwrap());
+
+    let code = |segment_ms: f64, options: &CombineOptions| {
+        combiner
+            .combine_segmented(vec![], segment_ms, options)
+            .err()
+            .unwrap()
+            .code()
+    };
+    assert_eq!(code(0.01, &options), "INVALID_OPTION");
+    assert_eq!(code(f64::NAN, &options), "INVALID_OPTION");
+    let mut ogg = CombineOptions::new();
+    ogg.format = OutputFormat::Ogg;
+    assert_eq!(code(300.0, &ogg), "INVALID_OPTION");
+}
+
+#[wasm_bindgen_test]
+fn cached_files_are_reused_until_their_shape_changes() {
+    let tone = || SingleAudioFile::from_pcm_f32(stereo_sine(440.0, 48000, 96000, 0.5), 48000, 2);
+    let mut combiner = AudioCombiner::new(vec![tone().unwrap(), tone().unwrap()]).unwrap();
+    let options = CombineOptions::new();
+    let uncached = combiner.combine_with_stats(vec![], &options).unwrap();
+    assert_eq!(combiner.cache_memory_bytes(), 0);
+
+    combiner.enable_decode_cache(true);
+    let first = combiner.combine_with_stats(vec![], &options).unwrap();
+    assert_eq!(first.stats().cached_files(), 0);
+    // Both files resampled to 44.1 kHz stereo.
+    assert_eq!(combiner.cache_memory_bytes(), 2 * 88200 * 2 * 4);
+    let mut quieter = MixTrack::new();
+    quieter.gain = 0.5;
+    let second = combiner
+        .combine_with_stats(vec![quieter.clone()], &options)
+        .unwrap();
+    assert_eq!(second.stats().cached_files(), 2);
+    assert!(
+        second.stats().mix_ms() < first.stats().mix_ms(),
+        "{} ms cached, {} ms without",
+        second.stats().mix_ms(),
+        first.stats().mix_ms()
+    );
+    let again = combiner.combine_with_stats(vec![], &options).unwrap();
+    assert_eq!(again.file().bytes(), uncached.file().bytes());
+
+    // A new trim reshapes the file, so only the other one is reused.
+    quieter.trim_start_ms = 100.0;
+    let trimmed = combiner
+        .combine_with_stats(vec![quieter], &options)
+        .unwrap();
+    assert_eq!(trimmed.stats().cached_files(), 1);
+
+    combiner.remove_file(0).unwrap();
+    combiner.add_file(tone().unwrap()).unwrap();
+    let changed = combiner.combine_with_stats(vec![], &options).unwrap();
+    assert_eq!(changed.stats().cached_files(), 1);
+
+    combiner.clear_cache();
+    assert_eq!(combiner.cache_memory_bytes(), 0);
+    combiner.enable_decode_cache(false);
+    let off = combiner.combine_with_stats(vec![], &options).unwrap();
+    assert_eq!(off.stats().cached_files(), 0);
+    assert_eq!(combiner.cache_memory_bytes(), 0);
+}
+
+#[wasm_bindgen_test]
+fn failed_files_can_be_skipped_keeping_every_other_index() {
+    let files = || {
+        let mut mp3 = SINE_MP3.to_vec();
+        let start = mp3.len() * 3 / 10;
+        for (i, b) in mp3[start..start + 400].iter_mut().enumerate() {
+            *b = (i * 37 % 251) as u8;
+        }
+        vec![
+            SingleAudioFile::sine(441.0, 500.0, 44100, 0.5).unwrap(),
+            SingleAudioFile::new(mp3, SingleAudioFileType::Mpeg),
+            SingleAudioFile::sine(660.0, 300.0, 44100, 0.5).unwrap(),
+            SingleAudioFile::new(b"RIFF, but not really".to_vec(), SingleAudioFileType::Wav),
+        ]
+    };
+    let tracks = |gains: &[f32]| -> Vec<MixTrack> {
+        gains
+            .iter()
+            .map(|&gain| {
+                let mut track = MixTrack::new();
+                track.gain = gain;
+                track
+            })
+            .collect()
+    };
+    let mut options = CombineOptions::new();
+    let error = AudioCombiner::combine_files(files(), tracks(&[1.0, 0.7, 0.5, 0.9]), &options)
+        .err()
+        .unwrap();
+    assert_eq!((error.code(), error.index()), ("CORRUPT_PACKET", Some(1)));
+
+    options.skip_failed_tracks = true;
+    let result =
+        AudioCombiner::combine_files(files(), tracks(&[1.0, 0.7, 0.5, 0.9]), &options).unwrap();
+    let skipped = result.skipped_tracks();
+    let summary: Vec<_> = skipped
+        .iter()
+        .map(|s| (s.index(), s.stage(), s.code()))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            (1, DecodeStage::Decode, "CORRUPT_PACKET".to_string()),
+            (3, DecodeStage::Probe, "UNSUPPORTED_FORMAT".to_string()),
+        ]
+    );
+    assert!(skipped[0].message().contains("file 1"));
+    assert_eq!(result.stats().track_gains(), [1.0, 0.0, 0.5, 0.0]);
+
+    // The good files keep their own gains, not those of the files before.
+    let good = files().into_iter().step_by(2).collect();
+    let expected = AudioCombiner::new(good)
+        .unwrap()
+        .combine_tracks(tracks(&[1.0, 0.5]), &options)
+        .unwrap();
+    assert_eq!(result.file().bytes(), expected.bytes());
+}
+
+#[wasm_bindgen_test]
+fn combine_files_gives_up_once_its_budget_runs_out() {
+    let files = || {
+        (0..3)
+            .map(|_| SingleAudioFile::new(SINE_OGG.to_vec(), SingleAudioFileType::Ogg))
+            .collect::<Vec<_>>()
+    };
+    let mut options = CombineOptions::new();
+    options.max_ms = Some(1e-6);
+    let error = AudioCombiner::combine_files(files(), vec![], &options)
+        .err()
+        .unwrap();
+    // The clock is only read every few packets, so the first file gets a
+    // little way in.
+    assert_eq!((error.code(), error.index()), ("TIMED_OUT", Some(0)));
+    let processed = js_sys::Reflect::get(&JsValue::from(error), &"processedMs".into()).unwrap();
+    let processed = js_sys::Float64Array::from(processed).to_vec();
+    assert_eq!(processed.len(), 3);
+    assert!(
+        processed[0] > 0.0 && processed[0] < 1000.0,
+        "{:?}",
+        processed
+    );
+    assert_eq!(processed[1..], [0.0, 0.0]);
+
+    options.max_ms = Some(60_000.0);
+    let result = AudioCombiner::combine_files(files(), vec![], &options).unwrap();
+    let expected = AudioCombiner::new(files())
+        .unwrap()
+        .combine(vec![])
+        .unwrap();
+    assert_eq!(result.file().bytes(), expected.bytes());
+
+    options.max_ms = Some(0.0);
+    let error = AudioCombiner::combine_files(files(), vec![], &options)
+        .err()
+        .unwrap();
+    assert_eq!(error.code(), "INVALID_OPTION");
+}
+
+#[wasm_bindgen_test]
+fn combine_files_seeks_to_trims_with_the_samples_of_a_full_decode() {
+    let sources = [
+        (SINE_MP3, SingleAudioFileType::Mpeg),
+        (SINE_M4A, SingleAudioFileType::Aac),
+        (SINE_FLAC_24, SingleAudioFileType::Flac),
+        (SINE_OGG, SingleAudioFileType::Ogg),
+    ];
+    let files = || -> Vec<_> {
+        sources
+            .iter()
+            .map(|&(bytes, r#type)| SingleAudioFile::new(bytes.to_vec(), r#type))
+            .collect()
+    };
+    let tracks: Vec<_> = [
+        (600.0, Some(900.0)),
+        (1200.0, None),
+        (700.0, Some(60_000.0)),
+        (0.0, Some(300.0)),
+    ]
+    .iter()
+    .enumerate()
+    .map(|(i, &(start, end))| {
+        let mut track = MixTrack::new();
+        track.trim_start_ms = start;
+        track.trim_end_ms = end;
+        track.offset_ms = i as f64 * 250.0;
+        track.gain = 0.5;
+        track
+    })
+    .collect();
+    let options = CombineOptions::new();
+    // `new` decodes every file whole and `combine_tracks` cuts the trims
+    // out of that, where `combine_files` seeks to them and stops there.
+    let decoded = AudioCombiner::new(files())
+        .unwrap()
+        .combine_tracks(tracks.clone(), &options)
+        .unwrap();
+    let seeked = AudioCombiner::combine_files(files(), tracks.clone(), &options).unwrap();
+    assert_eq!(seeked.file().bytes(), decoded.bytes());
+}
+
+#[wasm_bindgen_test]
+fn durations_are_estimated_from_headers_alone() {
+    let files = || {
+        vec![
+            SingleAudioFile::new(SINE_MP3.to_vec(), SingleAudioFileType::Mpeg),
+            SingleAudioFile::from_pcm_f32(stereo_sine(440.0, 48000, 24000, 0.5), 48000, 2).unwrap(),
+        ]
+    };
+    let infos = || -> Vec<_> { files().iter().map(|f| f.probe().unwrap()).collect() };
+    let mut looped = MixTrack::new();
+    looped.offset_ms = 250.0;
+    looped.trim_start_ms = 100.0;
+    looped.loop_count = 2;
+    let tracks = || vec![MixTrack::new(), looped.clone()];
+
+    for mode in [CombineMode::Mix, CombineMode::Concat] {
+        let mut options = CombineOptions::new();
+        options.mode = mode;
+        options.crossfade_ms = 100.0;
+        let estimate = AudioCombiner::estimate_duration(infos(), tracks(), &options).unwrap();
+        let actual = AudioCombiner::new(files())
+            .unwrap()
+            .combine_with_stats(tracks(), &options)
+            .unwrap()
+            .stats()
+            .duration_ms();
+        assert!(estimate.complete());
+        assert!(
+            (estimate.total_ms() - actual).abs() < 0.1,
+            "{:?}: estimated {} ms, mixed {} ms",
+            mode,
+            estimate.total_ms(),
+            actual
+        );
+        assert!((estimate.file_ms()[1] - 800.0).abs() < 0.1);
+    }
+
+    // The chained Ogg's headers give no length, so it counts as empty.
+    let mut infos = infos();
+    infos.push(
+        SingleAudioFile::new(CHAINED_OGG.to_vec(), SingleAudioFileType::Ogg)
+            .probe()
+            .unwrap(),
+    );
+    let options = CombineOptions::new();
+    let estimate = AudioCombiner::estimate_duration(infos.clone(), vec![], &options).unwrap();
+    assert!(!estimate.complete());
+    assert!(estimate.file_ms()[2].is_nan());
+    assert!((estimate.total_ms() - 992.65).abs() < 0.1);
+
+    // Muted, it leaves the mix and the estimate is complete again.
+    let mut muted = MixTrack::new();
+    muted.mute = true;
+    let tracks = vec![MixTrack::new(), MixTrack::new(), muted];
+    let estimate = AudioCombiner::estimate_duration(infos, tracks, &options).unwrap();
+    assert!(estimate.complete());
+}
+
+#[wasm_bindgen_test]
+fn wav_output_sizes_are_known_before_mixing() {
+    let files = || {
+        vec![
+            SingleAudioFile::new(SINE_MP3.to_vec(), SingleAudioFileType::Mpeg),
+            SingleAudioFile::from_pcm_f32(stereo_sine(440.0, 48000, 24000, 0.5), 48000, 2).unwrap(),
+        ]
+    };
+    let infos = || -> Vec<_> { files().iter().map(|f| f.probe().unwrap()).collect() };
+    let mut options = CombineOptions::new();
+    for bit_depth in [BitDepth::Int16, BitDepth::Int24, BitDepth::Float32] {
+        options.bit_depth = bit_depth;
+        options.metadata = Some(OutputMetadata {
+            title: Some("Preview".into()),
+            ..OutputMetadata::default()
+        });
+        let size = AudioCombiner::estimate_output_size(infos(), vec![], &options).unwrap();
+        let wav = AudioCombiner::new(files())
+            .unwrap()
+            .combine_tracks(vec![], &options)
+            .unwrap();
+        assert!(!size.is_estimate());
+        assert_eq!(size.bytes(), wav.bytes().len() as f64, "{:?}", bit_depth);
+    }
+
+    // A mix of only mono files is written as one channel.
+    let mono = || {
+        vec![SingleAudioFile::new(
+            SINE_MONO_MP3.to_vec(),
+            SingleAudioFileType::Mpeg,
+        )]
+    };
+    let mono_infos = mono().iter().map(|f| f.probe().unwrap()).collect();
+    let size = AudioCombiner::estimate_output_size(mono_infos, vec![], &options).unwrap();
+    let wav = AudioCombiner::new(mono())
+        .unwrap()
+        .combine_tracks(vec![], &options)
+        .unwrap();
+    assert_eq!(size.bytes(), wav.bytes().len() as f64);
+
+    // Compressed output goes by its bitrate: 128 kbps for just under a
+    // second.
+    options.format = OutputFormat::Mpeg;
+    let size = AudioCombiner::estimate_output_size(infos(), vec![], &options).unwrap();
+    assert!(size.is_estimate());
+    assert_eq!(size.bytes(), 15882.0);
+}
+
+/// The (sample offset, label) of each cue point in `wav`, checking on the
+/// way that every chunk is padded to an even length and that the RIFF size
+/// covers them exactly.
+fn wav_cues(wav: &[u8]) -> Vec<(u32, String)> {
+    let u32_at =
+        |pos: usize| u32::from_le_bytes([wav[pos], wav[pos + 1], wav[pos + 2], wav[pos + 3]]);
+    assert_eq!(u32_at(4) as usize, wav.len() - 8);
+    let mut offsets = Vec::new();
+    let mut labels = std::collections::HashMap::new();
+    let mut pos = 12;
+    while pos < wav.len() {
+        let size = u32_at(pos + 4) as usize;
+        let body = pos + 8;
+        match &wav[pos..pos + 4] {
+            b"cue " => {
+                assert_eq!(u32_at(body) as usize * 24 + 4, size);
+                for point in (body + 4..body + size).step_by(24) {
+                    assert_eq!(&wav[point + 8..point + 12], b"data");
+                    assert_eq!(u32_at(point + 4), u32_at(point + 20));
+                    offsets.push((u32_at(point), u32_at(point + 20)));
+                }
+            }
+            b"LIST" if &wav[body..body + 4] == b"adtl" => {
+                let mut sub = body + 4;
+                while sub < body + size {
+                    assert_eq!(&wav[sub..sub + 4], b"labl");
+                    let len = u32_at(sub + 4) as usize;
+                    let text = &wav[sub + 12..sub + 8 + len];
+                    assert_eq!(text.last(), Some(&0));
+                    let text = String::from_utf8(text[..text.len() - 1].to_vec()).unwrap();
+                    labels.insert(u32_at(sub + 8), text);
+                    sub += 8 + len + len % 2;
+                }
+                assert_eq!(sub, body + size);
+            }
+            _ => {}
+        }
+        assert_eq!(size % 2, 0, "{:?} is not padded", &wav[pos..pos + 4]);
+        pos = body + size;
+    }
+    assert_eq!(pos, wav.len());
+    offsets
+        .into_iter()
+        .map(|(id, offset)| (offset, labels.remove(&id).unwrap()))
+        .collect()
+}
+
+#[wasm_bindgen_test]
+fn wav_output_marks_file_starts_and_markers() {
+    let files = || {
+        [1000, 2500, 700]
+            .iter()
+            .map(|&frames| SingleAudioFile::from_pcm_f32(ramp(frames * 2), 44100, 2).unwrap())
+            .collect::<Vec<_>>()
+    };
+    let mut options = CombineOptions::new();
+    options.mode = CombineMode::Concat;
+    options.crossfade_ms = 10.0;
+    options.mark_file_starts = true;
+    options.markers = vec![
+        Marker::new(50.0, "Chorus".into()),
+        // Past the end of the 3318 frames.
+        Marker::new(1000.0, "Outro".into()),
+    ];
+    // An odd-length title, so the INFO chunk needs its padding too.
+    options.metadata = Some(OutputMetadata {
+        title: Some("Chapters".into()),
+        ..OutputMetadata::default()
+    });
+    let combiner = AudioCombiner::new(files()).unwrap();
+    let wav = combiner.combine_tracks(vec![], &options).unwrap().bytes();
+    // The 441-frame crossfades start each file that much before the end of
+    // the one before.
+    assert_eq!(
+        wav_cues(&wav),
+        vec![
+            (0, "Track 1".to_string()),
+            (559, "Track 2".to_string()),
+            (2205, "Chorus".to_string()),
+            (2618, "Track 3".to_string()),
+        ]
+    );
+
+    let mut unmarked = options.clone();
+    unmarked.mark_file_starts = false;
+    unmarked.markers = vec![];
+    let plain = combiner.combine_tracks(vec![], &unmarked).unwrap().bytes();
+    assert!(wav_cues(&plain).is_empty());
+    assert_eq!(riff_chunk(&wav, b"data"), riff_chunk(&plain, b"data"));
+    // The chunks do not get in the way of reading the file back.
+    let file = SingleAudioFile::new(wav.clone(), SingleAudioFileType::Wav);
+    let duration_ms = file.probe().unwrap().duration_ms().unwrap();
+    assert!((duration_ms - 3318.0 * 1000.0 / 44100.0).abs() < 1e-6);
+    assert!(AudioCombiner::new(vec![file]).is_ok());
+
+    let infos: Vec<_> = files().iter().map(|f| f.probe().unwrap()).collect();
+    let size = AudioCombiner::estimate_output_size(infos, vec![], &options).unwrap();
+    assert_eq!(size.bytes(), wav.len() as f64);
+
+    // Split up, each segment holds its own cues from its own start.
+    let segments = combiner
+        .combine_segmented(vec![], 2000.0 * 1000.0 / 44100.0, &options)
+        .unwrap();
+    let cues: Vec<_> = segments.iter().map(|s| wav_cues(&s.bytes())).collect();
+    assert_eq!(
+        cues,
+        vec![
+            vec![(0, "Track 1".to_string()), (559, "Track 2".to_string())],
+            vec![(205, "Chorus".to_string()), (618, "Track 3".to_string())],
+        ]
+    );
+
+    options.markers = vec![Marker::new(-1.0, "Before".into())];
+    let error = combiner.combine_tracks(vec![], &options).err().unwrap();
+    assert_eq!(error.code(), "INVALID_OPTION");
+}
+
+#[wasm_bindgen_test]
+fn wav_output_carries_a_broadcast_extension() {
+    let files = || vec![SingleAudioFile::from_pcm_f32(ramp(2 * 4410), 44100, 2).unwrap()];
+    let mut bext = BroadcastExtension::new();
+    bext.description = "Morning news, top of the hour".into();
+    bext.originator = "Studio 3".into();
+    bext.originator_reference = "NEWS0700".into();
+    bext.origination_date = "2024-05-01".into();
+    bext.origination_time = "07:00:00".into();
+    // 07:00:00 at 44.1 kHz, past 32 bits once doubled.
+    bext.time_reference = 7.0 * 3600.0 * 44100.0 * 2.0;
+    let mut options = CombineOptions::new();
+    options.bext = Some(bext.clone());
+    options.metadata = Some(OutputMetadata {
+        title: Some("News".into()),
+        ..OutputMetadata::default()
+    });
+    let combiner = AudioCombiner::new(files()).unwrap();
+    let wav = combiner.combine_tracks(vec![], &options).unwrap().bytes();
+
+    let chunk = riff_chunk(&wav, b"bext").unwrap();
+    assert_eq!(chunk.len(), 602);
+    let text = |range: std::ops::Range<usize>| &chunk[range];
+    let mut description = bext.description.as_bytes().to_vec();
+    description.resize(256, 0);
+    assert_eq!(text(0..256), &description[..]);
+    assert_eq!(&text(256..288)[..9], b"Studio 3\0");
+    assert_eq!(&text(288..320)[..9], b"NEWS0700\0");
+    assert_eq!(text(320..330), b"2024-05-01");
+    assert_eq!(text(330..338), b"07:00:00");
+    let time_reference = u64::from_le_bytes(chunk[338..346].try_into().unwrap());
+    assert_eq!(time_reference, 2_222_640_000);
+    assert_eq!(u16::from_le_bytes([chunk[346], chunk[347]]), 1);
+    assert!(chunk[348..].iter().all(|&b| b == 0));
+    // The INFO tags are still there, and the file still reads.
+    let info = riff_chunk(&wav, b"LIST").unwrap();
+    assert_eq!(&info[..8], b"INFOINAM");
+    let mut plain = options.clone();
+    plain.bext = None;
+    let plain = combiner.combine_tracks(vec![], &plain).unwrap().bytes();
+    assert_eq!(wav.len(), plain.len() + 610);
+    assert_eq!(riff_chunk(&wav, b"data"), riff_chunk(&plain, b"data"));
+    assert_eq!(
+        u32::from_le_bytes(wav[4..8].try_into().unwrap()) as usize,
+        wav.len() - 8
+    );
+    let infos: Vec<_> = files().iter().map(|f| f.probe().unwrap()).collect();
+    let size = AudioCombiner::estimate_output_size(infos, vec![], &options).unwrap();
+    assert_eq!(size.bytes(), wav.len() as f64);
+    assert!(AudioCombiner::new(vec![SingleAudioFile::new(wav, SingleAudioFileType::Wav)]).is_ok());
+
+    // Each segment's time reference is where it starts.
+    let segments = combiner.combine_segmented(vec![], 40.0, &options).unwrap();
+    let references: Vec<u64> = segments
+        .iter()
+        .map(|segment| {
+            let bytes = segment.bytes();
+            let chunk = riff_chunk(&bytes, b"bext").unwrap();
+            u64::from_le_bytes(chunk[338..346].try_into().unwrap())
+        })
+        .collect();
+    assert_eq!(
+        references,
+        vec![2_222_640_000, 2_222_641_764, 2_222_643_528]
+    );
+
+    let long = "x".repeat(257);
+    type BextSetter = fn(&mut BroadcastExtension, &str);
+    let cases: [(&str, BextSetter); 5] = [
+        ("description", |b, v| b.description = v.into()),
+        ("originator", |b, _| {
+            b.originator = "Studio Three of the Northern Regional Office".into()
+        }),
+        ("originator", |b, _| b.originator = "Studio Über".into()),
+        ("origination_date", |b, _| {
+            b.origination_date = "2024/05/01".into()
+        }),
+        ("time_reference", |b, _| b.time_reference = 1.5),
+    ];
+    for (field, set) in cases {
+        let mut bext = bext.clone();
+        set(&mut bext, &long);
+        options.bext = Some(bext);
+        let error = combiner.combine_tracks(vec![], &options).err().unwrap();
+        assert_eq!(error.code(), "INVALID_OPTION", "{}", field);
+        assert!(error.to_string().contains(field), "{}", error);
+    }
+}
+
+/// The sample period, MIDI unity note and single loop's (start, end) from
+/// the `smpl` chunk of `wav`.
+fn smpl_loop(wav: &[u8]) -> (u32, u32, (u32, u32)) {
+    let chunk = riff_chunk(wav, b"smpl").unwrap();
+    let field = |i: usize| u32::from_le_bytes(chunk[i * 4..i * 4 + 4].try_into().unwrap());
+    assert_eq!(chunk.len(), 60);
+    assert_eq!(field(7), 1, "loop count");
+    // A forward loop, played forever.
+    assert_eq!((field(10), field(14)), (0, 0));
+    (field(2), field(3), (field(11), field(12)))
+}
+
+#[wasm_bindgen_test]
+fn wav_output_carries_loop_points() {
+    let combiner = AudioCombiner::new(vec![
+        SingleAudioFile::from_pcm_f32(ramp(2 * 1000), 44100, 2).unwrap(),
+        SingleAudioFile::from_pcm_f32(ramp(2 * 600), 44100, 2).unwrap(),
+    ])
+    .unwrap();
+    let mut options = CombineOptions::new();
+    options.sample_loop = Some(SampleLoop::new());
+    let wav = combiner.combine_tracks(vec![], &options).unwrap().bytes();
+    assert_eq!(smpl_loop(&wav), (22676, 60, (0, 999)));
+    let infos: Vec<_> = [1000, 600]
+        .iter()
+        .map(|&frames| {
+            SingleAudioFile::from_pcm_f32(ramp(2 * frames), 44100, 2)
+                .unwrap()
+                .probe()
+                .unwrap()
+        })
+        .collect();
+    let size = AudioCombiner::estimate_output_size(infos, vec![], &options).unwrap();
+    assert_eq!(size.bytes(), wav.len() as f64);
+    assert!(AudioCombiner::new(vec![SingleAudioFile::new(wav, SingleAudioFileType::Wav)]).is_ok());
+
+    // The period follows the output rate, and the frames are the output's.
+    options.sample_rate = 48000;
+    options.sample_loop = Some(SampleLoop::between(100, 1087));
+    let wav = combiner.combine_tracks(vec![], &options).unwrap().bytes();
+    assert_eq!(riff_chunk(&wav, b"data").unwrap().len(), 1088 * 4);
+    assert_eq!(smpl_loop(&wav), (20833, 60, (100, 1087)));
+
+    let file = SingleAudioFile::from_pcm_f32(ramp(2 * 500), 44100, 2).unwrap();
+    options.sample_rate = 44100;
+    options.sample_loop = Some(SampleLoop::between(10, 20));
+    let wav = file.to_wav(&options).unwrap().bytes();
+    assert_eq!(smpl_loop(&wav).2, (10, 20));
+
+    for sample_loop in [
+        SampleLoop::between(0, 1000),
+        SampleLoop::between(500, 499),
+        SampleLoop {
+            start_frame: 1000,
+            end_frame: None,
+        },
+    ] {
+        options.sample_loop = Some(sample_loop);
+        let error = combiner.combine_tracks(vec![], &options).err().unwrap();
+        assert_eq!(error.code(), "INVALID_OPTION", "{:?}", sample_loop);
+    }
+    options.sample_loop = Some(SampleLoop::new());
+    let error = combiner
+        .combine_segmented(vec![], 10.0, &options)
+        .err()
+        .unwrap();
+    assert_eq!(error.code(), "INVALID_OPTION");
+}
+
+#[wasm_bindgen_test]
+fn projects_round_trip_every_setting() {
+    let files = || {
+        vec![
+            SingleAudioFile::new(SINE_MP3.to_vec(), SingleAudioFileType::Mpeg),
+            SingleAudioFile::from_pcm_f32(stereo_sine(220.0, 44100, 22050, 0.5), 44100, 2).unwrap(),
+        ]
+    };
+    let ids = || vec!["sine-a".to_string(), "sine-b".to_string()];
+    let mut lead = MixTrack::new();
+    lead.gain = 0.7;
+    lead.pan = -0.25;
+    lead.offset_ms = 120.5;
+    lead.trim_end_ms = Some(800.0);
+    lead.fade_in_ms = 50.0;
+    lead.fade_curve = FadeCurve::EqualPower;
+    lead.automation = vec![
+        AutomationPoint::new(0.0, 1.0),
+        AutomationPoint::new(400.0, 0.3),
+    ];
+    let mut eq = Equalizer::new();
+    eq.high_gain_db = -3.5;
+    lead.eq = Some(eq);
+    let mut bed = MixTrack::new();
+    bed.ducking = Some(Ducking::new(0));
+    bed.speed = 1.25;
+    let mut options = CombineOptions::new();
+    options.sample_rate = 48000;
+    options.bit_depth = BitDepth::Int24;
+    options.compressor = Some(Compressor::new());
+    options.metadata = Some(OutputMetadata {
+        title: Some("Saved mix".into()),
+        ..OutputMetadata::default()
+    });
+
+    let combiner = AudioCombiner::new(files()).unwrap();
+    let tracks = vec![lead, bed];
+    let json = combiner
+        .export_project(tracks.clone(), &options, ids())
+        .unwrap();
+    let project: MixProject = AudioCombiner::import_project(&json, files(), ids())
+        .ok()
+        .unwrap();
+
+    assert_eq!(format!("{:?}", project.tracks()), format!("{:?}", tracks));
+    assert_eq!(format!("{:?}", project.options()), format!("{:?}", options));
+    assert_eq!(
+        project.combine().unwrap().bytes(),
+        combiner.combine_tracks(tracks, &options).unwrap().bytes()
+    );
+    // What was restored saves to the same project.
+    let restored = project.tracks();
+    let options = project.options();
+    assert_eq!(
+        project
+            .into_combiner()
+            .export_project(restored, &options, ids())
+            .unwrap(),
+        json
+    );
+}
+
+#[wasm_bindgen_test]
+fn projects_only_load_against_the_files_they_name() {
+    let files = |n: usize| -> Vec<_> {
+        (0..n)
+            .map(|_| SingleAudioFile::new(SINE_MP3.to_vec(), SingleAudioFileType::Mpeg))
+            .collect()
+    };
+    let ids = |names: &[&str]| -> Vec<String> { names.iter().map(|s| s.to_string()).collect() };
+    let json = AudioCombiner::new(files(2))
+        .unwrap()
+        .export_project(vec![], &CombineOptions::new(), ids(&["a", "b"]))
+        .unwrap();
+    let import = |json: &str, n: usize, names: &[&str]| {
+        AudioCombiner::import_project(json, files(n), ids(names))
+            .err()
+            .unwrap()
+    };
+
+    let error = import(&json, 2, &["a", "c"]);
+    assert_eq!(error.code(), "PROJECT_FILE_MISMATCH");
+    assert_eq!(error.index(), Some(1));
+    assert_eq!(import(&json, 1, &["a"]).code(), "INVALID_PROJECT");
+    assert_eq!(import(&json, 2, &["a"]).code(), "INVALID_PROJECT");
+    assert_eq!(
+        import("{not json", 2, &["a", "b"]).code(),
+        "INVALID_PROJECT"
+    );
+    let newer = json.replace("\"version\":1", "\"version\":2");
+    assert_eq!(import(&newer, 2, &["a", "b"]).code(), "INVALID_PROJECT");
+    let error = import(
+        r#"{"version":1,"files":[{"id":"a"}],"tracks":[{"gain":"loud"}]}"#,
+        1,
+        &["a"],
+    );
+    assert!(error.to_string().contains("tracks[0].gain"), "{}", error);
+
+    // Exports need an id for every file.
+    let error = AudioCombiner::new(files(2))
+        .unwrap()
+        .export_project(vec![], &CombineOptions::new(), ids(&["a"]))
+        .unwrap_err();
+    assert_eq!(error.code(), "INVALID_PROJECT");
+}
+
+#[wasm_bindgen_test]
+fn first_version_projects_load_with_later_settings_at_their_defaults() {
+    // As an early build would have saved it, before most settings existed.
+    let json = r#"{
+        "version": 1,
+        "files": [{ "id": "voice" }, { "id": "music" }],
+        "tracks": [{ "gain": 0.5, "offset_ms": 250.0 }],
+        "options": { "sample_rate": 22050 }
+    }"#;
+    let files = vec![
+        SingleAudioFile::new(SINE_MP3.to_vec(), SingleAudioFileType::Mpeg),
+        SingleAudioFile::new(SINE_OGG.to_vec(), SingleAudioFileType::Ogg),
+    ];
+    let project = AudioCombiner::import_project(json, files, vec!["voice".into(), "music".into()])
+        .ok()
+        .unwrap();
+    let tracks = project.tracks();
+    assert_eq!(tracks.len(), 2);
+    assert_eq!(tracks[0].gain, 0.5);
+    assert_eq!(tracks[0].offset_ms, 250.0);
+    assert_eq!(tracks[0].speed, 1.0);
+    assert_eq!(tracks[1].gain, 1.0);
+    let options = project.options();
+    assert_eq!(options.sample_rate, 22050);
+    assert_eq!(options.bit_depth, BitDepth::Int16);
+    assert!(options.dither);
+    assert!(!project.combine().unwrap().bytes().is_empty());
+}
+
+#[wasm_bindgen_test]
+fn clones_share_their_buffers() {
+    // 32 MB of samples, so copying them for each clone would have to grow
+    // memory by far more than is left over from earlier tests.
+    let samples = stereo_sine(440.0, 48000, 4 * 1024 * 1024, 0.5);
+    let file = SingleAudioFile::from_pcm_f32(samples, 48000, 2).unwrap();
+    let combiner = AudioCombiner::new(vec![file.clone()]).unwrap();
+
+    let before = wasm_memory_bytes();
+    let files: Vec<SingleAudioFile> = (0..4).map(|_| file.duplicate()).collect();
+    let combiners: Vec<AudioCombiner> = (0..4).map(|_| combiner.duplicate()).collect();
+    let grown = wasm_memory_bytes() - before;
+    assert!(grown < 1024 * 1024, "memory grew by {} bytes", grown);
+    assert!(files.iter().all(|f| f.byte_length() == file.byte_length()));
+    assert!(combiners.iter().all(|c| c.len() == 1));
+}
+
+#[wasm_bindgen_test]
+fn changing_a_clone_leaves_the_original_alone() {
+    let voice = || SingleAudioFile::new(SINE_MP3.to_vec(), SingleAudioFileType::Mpeg);
+    let music = || SingleAudioFile::new(SINE_OGG.to_vec(), SingleAudioFileType::Ogg);
+    let mut full = AudioCombiner::new(vec![voice(), music()]).unwrap();
+    let mix = full.combine(vec![100, 100]).unwrap().bytes();
+
+    // An instrumental from a clone without the voice.
+    let mut instrumental = full.duplicate();
+    instrumental.remove_file(0).unwrap();
+    assert_eq!(instrumental.len(), 1);
+    assert_eq!(full.len(), 2);
+    assert_eq!(full.combine(vec![100, 100]).unwrap().bytes(), mix);
+    assert_eq!(
+        instrumental.combine(vec![100]).unwrap().bytes(),
+        AudioCombiner::new(vec![music()])
+            .unwrap()
+            .combine(vec![100])
+            .unwrap()
+            .bytes()
+    );
+
+    full.add_file(voice()).unwrap();
+    assert_eq!(full.len(), 3);
+    assert_eq!(instrumental.len(), 1);
+
+    let file = voice();
+    let mut copy = file.duplicate();
+    copy.set_bytes(SINE_OGG.to_vec());
+    copy.r#type = SingleAudioFileType::Ogg;
+    assert_eq!(file.bytes(), SINE_MP3);
+    assert_eq!(file.r#type, SingleAudioFileType::Mpeg);
+    assert_eq!(copy.bytes(), SINE_OGG);
+}
+
+#[wasm_bindgen_test]
+fn merged_combiners_mix_like_one_built_from_every_file() {
+    let music = || {
+        vec![
+            SingleAudioFile::new(SINE_OGG.to_vec(), SingleAudioFileType::Ogg),
+            SingleAudioFile::from_pcm_f32(stereo_sine(110.0, 44100, 44100, 0.3), 44100, 2).unwrap(),
+        ]
+    };
+    let dialogue = || {
+        vec![SingleAudioFile::new(
+            SINE_MP3.to_vec(),
+            SingleAudioFileType::Mpeg,
+        )]
+    };
+    let all = || {
+        let mut files = music();
+        files.extend(dialogue());
+        files
+    };
+    let volumes = vec![40, 70, 100];
+    let expected = AudioCombiner::new(all())
+        .unwrap()
+        .combine(volumes.clone())
+        .unwrap()
+        .bytes();
+
+    let mut merged = AudioCombiner::new(music()).unwrap();
+    let dialogue_combiner = AudioCombiner::new(dialogue()).unwrap();
+    merged.merge(&dialogue_combiner);
+    assert_eq!(merged.len(), 3);
+    assert_eq!(dialogue_combiner.len(), 1);
+    assert_eq!(merged.combine(volumes.clone()).unwrap().bytes(), expected);
+
+    let parts = AudioCombiner::from_parts(vec![
+        AudioCombiner::new(music()).unwrap(),
+        AudioCombiner::new(dialogue()).unwrap(),
+    ]);
+    assert_eq!(parts.combine(volumes).unwrap().bytes(), expected);
+
+    // Indexes carry on across the join, decode warnings' included.
+    let mut bytes = SINE_M4A.to_vec();
+    let start = bytes.len() * 3 / 10;
+    for (i, b) in bytes[start..start + 400].iter_mut().enumerate() {
+        *b = (i * 37 % 251) as u8;
+    }
+    let mut corrupt = SingleAudioFile::new(bytes, SingleAudioFileType::Aac);
+    corrupt.tolerate_decode_errors = true;
+    let mut with_warnings = AudioCombiner::new(music()).unwrap();
+    with_warnings.merge(&AudioCombiner::new(vec![corrupt]).unwrap());
+    let warnings = with_warnings
+        .combine_with_stats(vec![], &CombineOptions::new())
+        .unwrap()
+        .warnings();
+    assert!(!warnings.is_empty());
+    assert!(warnings.iter().all(|warning| warning.index() == 2));
+
+    merged.remove_file(1).unwrap();
+    let mut files = all();
+    files.remove(1);
+    assert_eq!(
+        merged.combine(vec![40, 100]).unwrap().bytes(),
+        AudioCombiner::new(files)
+            .unwrap()
+            .combine(vec![40, 100])
+            .unwrap()
+            .bytes()
+    );
+}